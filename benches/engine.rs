@@ -0,0 +1,188 @@
+#[macro_use]
+extern crate criterion;
+extern crate engine;
+extern crate env_logger;
+extern crate glium;
+#[macro_use]
+extern crate log;
+extern crate nalgebra;
+
+use std::path::PathBuf;
+
+use criterion::Criterion;
+
+use engine::{Camera, EventBus, FontCache, GlyphAtlas, InputState, Scene, TextureCache, Transform};
+use engine::bounds::Aabb;
+use engine::draw::{layout_lines, UpdateContext};
+use engine::ecs::{Entity, Parent, Script, World};
+use engine::model::ObjData;
+
+use glium::DisplayBuild;
+use glium::glutin::HeadlessRendererBuilder;
+
+use nalgebra::{Mat4, Vec3};
+
+const ENTITIES: usize = 100000;
+const HIERARCHY_NODES: usize = 10000;
+
+const TEXT: &'static str = "The quick brown fox jumps over the lazy dog. Pack my box with five \
+                            dozen liquor jugs.\nHow vexingly quick daft zebras jump!";
+
+fn resource(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("resources").join(name)
+}
+
+// 100k entities in groups of 10 under a parent, all moved by a script every update
+fn scene_update(c: &mut Criterion) {
+    let mut scene = Scene::new(Camera::new(Vec3::new(0., 0., 1.), 4. / 3.));
+    {
+        let world = scene.world_mut();
+        let mut parent = None;
+        for i in 0..ENTITIES {
+            let entity = world.spawn();
+            world.insert(entity, Transform::new(Vec3::new(i as f32, 0., 0.)));
+            if i % 10 == 0 {
+                parent = Some(entity);
+            } else if let Some(parent) = parent {
+                world.insert(entity, Parent(parent));
+            }
+            world.insert(entity, Script::new(|entity, world, ctxt| {
+                if let Some(transform) = world.get_mut::<Transform>(entity) {
+                    transform.position.y += ctxt.dt;
+                }
+            }));
+        }
+    }
+    let (input, events) = (InputState::new(), EventBus::new());
+    c.bench_function("scene update, 100k entities", move |b| b.iter(|| {
+        scene.update(&UpdateContext { dt: 1. / 30., input: &input, events: &events });
+    }));
+}
+
+fn spawn_node(world: &mut World, parent: Option<Entity>) -> Entity {
+    let entity = world.spawn();
+    world.insert(entity, Transform::new(Vec3::new(1., 0., 0.)));
+    if let Some(parent) = parent {
+        world.insert(entity, Parent(parent));
+    }
+    entity
+}
+
+fn move_entity(world: &mut World, entity: Entity) {
+    world.get_mut::<Transform>(entity).unwrap().position.y += 0.01;
+}
+
+// 100 roots with 99 children each, returning the roots and the leaves
+fn wide_hierarchy() -> (World, Vec<Entity>, Vec<Entity>) {
+    let mut world = World::new();
+    let (mut roots, mut leaves) = (Vec::new(), Vec::new());
+    for _ in 0..HIERARCHY_NODES / 100 {
+        let root = spawn_node(&mut world, None);
+        roots.push(root);
+        for _ in 0..99 {
+            leaves.push(spawn_node(&mut world, Some(root)));
+        }
+    }
+    world.update_world_transforms();
+    (world, roots, leaves)
+}
+
+// A single chain, returning the root and the leaf
+fn deep_hierarchy() -> (World, Entity, Entity) {
+    let mut world = World::new();
+    let root = spawn_node(&mut world, None);
+    let mut leaf = root;
+    for _ in 1..HIERARCHY_NODES {
+        leaf = spawn_node(&mut world, Some(leaf));
+    }
+    world.update_world_transforms();
+    (world, root, leaf)
+}
+
+// Recomputing cached world transforms of 10k entity hierarchies after different changes
+fn world_transforms(c: &mut Criterion) {
+    let (mut world, _, _) = wide_hierarchy();
+    c.bench_function("world transforms, wide, nothing changed", move |b| b.iter(|| {
+        world.update_world_transforms();
+    }));
+
+    let (mut world, roots, _) = wide_hierarchy();
+    c.bench_function("world transforms, wide, first root moved", move |b| b.iter(|| {
+        move_entity(&mut world, roots[0]);
+        world.update_world_transforms();
+    }));
+
+    let (mut world, _, leaves) = wide_hierarchy();
+    c.bench_function("world transforms, wide, every leaf moved", move |b| b.iter(|| {
+        for &leaf in &leaves {
+            move_entity(&mut world, leaf);
+        }
+        world.update_world_transforms();
+    }));
+
+    let (mut world, root, _) = deep_hierarchy();
+    c.bench_function("world transforms, deep, root moved", move |b| b.iter(|| {
+        move_entity(&mut world, root);
+        world.update_world_transforms();
+    }));
+
+    let (mut world, _, leaf) = deep_hierarchy();
+    c.bench_function("world transforms, deep, leaf moved", move |b| b.iter(|| {
+        move_entity(&mut world, leaf);
+        world.update_world_transforms();
+    }));
+}
+
+// The bounds transform and frustum test the scene does for every object it draws
+fn culling(c: &mut Criterion) {
+    let camera = Camera::new(Vec3::new(0., 0., 50.), 4. / 3.);
+    let frustum = camera.frustum();
+    let bounds = Aabb::new(Vec3::new(-1., -1., -1.), Vec3::new(1., 1., 1.));
+    let transforms: Vec<Mat4<f32>> = (0..ENTITIES)
+        .map(|i| {
+            let (x, z) = ((i % 300) as f32 - 150., (i / 300) as f32 - 150.);
+            Transform::new(Vec3::new(x, 0., z)).matrix()
+        })
+        .collect();
+    c.bench_function("culling, 100k boxes", move |b| b.iter(|| {
+        transforms.iter().filter(|m| frustum.intersects(&bounds.transform(m))).count()
+    }));
+}
+
+fn text_layout(c: &mut Criterion) {
+    let mut fonts = FontCache::new().unwrap();
+    let font = fonts.get_font(resource("FiraSans-Regular.ttf"), 24).unwrap();
+    let mut atlas = GlyphAtlas::new();
+    atlas.add_glyphs(&font, TEXT).unwrap();
+    c.bench_function("text layout, wrapped", move |b| b.iter(|| {
+        layout_lines(&atlas, &font, TEXT, Some(200.))
+    }));
+}
+
+fn obj_import(c: &mut Criterion) {
+    let path = resource("cube.obj");
+    c.bench_function("OBJ import, cube", move |b| b.iter(|| ObjData::load(&path).unwrap()));
+}
+
+// Needs an OpenGL driver that can render without a window, e.g. Mesa's OSMesa
+fn texture_cache_hit(c: &mut Criterion) {
+    // So the warning below shows up with `RUST_LOG=warn`
+    let _ = env_logger::init();
+    let display = match HeadlessRendererBuilder::new(64, 64).build_glium() {
+        Ok(display) => display,
+        Err(e) => {
+            warn!("Skipping texture cache hit, no headless context: {:?}", e);
+            return;
+        }
+    };
+    let mut cache = TextureCache::new();
+    let path = resource("cube.png");
+    cache.get_texture(&display, &path).unwrap();
+    c.bench_function("texture cache hit", move |b| b.iter(|| {
+        cache.get_texture(&display, &path).unwrap()
+    }));
+}
+
+criterion_group!(benches, scene_update, world_transforms, culling, text_layout, obj_import,
+                 texture_cache_hit);
+criterion_main!(benches);
@@ -0,0 +1,147 @@
+extern crate engine;
+extern crate env_logger;
+extern crate glium;
+extern crate nalgebra;
+
+use engine::{get_display_dim, Engine, EngineContext, Error, Game, Light, Scene};
+use engine::camera::FlyController;
+use engine::draw::{Cube, Grid, Text, TextStyle};
+use engine::pick_debug::PickDebug;
+
+use glium::glutin::{self, ElementState, Event, VirtualKeyCode};
+
+use nalgebra::{zero, BaseFloat, Vec3};
+
+const RELATIVE_ROTATION: bool = true;
+
+/// A grid, a rotating cube, and a frame rate counter. The camera can be translated (left click or
+/// WASD/QE, shift to sprint), rotated (right click, or the mouse after Tab turns on mouse look)
+/// and zoomed (scroll wheel), and R resets it. F5 reloads the shaders from disk, and F3 shows
+/// what clicking picks.
+struct CubeDemo {
+    controller: FlyController,
+    pick_debug: PickDebug,
+    right_mouse_pressed: bool,
+    left_mouse_pressed: bool,
+    old_mouse_coords: Option<(f32, f32)>,
+    fps: u32,
+}
+
+impl Game for CubeDemo {
+    fn title(&self) -> String {
+        "3D Cube".to_owned()
+    }
+
+    fn init(&mut self, ctxt: &mut EngineContext, scene: &mut Scene) -> Result<(), Error> {
+        scene.add(Grid::new(ctxt.display(), 20));
+        scene.add(try!(Cube::new(ctxt, 1., zero())));
+        scene.add_light(Light::directional(Vec3::new(-1., 0.5, -2.), [1., 1., 1.]));
+        scene.add_light(Light::point(Vec3::new(0.5, -0.5, 0.5), 2., [1., 0.6, 0.2]));
+
+        // FIXME: Text needs to go last
+        let font = try!(ctxt.default_font());
+        let style = TextStyle { shadow_color: Some([0., 0., 0.]), .. Default::default() };
+        let mut t = try!(Text::with_style(ctxt, &font, style, -0.9, -0.9, "Frame rate: 60fps"));
+        t.parent.name = Some("text".to_owned());
+        scene.add(t);
+        Ok(())
+    }
+
+    fn update(&mut self, ctxt: &mut EngineContext, scene: &mut Scene) -> Result<(), Error> {
+        self.controller.update(ctxt.input(), scene.camera_mut(), ctxt.delta_time());
+        self.controller.update_look(ctxt, scene.camera_mut());
+        self.pick_debug.update(ctxt, scene);
+
+        if ctxt.fps() != self.fps {
+            self.fps = ctxt.fps();
+            let text = scene.get_object::<Text>("text").unwrap();
+            try!(text.set_text(ctxt, &format!("Frame rate: {}fps", self.fps)));
+        }
+        Ok(())
+    }
+
+    fn event(&mut self, ctxt: &mut EngineContext, scene: &mut Scene, ev: &Event) {
+        match *ev {
+            Event::KeyboardInput(ElementState::Pressed, _, Some(key)) => {
+                match key {
+                    VirtualKeyCode::R => {
+                        let camera = scene.camera_mut();
+                        camera.set_pos(&Vec3::new(0., 0., 1.));
+                        camera.set_abs_rotation(0., 0.);
+                        camera.set_fov(BaseFloat::frac_pi_2());
+                    }
+                    VirtualKeyCode::F5 => ctxt.reload_shaders(),
+                    _ => ()
+                }
+            },
+            Event::MouseWheel(glutin::MouseScrollDelta::LineDelta(_, v)) => {
+                let camera = scene.camera_mut();
+                let fov = camera.fov();
+                let frac: f32 = (f32::pi() - fov) / f32::pi();
+                let new_fov = f32::max(0., fov + 0.05 * frac * v);
+                camera.set_fov(new_fov);
+            },
+            Event::MouseMoved((x, y)) => {
+                if self.right_mouse_pressed {
+                    // Rotation
+                    let (x, y) = (x as f32, y as f32);
+                    let (w, h) = get_display_dim(ctxt.display());
+                    let (w, h) = (w as f32, h as f32);
+                    if !RELATIVE_ROTATION {
+                        let pitch = (y / h) * f32::two_pi();
+                        let yaw = (x / w) * f32::two_pi();
+                        scene.camera_mut().set_abs_rotation(pitch, -yaw);
+                    } else {
+                        if let Some((x_old, y_old)) = self.old_mouse_coords {
+                            let delta_x = x - x_old;
+                            let delta_y = y - y_old;
+
+                            let pitch = (delta_y * 0.5 / h) * f32::two_pi();
+                            let yaw = (delta_x * 0.5 / w) * f32::two_pi();
+                            scene.camera_mut().rotate(pitch, yaw);
+                        }
+                        self.old_mouse_coords = Some((x, y));
+                    }
+                } else if self.left_mouse_pressed {
+                    // Translation
+                    let (x, y) = (x as f32, y as f32);
+                    if let Some((x_old, y_old)) = self.old_mouse_coords {
+                        let diff = Vec3::new(x_old - x, y - y_old, 0.) * 0.003 as f32;
+                        scene.camera_mut().translate(&diff);
+                    }
+                    self.old_mouse_coords = Some((x, y));
+                }
+            },
+            Event::MouseInput(state, button) => {
+                if state == ElementState::Released {
+                    self.old_mouse_coords = None;
+                };
+
+                match button {
+                    glutin::MouseButton::Left =>
+                        self.left_mouse_pressed = state == ElementState::Pressed,
+                    glutin::MouseButton::Right =>
+                        self.right_mouse_pressed = state == ElementState::Pressed,
+                    _ => ()
+                }
+            }
+            _ => ()
+        }
+    }
+}
+
+fn main() {
+    env_logger::init().unwrap();
+
+    let result = Engine::run(CubeDemo {
+        controller: FlyController::new(1.),
+        pick_debug: PickDebug::new(),
+        right_mouse_pressed: false,
+        left_mouse_pressed: false,
+        old_mouse_coords: None,
+        fps: 0,
+    });
+    if let Err(e) = result {
+        println!("Error: {}", e);
+    }
+}
@@ -0,0 +1,69 @@
+#version 140
+
+const uint COLOR_TYPE = uint(0);
+const uint TEXTURE_RGB_TYPE = uint(1);
+
+const uint DIRECTIONAL_LIGHT = uint(0);
+const uint POINT_LIGHT = uint(1);
+const uint SPOT_LIGHT = uint(2);
+
+const int MAX_LIGHTS = 8;
+
+in vec2 v_tex_coord;
+in vec3 v_normal;
+in vec3 v_world_pos;
+
+uniform sampler2D tex;
+uniform vec3 color;
+uniform uint type;
+uniform float alpha_cutoff;
+uniform bool clockwise_front;
+
+uniform uint light_count;
+uniform vec3 ambient;
+uniform uint light_type[MAX_LIGHTS];
+uniform vec3 light_position[MAX_LIGHTS];
+uniform vec3 light_direction[MAX_LIGHTS];
+uniform vec3 light_color[MAX_LIGHTS];
+uniform float light_range[MAX_LIGHTS];
+uniform float light_cutoff[MAX_LIGHTS];
+
+out vec4 out_color;
+
+void main() {
+    vec4 base;
+    if (type == COLOR_TYPE) {
+        base = vec4(color, 1.);
+    } else {
+        base = texture(tex, v_tex_coord);
+    }
+    // Pixels are either fully drawn or not at all, so no blending or sorting is needed
+    if (base.a < alpha_cutoff) {
+        discard;
+    }
+
+    // Both sides are drawn, and the back is lit as if it faced the other way
+    vec3 normal = normalize(v_normal);
+    if (gl_FrontFacing == clockwise_front) {
+        normal = -normal;
+    }
+
+    vec3 light = ambient;
+    for (int i = 0; i < int(light_count); i++) {
+        vec3 to_light;
+        float attenuation = 1.;
+        if (light_type[i] == DIRECTIONAL_LIGHT) {
+            to_light = -light_direction[i];
+        } else {
+            vec3 diff = light_position[i] - v_world_pos;
+            to_light = normalize(diff);
+            attenuation = clamp(1. - length(diff) / light_range[i], 0., 1.);
+            if (light_type[i] == SPOT_LIGHT && dot(-to_light, light_direction[i]) < light_cutoff[i]) {
+                attenuation = 0.;
+            }
+        }
+        light += light_color[i] * max(dot(normal, to_light), 0.) * attenuation;
+    }
+
+    out_color = vec4(base.rgb * light, 1.);
+}
@@ -0,0 +1,42 @@
+#version 140
+
+in vec2 v_tex_coord;
+
+uniform sampler2D tex;
+uniform float exposure;
+uniform vec3 white_balance;
+uniform float contrast;
+uniform float saturation;
+uniform sampler2D lut_from;
+uniform float lut_from_size;
+uniform sampler2D lut_to;
+uniform float lut_to_size;
+uniform float lut_blend;
+// Colorblind simulation or correction
+uniform mat3 colorblind;
+
+out vec4 out_color;
+
+// Looks a color up in a strip of `size` slices, blending between the two slices around its
+// blue channel. Green grows downwards in the image, which is uploaded bottom row first.
+vec3 lookup(sampler2D lut, float size, vec3 c) {
+    float slice = c.b * (size - 1.);
+    float first = floor(slice);
+    float second = min(first + 1., size - 1.);
+    vec2 uv = vec2((c.r * (size - 1.) + 0.5) / (size * size),
+                   1. - (c.g * (size - 1.) + 0.5) / size);
+    vec3 a = texture(lut, uv + vec2(first / size, 0.)).rgb;
+    vec3 b = texture(lut, uv + vec2(second / size, 0.)).rgb;
+    return mix(a, b, slice - first);
+}
+
+void main() {
+    vec3 c = texture(tex, v_tex_coord).rgb * exp2(exposure) * white_balance;
+    c = (c - 0.5) * contrast + 0.5;
+    float luma = dot(c, vec3(0.2126, 0.7152, 0.0722));
+    c = clamp(mix(vec3(luma), c, saturation), 0., 1.);
+
+    vec3 graded = mix(lookup(lut_from, lut_from_size, c), lookup(lut_to, lut_to_size, c),
+                      lut_blend);
+    out_color = vec4(clamp(colorblind * graded, 0., 1.), 1.);
+}
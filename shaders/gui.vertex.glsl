@@ -3,11 +3,17 @@
 in vec3 position;
 in vec2 tex_coord;
 
+layout(std140) uniform ObjectBlock {
+    mat4 transform;
+};
+
 out vec3 v_coord;
 out vec2 v_tex_coord;
+out vec3 v_color;
 
 void main() {
     v_coord = position;
     v_tex_coord = tex_coord;
-    gl_Position = vec4(position, 1.);
+    v_color = vec3(1.);
+    gl_Position = transform * vec4(position, 1.);
 }
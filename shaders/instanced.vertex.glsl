@@ -0,0 +1,54 @@
+#version 140
+
+in vec3 position;
+in vec2 tex_coord;
+in vec3 normal;
+in vec3 tangent;
+
+in vec4 instance_col0;
+in vec4 instance_col1;
+in vec4 instance_col2;
+in vec4 instance_col3;
+in vec3 instance_color;
+in float instance_layer;
+
+uniform mat4 proj_matrix;
+uniform mat4 view_matrix;
+layout(std140) uniform ObjectBlock {
+    mat4 transform;
+};
+uniform float time;
+uniform uint layer;
+uniform vec2 uv_offset;
+uniform vec2 uv_scale;
+uniform float uv_rotation;
+uniform vec2 uv_scroll;
+uniform float uv_spin;
+
+out vec3 v_coord;
+out vec2 v_tex_coord;
+out vec3 v_normal;
+out vec3 v_tangent;
+out vec3 v_world_pos;
+flat out float v_layer;
+out vec3 v_color;
+
+// Scales and rotates around the center of the texture, then moves it
+vec2 transform_uv(vec2 uv) {
+    float angle = uv_rotation + uv_spin * time;
+    mat2 rotation = mat2(cos(angle), sin(angle), -sin(angle), cos(angle));
+    return rotation * ((uv - .5) * uv_scale) + .5 + uv_offset + uv_scroll * time;
+}
+
+void main() {
+    mat4 model = transform * mat4(instance_col0, instance_col1, instance_col2, instance_col3);
+    vec4 world_pos = model * vec4(position * .1, 1.);
+    v_coord = position;
+    v_tex_coord = transform_uv(tex_coord);
+    v_normal = mat3(model) * normal;
+    v_tangent = mat3(model) * tangent;
+    v_world_pos = world_pos.xyz;
+    v_layer = float(layer) + instance_layer;
+    v_color = instance_color;
+    gl_Position = proj_matrix * view_matrix * world_pos;
+}
@@ -0,0 +1,67 @@
+#version 140
+
+in uvec4 position;
+in uvec2 tex_coord;
+in uint normal;
+in uint tangent;
+
+uniform mat4 proj_matrix;
+uniform mat4 view_matrix;
+layout(std140) uniform ObjectBlock {
+    mat4 transform;
+};
+uniform float time;
+uniform uint layer;
+uniform vec2 uv_offset;
+uniform vec2 uv_scale;
+uniform float uv_rotation;
+uniform vec2 uv_scroll;
+uniform float uv_spin;
+
+out vec3 v_coord;
+out vec2 v_tex_coord;
+out vec3 v_color;
+out vec3 v_normal;
+out vec3 v_tangent;
+out vec3 v_world_pos;
+flat out float v_layer;
+
+// Infinity and NaN aren't handled, `PackedVertex` never stores them for finite meshes
+float unpack_half(uint h) {
+    float sign = (h & 0x8000u) != 0u ? -1. : 1.;
+    uint exponent = (h >> 10) & 0x1fu;
+    float mantissa = float(h & 0x3ffu);
+    if (exponent == 0u) {
+        return sign * mantissa * exp2(-24.);
+    }
+    return sign * (1. + mantissa / 1024.) * exp2(float(exponent) - 15.);
+}
+
+// Three signed 10 bit components
+vec3 unpack_unit(uint p) {
+    ivec3 v = ivec3(int(p & 0x3ffu), int((p >> 10) & 0x3ffu), int((p >> 20) & 0x3ffu));
+    v -= ivec3(greaterThanEqual(v, ivec3(512))) * 1024;
+    return vec3(v) / 511.;
+}
+
+// Scales and rotates around the center of the texture, then moves it
+vec2 transform_uv(vec2 uv) {
+    float angle = uv_rotation + uv_spin * time;
+    mat2 rotation = mat2(cos(angle), sin(angle), -sin(angle), cos(angle));
+    return rotation * ((uv - .5) * uv_scale) + .5 + uv_offset + uv_scroll * time;
+}
+
+void main() {
+    vec3 pos = vec3(unpack_half(position.x), unpack_half(position.y), unpack_half(position.z));
+    vec2 uv = vec2(unpack_half(tex_coord.x), unpack_half(tex_coord.y));
+
+    vec4 world_pos = transform * vec4(pos * .1, 1.);
+    v_coord = pos;
+    v_tex_coord = transform_uv(uv);
+    v_color = vec3(1.);
+    v_normal = mat3(transform) * unpack_unit(normal);
+    v_tangent = mat3(transform) * unpack_unit(tangent);
+    v_world_pos = world_pos.xyz;
+    v_layer = float(layer);
+    gl_Position = proj_matrix * view_matrix * world_pos;
+}
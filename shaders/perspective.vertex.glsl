@@ -2,16 +2,46 @@
 
 in vec3 position;
 in vec2 tex_coord;
+in vec3 normal;
+in vec3 tangent;
 
 uniform mat4 proj_matrix;
 uniform mat4 view_matrix;
-uniform mat4 transform;
+layout(std140) uniform ObjectBlock {
+    mat4 transform;
+};
+uniform float time;
+uniform uint layer;
+uniform vec2 uv_offset;
+uniform vec2 uv_scale;
+uniform float uv_rotation;
+uniform vec2 uv_scroll;
+uniform float uv_spin;
 
 out vec3 v_coord;
 out vec2 v_tex_coord;
+out vec3 v_color;
+out vec3 v_normal;
+out vec3 v_tangent;
+out vec3 v_world_pos;
+flat out float v_layer;
+
+// Scales and rotates around the center of the texture, then moves it
+vec2 transform_uv(vec2 uv) {
+    float angle = uv_rotation + uv_spin * time;
+    mat2 rotation = mat2(cos(angle), sin(angle), -sin(angle), cos(angle));
+    return rotation * ((uv - .5) * uv_scale) + .5 + uv_offset + uv_scroll * time;
+}
 
 void main() {
+    vec4 world_pos = transform * vec4(position * .1, 1.);
     v_coord = position;
-    v_tex_coord = tex_coord;
-    gl_Position = proj_matrix * view_matrix * transform * vec4(position * .1, 1.);
+    v_tex_coord = transform_uv(tex_coord);
+    v_color = vec3(1.);
+    // Only correct for uniform scaling, which is all objects use for now
+    v_normal = mat3(transform) * normal;
+    v_tangent = mat3(transform) * tangent;
+    v_world_pos = world_pos.xyz;
+    v_layer = float(layer);
+    gl_Position = proj_matrix * view_matrix * world_pos;
 }
@@ -0,0 +1,28 @@
+#version 140
+
+in vec2 v_tex_coord;
+
+uniform sampler2D tex;
+uniform vec2 texel_size;
+uniform float sharpness;
+
+out vec4 out_color;
+
+// Contrast adaptive sharpening: the pixel minus a fraction of its four neighbours, where the
+// fraction shrinks as the neighbourhood's contrast grows so edges that are already sharp don't
+// ring
+void main() {
+    vec3 c = texture(tex, v_tex_coord).rgb;
+    vec3 n = texture(tex, v_tex_coord + vec2(0., texel_size.y)).rgb;
+    vec3 s = texture(tex, v_tex_coord - vec2(0., texel_size.y)).rgb;
+    vec3 e = texture(tex, v_tex_coord + vec2(texel_size.x, 0.)).rgb;
+    vec3 w = texture(tex, v_tex_coord - vec2(texel_size.x, 0.)).rgb;
+
+    vec3 lo = min(c, min(min(n, s), min(e, w)));
+    vec3 hi = max(c, max(max(n, s), max(e, w)));
+    vec3 amount = sqrt(clamp(min(lo, 1. - hi) / max(hi, 1e-5), 0., 1.));
+    vec3 weight = -amount / mix(8., 5., sharpness) * step(0.001, sharpness);
+
+    vec3 result = (c + (n + s + e + w) * weight) / (1. + 4. * weight);
+    out_color = vec4(clamp(result, 0., 1.), 1.);
+}
@@ -0,0 +1,73 @@
+#version 140
+
+const uint COLOR_TYPE = uint(0);
+const uint TEXTURE_RGB_TYPE = uint(1);
+
+const uint DIRECTIONAL_LIGHT = uint(0);
+const uint POINT_LIGHT = uint(1);
+const uint SPOT_LIGHT = uint(2);
+
+const int MAX_LIGHTS = 8;
+
+in vec2 v_tex_coord;
+in vec3 v_normal;
+in vec3 v_world_pos;
+
+uniform sampler2D tex;
+// Scales the specular highlight by its red channel
+uniform sampler2D specular_map;
+uniform vec3 color;
+uniform uint type;
+uniform float shininess;
+uniform float specular_strength;
+
+uniform vec3 camera_position;
+uniform uint light_count;
+uniform vec3 ambient;
+uniform uint light_type[MAX_LIGHTS];
+uniform vec3 light_position[MAX_LIGHTS];
+uniform vec3 light_direction[MAX_LIGHTS];
+uniform vec3 light_color[MAX_LIGHTS];
+uniform float light_range[MAX_LIGHTS];
+uniform float light_cutoff[MAX_LIGHTS];
+
+out vec4 out_color;
+
+void main() {
+    vec4 base;
+    if (type == COLOR_TYPE) {
+        base = vec4(color, 1.);
+    } else {
+        base = texture(tex, v_tex_coord);
+    }
+
+    vec3 normal = normalize(v_normal);
+    float specular_scale = texture(specular_map, v_tex_coord).r;
+    vec3 to_camera = normalize(camera_position - v_world_pos);
+    vec3 diffuse = ambient;
+    vec3 specular = vec3(0.);
+    for (int i = 0; i < int(light_count); i++) {
+        vec3 to_light;
+        float attenuation = 1.;
+        if (light_type[i] == DIRECTIONAL_LIGHT) {
+            to_light = -light_direction[i];
+        } else {
+            vec3 diff = light_position[i] - v_world_pos;
+            to_light = normalize(diff);
+            attenuation = clamp(1. - length(diff) / light_range[i], 0., 1.);
+            if (light_type[i] == SPOT_LIGHT && dot(-to_light, light_direction[i]) < light_cutoff[i]) {
+                attenuation = 0.;
+            }
+        }
+
+        float n_dot_l = dot(normal, to_light);
+        diffuse += light_color[i] * max(n_dot_l, 0.) * attenuation;
+        if (n_dot_l > 0.) {
+            vec3 reflected = reflect(-to_light, normal);
+            float s = pow(max(dot(reflected, to_camera), 0.), shininess);
+            specular += light_color[i] * s * specular_strength * specular_scale * attenuation;
+        }
+    }
+
+    out_color = vec4(base.rgb * diffuse + specular, base.a);
+}
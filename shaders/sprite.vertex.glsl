@@ -0,0 +1,28 @@
+#version 140
+
+in vec3 position;
+in vec2 tex_coord;
+
+uniform mat4 proj_matrix;
+uniform mat4 view_matrix;
+layout(std140) uniform ObjectBlock {
+    mat4 transform;
+};
+// Offset and size of the sprite sheet frame in texture coordinates
+uniform vec4 frame;
+uniform uint screen_space;
+
+out vec3 v_coord;
+out vec2 v_tex_coord;
+out vec3 v_color;
+
+void main() {
+    v_coord = position;
+    v_tex_coord = frame.xy + tex_coord * frame.zw;
+    v_color = vec3(1.);
+    if (screen_space == uint(1)) {
+        gl_Position = transform * vec4(position, 1.);
+    } else {
+        gl_Position = proj_matrix * view_matrix * transform * vec4(position, 1.);
+    }
+}
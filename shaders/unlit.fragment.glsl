@@ -3,13 +3,18 @@
 const uint COLOR_TYPE = uint(0);
 const uint TEXTURE_RGB_TYPE = uint(1);
 const uint TEXTURE_ALPHA_TYPE = uint(2);
+const uint TEXTURE_ALPHA_FADE_TYPE = uint(3);
+const uint VERTEX_COLOR_TYPE = uint(4);
+const uint TEXTURE_TINT_TYPE = uint(5);
 
 in vec3 v_coord;
 in vec2 v_tex_coord;
+in vec3 v_color;
 
 uniform sampler2D tex;
 uniform vec3 color;
 uniform uint type;
+uniform float alpha;
 
 out vec4 out_color;
 
@@ -21,5 +26,12 @@ void main() {
     } else if (type == TEXTURE_ALPHA_TYPE) {
         float a = texture(tex, v_tex_coord).r;
         out_color = vec4(color, a);
+    } else if (type == TEXTURE_ALPHA_FADE_TYPE) {
+        float a = texture(tex, v_tex_coord).r;
+        out_color = vec4(color, a * alpha);
+    } else if (type == VERTEX_COLOR_TYPE) {
+        out_color = vec4(v_color, 1.);
+    } else if (type == TEXTURE_TINT_TYPE) {
+        out_color = texture(tex, v_tex_coord) * vec4(color, alpha);
     }
 }
@@ -0,0 +1,14 @@
+#version 140
+
+in vec2 v_tex_coord;
+in vec2 v_tile;
+
+uniform sampler2D tex;
+uniform float tile_size;
+
+out vec4 out_color;
+
+void main() {
+    // Merged faces span several blocks, so repeat the tile across the face
+    out_color = texture(tex, (v_tile + fract(v_tex_coord)) * tile_size);
+}
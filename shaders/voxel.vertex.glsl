@@ -0,0 +1,20 @@
+#version 140
+
+in vec3 position;
+in vec2 tex_coord;
+in vec2 tile;
+
+uniform mat4 proj_matrix;
+uniform mat4 view_matrix;
+layout(std140) uniform ObjectBlock {
+    mat4 transform;
+};
+
+out vec2 v_tex_coord;
+out vec2 v_tile;
+
+void main() {
+    v_tex_coord = tex_coord;
+    v_tile = tile;
+    gl_Position = proj_matrix * view_matrix * transform * vec4(position * .1, 1.);
+}
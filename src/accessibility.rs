@@ -0,0 +1,159 @@
+use draw::TextStyle;
+
+/// A kind of color blindness, each missing one of the eye's three kinds of cones
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ColorVision {
+    /// No red cones
+    Protanopia,
+    /// No green cones, the most common kind
+    Deuteranopia,
+    /// No blue cones
+    Tritanopia,
+}
+
+/// A full screen color filter for color blind players, applied by `grading::ColorGrading`
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ColorblindFilter {
+    None,
+    /// Shows the frame as it looks with the given color vision, e.g. to check that a game stays
+    /// playable with it
+    Simulate(ColorVision),
+    /// Moves the differences the given color vision loses into colors it can still tell apart
+    Correct(ColorVision),
+}
+
+impl ColorblindFilter {
+    /// The filter as a matrix multiplying linear RGB colors, row by row
+    pub fn matrix(&self) -> [[f32; 3]; 3] {
+        match *self {
+            ColorblindFilter::None => IDENTITY,
+            ColorblindFilter::Simulate(vision) => simulation(vision),
+            ColorblindFilter::Correct(vision) => {
+                // Daltonization: the error between the real and the simulated color, shifted
+                // into the channels the color vision can still see, is added to the color
+                let sim = simulation(vision);
+                let shift = match vision {
+                    ColorVision::Protanopia | ColorVision::Deuteranopia => {
+                        [[0., 0., 0.], [0.7, 1., 0.], [0.7, 0., 1.]]
+                    }
+                    ColorVision::Tritanopia => [[1., 0., 0.7], [0., 1., 0.7], [0., 0., 0.]],
+                };
+                let mut m = IDENTITY;
+                for i in 0..3 {
+                    for j in 0..3 {
+                        for k in 0..3 {
+                            m[i][j] += shift[i][k] * (IDENTITY[k][j] - sim[k][j]);
+                        }
+                    }
+                }
+                m
+            }
+        }
+    }
+}
+
+const IDENTITY: [[f32; 3]; 3] = [[1., 0., 0.], [0., 1., 0.], [0., 0., 1.]];
+
+// Machado, Oliveira and Fernandes' simulation of complete dichromacy
+fn simulation(vision: ColorVision) -> [[f32; 3]; 3] {
+    match vision {
+        ColorVision::Protanopia => [[0.152286, 1.052583, -0.204868],
+                                    [0.114503, 0.786281, 0.099216],
+                                    [-0.003882, -0.048116, 1.051998]],
+        ColorVision::Deuteranopia => [[0.367322, 0.860646, -0.227968],
+                                      [0.280085, 0.672501, 0.047413],
+                                      [-0.011820, 0.042940, 0.968881]],
+        ColorVision::Tritanopia => [[1.255528, -0.076749, -0.178779],
+                                    [-0.078411, 0.930809, 0.147602],
+                                    [0.004733, 0.691367, 0.303900]],
+    }
+}
+
+/// Options that make games easier to play for people with impaired vision. Games start with the
+/// options in `EngineConfig::accessibility`, and settings menus change them with
+/// `EngineContext::set_accessibility`, which publishes `EngineEvent::AccessibilityChanged`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Accessibility {
+    /// Applied to the frame by `grading::ColorGrading`
+    pub colorblind: ColorblindFilter,
+    /// Multiplies the size of everything laid out by `layout::Anchored`, including its offset
+    /// from the anchor
+    pub ui_scale: f32,
+    /// Draws all text in white with a black outline on a black background, see `text_style`
+    pub high_contrast_text: bool,
+}
+
+impl Default for Accessibility {
+    fn default() -> Self {
+        Accessibility {
+            colorblind: ColorblindFilter::None,
+            ui_scale: 1.,
+            high_contrast_text: false,
+        }
+    }
+}
+
+impl Accessibility {
+    /// The style text is drawn with instead of `style`. Text keeps the style it was drawn with
+    /// until it's rebuilt, e.g. by `Text::set_style`.
+    pub fn text_style(&self, style: TextStyle) -> TextStyle {
+        if !self.high_contrast_text {
+            return style;
+        }
+        TextStyle {
+            color: [1., 1., 1.],
+            shadow_color: None,
+            outline_color: Some([0., 0., 0.]),
+            outline_width: style.outline_width.max(2.),
+            background_color: Some([0., 0., 0.]),
+            .. style
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ColorVision, ColorblindFilter};
+
+    fn apply(m: [[f32; 3]; 3], c: [f32; 3]) -> [f32; 3] {
+        let mut out = [0.; 3];
+        for i in 0..3 {
+            out[i] = m[i][0] * c[0] + m[i][1] * c[1] + m[i][2] * c[2];
+        }
+        out
+    }
+
+    fn assert_close(a: [f32; 3], b: [f32; 3]) {
+        for i in 0..3 {
+            assert!((a[i] - b[i]).abs() < 1e-3, "{:?} != {:?}", a, b);
+        }
+    }
+
+    #[test]
+    fn filters_keep_greys() {
+        let visions = [ColorVision::Protanopia, ColorVision::Deuteranopia,
+                       ColorVision::Tritanopia];
+        for &vision in visions.iter() {
+            for &filter in [ColorblindFilter::Simulate(vision),
+                            ColorblindFilter::Correct(vision)].iter() {
+                assert_close(apply(filter.matrix(), [1., 1., 1.]), [1., 1., 1.]);
+                assert_close(apply(filter.matrix(), [0.5, 0.5, 0.5]), [0.5, 0.5, 0.5]);
+            }
+        }
+    }
+
+    #[test]
+    fn correction_separates_red_and_green() {
+        let vision = ColorVision::Deuteranopia;
+        let (red, green) = ([1., 0., 0.], [0., 1., 0.]);
+        let distance = |filter: ColorblindFilter| {
+            // Both as the player sees them after the filter
+            let seen = |c| apply(ColorblindFilter::Simulate(vision).matrix(),
+                                 apply(filter.matrix(), c));
+            let (a, b) = (seen(red), seen(green));
+            (0..3).fold(0., |sum, i| sum + (a[i] - b[i]) * (a[i] - b[i]))
+        };
+        assert!(distance(ColorblindFilter::Correct(vision)) >
+                distance(ColorblindFilter::None));
+    }
+}
@@ -0,0 +1,437 @@
+use std::collections::HashMap;
+
+use coroutine::Coroutine;
+
+use nalgebra::Vec3;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Status {
+    Success,
+    Failure,
+    Running,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum BlackboardValue {
+    Bool(bool),
+    Int(i64),
+    Float(f32),
+    Vec3(Vec3<f32>),
+    Str(String),
+}
+
+/// Per agent memory shared by the nodes of a tree, e.g. the current target or the last position
+/// the player was seen at
+#[derive(Clone, Debug)]
+pub struct Blackboard {
+    values: HashMap<String, BlackboardValue>,
+}
+
+impl Blackboard {
+    pub fn new() -> Self {
+        Blackboard { values: HashMap::new() }
+    }
+
+    pub fn get(&self, key: &str) -> Option<&BlackboardValue> {
+        self.values.get(key)
+    }
+
+    pub fn set(&mut self, key: &str, value: BlackboardValue) {
+        self.values.insert(key.to_owned(), value);
+    }
+
+    pub fn remove(&mut self, key: &str) -> Option<BlackboardValue> {
+        self.values.remove(key)
+    }
+
+    /// False if `key` isn't set or isn't a bool
+    pub fn get_bool(&self, key: &str) -> bool {
+        match self.values.get(key) {
+            Some(&BlackboardValue::Bool(b)) => b,
+            _ => false,
+        }
+    }
+
+    pub fn get_float(&self, key: &str) -> Option<f32> {
+        match self.values.get(key) {
+            Some(&BlackboardValue::Float(f)) => Some(f),
+            _ => None,
+        }
+    }
+
+    pub fn get_vec3(&self, key: &str) -> Option<Vec3<f32>> {
+        match self.values.get(key) {
+            Some(&BlackboardValue::Vec3(v)) => Some(v),
+            _ => None,
+        }
+    }
+}
+
+pub type Task<T> = Box<FnMut(&mut T, &mut Blackboard) -> Status>;
+
+enum NodeKind<T> {
+    /// Runs children in order until one fails
+    Sequence(Vec<Node<T>>),
+    /// Runs children in order until one succeeds
+    Selector(Vec<Node<T>>),
+    /// Swaps success and failure
+    Invert(Box<Node<T>>),
+    /// Succeeds once the child finishes, whatever its result
+    Succeed(Box<Node<T>>),
+    /// Runs the child `count` times, failing as soon as it fails
+    Repeat(usize, Box<Node<T>>),
+    /// Runs the child until it fails, then succeeds
+    UntilFail(Box<Node<T>>),
+    Task(Task<T>),
+    /// Runs a fresh coroutine from the factory until it finishes, then succeeds
+    Script(Box<Fn() -> Coroutine<T>>, Option<Coroutine<T>>),
+}
+
+/// A node of a behavior tree. Composites and decorators remember which child is running, so a
+/// running task is resumed on the next tick instead of the tree being evaluated from the top.
+pub struct Node<T> {
+    name: String,
+    kind: NodeKind<T>,
+    // Index of the running child for composites, or the number of finished repetitions
+    current: usize,
+    // Status from the last tick the node ran in, for debugging
+    last_status: Option<Status>,
+}
+
+impl<T> Node<T> {
+    fn new(name: &str, kind: NodeKind<T>) -> Self {
+        Node { name: name.to_owned(), kind: kind, current: 0, last_status: None }
+    }
+
+    pub fn sequence(name: &str, children: Vec<Node<T>>) -> Self {
+        Node::new(name, NodeKind::Sequence(children))
+    }
+
+    pub fn selector(name: &str, children: Vec<Node<T>>) -> Self {
+        Node::new(name, NodeKind::Selector(children))
+    }
+
+    pub fn invert(child: Node<T>) -> Self {
+        Node::new("invert", NodeKind::Invert(Box::new(child)))
+    }
+
+    pub fn succeed(child: Node<T>) -> Self {
+        Node::new("succeed", NodeKind::Succeed(Box::new(child)))
+    }
+
+    pub fn repeat(count: usize, child: Node<T>) -> Self {
+        Node::new("repeat", NodeKind::Repeat(count, Box::new(child)))
+    }
+
+    pub fn until_fail(child: Node<T>) -> Self {
+        Node::new("until_fail", NodeKind::UntilFail(Box::new(child)))
+    }
+
+    /// A leaf calling into game code
+    pub fn task<F>(name: &str, task: F) -> Self
+        where F: FnMut(&mut T, &mut Blackboard) -> Status + 'static {
+        Node::new(name, NodeKind::Task(Box::new(task)))
+    }
+
+    /// A leaf that succeeds or fails depending on `condition`, never running
+    pub fn condition<F>(name: &str, condition: F) -> Self
+        where F: Fn(&T, &Blackboard) -> bool + 'static {
+        Node::task(name, move |agent: &mut T, bb: &mut Blackboard| {
+            if condition(agent, bb) { Status::Success } else { Status::Failure }
+        })
+    }
+
+    /// A leaf running a coroutine script, which keeps the node running until the script
+    /// finishes. Every time the node is entered it starts a new script from `script`.
+    pub fn script<F: Fn() -> Coroutine<T> + 'static>(name: &str, script: F) -> Self {
+        Node::new(name, NodeKind::Script(Box::new(script), None))
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn tick(&mut self, agent: &mut T, bb: &mut Blackboard, dt: f32) -> Status {
+        let status = match self.kind {
+            NodeKind::Sequence(ref mut children) => {
+                Self::tick_composite(children, &mut self.current, Status::Failure, agent, bb, dt)
+            }
+            NodeKind::Selector(ref mut children) => {
+                Self::tick_composite(children, &mut self.current, Status::Success, agent, bb, dt)
+            }
+            NodeKind::Invert(ref mut child) => {
+                match child.tick(agent, bb, dt) {
+                    Status::Success => Status::Failure,
+                    Status::Failure => Status::Success,
+                    Status::Running => Status::Running,
+                }
+            }
+            NodeKind::Succeed(ref mut child) => {
+                match child.tick(agent, bb, dt) {
+                    Status::Running => Status::Running,
+                    _ => Status::Success,
+                }
+            }
+            NodeKind::Repeat(count, ref mut child) => {
+                match child.tick(agent, bb, dt) {
+                    Status::Success => {
+                        self.current += 1;
+                        if self.current >= count {
+                            self.current = 0;
+                            Status::Success
+                        } else {
+                            Status::Running
+                        }
+                    }
+                    Status::Failure => {
+                        self.current = 0;
+                        Status::Failure
+                    }
+                    Status::Running => Status::Running,
+                }
+            }
+            NodeKind::UntilFail(ref mut child) => {
+                match child.tick(agent, bb, dt) {
+                    Status::Failure => Status::Success,
+                    _ => Status::Running,
+                }
+            }
+            NodeKind::Task(ref mut task) => task(agent, bb),
+            NodeKind::Script(ref factory, ref mut running) => {
+                let mut script = running.take().unwrap_or_else(|| factory());
+                if script.resume(agent, dt) {
+                    Status::Success
+                } else {
+                    *running = Some(script);
+                    Status::Running
+                }
+            }
+        };
+        self.last_status = Some(status);
+        status
+    }
+
+    // Ticks children starting from the running one, stopping at the first child that is running
+    // or returns `stop`
+    fn tick_composite(children: &mut [Node<T>], current: &mut usize, stop: Status,
+                      agent: &mut T, bb: &mut Blackboard, dt: f32) -> Status {
+        while *current < children.len() {
+            let status = children[*current].tick(agent, bb, dt);
+            if status == Status::Running {
+                return status;
+            }
+            if status == stop {
+                *current = 0;
+                return status;
+            }
+            *current += 1;
+        }
+        *current = 0;
+        if stop == Status::Failure { Status::Success } else { Status::Failure }
+    }
+
+    fn children(&self) -> Vec<&Node<T>> {
+        match self.kind {
+            NodeKind::Sequence(ref children) | NodeKind::Selector(ref children) => {
+                children.iter().collect()
+            }
+            NodeKind::Invert(ref child) | NodeKind::Succeed(ref child) |
+            NodeKind::Repeat(_, ref child) | NodeKind::UntilFail(ref child) => vec![&**child],
+            NodeKind::Task(_) | NodeKind::Script(..) => Vec::new(),
+        }
+    }
+
+    fn active_branch<'b>(&'b self, branch: &mut Vec<&'b str>) {
+        if self.last_status != Some(Status::Running) {
+            return;
+        }
+        branch.push(&self.name);
+        for child in self.children() {
+            if child.last_status == Some(Status::Running) {
+                child.active_branch(branch);
+                return;
+            }
+        }
+    }
+
+    fn debug_lines(&self, depth: usize, lines: &mut Vec<String>) {
+        let status = match self.last_status {
+            Some(Status::Success) => "+",
+            Some(Status::Failure) => "-",
+            Some(Status::Running) => ">",
+            None => " ",
+        };
+        let indent: String = (0..depth).map(|_| "  ").collect();
+        lines.push(format!("{}{} {}", indent, status, self.name));
+        for child in self.children() {
+            child.debug_lines(depth + 1, lines);
+        }
+    }
+}
+
+/// A behavior tree with its own blackboard. Each agent gets its own tree, which is ticked from the
+/// agent's `update`.
+pub struct BehaviorTree<T> {
+    root: Node<T>,
+    blackboard: Blackboard,
+}
+
+impl<T> BehaviorTree<T> {
+    pub fn new(root: Node<T>) -> Self {
+        BehaviorTree { root: root, blackboard: Blackboard::new() }
+    }
+
+    /// Builds a tree from an indented text definition, looking up leaves in `registry`:
+    ///
+    /// ```text
+    /// selector
+    ///   sequence
+    ///     enemy_visible
+    ///     repeat 3
+    ///       attack
+    ///   wander
+    /// ```
+    ///
+    /// `sequence` and `selector` take any number of children, and `invert`, `succeed`,
+    /// `repeat <n>` and `until_fail` take exactly one. Anything else is a leaf name. A composite
+    /// can be given a name for debugging, e.g. `sequence attack_player`.
+    pub fn from_definition(definition: &str, registry: &LeafRegistry<T>) -> Result<Self, String> {
+        let lines: Vec<_> = definition.lines()
+            .enumerate()
+            .filter(|&(_, l)| !l.trim().is_empty() && !l.trim().starts_with('#'))
+            .map(|(i, l)| (i + 1, l.len() - l.trim_left().len(), l.trim()))
+            .collect();
+        if lines.is_empty() {
+            return Err("Empty behavior tree definition".to_owned());
+        }
+
+        let mut pos = 0;
+        let root = try!(parse_node(&lines, &mut pos, registry));
+        if pos < lines.len() {
+            return Err(format!("Line {}: more than one root node", lines[pos].0));
+        }
+        Ok(BehaviorTree::new(root))
+    }
+
+    /// `dt` is the seconds since the previous tick, which script nodes count their waits down by
+    pub fn tick(&mut self, agent: &mut T, dt: f32) -> Status {
+        self.root.tick(agent, &mut self.blackboard, dt)
+    }
+
+    pub fn blackboard(&self) -> &Blackboard {
+        &self.blackboard
+    }
+
+    pub fn blackboard_mut(&mut self) -> &mut Blackboard {
+        &mut self.blackboard
+    }
+
+    /// Names of the nodes from the root down to the running leaf, empty if the tree finished on
+    /// the last tick
+    pub fn active_branch(&self) -> Vec<&str> {
+        let mut branch = Vec::new();
+        self.root.active_branch(&mut branch);
+        branch
+    }
+
+    /// The whole tree with the status of every node from the last tick it ran in (`>` running,
+    /// `+` succeeded, `-` failed), one node per line. Meant to be shown with a `Text`.
+    pub fn debug_string(&self) -> String {
+        let mut lines = Vec::new();
+        self.root.debug_lines(0, &mut lines);
+        lines.join("\n")
+    }
+}
+
+/// Leaves that tree definitions can refer to by name. Every lookup creates a new leaf, so one
+/// registry can build trees for many agents.
+pub struct LeafRegistry<T> {
+    leaves: HashMap<String, Box<Fn() -> Node<T>>>,
+}
+
+impl<T: 'static> LeafRegistry<T> {
+    pub fn new() -> Self {
+        LeafRegistry { leaves: HashMap::new() }
+    }
+
+    pub fn task<F>(&mut self, name: &str, task: F)
+        where F: Fn(&mut T, &mut Blackboard) -> Status + Clone + 'static {
+        let owned = name.to_owned();
+        self.leaves.insert(name.to_owned(), Box::new(move || Node::task(&owned, task.clone())));
+    }
+
+    pub fn condition<F>(&mut self, name: &str, condition: F)
+        where F: Fn(&T, &Blackboard) -> bool + Clone + 'static {
+        let owned = name.to_owned();
+        self.leaves.insert(name.to_owned(),
+                           Box::new(move || Node::condition(&owned, condition.clone())));
+    }
+
+    pub fn script<F>(&mut self, name: &str, script: F)
+        where F: Fn() -> Coroutine<T> + Clone + 'static {
+        let owned = name.to_owned();
+        self.leaves.insert(name.to_owned(), Box::new(move || Node::script(&owned, script.clone())));
+    }
+
+    fn create(&self, name: &str) -> Option<Node<T>> {
+        self.leaves.get(name).map(|f| f())
+    }
+}
+
+// Parses the node on `lines[*pos]` and its children, which are the following lines indented
+// further than it
+fn parse_node<T: 'static>(lines: &[(usize, usize, &str)], pos: &mut usize,
+                          registry: &LeafRegistry<T>) -> Result<Node<T>, String> {
+    let (line, indent, text) = lines[*pos];
+    *pos += 1;
+
+    let mut children = Vec::new();
+    if *pos < lines.len() && lines[*pos].1 > indent {
+        let child_indent = lines[*pos].1;
+        while *pos < lines.len() && lines[*pos].1 > indent {
+            if lines[*pos].1 != child_indent {
+                return Err(format!("Line {}: inconsistent indentation", lines[*pos].0));
+            }
+            children.push(try!(parse_node(lines, pos, registry)));
+        }
+    }
+
+    let words: Vec<_> = text.split_whitespace().collect();
+    let single_child = |mut children: Vec<Node<T>>| {
+        if children.len() == 1 {
+            Ok(children.pop().unwrap())
+        } else {
+            Err(format!("Line {}: `{}` needs exactly one child", line, words[0]))
+        }
+    };
+
+    match words[0] {
+        "sequence" | "selector" => {
+            let name = if words.len() > 1 { words[1] } else { words[0] };
+            if children.is_empty() {
+                return Err(format!("Line {}: `{}` has no children", line, words[0]));
+            }
+            Ok(if words[0] == "sequence" {
+                Node::sequence(name, children)
+            } else {
+                Node::selector(name, children)
+            })
+        }
+        "invert" => single_child(children).map(Node::invert),
+        "succeed" => single_child(children).map(Node::succeed),
+        "until_fail" => single_child(children).map(Node::until_fail),
+        "repeat" => {
+            let count = match words.get(1).and_then(|n| n.parse().ok()) {
+                Some(count) => count,
+                None => return Err(format!("Line {}: `repeat` needs a count", line)),
+            };
+            single_child(children).map(|child| Node::repeat(count, child))
+        }
+        name => {
+            if !children.is_empty() {
+                return Err(format!("Line {}: leaf `{}` can't have children", line, name));
+            }
+            registry.create(name).ok_or_else(|| format!("Line {}: unknown leaf `{}`", line, name))
+        }
+    }
+}
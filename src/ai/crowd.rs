@@ -0,0 +1,223 @@
+use std::f32::consts::PI;
+
+use draw::{GameObject, Object, ObjectBuilder, Vertex};
+use material::Material;
+
+use glium::{DepthTest, Display, DrawParameters, VertexBuffer};
+use glium::index::{NoIndices, PrimitiveType};
+
+use nalgebra::{dot, Norm, Vec3};
+
+/// Number of directions candidate velocities are sampled in
+const SAMPLE_DIRECTIONS: usize = 16;
+/// Number of speeds sampled in every direction
+const SAMPLE_SPEEDS: usize = 3;
+
+#[derive(Copy, Clone, Debug)]
+pub struct CrowdAgent {
+    pub position: Vec3<f32>,
+    pub velocity: Vec3<f32>,
+    /// Velocity the agent would like to move at, e.g. towards the next point on its path
+    pub preferred_velocity: Vec3<f32>,
+    pub radius: f32,
+    pub max_speed: f32,
+    /// Agents with a higher priority take a smaller share of the avoidance when they meet others
+    pub priority: f32,
+}
+
+impl CrowdAgent {
+    pub fn new(position: Vec3<f32>, radius: f32, max_speed: f32) -> Self {
+        CrowdAgent {
+            position: position,
+            velocity: Vec3::new(0., 0., 0.),
+            preferred_velocity: Vec3::new(0., 0., 0.),
+            radius: radius,
+            max_speed: max_speed,
+            priority: 1.,
+        }
+    }
+}
+
+/// Local avoidance for many agents moving on the ground plane using reciprocal velocity obstacles.
+/// Every step each agent picks the velocity closest to its preferred one that doesn't collide with
+/// its neighbors soon, assuming they share the work of avoiding each other.
+pub struct Crowd {
+    agents: Vec<CrowdAgent>,
+    /// Only agents closer than this are avoided
+    pub neighbor_distance: f32,
+    /// Collisions further than this many seconds away are ignored
+    pub time_horizon: f32,
+    /// How much collision time matters compared to deviating from the preferred velocity
+    pub avoidance_weight: f32,
+}
+
+impl Crowd {
+    pub fn new() -> Self {
+        Crowd { agents: Vec::new(), neighbor_distance: 5., time_horizon: 2., avoidance_weight: 1. }
+    }
+
+    /// Adds an agent, returning its index
+    pub fn add(&mut self, agent: CrowdAgent) -> usize {
+        self.agents.push(agent);
+        self.agents.len() - 1
+    }
+
+    pub fn agents(&self) -> &[CrowdAgent] {
+        &self.agents
+    }
+
+    pub fn agent_mut(&mut self, id: usize) -> &mut CrowdAgent {
+        &mut self.agents[id]
+    }
+
+    pub fn set_preferred_velocity(&mut self, id: usize, velocity: Vec3<f32>) {
+        self.agents[id].preferred_velocity = velocity;
+    }
+
+    /// Picks new velocities for every agent and moves them `dt` seconds
+    pub fn step(&mut self, dt: f32) {
+        let velocities: Vec<_> = (0..self.agents.len()).map(|i| self.choose_velocity(i)).collect();
+        for (agent, velocity) in self.agents.iter_mut().zip(velocities) {
+            agent.velocity = velocity;
+            agent.position = agent.position + velocity * dt;
+        }
+    }
+
+    fn choose_velocity(&self, i: usize) -> Vec3<f32> {
+        let agent = &self.agents[i];
+        let preferred = flatten(agent.preferred_velocity);
+
+        let mut best = Vec3::new(0., 0., 0.);
+        let mut best_penalty = ::std::f32::MAX;
+        for candidate in Self::candidates(agent, preferred) {
+            let penalty = self.penalty(i, candidate, preferred);
+            if penalty < best_penalty {
+                best_penalty = penalty;
+                best = candidate;
+            }
+        }
+        best
+    }
+
+    // The preferred velocity, standing still, and rings of velocities up to the max speed
+    fn candidates(agent: &CrowdAgent, preferred: Vec3<f32>) -> Vec<Vec3<f32>> {
+        let mut candidates = vec![clamp_speed(preferred, agent.max_speed), Vec3::new(0., 0., 0.)];
+        for s in 1..SAMPLE_SPEEDS + 1 {
+            let speed = agent.max_speed * s as f32 / SAMPLE_SPEEDS as f32;
+            for d in 0..SAMPLE_DIRECTIONS {
+                let angle = 2. * PI * d as f32 / SAMPLE_DIRECTIONS as f32;
+                candidates.push(Vec3::new(angle.cos(), angle.sin(), 0.) * speed);
+            }
+        }
+        candidates
+    }
+
+    fn penalty(&self, i: usize, candidate: Vec3<f32>, preferred: Vec3<f32>) -> f32 {
+        let agent = &self.agents[i];
+        let mut time_to_collision = ::std::f32::MAX;
+        for (j, other) in self.agents.iter().enumerate() {
+            let offset = flatten(other.position - agent.position);
+            if j == i || offset.sqnorm() > self.neighbor_distance * self.neighbor_distance {
+                continue;
+            }
+
+            // Share of the avoidance this agent takes on
+            let share = other.priority / (agent.priority + other.priority);
+            let velocity = flatten(agent.velocity);
+            let relative = (candidate - velocity) / share + velocity - flatten(other.velocity);
+
+            let t = collision_time(offset, relative, agent.radius + other.radius);
+            if t < time_to_collision {
+                time_to_collision = t;
+            }
+        }
+
+        let deviation = (candidate - preferred).norm();
+        if time_to_collision >= self.time_horizon {
+            deviation
+        } else {
+            self.avoidance_weight / time_to_collision.max(1e-3) + deviation
+        }
+    }
+}
+
+fn flatten(v: Vec3<f32>) -> Vec3<f32> {
+    Vec3::new(v.x, v.y, 0.)
+}
+
+fn clamp_speed(v: Vec3<f32>, max: f32) -> Vec3<f32> {
+    let len = v.norm();
+    if len > max { v * (max / len) } else { v }
+}
+
+// Time until a point moving at `velocity` from the origin enters the circle of `radius` around
+// `center`, or 0 if it is already inside
+fn collision_time(center: Vec3<f32>, velocity: Vec3<f32>, radius: f32) -> f32 {
+    let c = center.sqnorm() - radius * radius;
+    if c < 0. {
+        return 0.;
+    }
+    let a = velocity.sqnorm();
+    let b = dot(&center, &velocity);
+    let discriminant = b * b - a * c;
+    if a == 0. || b <= 0. || discriminant < 0. {
+        return ::std::f32::MAX;
+    }
+    (b - discriminant.sqrt()) / a
+}
+
+/// Draws every agent of a crowd as a circle with a line along its velocity. Call `rebuild` after
+/// stepping the crowd.
+pub struct CrowdDebug<'a> {
+    parent: Object<'a>,
+}
+
+impl GameObject for CrowdDebug<'static> {
+    fn parent(&self) -> &Object {
+        &self.parent
+    }
+}
+
+impl<'a> CrowdDebug<'a> {
+    pub fn new(display: &Display, crowd: &Crowd, color: [f32; 3]) -> Self {
+        CrowdDebug { parent: Self::build(display, crowd, Material::color(color)) }
+    }
+
+    pub fn rebuild(&mut self, display: &Display, crowd: &Crowd) {
+        let material = self.parent.material.clone();
+        self.parent = Self::build(display, crowd, material);
+    }
+
+    fn build(display: &Display, crowd: &Crowd, material: Material) -> Object<'a> {
+        const SEGMENTS: usize = 16;
+
+        let mut shape = Vec::new();
+        for agent in crowd.agents() {
+            let p = agent.position;
+            for s in 0..SEGMENTS {
+                let a0 = 2. * PI * s as f32 / SEGMENTS as f32;
+                let a1 = 2. * PI * (s + 1) as f32 / SEGMENTS as f32;
+                shape.push(Vertex::new(p.x + a0.cos() * agent.radius,
+                                       p.y + a0.sin() * agent.radius, p.z));
+                shape.push(Vertex::new(p.x + a1.cos() * agent.radius,
+                                       p.y + a1.sin() * agent.radius, p.z));
+            }
+            let end = p + agent.velocity;
+            shape.push(Vertex::new(p.x, p.y, p.z));
+            shape.push(Vertex::new(end.x, end.y, end.z));
+        }
+
+        let params = DrawParameters {
+            depth_test: DepthTest::IfLess,
+            depth_write: true,
+            .. Default::default()
+        };
+
+        let vb = VertexBuffer::new(display, shape).into_vertex_buffer_any();
+        ObjectBuilder::new()
+            .vertex_buffer(vb, NoIndices(PrimitiveType::LinesList))
+            .draw_params(params)
+            .material(material)
+            .build()
+    }
+}
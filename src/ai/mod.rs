@@ -0,0 +1,9 @@
+//! Building blocks for non-player characters
+
+pub mod behavior_tree;
+pub mod crowd;
+pub mod steering;
+pub mod vision;
+
+pub use self::behavior_tree::{BehaviorTree, Blackboard, BlackboardValue, LeafRegistry, Node,
+                              Status};
@@ -0,0 +1,287 @@
+use std::f32::consts::PI;
+
+use nalgebra::{dot, Norm, Vec3};
+
+/// The movement state steering behaviors read. Steering functions return an acceleration, which
+/// `apply` integrates or which can be handed to whatever else moves the agent.
+#[derive(Copy, Clone, Debug)]
+pub struct Agent {
+    pub position: Vec3<f32>,
+    pub velocity: Vec3<f32>,
+    pub max_speed: f32,
+    /// Largest acceleration the agent can produce
+    pub max_force: f32,
+    pub radius: f32,
+}
+
+impl Agent {
+    pub fn new(position: Vec3<f32>, max_speed: f32, max_force: f32, radius: f32) -> Self {
+        Agent {
+            position: position,
+            velocity: Vec3::new(0., 0., 0.),
+            max_speed: max_speed,
+            max_force: max_force,
+            radius: radius,
+        }
+    }
+
+    /// Applies `acceleration` (clamped to `max_force`) for `dt` seconds
+    pub fn apply(&mut self, acceleration: Vec3<f32>, dt: f32) {
+        let acceleration = truncate(acceleration, self.max_force);
+        self.velocity = truncate(self.velocity + acceleration * dt, self.max_speed);
+        self.position = self.position + self.velocity * dt;
+    }
+}
+
+/// A sphere agents steer around
+#[derive(Copy, Clone, Debug)]
+pub struct Obstacle {
+    pub center: Vec3<f32>,
+    pub radius: f32,
+}
+
+fn truncate(v: Vec3<f32>, max: f32) -> Vec3<f32> {
+    let len = v.norm();
+    if len > max && len > 0. { v * (max / len) } else { v }
+}
+
+fn normalize_or_zero(v: Vec3<f32>) -> Vec3<f32> {
+    if v.sqnorm() > 0. { v.normalize() } else { v }
+}
+
+// Acceleration that turns the agent's velocity into `desired`
+fn steer_towards(agent: &Agent, desired: Vec3<f32>) -> Vec3<f32> {
+    truncate(desired - agent.velocity, agent.max_force)
+}
+
+/// Heads towards `target` at full speed
+pub fn seek(agent: &Agent, target: Vec3<f32>) -> Vec3<f32> {
+    steer_towards(agent, normalize_or_zero(target - agent.position) * agent.max_speed)
+}
+
+/// Heads away from `target` at full speed
+pub fn flee(agent: &Agent, target: Vec3<f32>) -> Vec3<f32> {
+    steer_towards(agent, normalize_or_zero(agent.position - target) * agent.max_speed)
+}
+
+/// Like `seek`, but slows down within `slowing_radius` of `target` to stop on it
+pub fn arrive(agent: &Agent, target: Vec3<f32>, slowing_radius: f32) -> Vec3<f32> {
+    let offset = target - agent.position;
+    let distance = offset.norm();
+    let speed = if distance < slowing_radius {
+        agent.max_speed * distance / slowing_radius
+    } else {
+        agent.max_speed
+    };
+    steer_towards(agent, normalize_or_zero(offset) * speed)
+}
+
+/// Wandering around aimlessly on the ground plane, by seeking a point that drifts around a circle
+/// in front of the agent
+#[derive(Copy, Clone, Debug)]
+pub struct Wander {
+    /// Distance from the agent to the center of the circle
+    pub distance: f32,
+    pub radius: f32,
+    /// Largest change of the angle on the circle per call, in radians
+    pub jitter: f32,
+    angle: f32,
+    seed: u32,
+}
+
+impl Wander {
+    /// `seed` should differ between agents so they don't all wander the same way
+    pub fn new(distance: f32, radius: f32, jitter: f32, seed: u32) -> Self {
+        Wander { distance: distance, radius: radius, jitter: jitter, angle: 0.,
+                 seed: if seed == 0 { 1 } else { seed } }
+    }
+
+    pub fn steer(&mut self, agent: &Agent) -> Vec3<f32> {
+        // xorshift, good enough for wandering
+        self.seed ^= self.seed << 13;
+        self.seed ^= self.seed >> 17;
+        self.seed ^= self.seed << 5;
+        let r = self.seed as f32 / ::std::u32::MAX as f32;
+        self.angle = (self.angle + (r * 2. - 1.) * self.jitter) % (2. * PI);
+
+        let heading = if agent.velocity.sqnorm() > 0. {
+            agent.velocity.normalize()
+        } else {
+            Vec3::new(1., 0., 0.)
+        };
+        let center = agent.position + heading * self.distance;
+        let target = center + Vec3::new(self.angle.cos(), self.angle.sin(), 0.) * self.radius;
+        seek(agent, target)
+    }
+}
+
+// Calls `f` with every neighbor within `radius` of `agent`, skipping the agent itself
+fn for_neighbors<F: FnMut(&Agent, Vec3<f32>, f32)>(agent: &Agent, neighbors: &[Agent],
+                                                   radius: f32, mut f: F) -> usize {
+    let mut count = 0;
+    for other in neighbors {
+        let offset = agent.position - other.position;
+        let distance = offset.norm();
+        if distance > 0. && distance < radius {
+            f(other, offset, distance);
+            count += 1;
+        }
+    }
+    count
+}
+
+/// Keeps away from neighbors within `radius`, more strongly the closer they are
+pub fn separation(agent: &Agent, neighbors: &[Agent], radius: f32) -> Vec3<f32> {
+    let mut push = Vec3::new(0., 0., 0.);
+    let count = for_neighbors(agent, neighbors, radius, |_, offset, distance| {
+        push = push + offset / (distance * distance);
+    });
+    if count == 0 {
+        return push;
+    }
+    steer_towards(agent, normalize_or_zero(push) * agent.max_speed)
+}
+
+/// Heads towards the center of the neighbors within `radius`
+pub fn cohesion(agent: &Agent, neighbors: &[Agent], radius: f32) -> Vec3<f32> {
+    let mut center = Vec3::new(0., 0., 0.);
+    let count = for_neighbors(agent, neighbors, radius, |other, _, _| {
+        center = center + other.position;
+    });
+    if count == 0 {
+        return center;
+    }
+    seek(agent, center / count as f32)
+}
+
+/// Matches the average velocity of the neighbors within `radius`
+pub fn alignment(agent: &Agent, neighbors: &[Agent], radius: f32) -> Vec3<f32> {
+    let mut velocity = Vec3::new(0., 0., 0.);
+    let count = for_neighbors(agent, neighbors, radius, |other, _, _| {
+        velocity = velocity + other.velocity;
+    });
+    if count == 0 {
+        return velocity;
+    }
+    steer_towards(agent, truncate(velocity / count as f32, agent.max_speed))
+}
+
+/// Steers sideways away from the nearest obstacle the agent would hit within `look_ahead`
+/// seconds at its current velocity
+pub fn avoid_obstacles(agent: &Agent, obstacles: &[Obstacle], look_ahead: f32) -> Vec3<f32> {
+    let speed = agent.velocity.norm();
+    if speed == 0. {
+        return Vec3::new(0., 0., 0.);
+    }
+    let heading = agent.velocity / speed;
+    let range = speed * look_ahead;
+
+    let mut nearest: Option<(f32, &Obstacle)> = None;
+    for obstacle in obstacles {
+        let to_center = obstacle.center - agent.position;
+        let along = dot(&to_center, &heading);
+        if along < 0. || along > range + obstacle.radius {
+            continue;
+        }
+        let closest = agent.position + heading * along;
+        let clearance = obstacle.radius + agent.radius;
+        if (obstacle.center - closest).sqnorm() < clearance * clearance {
+            if nearest.map_or(true, |(d, _)| along < d) {
+                nearest = Some((along, obstacle));
+            }
+        }
+    }
+
+    match nearest {
+        Some((along, obstacle)) => {
+            let closest = agent.position + heading * along;
+            let mut away = closest - obstacle.center;
+            if away.sqnorm() == 0. {
+                // Heading straight at the center, pick a side
+                away = Vec3::new(-heading.y, heading.x, 0.);
+            }
+            steer_towards(agent, normalize_or_zero(away) * agent.max_speed)
+        }
+        None => Vec3::new(0., 0., 0.),
+    }
+}
+
+/// How strongly each behavior contributes to an agent's steering. Behaviors with a weight of zero
+/// aren't evaluated.
+#[derive(Copy, Clone, Debug)]
+pub struct SteeringWeights {
+    pub seek: f32,
+    pub arrive: f32,
+    pub flee: f32,
+    pub wander: f32,
+    pub separation: f32,
+    pub cohesion: f32,
+    pub alignment: f32,
+    pub avoidance: f32,
+}
+
+impl Default for SteeringWeights {
+    fn default() -> Self {
+        SteeringWeights {
+            seek: 0.,
+            arrive: 1.,
+            flee: 0.,
+            wander: 0.,
+            separation: 1.5,
+            cohesion: 0.,
+            alignment: 0.,
+            avoidance: 2.,
+        }
+    }
+}
+
+/// Per agent steering configuration combining several behaviors
+#[derive(Copy, Clone, Debug)]
+pub struct Steering {
+    pub weights: SteeringWeights,
+    /// Radius within which other agents count as neighbors for flocking
+    pub neighbor_radius: f32,
+    pub slowing_radius: f32,
+    pub look_ahead: f32,
+    pub wander: Wander,
+}
+
+impl Steering {
+    pub fn new(weights: SteeringWeights) -> Self {
+        Steering {
+            weights: weights,
+            neighbor_radius: 2.,
+            slowing_radius: 1.,
+            look_ahead: 1.,
+            wander: Wander::new(2., 1., 0.3, 1),
+        }
+    }
+
+    /// Weighted sum of the configured behaviors. `target` is used by seek, arrive and flee.
+    pub fn steer(&mut self, agent: &Agent, target: Option<Vec3<f32>>, neighbors: &[Agent],
+                 obstacles: &[Obstacle]) -> Vec3<f32> {
+        let w = self.weights;
+        let mut total = Vec3::new(0., 0., 0.);
+        if let Some(target) = target {
+            if w.seek != 0. { total = total + seek(agent, target) * w.seek; }
+            if w.arrive != 0. {
+                total = total + arrive(agent, target, self.slowing_radius) * w.arrive;
+            }
+            if w.flee != 0. { total = total + flee(agent, target) * w.flee; }
+        }
+        if w.wander != 0. { total = total + self.wander.steer(agent) * w.wander; }
+        if w.separation != 0. {
+            total = total + separation(agent, neighbors, self.neighbor_radius) * w.separation;
+        }
+        if w.cohesion != 0. {
+            total = total + cohesion(agent, neighbors, self.neighbor_radius) * w.cohesion;
+        }
+        if w.alignment != 0. {
+            total = total + alignment(agent, neighbors, self.neighbor_radius) * w.alignment;
+        }
+        if w.avoidance != 0. {
+            total = total + avoid_obstacles(agent, obstacles, self.look_ahead) * w.avoidance;
+        }
+        truncate(total, agent.max_force)
+    }
+}
@@ -0,0 +1,165 @@
+use std::collections::HashMap;
+
+use ai::steering::Obstacle;
+use draw::{GameObject, Object, ObjectBuilder, Vertex};
+use material::Material;
+
+use glium::{DepthTest, Display, DrawParameters, VertexBuffer};
+use glium::index::{NoIndices, PrimitiveType};
+
+use nalgebra::{dot, Norm, Vec3};
+
+/// What an agent can see: everything within `half_angle` radians of the direction it is facing
+/// and closer than `max_distance`
+#[derive(Copy, Clone, Debug)]
+pub struct VisionCone {
+    pub half_angle: f32,
+    pub max_distance: f32,
+}
+
+impl VisionCone {
+    pub fn new(half_angle: f32, max_distance: f32) -> Self {
+        VisionCone { half_angle: half_angle, max_distance: max_distance }
+    }
+
+    /// Whether `target` is inside the cone, ignoring anything in the way
+    pub fn contains(&self, eye: Vec3<f32>, forward: Vec3<f32>, target: Vec3<f32>) -> bool {
+        let offset = target - eye;
+        let distance = offset.norm();
+        if distance > self.max_distance {
+            return false;
+        }
+        if distance == 0. || forward.sqnorm() == 0. {
+            return true;
+        }
+        dot(&(offset / distance), &forward.normalize()) >= self.half_angle.cos()
+    }
+}
+
+/// Answers whether something blocks the line between two points, e.g. a raycast against level
+/// geometry
+pub trait Occluder {
+    fn blocked(&self, from: Vec3<f32>, to: Vec3<f32>) -> bool;
+}
+
+impl<F: Fn(Vec3<f32>, Vec3<f32>) -> bool> Occluder for F {
+    fn blocked(&self, from: Vec3<f32>, to: Vec3<f32>) -> bool {
+        self(from, to)
+    }
+}
+
+/// Nothing blocks line of sight
+impl Occluder for () {
+    fn blocked(&self, _: Vec3<f32>, _: Vec3<f32>) -> bool {
+        false
+    }
+}
+
+/// The same obstacles agents steer around also block their sight
+impl Occluder for [Obstacle] {
+    fn blocked(&self, from: Vec3<f32>, to: Vec3<f32>) -> bool {
+        let segment = to - from;
+        let length_sq = segment.sqnorm();
+        self.iter().any(|o| {
+            // Closest point on the segment to the obstacle's center
+            let t = if length_sq > 0. {
+                (dot(&(o.center - from), &segment) / length_sq).max(0.).min(1.)
+            } else {
+                0.
+            };
+            (from + segment * t - o.center).sqnorm() < o.radius * o.radius
+        })
+    }
+}
+
+/// Line of sight checks cached for the current tick, so several behaviors asking whether the same
+/// agent sees the same target only pay for one occlusion test. Agents and targets are identified
+/// by whatever ids the game uses for them.
+pub struct Sight {
+    cache: HashMap<(usize, usize), bool>,
+}
+
+impl Sight {
+    pub fn new() -> Self {
+        Sight { cache: HashMap::new() }
+    }
+
+    /// Forgets the results of the last tick. Call once per update, before any `can_see`.
+    pub fn begin_tick(&mut self) {
+        self.cache.clear();
+    }
+
+    /// Whether `observer`, at `eye` looking along `forward`, can see `target` at `target_pos`
+    pub fn can_see<O: Occluder + ?Sized>(&mut self, observer: usize, eye: Vec3<f32>,
+                                         forward: Vec3<f32>, cone: &VisionCone, target: usize,
+                                         target_pos: Vec3<f32>, occluder: &O) -> bool {
+        if let Some(&visible) = self.cache.get(&(observer, target)) {
+            return visible;
+        }
+        let visible = cone.contains(eye, forward, target_pos) &&
+                      !occluder.blocked(eye, target_pos);
+        self.cache.insert((observer, target), visible);
+        visible
+    }
+}
+
+/// Draws the outline of a vision cone on the ground plane, for debugging. Call `rebuild` when the
+/// agent moves or turns.
+pub struct VisionConeDebug<'a> {
+    parent: Object<'a>,
+}
+
+impl GameObject for VisionConeDebug<'static> {
+    fn parent(&self) -> &Object {
+        &self.parent
+    }
+}
+
+impl<'a> VisionConeDebug<'a> {
+    pub fn new(display: &Display, eye: Vec3<f32>, forward: Vec3<f32>, cone: &VisionCone,
+               color: [f32; 3]) -> Self {
+        let material = Material::color(color);
+        VisionConeDebug { parent: Self::build(display, eye, forward, cone, material) }
+    }
+
+    pub fn rebuild(&mut self, display: &Display, eye: Vec3<f32>, forward: Vec3<f32>,
+                   cone: &VisionCone) {
+        let material = self.parent.material.clone();
+        self.parent = Self::build(display, eye, forward, cone, material);
+    }
+
+    fn build(display: &Display, eye: Vec3<f32>, forward: Vec3<f32>, cone: &VisionCone,
+             material: Material) -> Object<'a> {
+        const SEGMENTS: usize = 16;
+
+        let heading = forward.y.atan2(forward.x);
+        let point = |angle: f32| {
+            let a = heading + angle;
+            Vertex::new(eye.x + a.cos() * cone.max_distance, eye.y + a.sin() * cone.max_distance,
+                        eye.z)
+        };
+        let eye_vertex = Vertex::new(eye.x, eye.y, eye.z);
+
+        let mut shape = vec![eye_vertex, point(-cone.half_angle),
+                             eye_vertex, point(cone.half_angle)];
+        for s in 0..SEGMENTS {
+            let a0 = -cone.half_angle + 2. * cone.half_angle * s as f32 / SEGMENTS as f32;
+            let a1 = -cone.half_angle + 2. * cone.half_angle * (s + 1) as f32 / SEGMENTS as f32;
+            shape.push(point(a0));
+            shape.push(point(a1));
+        }
+
+        let params = DrawParameters {
+            depth_test: DepthTest::IfLess,
+            depth_write: true,
+            .. Default::default()
+        };
+
+        let vb = VertexBuffer::new(display, shape).into_vertex_buffer_any();
+        ObjectBuilder::new()
+            .vertex_buffer(vb, NoIndices(PrimitiveType::LinesList))
+            .draw_params(params)
+            .material(material)
+            .build()
+    }
+}
@@ -0,0 +1,262 @@
+use std::f32::consts::PI;
+use std::rc::Rc;
+
+use transform::{self, Transform};
+
+use nalgebra::{UnitQuat, Vec3};
+
+/// How a value moves from one keyframe to the next
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Easing {
+    Linear,
+    /// Holds the value until the next keyframe, e.g. for flipping a switch
+    Step,
+    /// Starts slowly and speeds up
+    EaseIn,
+    /// Starts quickly and slows down
+    EaseOut,
+    EaseInOut,
+    /// Starts and ends slowly like `EaseInOut`, following a sine curve
+    Sine,
+}
+
+impl Easing {
+    /// How far to blend from one keyframe to the next at `t` of the way between them
+    pub fn apply(&self, t: f32) -> f32 {
+        let t = t.max(0.).min(1.);
+        match *self {
+            Easing::Linear => t,
+            Easing::Step => 0.,
+            Easing::EaseIn => t * t * t,
+            Easing::EaseOut => 1. - (1. - t) * (1. - t) * (1. - t),
+            Easing::EaseInOut => t * t * (3. - 2. * t),
+            Easing::Sine => (1. - (t * PI).cos()) / 2.,
+        }
+    }
+}
+
+/// What happens when an animation reaches its end
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum LoopMode {
+    /// Stops on the last keyframe
+    Once,
+    /// Starts over from the beginning
+    Loop,
+    /// Plays backwards to the beginning, then forwards again
+    PingPong,
+}
+
+/// Values that can be blended between keyframes
+pub trait Interpolate: Copy {
+    fn interpolate(&self, other: &Self, t: f32) -> Self;
+}
+
+impl Interpolate for f32 {
+    fn interpolate(&self, other: &f32, t: f32) -> f32 {
+        self + (other - self) * t
+    }
+}
+
+impl Interpolate for Vec3<f32> {
+    fn interpolate(&self, other: &Vec3<f32>, t: f32) -> Vec3<f32> {
+        *self * (1. - t) + *other * t
+    }
+}
+
+impl Interpolate for UnitQuat<f32> {
+    fn interpolate(&self, other: &UnitQuat<f32>, t: f32) -> UnitQuat<f32> {
+        transform::slerp(self, other, t)
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct Keyframe<T> {
+    /// Seconds from the start of the animation
+    pub time: f32,
+    pub value: T,
+    /// How the value moves from this keyframe to the next one
+    pub easing: Easing,
+}
+
+/// Keyframes of one value over time. Before the first keyframe the value is the first keyframe's,
+/// and after the last one it's the last keyframe's.
+#[derive(Clone, Debug)]
+pub struct Track<T> {
+    keyframes: Vec<Keyframe<T>>,
+}
+
+impl<T: Interpolate> Track<T> {
+    pub fn new() -> Self {
+        Track { keyframes: Vec::new() }
+    }
+
+    /// Adds a keyframe, keeping them sorted by time
+    pub fn key(mut self, time: f32, value: T, easing: Easing) -> Self {
+        let len = self.keyframes.len();
+        let i = self.keyframes.iter().position(|k| k.time > time).unwrap_or(len);
+        self.keyframes.insert(i, Keyframe { time: time, value: value, easing: easing });
+        self
+    }
+
+    pub fn keyframes(&self) -> &[Keyframe<T>] {
+        &self.keyframes
+    }
+
+    /// Time of the last keyframe
+    pub fn duration(&self) -> f32 {
+        self.keyframes.last().map_or(0., |k| k.time)
+    }
+
+    /// The value at `time`, or `None` if the track has no keyframes
+    pub fn sample(&self, time: f32) -> Option<T> {
+        let next = match self.keyframes.iter().position(|k| k.time > time) {
+            Some(0) => return self.keyframes.first().map(|k| k.value),
+            Some(next) => next,
+            None => return self.keyframes.last().map(|k| k.value),
+        };
+        let (a, b) = (&self.keyframes[next - 1], &self.keyframes[next]);
+        let t = (time - a.time) / (b.time - a.time);
+        Some(a.value.interpolate(&b.value, a.easing.apply(t)))
+    }
+}
+
+/// Position, rotation and scale tracks played together. Parts of the transform without a track
+/// are left alone, so e.g. a bobbing animation can be played on an object that turns by itself.
+#[derive(Clone, Debug)]
+pub struct Clip {
+    pub position: Option<Track<Vec3<f32>>>,
+    pub rotation: Option<Track<UnitQuat<f32>>>,
+    pub scale: Option<Track<Vec3<f32>>>,
+    pub loop_mode: LoopMode,
+}
+
+impl Clip {
+    pub fn new(loop_mode: LoopMode) -> Self {
+        Clip { position: None, rotation: None, scale: None, loop_mode: loop_mode }
+    }
+
+    pub fn position(mut self, track: Track<Vec3<f32>>) -> Self {
+        self.position = Some(track);
+        self
+    }
+
+    pub fn rotation(mut self, track: Track<UnitQuat<f32>>) -> Self {
+        self.rotation = Some(track);
+        self
+    }
+
+    pub fn scale(mut self, track: Track<Vec3<f32>>) -> Self {
+        self.scale = Some(track);
+        self
+    }
+
+    /// Time of the last keyframe of any track
+    pub fn duration(&self) -> f32 {
+        let position = self.position.as_ref().map_or(0., |t| t.duration());
+        let rotation = self.rotation.as_ref().map_or(0., |t| t.duration());
+        let scale = self.scale.as_ref().map_or(0., |t| t.duration());
+        position.max(rotation).max(scale)
+    }
+
+    /// Sets the animated parts of `transform` to their values at `time`, which is wrapped
+    /// according to the loop mode
+    pub fn apply(&self, time: f32, transform: &mut Transform) {
+        let time = self.local_time(time);
+        if let Some(position) = self.position.as_ref().and_then(|t| t.sample(time)) {
+            transform.position = position;
+        }
+        if let Some(rotation) = self.rotation.as_ref().and_then(|t| t.sample(time)) {
+            transform.rotation = rotation;
+        }
+        if let Some(scale) = self.scale.as_ref().and_then(|t| t.sample(time)) {
+            transform.scale = scale;
+        }
+    }
+
+    // `time` mapped into the clip
+    fn local_time(&self, time: f32) -> f32 {
+        let duration = self.duration();
+        if duration <= 0. {
+            return 0.;
+        }
+        match self.loop_mode {
+            LoopMode::Once => time.max(0.).min(duration),
+            LoopMode::Loop => {
+                let t = time % duration;
+                if t < 0. { t + duration } else { t }
+            }
+            LoopMode::PingPong => {
+                let t = time % (2. * duration);
+                let t = if t < 0. { t + 2. * duration } else { t };
+                if t > duration { 2. * duration - t } else { t }
+            }
+        }
+    }
+}
+
+/// Plays a `Clip` on a transform. Objects call `update` from `GameObject::update` with their own
+/// transform, and entities with an `Animator` and a `Transform` are animated by their
+/// world. Clips are shared, so many objects can play the same animation at different times.
+#[derive(Clone, Debug)]
+pub struct Animator {
+    clip: Rc<Clip>,
+    time: f32,
+    /// Playback rate, e.g. 2 for twice as fast or -1 to play backwards
+    pub speed: f32,
+    playing: bool,
+}
+
+impl Animator {
+    pub fn new(clip: Rc<Clip>) -> Self {
+        Animator { clip: clip, time: 0., speed: 1., playing: true }
+    }
+
+    pub fn clip(&self) -> &Rc<Clip> {
+        &self.clip
+    }
+
+    /// Switches to another clip, starting from its beginning
+    pub fn set_clip(&mut self, clip: Rc<Clip>) {
+        self.clip = clip;
+        self.time = 0.;
+    }
+
+    pub fn play(&mut self) {
+        self.playing = true;
+    }
+
+    pub fn pause(&mut self) {
+        self.playing = false;
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.playing
+    }
+
+    /// Seconds since the clip started, not wrapped by its loop mode
+    pub fn time(&self) -> f32 {
+        self.time
+    }
+
+    pub fn seek(&mut self, time: f32) {
+        self.time = time;
+    }
+
+    /// Whether a clip that plays once has reached its end, or its start when playing backwards
+    pub fn finished(&self) -> bool {
+        self.clip.loop_mode == LoopMode::Once &&
+            (self.time >= self.clip.duration() && self.speed >= 0. ||
+             self.time <= 0. && self.speed < 0.)
+    }
+
+    /// Advances the clip by `dt` seconds and applies it to `transform`
+    pub fn update(&mut self, dt: f32, transform: &mut Transform) {
+        if self.playing {
+            self.time += dt * self.speed;
+            if self.clip.loop_mode == LoopMode::Once {
+                self.time = self.time.max(0.).min(self.clip.duration());
+            }
+        }
+        self.clip.apply(self.time, transform);
+    }
+}
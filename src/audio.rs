@@ -0,0 +1,291 @@
+use std::collections::HashMap;
+use std::f32::consts::FRAC_PI_4;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use Error;
+use draw::GameObject;
+use scene::Scene;
+
+use nalgebra::{self, dot, Col, Mat4, Norm, Vec3};
+
+use rodio::{self, Decoder, Endpoint, Sink, Source};
+use rodio::source::Buffered;
+
+/// A decoded WAV or OGG Vorbis file, from `Audio::load`. Cloning it is cheap, and every clone
+/// plays from the start.
+#[derive(Clone)]
+pub struct Sound {
+    source: Buffered<Decoder<BufReader<File>>>,
+}
+
+/// Identifies a sound attached to an object with `Audio::attach`
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+pub struct EmitterId(usize);
+
+/// A sound that is playing. Dropping it stops the sound.
+pub struct Playback {
+    sink: Sink,
+    // Left and right channel gains, read by the audio thread
+    gains: Arc<Mutex<[f32; 2]>>,
+    // Set by the audio thread once a sound that doesn't loop has played to the end
+    finished: Arc<AtomicBool>,
+}
+
+impl Playback {
+    /// Volume from 0 to 1, multiplied with the gains from panning
+    pub fn set_volume(&mut self, volume: f32) {
+        self.sink.set_volume(volume);
+    }
+
+    /// `pan` goes from -1 for only the left speaker to 1 for only the right one. Equal power
+    /// panning keeps the sound as loud in the middle as at either side.
+    pub fn set_pan(&mut self, pan: f32) {
+        let angle = (pan.max(-1.).min(1.) + 1.) * FRAC_PI_4;
+        *self.gains.lock().unwrap() = [angle.cos(), angle.sin()];
+    }
+
+    pub fn pause(&self) {
+        self.sink.pause();
+    }
+
+    pub fn resume(&self) {
+        self.sink.play();
+    }
+
+    /// Whether the sound has played to the end. Looping sounds never finish.
+    pub fn finished(&self) -> bool {
+        self.finished.load(Ordering::Relaxed)
+    }
+}
+
+struct Emitter {
+    object: String,
+    volume: f32,
+    playback: Playback,
+}
+
+/// Plays sounds on the default output device. Sounds are either played as is, or attached to a
+/// named object in the scene so their volume and pan follow where the object is relative to the
+/// scene's camera. Call `update` once per update step to move attached sounds with their objects.
+pub struct Audio {
+    endpoint: Endpoint,
+    sounds: HashMap<String, Sound>,
+    emitters: HashMap<usize, Emitter>,
+    next_id: usize,
+    /// Attached sounds are at full volume up to this distance from the camera
+    pub min_distance: f32,
+    /// Attached sounds fade out linearly until they're silent at this distance
+    pub max_distance: f32,
+}
+
+impl Audio {
+    pub fn new() -> Result<Self, Error> {
+        let endpoint = match rodio::get_default_endpoint() {
+            Some(endpoint) => endpoint,
+            None => return Err(Error::Audio("no audio output device".to_owned())),
+        };
+        Ok(Audio {
+            endpoint: endpoint,
+            sounds: HashMap::new(),
+            emitters: HashMap::new(),
+            next_id: 0,
+            min_distance: 1.,
+            max_distance: 50.,
+        })
+    }
+
+    /// Loads a WAV or OGG Vorbis file, or returns the sound loaded earlier from the same path
+    pub fn load<P: AsRef<Path>>(&mut self, path: P) -> Result<Sound, Error> {
+        let path = path.as_ref();
+        let s = path.to_string_lossy().into_owned();
+        if let Some(sound) = self.sounds.get(&s) {
+            return Ok(sound.clone());
+        }
+
+        let file = BufReader::new(try!(File::open(path)));
+        let decoder = try!(Decoder::new(file).map_err(|e| {
+            Error::Audio(format!("couldn't decode {}: {:?}", s, e))
+        }));
+        let sound = Sound { source: decoder.buffered() };
+        self.sounds.insert(s, sound.clone());
+        Ok(sound)
+    }
+
+    /// Plays `sound` once at `volume`, from 0 to 1
+    pub fn play(&self, sound: &Sound, volume: f32) {
+        let sink = Sink::new(&self.endpoint);
+        sink.set_volume(volume);
+        sink.append(sound.source.clone());
+        // Keeps playing after the sink is dropped
+        sink.detach();
+    }
+
+    /// Plays `sound` until the returned `Playback` is dropped
+    pub fn play_looping(&self, sound: &Sound) -> Playback {
+        self.start(sound, true)
+    }
+
+    /// Plays `sound` from the position of the object called `name`, once or until it's
+    /// detached. The sound stops when the object is removed from the scene, and sounds that don't
+    /// loop are detached by `update` once they've played to the end.
+    pub fn attach(&mut self, name: &str, sound: &Sound, volume: f32, looping: bool) -> EmitterId {
+        let mut playback = self.start(sound, looping);
+        // Silent until the first update has placed it
+        playback.set_volume(0.);
+        let id = self.next_id;
+        self.next_id += 1;
+        self.emitters.insert(id, Emitter {
+            object: name.to_owned(),
+            volume: volume,
+            playback: playback,
+        });
+        EmitterId(id)
+    }
+
+    /// Stops an attached sound. Returns false if it was already stopped.
+    pub fn detach(&mut self, id: EmitterId) -> bool {
+        self.emitters.remove(&id.0).is_some()
+    }
+
+    /// Sets the volume and pan of every attached sound from the distance and direction of its
+    /// object from the camera, and detaches sounds that finished or whose object is gone
+    pub fn update(&mut self, scene: &Scene) {
+        let camera = scene.camera();
+        let (listener, right) = (camera.position(), camera.right());
+        let (min, max) = (self.min_distance, self.max_distance);
+        let identity: Mat4<f32> = nalgebra::new_identity(4);
+        let mut gone = Vec::new();
+        for (&id, emitter) in self.emitters.iter_mut() {
+            if emitter.playback.finished() {
+                gone.push(id);
+                continue;
+            }
+            let position = match scene.find_object(&emitter.object) {
+                Some(object) => {
+                    let translation = object.world_transform(&identity).col(3);
+                    Vec3::new(translation.x, translation.y, translation.z)
+                }
+                None => {
+                    gone.push(id);
+                    continue;
+                }
+            };
+            let offset = position - listener;
+            let distance = offset.norm();
+            let falloff = if distance <= min {
+                1.
+            } else {
+                (1. - (distance - min) / (max - min)).max(0.)
+            };
+            // A sound at the camera comes from both sides
+            let pan = if distance > 0. { dot(&(offset / distance), &right) } else { 0. };
+            emitter.playback.set_volume(emitter.volume * falloff);
+            emitter.playback.set_pan(pan);
+        }
+        for id in gone {
+            self.emitters.remove(&id);
+        }
+    }
+
+    fn start(&self, sound: &Sound, looping: bool) -> Playback {
+        let gains = Arc::new(Mutex::new([1., 1.]));
+        let finished = Arc::new(AtomicBool::new(false));
+        let sink = Sink::new(&self.endpoint);
+        let source = sound.source.clone();
+        if looping {
+            sink.append(Panned::new(source.repeat_infinite(), gains.clone(), finished.clone()));
+        } else {
+            sink.append(Panned::new(source, gains.clone(), finished.clone()));
+        }
+        let mut playback = Playback { sink: sink, gains: gains, finished: finished };
+        playback.set_pan(0.);
+        playback
+    }
+}
+
+// Scales the left and right channels of a sound by gains that can change while it plays. Mono
+// sounds are turned into stereo so they can be panned.
+struct Panned<S> {
+    source: S,
+    gains: Arc<Mutex<[f32; 2]>>,
+    finished: Arc<AtomicBool>,
+    // The gains used for the current frame, so the lock is only taken once per frame
+    frame_gains: [f32; 2],
+    mono: bool,
+    // Output channel of the next sample
+    channel: u16,
+    mono_sample: i16,
+}
+
+impl<S: Source<Item=i16>> Panned<S> {
+    fn new(source: S, gains: Arc<Mutex<[f32; 2]>>, finished: Arc<AtomicBool>) -> Self {
+        let mono = source.channels() == 1;
+        Panned {
+            source: source,
+            gains: gains,
+            finished: finished,
+            frame_gains: [1., 1.],
+            mono: mono,
+            channel: 0,
+            mono_sample: 0,
+        }
+    }
+}
+
+impl<S: Source<Item=i16>> Iterator for Panned<S> {
+    type Item = i16;
+
+    fn next(&mut self) -> Option<i16> {
+        if self.channel == 0 {
+            self.frame_gains = *self.gains.lock().unwrap();
+        }
+        let sample = if !self.mono || self.channel == 0 {
+            match self.source.next() {
+                Some(sample) => {
+                    self.mono_sample = sample;
+                    sample
+                }
+                None => {
+                    self.finished.store(true, Ordering::Relaxed);
+                    return None;
+                }
+            }
+        } else {
+            self.mono_sample
+        };
+
+        let gains = self.frame_gains;
+        let gain = match self.channel {
+            0 => gains[0],
+            1 => gains[1],
+            // Surround channels aren't panned
+            _ => (gains[0] + gains[1]) / 2.,
+        };
+        self.channel = (self.channel + 1) % self.channels();
+        Some((sample as f32 * gain) as i16)
+    }
+}
+
+impl<S: Source<Item=i16>> Source for Panned<S> {
+    fn current_frame_len(&self) -> Option<usize> {
+        let len = self.source.current_frame_len();
+        if self.mono { len.map(|n| n * 2) } else { len }
+    }
+
+    fn channels(&self) -> u16 {
+        if self.mono { 2 } else { self.source.channels() }
+    }
+
+    fn samples_rate(&self) -> u32 {
+        self.source.samples_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.source.total_duration()
+    }
+}
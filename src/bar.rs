@@ -0,0 +1,180 @@
+use std::cell::Cell;
+use std::rc::Rc;
+
+use draw::{GameObject, Object, ObjectBuilder, Vertex};
+use material::Material;
+use shader::VertexShaderType;
+use camera::Camera;
+
+use glium::{DepthTest, Display, DrawParameters, VertexBuffer};
+use glium::index::{NoIndices, PrimitiveType};
+
+use nalgebra::{self, Col, Mat4, Vec3, Vec4};
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum BarSpace {
+    /// Positioned and sized in normalized device coordinates, like `Text`
+    Screen,
+    /// An upright quad in the world facing -y
+    World,
+    /// A quad in the world that always faces the camera, e.g. above a character's head
+    Billboard,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct BarStyle {
+    pub width: f32,
+    pub height: f32,
+    pub border: f32,
+    pub fill_color: [f32; 3],
+    pub background_color: [f32; 3],
+    pub border_color: [f32; 3],
+}
+
+impl Default for BarStyle {
+    fn default() -> Self {
+        BarStyle {
+            width: 1.,
+            height: 0.1,
+            border: 0.02,
+            fill_color: [0.8, 0., 0.],
+            background_color: [0.2, 0.2, 0.2],
+            border_color: [0., 0., 0.],
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+struct BarState {
+    fill: f32,
+    pos: Vec3<f32>,
+}
+
+/// A health/progress bar made of a border, a background and a fill quad. Changing the fill amount
+/// or position only updates a few floats, the vertex buffers are built once.
+pub struct Bar<'a> {
+    parent: Object<'a>,
+    quads: Vec<Box<GameObject>>,
+    state: Rc<Cell<BarState>>,
+}
+
+impl GameObject for Bar<'static> {
+    fn parent(&self) -> &Object {
+        &self.parent
+    }
+
+    fn children(&self) -> Option<&[Box<GameObject>]> {
+        Some(&*self.quads)
+    }
+}
+
+impl<'a> Bar<'a> {
+    pub fn new(display: &Display, space: BarSpace, style: BarStyle, pos: Vec3<f32>) -> Self {
+        let state = Rc::new(Cell::new(BarState { fill: 1., pos: pos }));
+        let (w, h, b) = (style.width, style.height, style.border);
+
+        // Drawn in this order without writing depth, so later quads cover earlier ones
+        let quads = vec![
+            BarQuad::new(display, space, state.clone(), (-b, -b, w + b, h + b),
+                         style.border_color, false),
+            BarQuad::new(display, space, state.clone(), (0., 0., w, h),
+                         style.background_color, false),
+            BarQuad::new(display, space, state.clone(), (0., 0., w, h),
+                         style.fill_color, true),
+        ].into_iter().map(|q| Box::new(q) as Box<GameObject>).collect();
+
+        Bar { parent: ObjectBuilder::new().build(), quads: quads, state: state }
+    }
+
+    pub fn fill(&self) -> f32 {
+        self.state.get().fill
+    }
+
+    /// Sets the filled fraction of the bar, clamped to [0, 1]
+    pub fn set_fill(&mut self, fill: f32) {
+        let mut state = self.state.get();
+        state.fill = fill.max(0.).min(1.);
+        self.state.set(state);
+    }
+
+    pub fn set_pos(&mut self, pos: Vec3<f32>) {
+        let mut state = self.state.get();
+        state.pos = pos;
+        self.state.set(state);
+    }
+}
+
+struct BarQuad<'a> {
+    parent: Object<'a>,
+    space: BarSpace,
+    state: Rc<Cell<BarState>>,
+    scale_by_fill: bool,
+}
+
+impl GameObject for BarQuad<'static> {
+    fn parent(&self) -> &Object {
+        &self.parent
+    }
+
+    fn draw_transform(&self, camera: &Camera, parent_transform: &Mat4<f32>, _: f32) -> Mat4<f32> {
+        *parent_transform * self.transform(camera)
+    }
+}
+
+impl<'a> BarQuad<'a> {
+    fn new(display: &Display, space: BarSpace, state: Rc<Cell<BarState>>,
+           (x0, y0, x1, y1): (f32, f32, f32, f32), color: [f32; 3],
+           scale_by_fill: bool) -> Self {
+        let shape = vec![Vertex::new(x0, y0, 0.), Vertex::new(x1, y0, 0.), Vertex::new(x1, y1, 0.),
+                         Vertex::new(x0, y0, 0.), Vertex::new(x1, y1, 0.), Vertex::new(x0, y1, 0.)];
+        let vb = VertexBuffer::new(display, shape).into_vertex_buffer_any();
+
+        let builder = ObjectBuilder::new()
+            .vertex_buffer(vb, NoIndices(PrimitiveType::TrianglesList))
+            .material(Material::color(color));
+        let parent = match space {
+            BarSpace::Screen => builder.vert_shader(VertexShaderType::Gui).build(),
+            BarSpace::World | BarSpace::Billboard => {
+                builder.draw_params(DrawParameters {
+                    depth_test: DepthTest::IfLess,
+                    .. Default::default()
+                }).build()
+            }
+        };
+
+        BarQuad {
+            parent: parent,
+            space: space,
+            state: state,
+            scale_by_fill: scale_by_fill,
+        }
+    }
+
+    fn transform(&self, camera: &Camera) -> Mat4<f32> {
+        let state = self.state.get();
+
+        let mut rotation: Mat4<f32> = match self.space {
+            BarSpace::Screen => nalgebra::new_identity(4),
+            BarSpace::World => Mat4::new(1., 0., 0.,  0.,
+                                         0., 0., -1., 0.,
+                                         0., 1., 0.,  0.,
+                                         0., 0., 0.,  1.),
+            BarSpace::Billboard => {
+                // The inverse of the view rotation turns the quad to face the camera
+                let v = camera.view_matrix();
+                Mat4::new(v[(0, 0)], v[(1, 0)], v[(2, 0)], 0.,
+                          v[(0, 1)], v[(1, 1)], v[(2, 1)], 0.,
+                          v[(0, 2)], v[(1, 2)], v[(2, 2)], 0.,
+                          0.,        0.,        0.,        1.)
+            }
+        };
+        rotation.set_col(3, Vec4::new(state.pos.x, state.pos.y, state.pos.z, 1.));
+
+        let fill = if self.scale_by_fill { state.fill } else { 1. };
+        let scale = Mat4::new(fill, 0., 0., 0.,
+                              0.,   1., 0., 0.,
+                              0.,   0., 1., 0.,
+                              0.,   0., 0., 1.);
+        rotation * scale
+    }
+}
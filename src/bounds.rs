@@ -0,0 +1,158 @@
+use std::f32;
+
+use draw::Vertex;
+
+use nalgebra::{dot, Mat4, Norm, Vec3, Vec4};
+
+/// A half line starting at `origin`, e.g. from the camera through the mouse cursor
+#[derive(Copy, Clone, Debug)]
+pub struct Ray {
+    pub origin: Vec3<f32>,
+    /// Always normalized
+    pub direction: Vec3<f32>,
+}
+
+impl Ray {
+    pub fn new(origin: Vec3<f32>, direction: Vec3<f32>) -> Self {
+        Ray { origin: origin, direction: direction.normalize() }
+    }
+
+    /// The point `distance` units along the ray
+    pub fn at(&self, distance: f32) -> Vec3<f32> {
+        self.origin + self.direction * distance
+    }
+}
+
+/// Axis-aligned bounding box
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Aabb {
+    pub min: Vec3<f32>,
+    pub max: Vec3<f32>,
+}
+
+impl Aabb {
+    pub fn new(min: Vec3<f32>, max: Vec3<f32>) -> Self {
+        Aabb { min: min, max: max }
+    }
+
+    /// The smallest box containing every point, or `None` if there are none
+    pub fn from_points<I: IntoIterator<Item=Vec3<f32>>>(points: I) -> Option<Self> {
+        let mut points = points.into_iter();
+        let first = match points.next() {
+            Some(p) => p,
+            None => return None,
+        };
+        Some(points.fold(Aabb::new(first, first), |b, p| b.grow(p)))
+    }
+
+    pub fn from_vertices(vertices: &[Vertex]) -> Option<Self> {
+        Self::from_points(vertices.iter().map(|v| {
+            Vec3::new(v.position[0], v.position[1], v.position[2])
+        }))
+    }
+
+    /// The box extended to contain `p`
+    pub fn grow(&self, p: Vec3<f32>) -> Self {
+        Aabb::new(Vec3::new(self.min.x.min(p.x), self.min.y.min(p.y), self.min.z.min(p.z)),
+                  Vec3::new(self.max.x.max(p.x), self.max.y.max(p.y), self.max.z.max(p.z)))
+    }
+
+    pub fn union(&self, other: &Aabb) -> Self {
+        self.grow(other.min).grow(other.max)
+    }
+
+    pub fn center(&self) -> Vec3<f32> {
+        (self.min + self.max) * 0.5
+    }
+
+    /// The box containing this one after `transform` is applied to it. Rotations make it larger
+    /// than the transformed shape.
+    pub fn transform(&self, transform: &Mat4<f32>) -> Self {
+        let (a, b) = (self.min, self.max);
+        let corners = [Vec3::new(a.x, a.y, a.z), Vec3::new(b.x, a.y, a.z),
+                       Vec3::new(a.x, b.y, a.z), Vec3::new(b.x, b.y, a.z),
+                       Vec3::new(a.x, a.y, b.z), Vec3::new(b.x, a.y, b.z),
+                       Vec3::new(a.x, b.y, b.z), Vec3::new(b.x, b.y, b.z)];
+        Self::from_points(corners.iter().map(|c| {
+            let p = *transform * Vec4::new(c.x, c.y, c.z, 1.);
+            Vec3::new(p.x, p.y, p.z)
+        })).unwrap()
+    }
+
+    /// Outward normal of the face closest to `p`, e.g. the face a ray hit at `p`
+    pub fn normal_at(&self, p: Vec3<f32>) -> Vec3<f32> {
+        let faces = [(p.x - self.min.x, Vec3::new(-1., 0., 0.)),
+                     (self.max.x - p.x, Vec3::new(1., 0., 0.)),
+                     (p.y - self.min.y, Vec3::new(0., -1., 0.)),
+                     (self.max.y - p.y, Vec3::new(0., 1., 0.)),
+                     (p.z - self.min.z, Vec3::new(0., 0., -1.)),
+                     (self.max.z - p.z, Vec3::new(0., 0., 1.))];
+        let mut closest = faces[0];
+        for &face in faces.iter().skip(1) {
+            if face.0.abs() < closest.0.abs() {
+                closest = face;
+            }
+        }
+        closest.1
+    }
+
+    /// Distance along `ray` to where it enters the box, 0 if it starts inside, or `None` if it
+    /// misses
+    pub fn intersect_ray(&self, ray: &Ray) -> Option<f32> {
+        let origin = [ray.origin.x, ray.origin.y, ray.origin.z];
+        let direction = [ray.direction.x, ray.direction.y, ray.direction.z];
+        let min = [self.min.x, self.min.y, self.min.z];
+        let max = [self.max.x, self.max.y, self.max.z];
+
+        // Clip the ray against the slab between each pair of opposite faces
+        let (mut near, mut far) = (0., f32::INFINITY);
+        for i in 0..3 {
+            if direction[i] == 0. {
+                if origin[i] < min[i] || origin[i] > max[i] {
+                    return None;
+                }
+                continue;
+            }
+            let t1 = (min[i] - origin[i]) / direction[i];
+            let t2 = (max[i] - origin[i]) / direction[i];
+            near = t1.min(t2).max(near);
+            far = t1.max(t2).min(far);
+            if near > far {
+                return None;
+            }
+        }
+        Some(near)
+    }
+}
+
+/// The volume a camera can see, as six planes facing inwards
+#[derive(Copy, Clone, Debug)]
+pub struct Frustum {
+    // Normal and offset of each plane, where `dot(normal, p) + offset >= 0` inside
+    planes: [(Vec3<f32>, f32); 6],
+}
+
+impl Frustum {
+    /// Extracts the planes from a combined projection and view matrix
+    pub fn from_matrix(m: &Mat4<f32>) -> Self {
+        let row = |r: usize| Vec4::new(m[(r, 0)], m[(r, 1)], m[(r, 2)], m[(r, 3)]);
+        let plane = |v: Vec4<f32>| (Vec3::new(v.x, v.y, v.z), v.w);
+        let (x, y, z, w) = (row(0), row(1), row(2), row(3));
+        Frustum {
+            planes: [plane(w + x), plane(w - x), plane(w + y), plane(w - y), plane(w + z),
+                     plane(w - z)],
+        }
+    }
+
+    /// Whether any part of `aabb` might be visible. Boxes near the corners of the frustum can be
+    /// reported as visible when they aren't, which only costs a draw call.
+    pub fn intersects(&self, aabb: &Aabb) -> bool {
+        self.planes.iter().all(|&(n, d)| {
+            // The corner furthest along the plane's normal
+            let p = Vec3::new(if n.x >= 0. { aabb.max.x } else { aabb.min.x },
+                              if n.y >= 0. { aabb.max.y } else { aabb.min.y },
+                              if n.z >= 0. { aabb.max.z } else { aabb.min.z });
+            dot(&n, &p) + d >= 0.
+        })
+    }
+}
@@ -0,0 +1,118 @@
+use std::collections::VecDeque;
+
+use time;
+
+/// Identifies a task added to a `BudgetScheduler`
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+pub struct TaskId(usize);
+
+struct Task<T: ?Sized> {
+    id: TaskId,
+    name: String,
+    // Does one slice of the work, returning true once there's nothing left to do
+    work: Box<FnMut(&mut T) -> bool>,
+    slices: u32,
+    // Time spent in the task so far, in ns
+    elapsed: u64,
+}
+
+/// Spreads heavy work like remeshing terrain, baking lightmaps or rebuilding a navmesh over many
+/// frames, so it never takes more than a fixed number of milliseconds of a frame. Tasks split
+/// their work into small slices and return after each one, and `run` calls the tasks in turn
+/// until the frame's budget is used up. Work keeps its place between frames in the task's
+/// closure, e.g. as the index of the next chunk to remesh.
+///
+/// Call `run` once per frame, e.g. from `Game::draw` before drawing the scene, with whatever the
+/// tasks work on. At least one slice runs every frame, so a slice that takes longer than the
+/// whole budget still finishes eventually, but makes the frame late.
+pub struct BudgetScheduler<T: ?Sized> {
+    tasks: VecDeque<Task<T>>,
+    /// Milliseconds of work `run` does per frame
+    pub budget_ms: f32,
+    next_id: usize,
+    // Time the last `run` took, in ns
+    last_run: u64,
+}
+
+impl<T: ?Sized> BudgetScheduler<T> {
+    pub fn new(budget_ms: f32) -> Self {
+        BudgetScheduler { tasks: VecDeque::new(), budget_ms: budget_ms, next_id: 0, last_run: 0 }
+    }
+
+    /// Adds a task after the ones already waiting. `work` does one slice of work each time it's
+    /// called and returns true once the task is done.
+    pub fn add<F: FnMut(&mut T) -> bool + 'static>(&mut self, name: &str, work: F) -> TaskId {
+        let id = TaskId(self.next_id);
+        self.next_id += 1;
+        self.tasks.push_back(Task {
+            id: id,
+            name: name.to_owned(),
+            work: Box::new(work),
+            slices: 0,
+            elapsed: 0,
+        });
+        id
+    }
+
+    /// Drops a task before it's done, returning false if it already finished
+    pub fn cancel(&mut self, id: TaskId) -> bool {
+        match self.tasks.iter().position(|t| t.id == id) {
+            Some(i) => {
+                self.tasks.remove(i);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Whether a task is still waiting for or getting time
+    pub fn is_pending(&self, id: TaskId) -> bool {
+        self.tasks.iter().any(|t| t.id == id)
+    }
+
+    /// Number of unfinished tasks
+    pub fn len(&self) -> usize {
+        self.tasks.len()
+    }
+
+    /// Milliseconds the last `run` took
+    pub fn last_run_ms(&self) -> f32 {
+        self.last_run as f32 / 1e6
+    }
+
+    /// Runs slices of the tasks in turn until the budget is used up or every task is done,
+    /// returning how many tasks finished
+    pub fn run(&mut self, target: &mut T) -> usize {
+        let start = time::precise_time_ns();
+        let budget = (self.budget_ms.max(0.) * 1e6) as u64;
+        let mut finished = 0;
+        let mut now = start;
+        while let Some(mut task) = self.tasks.pop_front() {
+            let done = (task.work)(target);
+            let slice_end = time::precise_time_ns();
+            task.slices += 1;
+            task.elapsed += slice_end - now;
+            now = slice_end;
+
+            if done {
+                debug!("Task {} finished after {} slices and {:.2}ms", task.name, task.slices,
+                       task.elapsed as f64 / 1e6);
+                finished += 1;
+            } else {
+                self.tasks.push_back(task);
+            }
+            if now - start >= budget {
+                break;
+            }
+        }
+        self.last_run = now - start;
+        finished
+    }
+
+    /// Runs every task to completion regardless of the budget, e.g. before saving or quitting
+    pub fn finish_all(&mut self, target: &mut T) {
+        while let Some(mut task) = self.tasks.pop_front() {
+            while !(task.work)(target) {}
+        }
+    }
+}
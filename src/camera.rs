@@ -1,38 +1,78 @@
 use std::cell::Cell;
 
-use nalgebra::{self, dot, BaseFloat, Col, Mat4, Vec3, Vec4};
+use EngineContext;
+use bounds::{Frustum, Ray};
+use input::{InputState, Key};
+use transform::{self, Transform};
+
+use nalgebra::{self, dot, BaseFloat, Col, Mat4, Norm, UnitQuat, Vec3, Vec4};
+
+/// How the camera maps the view volume onto the screen
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Projection {
+    /// Objects shrink with distance, using the camera's field of view
+    Perspective,
+    /// Objects keep their size regardless of distance, e.g. for 2D games. `height` is how many
+    /// units fit vertically on the screen, and the width follows from the aspect ratio.
+    Orthographic { height: f32 },
+}
 
 pub struct Camera {
+    projection: Projection,
     fov: f32,
     near: f32,
     far: f32,
     aspect_ratio: f32,
-    transform: Mat4<f32>,
+    transform: Transform,
     view_matrix: Cell<Mat4<f32>>,
     proj_matrix: Cell<Mat4<f32>>,
     view_dirty: Cell<bool>,
     proj_dirty: Cell<bool>,
+    culling_mask: u32,
 }
 
 impl Camera {
     pub fn new(pos: Vec3<f32>, aspect_ratio: f32) -> Self {
-        let transform = Mat4::new(1., 0., 0., pos.x,
-                                  0., 1., 0., pos.y,
-                                  0., 0., 1., pos.z,
-                                  0., 0., 0., 1.);
         Camera {
+            projection: Projection::Perspective,
             fov: BaseFloat::frac_pi_2(),
             near: 0.1,
             far: 1024.,
             aspect_ratio: aspect_ratio,
-            transform: transform,
+            transform: Transform::new(pos),
             view_matrix: Cell::new(nalgebra::new_identity(4)),
             proj_matrix: Cell::new(nalgebra::new_identity(4)),
             view_dirty: Cell::new(true),
             proj_dirty: Cell::new(true),
+            culling_mask: !0,
         }
     }
 
+    /// Bit mask of the object layers the camera draws, all of them by default
+    pub fn culling_mask(&self) -> u32 {
+        self.culling_mask
+    }
+
+    pub fn set_culling_mask(&mut self, mask: u32) {
+        self.culling_mask = mask;
+    }
+
+    /// Whether objects on `layers` are drawn by this camera
+    pub fn sees_layers(&self, layers: u32) -> bool {
+        layers & self.culling_mask != 0
+    }
+
+    pub fn projection(&self) -> Projection {
+        self.projection
+    }
+
+    pub fn set_projection(&mut self, projection: Projection) {
+        self.proj_dirty.set(true);
+
+        self.projection = projection;
+        debug!("Camera projection set to {:?}", projection);
+    }
+
     pub fn fov(&self) -> f32 {
         self.fov
     }
@@ -44,34 +84,23 @@ impl Camera {
         debug!("Camera fov set to {:?}", fov);
     }
 
+    pub fn position(&self) -> Vec3<f32> {
+        self.transform.position
+    }
+
     pub fn set_pos(&mut self, pos: &Vec3<f32>) {
         self.view_dirty.set(true);
 
-        self.transform.set_col(3, Vec4::new(pos.x, pos.y, pos.z, 1.));
+        self.transform.position = *pos;
         debug!("Camera position set to {:?}", pos);
     }
 
+    /// Moves the camera by `diff` in its own space, so -z moves forward along the view direction
     pub fn translate(&mut self, diff: &Vec3<f32>) {
         self.view_dirty.set(true);
 
-        let diff = Vec4::new(diff.x, diff.y, diff.z, 0.);
-
-        // Pull out just the rotation matrix and transpose it (invert it)
-        let mut t = self.transform;
-        t.set_col(3, Vec4::new(0., 0., 0., 1.));
-        t = nalgebra::transpose(&t);
-
-        // Multiply our translation amount by the inverse rotation to get the translation in
-        // normal, unrotated coordinates
-        let p = Vec4::new(dot(&diff, &t.col(0)),
-                          dot(&diff, &t.col(1)),
-                          dot(&diff, &t.col(2)),
-                          0.);
-        let mut pos = self.transform.col(3);
-        pos = pos + p;
-
-        self.transform.set_col(3, pos);
-        debug!("Camera position set to {:?}", pos);
+        self.transform.position = self.transform.position + self.transform.axis(*diff);
+        debug!("Camera position set to {:?}", self.transform.position);
     }
 
     pub fn set_aspect_ratio(&mut self, aspect_ratio: f32) {
@@ -80,40 +109,98 @@ impl Camera {
         self.aspect_ratio = aspect_ratio;
     }
 
-    pub fn set_abs_rotation(&mut self, pitch: f32, yaw: f32) {
+    pub fn orientation(&self) -> UnitQuat<f32> {
+        self.transform.rotation
+    }
+
+    pub fn set_orientation(&mut self, orientation: UnitQuat<f32>) {
         self.view_dirty.set(true);
 
-        let pitch_mat = Mat4::new(1., 0.,           0.,          0.,
-                                  0., pitch.cos(), -pitch.sin(), 0.,
-                                  0., pitch.sin(),  pitch.cos(), 0.,
-                                  0., 0.,           0.,          1.);
-        let yaw_mat = Mat4::new(yaw.cos(),  0., yaw.sin(), 0.,
-                                0.,         1., 0.,        0.,
-                                -yaw.sin(), 0., yaw.cos(), 0.,
-                                0.,         0., 0.,        1.);
+        self.transform.rotation = orientation;
+    }
+
+    /// The direction the camera is looking in
+    pub fn forward(&self) -> Vec3<f32> {
+        self.transform.axis(Vec3::new(0., 0., -1.))
+    }
 
-        let trans_row = self.transform.col(3);
-        self.transform = pitch_mat * yaw_mat;
-        self.transform.set_col(3, trans_row);
+    /// The direction to the right of the screen
+    pub fn right(&self) -> Vec3<f32> {
+        self.transform.axis(Vec3::new(1., 0., 0.))
+    }
+
+    /// Turns the camera to `yaw` radians around the world y axis and `pitch` radians up or down
+    /// from there, without any roll
+    pub fn set_abs_rotation(&mut self, pitch: f32, yaw: f32) {
+        self.view_dirty.set(true);
 
-        debug!("Transform set to {:?}", self.transform);
+        self.transform.rotation = UnitQuat::new(Vec3::new(0., yaw, 0.)) *
+                                  UnitQuat::new(Vec3::new(pitch, 0., 0.));
+        debug!("Camera orientation set to {:?}", self.transform.rotation);
     }
 
+    /// Turns the camera `yaw` radians around the world y axis and `pitch` radians around its own
+    /// x axis. Keeping yaw in world space stops roll from creeping in as rotations add up.
     pub fn rotate(&mut self, pitch: f32, yaw: f32) {
         self.view_dirty.set(true);
 
-        let pitch_mat = Mat4::new(1., 0.,           0.,          0.,
-                                  0., pitch.cos(), -pitch.sin(), 0.,
-                                  0., pitch.sin(),  pitch.cos(), 0.,
-                                  0., 0.,           0.,          1.);
-        let yaw_mat = Mat4::new(yaw.cos(),  0., yaw.sin(), 0.,
-                                0.,         1., 0.,        0.,
-                                -yaw.sin(), 0., yaw.cos(), 0.,
-                                0.,         0., 0.,        1.);
+        let pitched = self.transform.rotation * UnitQuat::new(Vec3::new(pitch, 0., 0.));
+        self.transform.rotation = pitched;
+        self.transform.rotate(&UnitQuat::new(Vec3::new(0., yaw, 0.)));
+        debug!("Camera orientation set to {:?}", self.transform.rotation);
+    }
+
+    /// Turns the camera to face `target`, keeping the world y axis up on the screen
+    pub fn look_at(&mut self, target: &Vec3<f32>) {
+        self.view_dirty.set(true);
+
+        self.transform.look_at(*target, Vec3::new(0., 1., 0.));
+    }
+
+    /// Turns the camera `t` of the way from its current orientation to `target`. Calling this
+    /// every update with a small `t` eases the camera towards the target.
+    pub fn rotate_towards(&mut self, target: &UnitQuat<f32>, t: f32) {
+        self.view_dirty.set(true);
+
+        self.transform.rotation = transform::slerp(&self.transform.rotation, target, t);
+    }
+
+    /// The camera's position and orientation in the world as a matrix
+    pub fn transform(&self) -> Mat4<f32> {
+        self.transform.matrix()
+    }
+
+    /// The ray from the camera through the pixel at `(x, y)`, measured from the top left corner
+    /// of a window `dim` pixels wide and high, e.g. to find what the mouse is pointing at
+    pub fn screen_to_ray(&self, x: f32, y: f32, dim: (u32, u32)) -> Ray {
+        // Normalized device coordinates, with y pointing up
+        let ndc_x = 2. * x / dim.0 as f32 - 1.;
+        let ndc_y = 1. - 2. * y / dim.1 as f32;
 
-        self.transform = self.transform * pitch_mat * yaw_mat;
+        // The camera looks down its local -z axis
+        let (origin, dir) = match self.projection {
+            Projection::Perspective => {
+                let half_height = (self.fov / 2.).tan();
+                let half_width = half_height * self.aspect_ratio;
+                (Vec4::new(0., 0., 0., 1.),
+                 Vec4::new(ndc_x * half_width, ndc_y * half_height, -1., 0.))
+            }
+            Projection::Orthographic { height } => {
+                let half_height = height / 2.;
+                let half_width = half_height * self.aspect_ratio;
+                (Vec4::new(ndc_x * half_width, ndc_y * half_height, 0., 1.),
+                 Vec4::new(0., 0., -1., 0.))
+            }
+        };
+        let transform = self.transform();
+        let origin = transform * origin;
+        let dir = transform * dir;
+        Ray::new(Vec3::new(origin.x, origin.y, origin.z), Vec3::new(dir.x, dir.y, dir.z))
+    }
 
-        debug!("Transform set to {:?}", self.transform);
+    /// The volume the camera can see, for culling objects outside of it
+    pub fn frustum(&self) -> Frustum {
+        Frustum::from_matrix(&(self.projection_matrix() * self.view_matrix()))
     }
 
     pub fn projection_matrix(&self) -> Mat4<f32> {
@@ -123,15 +210,30 @@ impl Camera {
             let n = self.near;
             let f = self.far;
 
-            let y = 1. / (self.fov / 2.).tan();
-            let x = y / self.aspect_ratio;
-            let a = (f + n) / (n - f);
-            let b = (2. * f * n) / (n - f);
+            self.proj_matrix.set(match self.projection {
+                Projection::Perspective => {
+                    let y = 1. / (self.fov / 2.).tan();
+                    let x = y / self.aspect_ratio;
+                    let a = (f + n) / (n - f);
+                    let b = (2. * f * n) / (n - f);
+
+                    Mat4::new(x,  0., 0.,  0.,
+                              0., y,  0.,  0.,
+                              0., 0., a,   b,
+                              0., 0., -1., 0.)
+                }
+                Projection::Orthographic { height } => {
+                    let y = 2. / height;
+                    let x = y / self.aspect_ratio;
+                    let a = 2. / (n - f);
+                    let b = (f + n) / (n - f);
 
-            self.proj_matrix.set(Mat4::new(x,  0., 0.,  0.,
-                                           0., y,  0.,  0.,
-                                           0., 0., a,   b,
-                                           0., 0., -1., 0.))
+                    Mat4::new(x,  0., 0., 0.,
+                              0., y,  0., 0.,
+                              0., 0., a,  b,
+                              0., 0., 0., 1.)
+                }
+            })
         }
         self.proj_matrix.get()
     }
@@ -139,7 +241,7 @@ impl Camera {
     pub fn view_matrix(&self) -> Mat4<f32> {
         if self.view_dirty.get() {
             self.view_dirty.set(false);
-            let t = &self.transform;
+            let t = &self.transform();
 
             // This is the camera position applied after the rotation
             let p = (-dot(&t.col(3), &t.col(0)),
@@ -156,3 +258,69 @@ impl Camera {
         self.view_matrix.get()
     }
 }
+
+/// First person fly camera movement: WASD moves along the view direction and strafes, Q and E
+/// move down and up, and holding shift sprints. Call `update` once per update step.
+pub struct FlyController {
+    /// Units per second
+    pub speed: f32,
+    /// Speed multiplier while sprinting
+    pub sprint_multiplier: f32,
+    /// Key held to sprint, `None` to disable sprinting
+    pub sprint_key: Option<Key>,
+    /// Radians the camera turns per pixel of mouse movement in mouse look mode
+    pub look_sensitivity: f32,
+    /// Key toggling mouse look mode, `None` to disable it
+    pub look_key: Option<Key>,
+}
+
+impl FlyController {
+    pub fn new(speed: f32) -> Self {
+        FlyController {
+            speed: speed,
+            sprint_multiplier: 3.,
+            sprint_key: Some(Key::LShift),
+            look_sensitivity: 0.003,
+            look_key: Some(Key::Tab),
+        }
+    }
+
+    /// Toggles mouse look when `look_key` is pressed, grabbing the cursor while it's on, and
+    /// turns `camera` by the mouse movement since the last frame while it's on
+    pub fn update_look(&self, ctxt: &mut EngineContext, camera: &mut Camera) {
+        if self.look_key.map_or(false, |k| ctxt.input().was_key_pressed(k)) {
+            let grabbed = ctxt.cursor_grabbed();
+            ctxt.set_cursor_grabbed(!grabbed);
+            return;
+        }
+        if !ctxt.cursor_grabbed() {
+            return;
+        }
+
+        let (dx, dy) = ctxt.input().mouse_delta();
+        if (dx, dy) != (0, 0) {
+            // Moving the mouse right turns right and moving it down looks down
+            camera.rotate(-dy as f32 * self.look_sensitivity, -dx as f32 * self.look_sensitivity);
+        }
+    }
+
+    /// Moves `camera` according to the keys currently held down, `dt` seconds worth
+    pub fn update(&self, input: &InputState, camera: &mut Camera, dt: f32) {
+        let axis = |positive, negative| {
+            let mut v = 0.;
+            if input.is_key_down(positive) { v += 1.; }
+            if input.is_key_down(negative) { v -= 1.; }
+            v
+        };
+
+        // The camera looks down its local -z axis
+        let dir = Vec3::new(axis(Key::D, Key::A), axis(Key::E, Key::Q), axis(Key::S, Key::W));
+        if dir == Vec3::new(0., 0., 0.) {
+            return;
+        }
+
+        let sprinting = self.sprint_key.map_or(false, |k| input.is_key_down(k));
+        let speed = if sprinting { self.speed * self.sprint_multiplier } else { self.speed };
+        camera.translate(&(dir.normalize() * speed * dt));
+    }
+}
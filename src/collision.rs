@@ -0,0 +1,408 @@
+use std::cmp::Ordering;
+use std::collections::HashSet;
+
+use bounds::Aabb;
+use ecs::{Entity, World};
+use events::EventBus;
+use transform::Transform;
+
+use nalgebra::{cross, dot, Mat4, Norm, Vec3};
+
+/// The shape of a `Collider`, centered on the entity's origin moved by `Collider::offset`, and
+/// scaled with the entity
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Shape {
+    /// A box that stays aligned with the world axes however the entity is rotated
+    Aabb { half_extents: Vec3<f32> },
+    Sphere { radius: f32 },
+    /// A box that rotates with the entity
+    Obb { half_extents: Vec3<f32> },
+}
+
+/// Makes an entity with a `Transform` collide with other colliders in its world. The world finds
+/// overlapping colliders every update, publishes a `CollisionEvent` for every pair on the
+/// `EventBus`, and pushes colliders that block each other apart. There is no other physics: the
+/// game moves entities, and colliders only keep them from overlapping.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Collider {
+    pub shape: Shape,
+    /// Where the center of the shape is, in the entity's local space
+    pub offset: Vec3<f32>,
+    /// Triggers report overlaps without blocking anything, e.g. a zone that opens a door
+    pub trigger: bool,
+    /// Fixed colliders block others without being pushed themselves, e.g. walls. Pushed entities
+    /// are moved in their parent's space, so they shouldn't have a scaled or rotated parent.
+    pub fixed: bool,
+}
+
+impl Collider {
+    pub fn new(shape: Shape) -> Self {
+        Collider { shape: shape, offset: Vec3::new(0., 0., 0.), trigger: false, fixed: false }
+    }
+
+    pub fn trigger(shape: Shape) -> Self {
+        Collider { trigger: true, .. Self::new(shape) }
+    }
+
+    pub fn fixed(shape: Shape) -> Self {
+        Collider { fixed: true, .. Self::new(shape) }
+    }
+
+    pub fn with_offset(mut self, offset: Vec3<f32>) -> Self {
+        self.offset = offset;
+        self
+    }
+}
+
+/// Two overlapping colliders. `a` is always ordered before `b`, so a pair is reported the same
+/// way every update.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Contact {
+    pub a: Entity,
+    pub b: Entity,
+    /// Direction to move `b` to separate the colliders, or `a` the opposite way
+    pub normal: Vec3<f32>,
+    /// How far the colliders overlap along `normal`
+    pub depth: f32,
+    /// Whether either collider is a trigger, so nothing was blocked
+    pub trigger: bool,
+}
+
+/// Published for every pair of colliders that started, kept or stopped overlapping during an
+/// update
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum CollisionEvent {
+    Enter(Contact),
+    Stay(Contact),
+    /// Also published when one of the entities was despawned or lost its collider
+    Exit(Entity, Entity),
+}
+
+/// Which colliders of a world overlap, as of its last update. See `World::collisions`.
+pub struct Collisions {
+    contacts: Vec<Contact>,
+    touching: HashSet<(Entity, Entity)>,
+}
+
+impl Collisions {
+    pub fn new() -> Self {
+        Collisions { contacts: Vec::new(), touching: HashSet::new() }
+    }
+
+    pub fn contacts(&self) -> &[Contact] {
+        &self.contacts
+    }
+
+    /// Whether the colliders of `a` and `b` overlapped, in either order
+    pub fn touching(&self, a: Entity, b: Entity) -> bool {
+        self.touching.contains(&if a < b { (a, b) } else { (b, a) })
+    }
+
+    /// Finds the overlapping colliders, sorting their bounding boxes along x so only neighbours
+    /// are tested against each other, then publishes the events and pushes apart the colliders
+    /// that block each other. Called by the world at the end of every update.
+    pub fn update(&mut self, world: &mut World, events: &EventBus) {
+        let mut bodies: Vec<_> = world.entities_with::<Collider>().into_iter()
+            .filter_map(|entity| {
+                let collider = *world.get::<Collider>(entity).unwrap();
+                world.world_transform(entity).map(|m| Body::new(entity, collider, &m))
+            })
+            .collect();
+        bodies.sort_by(|a, b| {
+            a.bounds.min.x.partial_cmp(&b.bounds.min.x).unwrap_or(Ordering::Equal)
+        });
+
+        let mut contacts = Vec::new();
+        for (i, first) in bodies.iter().enumerate() {
+            for second in bodies[i + 1..].iter() {
+                if second.bounds.min.x > first.bounds.max.x {
+                    break;
+                }
+                if !overlap(&first.bounds, &second.bounds) {
+                    continue;
+                }
+                let (a, b) = if first.entity < second.entity {
+                    (first, second)
+                } else {
+                    (second, first)
+                };
+                if let Some((normal, depth)) = collide(&a.shape, &b.shape) {
+                    contacts.push((Contact {
+                        a: a.entity,
+                        b: b.entity,
+                        normal: normal,
+                        depth: depth,
+                        trigger: a.collider.trigger || b.collider.trigger,
+                    }, a.collider.fixed, b.collider.fixed));
+                }
+            }
+        }
+
+        let mut touching = HashSet::new();
+        for &(contact, _, _) in contacts.iter() {
+            let pair = (contact.a, contact.b);
+            if self.touching.contains(&pair) {
+                events.publish(CollisionEvent::Stay(contact));
+            } else {
+                events.publish(CollisionEvent::Enter(contact));
+            }
+            touching.insert(pair);
+        }
+        for &(a, b) in self.touching.difference(&touching) {
+            events.publish(CollisionEvent::Exit(a, b));
+        }
+
+        let mut pushed = false;
+        for &(contact, a_fixed, b_fixed) in contacts.iter().filter(|c| !c.0.trigger) {
+            let (a_share, b_share) = match (a_fixed, b_fixed) {
+                (true, true) => continue,
+                (true, false) => (0., 1.),
+                (false, true) => (1., 0.),
+                (false, false) => (0.5, 0.5),
+            };
+            let push = contact.normal * contact.depth;
+            if let Some(transform) = world.get_mut::<Transform>(contact.a) {
+                transform.position = transform.position - push * a_share;
+            }
+            if let Some(transform) = world.get_mut::<Transform>(contact.b) {
+                transform.position = transform.position + push * b_share;
+            }
+            pushed = true;
+        }
+        if pushed {
+            world.update_world_transforms();
+        }
+
+        self.touching = touching;
+        self.contacts = contacts.into_iter().map(|c| c.0).collect();
+    }
+}
+
+// A collider in world space
+struct Body {
+    entity: Entity,
+    collider: Collider,
+    shape: WorldShape,
+    bounds: Aabb,
+}
+
+enum WorldShape {
+    Sphere { center: Vec3<f32>, radius: f32 },
+    // Unit axes, and how far the box extends along each of them
+    Box { center: Vec3<f32>, axes: [Vec3<f32>; 3], half: [f32; 3] },
+}
+
+impl Body {
+    fn new(entity: Entity, collider: Collider, m: &Mat4<f32>) -> Self {
+        let column = |c: usize| Vec3::new(m[(0, c)], m[(1, c)], m[(2, c)]);
+        let (x, y, z) = (column(0), column(1), column(2));
+        let o = collider.offset;
+        let center = column(3) + x * o.x + y * o.y + z * o.z;
+        let scale = [x.norm(), y.norm(), z.norm()];
+        let scaled = |h: Vec3<f32>| [h.x * scale[0], h.y * scale[1], h.z * scale[2]];
+        let world_axes = [Vec3::new(1., 0., 0.), Vec3::new(0., 1., 0.), Vec3::new(0., 0., 1.)];
+
+        let shape = match collider.shape {
+            Shape::Sphere { radius } => {
+                let scale = scale[0].max(scale[1]).max(scale[2]);
+                WorldShape::Sphere { center: center, radius: radius * scale }
+            }
+            Shape::Aabb { half_extents } => {
+                WorldShape::Box { center: center, axes: world_axes, half: scaled(half_extents) }
+            }
+            Shape::Obb { half_extents } => {
+                // A flattened axis keeps the world axis, since the box has no extent along it
+                let unit = |v: Vec3<f32>, s: f32, i: usize| {
+                    if s > 0. { v / s } else { world_axes[i] }
+                };
+                let axes = [unit(x, scale[0], 0), unit(y, scale[1], 1), unit(z, scale[2], 2)];
+                WorldShape::Box { center: center, axes: axes, half: scaled(half_extents) }
+            }
+        };
+        let bounds = match shape {
+            WorldShape::Sphere { center, radius } => {
+                let r = Vec3::new(radius, radius, radius);
+                Aabb::new(center - r, center + r)
+            }
+            WorldShape::Box { center, axes, half } => {
+                let extent = |k: usize| (0..3).map(|i| component(axes[i], k).abs() * half[i])
+                    .fold(0., |a, b| a + b);
+                let e = Vec3::new(extent(0), extent(1), extent(2));
+                Aabb::new(center - e, center + e)
+            }
+        };
+        Body { entity: entity, collider: collider, shape: shape, bounds: bounds }
+    }
+}
+
+fn component(v: Vec3<f32>, k: usize) -> f32 {
+    match k {
+        0 => v.x,
+        1 => v.y,
+        _ => v.z,
+    }
+}
+
+fn overlap(a: &Aabb, b: &Aabb) -> bool {
+    a.min.x <= b.max.x && b.min.x <= a.max.x &&
+        a.min.y <= b.max.y && b.min.y <= a.max.y &&
+        a.min.z <= b.max.z && b.min.z <= a.max.z
+}
+
+// The direction from `a` to `b` and how far they overlap, if they do
+fn collide(a: &WorldShape, b: &WorldShape) -> Option<(Vec3<f32>, f32)> {
+    match (a, b) {
+        (&WorldShape::Sphere { center: ca, radius: ra },
+         &WorldShape::Sphere { center: cb, radius: rb }) => {
+            let d = cb - ca;
+            let distance = d.norm();
+            if distance >= ra + rb {
+                return None;
+            }
+            // Spheres at the same place are pushed apart vertically
+            let normal = if distance > 1e-6 { d / distance } else { Vec3::new(0., 1., 0.) };
+            Some((normal, ra + rb - distance))
+        }
+        (&WorldShape::Sphere { center, radius }, &WorldShape::Box { center: cb, axes, half }) => {
+            sphere_box(center, radius, cb, &axes, &half)
+        }
+        (&WorldShape::Box { center: ca, axes, half }, &WorldShape::Sphere { center, radius }) => {
+            sphere_box(center, radius, ca, &axes, &half).map(|(n, d)| (-n, d))
+        }
+        (&WorldShape::Box { center: ca, axes: ref aa, half: ref ha },
+         &WorldShape::Box { center: cb, axes: ref ab, half: ref hb }) => {
+            box_box(ca, aa, ha, cb, ab, hb)
+        }
+    }
+}
+
+fn sphere_box(center: Vec3<f32>, radius: f32, box_center: Vec3<f32>, axes: &[Vec3<f32>; 3],
+              half: &[f32; 3]) -> Option<(Vec3<f32>, f32)> {
+    let local = center - box_center;
+    let offsets = [dot(&local, &axes[0]), dot(&local, &axes[1]), dot(&local, &axes[2])];
+    let inside = (0..3).all(|i| offsets[i].abs() <= half[i]);
+    if inside {
+        // Out through the nearest face
+        let i = (0..3).fold(0, |best, i| {
+            if half[i] - offsets[i].abs() < half[best] - offsets[best].abs() { i } else { best }
+        });
+        let sign = if offsets[i] >= 0. { 1. } else { -1. };
+        return Some((axes[i] * -sign, half[i] - offsets[i].abs() + radius));
+    }
+
+    let mut closest = box_center;
+    for i in 0..3 {
+        closest = closest + axes[i] * offsets[i].max(-half[i]).min(half[i]);
+    }
+    let d = closest - center;
+    let distance = d.norm();
+    if distance >= radius {
+        return None;
+    }
+    Some((d / distance, radius - distance))
+}
+
+// Separating axis test over the face normals of both boxes and the cross products of their edges
+fn box_box(ca: Vec3<f32>, aa: &[Vec3<f32>; 3], ha: &[f32; 3], cb: Vec3<f32>,
+           ab: &[Vec3<f32>; 3], hb: &[f32; 3]) -> Option<(Vec3<f32>, f32)> {
+    let mut candidates = Vec::with_capacity(15);
+    candidates.extend_from_slice(aa);
+    candidates.extend_from_slice(ab);
+    for a in aa.iter() {
+        for b in ab.iter() {
+            let axis = cross(a, b);
+            // Parallel edges are already covered by the face normals
+            if axis.sqnorm() > 1e-6 {
+                candidates.push(axis.normalize());
+            }
+        }
+    }
+
+    let d = cb - ca;
+    let mut best: Option<(Vec3<f32>, f32)> = None;
+    for axis in candidates {
+        let radius = |axes: &[Vec3<f32>; 3], half: &[f32; 3]| {
+            (0..3).map(|i| dot(&axes[i], &axis).abs() * half[i]).fold(0., |a, b| a + b)
+        };
+        let distance = dot(&d, &axis);
+        let overlap = radius(aa, ha) + radius(ab, hb) - distance.abs();
+        if overlap <= 0. {
+            return None;
+        }
+        if best.map_or(true, |(_, depth)| overlap < depth) {
+            let normal = if distance >= 0. { axis } else { -axis };
+            best = Some((normal, overlap));
+        }
+    }
+    best
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::FRAC_1_SQRT_2;
+
+    use super::{collide, WorldShape};
+
+    use nalgebra::{Norm, Vec3};
+
+    fn sphere(x: f32, y: f32, z: f32, radius: f32) -> WorldShape {
+        WorldShape::Sphere { center: Vec3::new(x, y, z), radius: radius }
+    }
+
+    fn unit_box(x: f32, y: f32, z: f32) -> WorldShape {
+        let axes = [Vec3::new(1., 0., 0.), Vec3::new(0., 1., 0.), Vec3::new(0., 0., 1.)];
+        WorldShape::Box { center: Vec3::new(x, y, z), axes: axes, half: [0.5, 0.5, 0.5] }
+    }
+
+    // A unit box turned 45 degrees around the z axis
+    fn diamond(x: f32, y: f32, z: f32) -> WorldShape {
+        let (c, s) = (FRAC_1_SQRT_2, FRAC_1_SQRT_2);
+        let axes = [Vec3::new(c, s, 0.), Vec3::new(-s, c, 0.), Vec3::new(0., 0., 1.)];
+        WorldShape::Box { center: Vec3::new(x, y, z), axes: axes, half: [0.5, 0.5, 0.5] }
+    }
+
+    fn assert_contact(contact: Option<(Vec3<f32>, f32)>, normal: Vec3<f32>, depth: f32) {
+        match contact {
+            Some((n, d)) if (n - normal).norm() < 1e-4 && (d - depth).abs() < 1e-4 => (),
+            other => panic!("expected normal {:?} and depth {}, got {:?}", normal, depth, other),
+        }
+    }
+
+    #[test]
+    fn sphere_sphere() {
+        let (a, b) = (sphere(0., 0., 0., 1.), sphere(1.5, 0., 0., 1.));
+        assert_contact(collide(&a, &b), Vec3::new(1., 0., 0.), 0.5);
+        assert!(collide(&a, &sphere(2.5, 0., 0., 1.)).is_none());
+    }
+
+    #[test]
+    fn sphere_inside_box() {
+        let (a, b) = (sphere(0.3, 0., 0., 0.25), unit_box(0., 0., 0.));
+        // Out through the nearest face, at +x, so the box is towards -x from the sphere
+        assert_contact(collide(&a, &b), Vec3::new(-1., 0., 0.), 0.2 + 0.25);
+    }
+
+    #[test]
+    fn rotated_boxes_separate_where_their_bounds_overlap() {
+        // The diamond's corners reach 0.71 along x and y, so the bounding boxes overlap, but
+        // along the normal of the diamond's facing side the box is 1.41 away and they only reach
+        // 1.21 between them
+        assert!(collide(&diamond(0., 0., 0.), &unit_box(1., 1., 0.)).is_none());
+        let depth = FRAC_1_SQRT_2 + 0.5 - 1.1;
+        assert_contact(collide(&diamond(0., 0., 0.), &unit_box(1.1, 0., 0.)),
+                       Vec3::new(1., 0., 0.), depth);
+    }
+
+    #[test]
+    fn normal_points_from_a_to_b() {
+        let pairs = [(sphere(0., 0., 0., 1.), sphere(1.5, 0., 0., 1.)),
+                     (sphere(0., 0., 0., 0.25), unit_box(0.6, 0., 0.)),
+                     (unit_box(0., 0., 0.), unit_box(0.8, 0., 0.)),
+                     (diamond(0., 0., 0.), unit_box(1.1, 0., 0.))];
+        for &(ref a, ref b) in pairs.iter() {
+            let (forward, depth) = collide(a, b).unwrap();
+            assert!(forward.x > 0.99, "{:?} doesn't point towards +x", forward);
+            assert_contact(collide(b, a), -forward, depth);
+        }
+    }
+}
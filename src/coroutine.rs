@@ -0,0 +1,120 @@
+enum Step<T: ?Sized> {
+    Wait(f32),
+    WaitUntil(Box<FnMut(&mut T) -> bool>),
+    Do(Box<FnMut(&mut T)>),
+}
+
+/// A scripted sequence of actions and waits, e.g. a boss attack pattern. A coroutine runs every
+/// step it can each time it is resumed, and suspends at the first wait that isn't over yet. Waits
+/// are in seconds of game time, counted down by the `dt` passed to `resume`, so they stop while
+/// the game is paused and play out the same way at any frame rate.
+///
+/// Entities run one as their `Script` with `Script::coroutine`. Objects usually keep theirs in an
+/// `Option` and resume it from `GameObject::update`, taking it out while it runs so the steps can
+/// borrow the object mutably:
+///
+/// ```ignore
+/// fn update(&mut self, ctxt: &UpdateContext) {
+///     if let Some(mut script) = self.script.take() {
+///         script.resume(self, ctxt.dt);
+///         self.script = Some(script);
+///     }
+/// }
+/// ```
+pub struct Coroutine<T: ?Sized> {
+    steps: Vec<Step<T>>,
+    current: usize,
+    // Seconds left in the last `wait`
+    waiting: f32,
+    looping: bool,
+}
+
+impl<T: ?Sized> Coroutine<T> {
+    pub fn new() -> Self {
+        Coroutine { steps: Vec::new(), current: 0, waiting: 0., looping: false }
+    }
+
+    /// Suspends the coroutine for `seconds`
+    pub fn wait(mut self, seconds: f32) -> Self {
+        self.steps.push(Step::Wait(seconds));
+        self
+    }
+
+    /// Suspends the coroutine until `condition` returns true, e.g. until an animation ends
+    pub fn wait_until<F: FnMut(&mut T) -> bool + 'static>(mut self, condition: F) -> Self {
+        self.steps.push(Step::WaitUntil(Box::new(condition)));
+        self
+    }
+
+    pub fn then<F: FnMut(&mut T) + 'static>(mut self, action: F) -> Self {
+        self.steps.push(Step::Do(Box::new(action)));
+        self
+    }
+
+    /// Restarts from the first step after the last one finishes
+    pub fn looping(mut self) -> Self {
+        self.looping = true;
+        self
+    }
+
+    pub fn finished(&self) -> bool {
+        self.current >= self.steps.len() && self.waiting <= 0.
+    }
+
+    /// Runs steps until one of them has to wait, `dt` seconds after the last time it was
+    /// resumed. Returns true once the coroutine has finished.
+    pub fn resume(&mut self, target: &mut T, dt: f32) -> bool {
+        if self.waiting > 0. {
+            self.waiting -= dt;
+            if self.waiting > 0. {
+                return false;
+            }
+        }
+
+        // Guards against looping forever when every step of a looping coroutine completes
+        // immediately
+        let mut steps_run = 0;
+        while steps_run <= self.steps.len() {
+            if self.finished() {
+                if !self.looping || self.steps.is_empty() {
+                    return true;
+                }
+                self.current = 0;
+            }
+
+            match self.steps[self.current] {
+                // Counted down by the following resumes
+                Step::Wait(seconds) => self.waiting = seconds,
+                Step::WaitUntil(ref mut condition) => if !condition(target) {
+                    return false;
+                },
+                Step::Do(ref mut action) => action(target),
+            }
+            self.current += 1;
+            steps_run += 1;
+            if self.waiting > 0. {
+                return false;
+            }
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Coroutine;
+
+    #[test]
+    fn waits_count_down_dt() {
+        let mut script = Coroutine::new().wait(1.).then(|count: &mut u32| *count += 1);
+        let mut count = 0;
+        // Starts the wait, which is counted down by the following resumes
+        assert!(!script.resume(&mut count, 0.));
+        assert!(!script.resume(&mut count, 0.5));
+        // No time passes while the game is paused
+        assert!(!script.resume(&mut count, 0.));
+        assert_eq!(count, 0);
+        assert!(script.resume(&mut count, 0.5));
+        assert_eq!(count, 1);
+    }
+}
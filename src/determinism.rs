@@ -0,0 +1,48 @@
+use std::mem;
+
+use transform::Transform;
+
+use nalgebra::Vec3;
+
+/// Hashes simulation state bit for bit, so two runs only hash the same if every float came out
+/// exactly the same. FNV-1a, which is simple enough to give the same result everywhere. The
+/// integration test in `tests/determinism.rs` compares these against references per platform.
+#[derive(Copy, Clone, Debug)]
+pub struct StateHash(u64);
+
+impl StateHash {
+    pub fn new() -> Self {
+        StateHash(0xcbf29ce484222325)
+    }
+
+    pub fn write_u32(&mut self, value: u32) {
+        for i in 0..4 {
+            self.0 ^= (value >> (i * 8)) as u8 as u64;
+            self.0 = self.0.wrapping_mul(0x100000001b3);
+        }
+    }
+
+    pub fn write_f32(&mut self, value: f32) {
+        // Bit for bit, so -0 and 0 or different NaNs don't hash the same
+        self.write_u32(unsafe { mem::transmute::<f32, u32>(value) });
+    }
+
+    pub fn write_vec3(&mut self, v: Vec3<f32>) {
+        self.write_f32(v.x);
+        self.write_f32(v.y);
+        self.write_f32(v.z);
+    }
+
+    pub fn write_transform(&mut self, transform: &Transform) {
+        self.write_vec3(transform.position);
+        let q = transform.rotation.quat();
+        for &c in [q.w, q.i, q.j, q.k].iter() {
+            self.write_f32(c);
+        }
+        self.write_vec3(transform.scale);
+    }
+
+    pub fn finish(&self) -> u64 {
+        self.0
+    }
+}
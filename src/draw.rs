@@ -1,61 +1,104 @@
-use std::io::{BufReader, Read};
-use std::fs::File;
+use std::any::Any;
+use std::borrow::Cow;
+use std::cell::{Cell, RefCell};
+use std::cmp;
+use std::f32;
+use std::mem;
 use std::path::Path;
 use std::rc::Rc;
 
-use {Character, EngineContext};
+use {CachedMesh, EngineContext, Error, Font, Glyph, GlyphAtlas};
+use bounds::Aabb;
+use events::EventBus;
+use input::InputState;
+use light::{self, Light};
+use material::{Albedo, CullFace, Material, Winding};
+use model::ObjData;
 use shader::{FragmentShaderType, VertexShaderType};
+use transform::Transform;
 use camera::Camera;
 
-use freetype as ft;
-
-use genmesh;
-
 use glium::{BlendingFunction, DepthTest, Display, DrawParameters, LinearBlendingFactor,
             VertexBuffer};
 use glium::backend::Facade;
 use glium::index::{IndicesSource, NoIndices, PrimitiveType};
-use glium::texture::Texture2d;
+use glium::texture::{ClientFormat, RawImage2d, Texture2d};
 use glium::uniforms::{MinifySamplerFilter, MagnifySamplerFilter, SamplerBehavior,
-                      SamplerWrapFunction, UniformValue, Uniforms};
+                      SamplerWrapFunction, UniformBuffer, UniformValue, Uniforms};
 use glium::vertex::VertexBufferAny;
 
-use nalgebra::{self, Col, Mat4, Vec3, Vec4};
-
-use obj;
+use nalgebra::{self, Col, Mat4, Norm, UnitQuat, Vec3};
 
-use time;
-
-const COLOR_TYPE: u32 = 0;
-const TEXTURE_RGB_TYPE: u32 = 1;
-const TEXTURE_ALPHA_TYPE: u32 = 2;
+pub const COLOR_TYPE: u32 = 0;
+pub const TEXTURE_RGB_TYPE: u32 = 1;
+pub const TEXTURE_ALPHA_TYPE: u32 = 2;
+pub const TEXTURE_ALPHA_FADE_TYPE: u32 = 3;
+pub const VERTEX_COLOR_TYPE: u32 = 4;
+pub const TEXTURE_TINT_TYPE: u32 = 5;
+pub const TEXTURE_ARRAY_TYPE: u32 = 6;
 
 #[derive(Copy, Clone, Debug)]
-struct Vertex {
-    position: [f32; 3],
-    tex_coord: [f32; 2],
+pub struct Vertex {
+    pub position: [f32; 3],
+    pub tex_coord: [f32; 2],
+    pub normal: [f32; 3],
+    /// Direction of increasing u along the surface, used by normal mapping
+    pub tangent: [f32; 3],
 }
 
 impl Vertex {
-    fn new(x: f32, y: f32, z: f32) -> Self {
-        Vertex { position: [x, y, z], tex_coord: [0., 0.] }
+    pub fn new(x: f32, y: f32, z: f32) -> Self {
+        Vertex::with_texture(x, y, z, 0., 0.)
+    }
+
+    pub fn with_texture(x: f32, y: f32, z: f32, u: f32, v: f32) -> Self {
+        Vertex::with_normal([x, y, z], [u, v], [0., 0., 0.])
     }
 
-    fn with_texture(x: f32, y: f32, z: f32, u: f32, v: f32) -> Self {
-        Vertex { position: [x, y, z], tex_coord: [u, v] }
+    pub fn with_normal(position: [f32; 3], tex_coord: [f32; 2], normal: [f32; 3]) -> Self {
+        Vertex { position: position, tex_coord: tex_coord, normal: normal, tangent: [0., 0., 0.] }
     }
 
+    /// Sets the tangents of a triangle's vertices from their positions and texture coordinates.
+    /// Triangles without usable texture coordinates are given a zero tangent.
+    pub fn compute_tangents(triangle: &mut [Vertex]) {
+        let p = |i: usize| {
+            let p = triangle[i].position;
+            Vec3::new(p[0], p[1], p[2])
+        };
+        let (e1, e2) = (p(1) - p(0), p(2) - p(0));
+        let du1 = triangle[1].tex_coord[0] - triangle[0].tex_coord[0];
+        let dv1 = triangle[1].tex_coord[1] - triangle[0].tex_coord[1];
+        let du2 = triangle[2].tex_coord[0] - triangle[0].tex_coord[0];
+        let dv2 = triangle[2].tex_coord[1] - triangle[0].tex_coord[1];
+        let det = du1 * dv2 - du2 * dv1;
+        let tangent = if det.abs() > f32::EPSILON {
+            let t = (e1 * dv2 - e2 * dv1) / det;
+            if t.sqnorm() > 0. { t.normalize() } else { t }
+        } else {
+            nalgebra::zero()
+        };
+        for v in triangle.iter_mut() {
+            v.tangent = [tangent.x, tangent.y, tangent.z];
+        }
+    }
 }
 
-implement_vertex!(Vertex, position, tex_coord);
+implement_vertex!(Vertex, position, tex_coord, normal, tangent);
+
+/// The layer objects are on unless they are given others
+pub const DEFAULT_LAYERS: u32 = 1;
 
 pub struct ObjectBuilder<'a> {
-    vertex_buffer: Option<VertexBufferAny>,
+    vertex_buffer: Option<Rc<VertexBufferAny>>,
     indices: Option<IndicesSource<'a>>,
+    instances: Option<VertexBufferAny>,
     draw_params: Option<DrawParameters<'a>>,
-    transform: Option<Mat4<f32>>,
-    vert_shader_type: Option<VertexShaderType>,
-    frag_shader_type: Option<FragmentShaderType>,
+    transform: Option<Transform>,
+    bounds: Option<Aabb>,
+    material: Material,
+    visible: bool,
+    layers: u32,
 }
 
 impl<'a> ObjectBuilder<'a> {
@@ -63,24 +106,61 @@ impl<'a> ObjectBuilder<'a> {
         ObjectBuilder {
             vertex_buffer: None,
             indices: None,
+            instances: None,
             draw_params: None,
             transform: None,
-            vert_shader_type: None,
-            frag_shader_type: None,
+            bounds: None,
+            material: Material::color([1., 1., 1.]),
+            visible: true,
+            layers: DEFAULT_LAYERS,
         }
     }
 
-    pub fn vertex_buffer<I: Into<IndicesSource<'a>>>(mut self, vb: VertexBufferAny,
+    pub fn vertex_buffer<I: Into<IndicesSource<'a>>>(self, vb: VertexBufferAny,
                                                      indices: I) -> Self {
+        self.shared_vertex_buffer(Rc::new(vb), indices)
+    }
+
+    /// Draws a vertex buffer that other objects may be drawing too
+    pub fn shared_vertex_buffer<I: Into<IndicesSource<'a>>>(mut self, vb: Rc<VertexBufferAny>,
+                                                            indices: I) -> Self {
         self.vertex_buffer = Some(vb);
         self.indices = Some(indices.into());
         self
     }
 
-    pub fn from_obj<F, I, P>(facade: &F, path: P, indices: I) -> Self
+    /// Draws a mesh from the `MeshCache`, sharing its vertex buffer and bounds. Packed meshes
+    /// switch the material to the `Packed` vertex shader.
+    pub fn mesh<I: Into<IndicesSource<'a>>>(self, mesh: &CachedMesh, indices: I) -> Self {
+        let mut builder = self.shared_vertex_buffer(mesh.vertex_buffer.clone(), indices);
+        if mesh.packed {
+            builder.material.vert_shader = VertexShaderType::Packed;
+        }
+        match mesh.bounds {
+            Some(bounds) => builder.bounds(bounds),
+            None => builder,
+        }
+    }
+
+    /// Loads every object in an OBJ file into one vertex buffer, ignoring materials. Use
+    /// `model::Model` to draw each part with its material.
+    pub fn from_obj<F, I, P>(facade: &F, path: P, indices: I) -> Result<Self, Error>
     where F: Facade, I: Into<IndicesSource<'a>>, P: AsRef<Path> {
-        let vb = load_obj(facade, &mut BufReader::new(File::open(path).unwrap()));
-        ObjectBuilder::new().vertex_buffer(vb, indices)
+        let data = try!(ObjData::load(path));
+        let vertices = data.vertices();
+        let bounds = Aabb::from_vertices(&vertices);
+        let vb = VertexBuffer::new(facade, vertices).into_vertex_buffer_any();
+        let builder = ObjectBuilder::new().vertex_buffer(vb, indices);
+        Ok(match bounds {
+            Some(bounds) => builder.bounds(bounds),
+            None => builder,
+        })
+    }
+
+    /// Per instance attributes. The vertex buffer is drawn once for every element of `instances`.
+    pub fn instances(mut self, instances: VertexBufferAny) -> Self {
+        self.instances = Some(instances);
+        self
     }
 
     pub fn draw_params(mut self, params: DrawParameters<'a>) -> Self {
@@ -88,18 +168,54 @@ impl<'a> ObjectBuilder<'a> {
         self
     }
 
-    pub fn transform(mut self, transform: Mat4<f32>) -> Self {
+    pub fn transform(mut self, transform: Transform) -> Self {
         self.transform = Some(transform);
         self
     }
 
+    /// Bounding box of the vertices, used for picking and frustum culling. Objects without one
+    /// can't be picked and are always drawn.
+    pub fn bounds(mut self, bounds: Aabb) -> Self {
+        self.bounds = Some(bounds);
+        self
+    }
+
+    /// Replaces the whole material, including shaders set with `vert_shader` or `frag_shader`
+    pub fn material(mut self, material: Material) -> Self {
+        self.material = material;
+        self
+    }
+
     pub fn vert_shader(mut self, vert_shader_type: VertexShaderType) -> Self {
-        self.vert_shader_type = Some(vert_shader_type);
+        self.material.vert_shader = vert_shader_type;
         self
     }
 
     pub fn frag_shader(mut self, frag_shader_type: FragmentShaderType) -> Self {
-        self.frag_shader_type = Some(frag_shader_type);
+        self.material.frag_shader = frag_shader_type;
+        self
+    }
+
+    pub fn cull_face(mut self, cull_face: CullFace) -> Self {
+        self.material.cull_face = cull_face;
+        self
+    }
+
+    /// Which winding is the front of a triangle, e.g. `Clockwise` for a mesh that's culled
+    /// inside out
+    pub fn front_face(mut self, front_face: Winding) -> Self {
+        self.material.front_face = front_face;
+        self
+    }
+
+    pub fn visible(mut self, visible: bool) -> Self {
+        self.visible = visible;
+        self
+    }
+
+    /// Bit mask of the layers the object is on, see `Object::layers`
+    pub fn layers(mut self, layers: u32) -> Self {
+        self.layers = layers;
         self
     }
 
@@ -108,10 +224,15 @@ impl<'a> ObjectBuilder<'a> {
             name: None,
             vertex_buffer: self.vertex_buffer,
             indices: self.indices,
+            instances: self.instances,
             draw_params: self.draw_params.unwrap_or_else(|| Default::default()),
-            transform: self.transform.unwrap_or_else(|| nalgebra::new_identity(4)),
-            vert_shader_type: self.vert_shader_type.unwrap_or(VertexShaderType::Perspective),
-            frag_shader_type: self.frag_shader_type.unwrap_or(FragmentShaderType::Unlit),
+            transform: self.transform.unwrap_or_else(Transform::identity),
+            previous_transform: Cell::new(None),
+            transform_buffer: RefCell::new(None),
+            bounds: self.bounds,
+            material: self.material,
+            visible: self.visible,
+            layers: self.layers,
         }
     }
 }
@@ -119,27 +240,193 @@ impl<'a> ObjectBuilder<'a> {
 // FIXME: Use getters instead of public fields
 pub struct Object<'a> {
     pub name: Option<String>,
-    pub vertex_buffer: Option<VertexBufferAny>,
+    pub vertex_buffer: Option<Rc<VertexBufferAny>>,
     pub indices: Option<IndicesSource<'a>>,
+    pub instances: Option<VertexBufferAny>,
     pub draw_params: DrawParameters<'a>,
-    pub transform: Mat4<f32>,
-    pub vert_shader_type: VertexShaderType,
-    pub frag_shader_type: FragmentShaderType,
+    /// Transform relative to the object's parent, or to the world for top level objects
+    pub transform: Transform,
+    // The transform before the last update, for drawing between updates
+    previous_transform: Cell<Option<Transform>>,
+    // The transform the object was last drawn with, and the buffer holding it
+    transform_buffer: RefCell<Option<(Mat4<f32>, Rc<UniformBuffer<[[f32; 4]; 4]>>)>>,
+    /// Bounding box of the vertex buffer, before the vertex shader scales it
+    pub bounds: Option<Aabb>,
+    pub material: Material,
+    /// Hidden objects and their children aren't drawn or picked, but are still updated
+    pub visible: bool,
+    /// Bit mask of the layers the object is on. It's only drawn by cameras whose culling mask
+    /// shares a layer with it, e.g. to keep debug objects out of a minimap. Children have their
+    /// own layers.
+    pub layers: u32,
 }
 
-pub trait GameObject {
+impl<'a> Object<'a> {
+    /// Bounding box of the object in the world when drawn with `transform`, or `None` if it has
+    /// no bounds or isn't drawn in the world
+    pub fn transformed_bounds(&self, transform: &Mat4<f32>) -> Option<Aabb> {
+        match (self.bounds, self.material.vert_shader.world_scale()) {
+            (Some(bounds), Some(scale)) => {
+                let mut transform = *transform;
+                for i in 0..3 {
+                    let col = transform.col(i) * scale;
+                    transform.set_col(i, col);
+                }
+                Some(bounds.transform(&transform))
+            }
+            _ => None,
+        }
+    }
+
+    /// Remembers the current transform as the one to interpolate from. The scene calls this
+    /// before every update.
+    pub fn save_transform(&self) {
+        self.previous_transform.set(Some(self.transform));
+    }
+
+    /// The transform `alpha` of the way from the one before the last update to the current one,
+    /// as a matrix
+    pub fn interpolated_transform(&self, alpha: f32) -> Mat4<f32> {
+        match self.previous_transform.get() {
+            Some(previous) => previous.lerp(&self.transform, alpha).matrix(),
+            None => self.transform.matrix(),
+        }
+    }
+
+    /// The uniform buffer the object's shaders read `transform` from. It's kept between frames
+    /// and only written when the object is drawn with a different transform than last time, so
+    /// objects that don't move don't upload their matrix every frame.
+    pub fn transform_buffer<F: Facade>(&self, facade: &F,
+                                       transform: &Mat4<f32>) -> Rc<UniformBuffer<[[f32; 4]; 4]>> {
+        let mut cached = self.transform_buffer.borrow_mut();
+        if let Some((ref mut last, ref buffer)) = *cached {
+            if *last != *transform {
+                buffer.write(transform.as_array());
+                *last = *transform;
+            }
+            return buffer.clone();
+        }
+        let buffer = Rc::new(UniformBuffer::new(facade, *transform.as_array()));
+        *cached = Some((*transform, buffer.clone()));
+        buffer
+    }
+}
+
+/// What objects get to see while updating
+pub struct UpdateContext<'a> {
+    /// Seconds since the last update
+    pub dt: f32,
+    pub input: &'a InputState,
+    /// Events published since the previous update step, and where to publish new ones
+    pub events: &'a EventBus,
+}
+
+/// Lets game objects be downcast to their concrete type. Implemented for every `'static` type.
+pub trait AsAny {
+    fn as_any(&self) -> &Any;
+    fn as_any_mut(&mut self) -> &mut Any;
+}
+
+impl<T: Any> AsAny for T {
+    fn as_any(&self) -> &Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut Any {
+        self
+    }
+}
+
+pub trait GameObject: AsAny {
     fn name(&self) -> Option<&str> {
         self.parent().name.as_ref().map(|s| &*s as &str)
     }
-    fn update(&mut self) {}
+    fn update(&mut self, _: &UpdateContext) {}
+
+    /// Whether the object is done and should be removed from its scene or group after the current
+    /// update, e.g. a bullet that hit something
+    fn is_dead(&self) -> bool {
+        false
+    }
+
     fn parent(&self) -> &Object;
     fn children(&self) -> Option<&[Box<GameObject>]> {
         None
     }
-    fn construct_uniforms(&self, &Camera) -> UniformsVec;
+
+    /// Uniforms the object's shaders need besides the ones from its material, the camera and its
+    /// transform
+    fn construct_uniforms(&self, _: &Camera) -> UniformsVec {
+        UniformsVec(Vec::new())
+    }
+
+    /// The object's transform composed with its parent's world transform
+    fn world_transform(&self, parent_transform: &Mat4<f32>) -> Mat4<f32> {
+        *parent_transform * self.parent().transform.matrix()
+    }
+
+    /// Bounding box of the object in the world, or `None` if it has no bounds or isn't drawn in
+    /// the world
+    fn world_bounds(&self, parent_transform: &Mat4<f32>) -> Option<Aabb> {
+        self.parent().transformed_bounds(&self.world_transform(parent_transform))
+    }
+
+    /// The transform the object is drawn with. `parent_transform` is the interpolated world
+    /// transform of the object's parent, or the identity for top level objects, and `alpha` is
+    /// how far the frame is between the last two updates. Only objects that depend on the
+    /// camera, like billboards, need to override this.
+    fn draw_transform(&self, _: &Camera, parent_transform: &Mat4<f32>, alpha: f32) -> Mat4<f32> {
+        *parent_transform * self.parent().interpolated_transform(alpha)
+    }
+}
+
+/// Everything drawn from one camera in a frame shares these, so they are computed once by
+/// `EngineContext::frame_uniforms` and merged into each object's uniforms by `draw_in_frame`.
+/// Objects only need `construct_uniforms` for uniforms of their own.
+pub struct FrameUniforms<'a> {
+    pub camera: &'a Camera,
+    /// Passed to objects using a lit fragment shader
+    pub lights: &'a [Light],
+    pub ambient: [f32; 3],
+    /// Seconds since the engine started, e.g. for animated shaders
+    pub time: f32,
+    /// Size of the surface being drawn to in pixels
+    pub viewport: (u32, u32),
+    proj_matrix: [[f32; 4]; 4],
+    view_matrix: [[f32; 4]; 4],
+}
+
+impl<'a> FrameUniforms<'a> {
+    pub fn new(camera: &'a Camera, lights: &'a [Light], ambient: [f32; 3], time: f32,
+               viewport: (u32, u32)) -> Self {
+        FrameUniforms {
+            camera: camera,
+            lights: lights,
+            ambient: ambient,
+            time: time,
+            viewport: viewport,
+            proj_matrix: *camera.projection_matrix().as_array(),
+            view_matrix: *camera.view_matrix().as_array(),
+        }
+    }
+
+    /// Appends `proj_matrix`, `view_matrix`, `camera_position`, `time` and `viewport` to
+    /// `uniforms`, and the lights if `lit` is true
+    pub fn push_uniforms(&self, uniforms: &mut UniformsVec, lit: bool) {
+        let p = self.camera.position();
+        let (w, h) = self.viewport;
+        uniforms.0.push(("proj_matrix", UniformValue::Mat4(self.proj_matrix)));
+        uniforms.0.push(("view_matrix", UniformValue::Mat4(self.view_matrix)));
+        uniforms.0.push(("camera_position", UniformValue::Vec3([p.x, p.y, p.z])));
+        uniforms.0.push(("time", UniformValue::Float(self.time)));
+        uniforms.0.push(("viewport", UniformValue::Vec2([w as f32, h as f32])));
+        if lit {
+            light::push_uniforms(uniforms, self.lights, self.ambient);
+        }
+    }
 }
 
-struct UniformsVec<'a>(Vec<(&'static str, UniformValue<'a>)>);
+pub struct UniformsVec<'a>(pub Vec<(&'static str, UniformValue<'a>)>);
 impl<'b> Uniforms for UniformsVec<'b> {
     fn visit_values<'a, F: FnMut(&str, UniformValue<'a>)>(&'a self, mut f: F) {
         for v in self.0.iter() {
@@ -148,22 +435,55 @@ impl<'b> Uniforms for UniformsVec<'b> {
     }
 }
 
-pub struct Grid<'a> {
-    parent: Object<'a>,
+/// An object without geometry of its own that moves its children with it. Children are positioned
+/// relative to the group, so e.g. a turret added to a tank's group follows the tank around.
+pub struct Group<'a> {
+    pub parent: Object<'a>,
+    children: Vec<Box<GameObject>>,
 }
 
-impl<'a> GameObject for Grid<'a> {
+impl GameObject for Group<'static> {
     fn parent(&self) -> &Object {
         &self.parent
     }
 
-    fn construct_uniforms(&self, camera: &Camera) -> UniformsVec {
-        UniformsVec(vec![
-            ("type", UniformValue::UnsignedInt(COLOR_TYPE)),
-            ("proj_matrix", UniformValue::Mat4(*camera.projection_matrix().as_array())),
-            ("view_matrix", UniformValue::Mat4(*camera.view_matrix().as_array())),
-            ("transform", UniformValue::Mat4(*self.parent.transform.as_array())),
-            ("color", UniformValue::Vec3([1., 1., 1.]))])
+    fn update(&mut self, ctxt: &UpdateContext) {
+        for child in self.children.iter_mut() {
+            child.update(ctxt);
+        }
+        self.children.retain(|c| !c.is_dead());
+    }
+
+    fn children(&self) -> Option<&[Box<GameObject>]> {
+        Some(&*self.children)
+    }
+}
+
+impl<'a> Group<'a> {
+    pub fn new(transform: Transform) -> Self {
+        Group { parent: ObjectBuilder::new().transform(transform).build(), children: Vec::new() }
+    }
+
+    pub fn add<G: GameObject + 'static>(&mut self, child: G) {
+        self.children.push(Box::new(child));
+    }
+
+    pub fn children_mut(&mut self) -> &mut [Box<GameObject>] {
+        &mut self.children
+    }
+
+    pub fn set_transform(&mut self, transform: Transform) {
+        self.parent.transform = transform;
+    }
+}
+
+pub struct Grid<'a> {
+    parent: Object<'a>,
+}
+
+impl GameObject for Grid<'static> {
+    fn parent(&self) -> &Object {
+        &self.parent
     }
 }
 
@@ -203,40 +523,34 @@ impl<'a> Grid<'a> {
 
 pub struct Cube<'a> {
     parent: Object<'a>,
-    texture: Rc<Texture2d>,
+    // Rotation around the z axis in radians
+    angle: f32,
 }
 
-impl<'a> GameObject for Cube<'a> {
+impl GameObject for Cube<'static> {
     fn parent(&self) -> &Object {
         &self.parent
     }
 
-    fn update(&mut self) {
-        let mut rot_mat = Self::get_rotation_mat(time::get_time());
-        rot_mat.set_col(3, self.parent.transform.col(3));
-        self.parent.transform = rot_mat;
+    fn update(&mut self, ctxt: &UpdateContext) {
+        self.angle = (self.angle + ctxt.dt) % (2. * f32::consts::PI);
+        self.parent.transform.rotation = UnitQuat::new(Vec3::new(0., 0., self.angle));
     }
+}
 
-    fn construct_uniforms(&self, camera: &Camera) -> UniformsVec {
+impl<'a> Cube<'a> {
+    pub fn new(ctxt: &mut EngineContext, dim: f32, pos: Vec3<f32>) -> Result<Self, Error> {
+        let mut path = ctxt.resource_dir.clone();
+        path.push("cube.png");
+        let tex = try!(ctxt.texture_cache.get_texture(&ctxt.display, path));
         let sampler = SamplerBehavior {
             minify_filter: MinifySamplerFilter::Nearest,
             magnify_filter: MagnifySamplerFilter::Nearest,
             .. Default::default()
         };
-        UniformsVec(vec![
-            ("type", UniformValue::UnsignedInt(TEXTURE_RGB_TYPE)),
-            ("proj_matrix", UniformValue::Mat4(*camera.projection_matrix().as_array())),
-            ("view_matrix", UniformValue::Mat4(*camera.view_matrix().as_array())),
-            ("transform", UniformValue::Mat4(*self.parent.transform.as_array())),
-            ("tex", UniformValue::Texture2d(&self.texture, Some(sampler)))])
-    }
-}
-
-impl<'a> Cube<'a> {
-    pub fn new(ctxt: &mut EngineContext, dim: f32, pos: Vec3<f32>) -> Self {
-        let mut path = ctxt.resource_dir.clone();
-        path.push("cube.png");
-        let tex = ctxt.texture_cache.get_texture(&ctxt.display, path);
+        let material = Material::texture(tex)
+            .frag_shader(FragmentShaderType::Phong)
+            .sampler(sampler);
 
         let params = DrawParameters {
             depth_test: DepthTest::IfLess,
@@ -244,156 +558,706 @@ impl<'a> Cube<'a> {
             .. Default::default()
         };
 
-        let mut transform: Mat4<f32> = nalgebra::new_identity(4);
-        transform = transform * dim;
-        transform.set_col(3, Vec4::new(pos.x, pos.y, pos.z, 1.));
+        let transform = Transform::new(pos).with_uniform_scale(dim);
 
         let mut path = ctxt.resource_dir.clone();
         path.push("cube.obj");
-        let parent = ObjectBuilder::from_obj(&ctxt.display, path,
-                                             NoIndices(PrimitiveType::TrianglesList))
+        let mesh = try!(ctxt.mesh_cache.get_obj(&ctxt.display, path));
+        let parent = ObjectBuilder::new()
+            .mesh(&mesh, NoIndices(PrimitiveType::TrianglesList))
             .draw_params(params)
             .transform(transform)
+            .material(material)
             .build();
 
-        Cube { parent: parent, texture: tex }
+        Ok(Cube { parent: parent, angle: 0. })
+    }
+}
+
+/// One copy of an instanced mesh
+#[derive(Copy, Clone, Debug)]
+pub struct Instance {
+    pub transform: Mat4<f32>,
+    /// Only used when the material is colored per instance
+    pub color: [f32; 3],
+    /// Added to the material's layer when it samples a texture array
+    pub layer: u32,
+}
+
+// Matrices are passed as four column attributes
+#[derive(Copy, Clone, Debug)]
+struct InstanceAttributes {
+    instance_col0: [f32; 4],
+    instance_col1: [f32; 4],
+    instance_col2: [f32; 4],
+    instance_col3: [f32; 4],
+    instance_color: [f32; 3],
+    instance_layer: f32,
+}
+
+implement_vertex!(InstanceAttributes, instance_col0, instance_col1, instance_col2, instance_col3,
+                  instance_color, instance_layer);
+
+/// Many copies of the same mesh drawn with a single draw call, each with its own transform and
+/// color, or layer of a texture array. Instance transforms are relative to the object's own
+/// transform.
+pub struct InstancedObject<'a> {
+    parent: Object<'a>,
+    len: usize,
+}
+
+impl GameObject for InstancedObject<'static> {
+    fn parent(&self) -> &Object {
+        &self.parent
+    }
+}
+
+impl<'a> InstancedObject<'a> {
+    /// Draws the mesh in `mesh` (e.g. from `ObjectBuilder::from_obj` or `MeshBuilder::object`)
+    /// once per instance. The vertex shader is replaced with the instanced one, or the packed
+    /// instanced one for packed meshes, and a plain color material is replaced with the per
+    /// instance colors.
+    pub fn new(display: &Display, mesh: ObjectBuilder<'a>, instances: &[Instance]) -> Self {
+        let shader = match mesh.material.vert_shader {
+            VertexShaderType::Packed | VertexShaderType::PackedInstanced => {
+                VertexShaderType::PackedInstanced
+            }
+            _ => VertexShaderType::Instanced,
+        };
+        let mut mesh = mesh.vert_shader(shader)
+            .instances(Self::build_instances(display, instances));
+        // The mesh's bounds only cover one instance
+        mesh.bounds = None;
+        if let Albedo::Color(_) = mesh.material.albedo {
+            mesh.material.albedo = Albedo::VertexColor;
+        }
+        InstancedObject { parent: mesh.build(), len: instances.len() }
+    }
+
+    /// Textures every instance with `texture`, or uses the per instance colors for `None`
+    pub fn set_texture(&mut self, texture: Option<Rc<Texture2d>>) {
+        self.parent.material.albedo = match texture {
+            Some(texture) => Albedo::Texture(texture),
+            None => Albedo::VertexColor,
+        };
+    }
+
+    /// Replaces every instance, e.g. after some of them moved
+    pub fn set_instances(&mut self, display: &Display, instances: &[Instance]) {
+        self.parent.instances = Some(Self::build_instances(display, instances));
+        self.len = instances.len();
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
     }
 
-    pub fn get_rotation_mat(t: time::Timespec) -> Mat4<f32> {
-        let sec = (t.sec as f64) + ((t.nsec as f64)/1e9);
+    fn build_instances(display: &Display, instances: &[Instance]) -> VertexBufferAny {
+        let data: Vec<_> = instances.iter().map(|i| {
+            let col = |c: usize| {
+                let v = i.transform.col(c);
+                [v.x, v.y, v.z, v.w]
+            };
+            InstanceAttributes {
+                instance_col0: col(0),
+                instance_col1: col(1),
+                instance_col2: col(2),
+                instance_col3: col(3),
+                instance_color: i.color,
+                instance_layer: i.layer as f32,
+            }
+        }).collect();
+        VertexBuffer::new(display, data).into_vertex_buffer_any()
+    }
+}
+
+/// How the lines of a `Text` are placed relative to its position
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum TextAlign {
+    /// Lines start at the position
+    Left,
+    /// Lines are centered on the position
+    Center,
+    /// Lines end at the position
+    Right,
+}
+
+/// How a `Text` is drawn. Shadows, outlines and backgrounds keep text readable over bright or
+/// busy scenes.
+#[derive(Copy, Clone, Debug)]
+pub struct TextStyle {
+    pub color: [f32; 3],
+    /// Color of a copy of the text drawn behind it, `None` for no shadow
+    pub shadow_color: Option<[f32; 3]>,
+    /// How far the shadow is moved right and down, in pixels
+    pub shadow_offset: (f32, f32),
+    /// Color of an outline around every glyph, `None` for no outline
+    pub outline_color: Option<[f32; 3]>,
+    /// Width of the outline in pixels
+    pub outline_width: f32,
+    /// Color of a quad behind all of the text, `None` for no background
+    pub background_color: Option<[f32; 3]>,
+    /// Space between the text and the edges of the background, in pixels
+    pub padding: f32,
+}
 
-        Mat4::new(sec.cos() as f32,  -sec.sin() as f32, 0., 0.,
-                  sec.sin() as f32,  sec.cos() as f32,  0., 0.,
-                  0.,                0.,                1., 0.,
-                  0.,                0.,                0., 1.)
+impl Default for TextStyle {
+    fn default() -> Self {
+        TextStyle {
+            color: [0., 1., 0.],
+            shadow_color: None,
+            shadow_offset: (2., 2.),
+            outline_color: None,
+            outline_width: 1.,
+            background_color: None,
+            padding: 4.,
+        }
     }
 }
 
+/// Screen space text in a font from the `FontCache`. `\n` starts a new line, spaced by the font's
+/// line height, and lines longer than the maximum width, if there is one, are wrapped between
+/// words. The background, shadow, outline and glyphs are children of the text, drawn in that
+/// order.
 pub struct Text<'a> {
     pub parent: Object<'a>,
-    chars: Vec<Box<GameObject>>,
-    face: ft::Face<'a>, // TODO: Lifetime?
+    layers: Vec<Box<GameObject>>,
+    font: Font,
     x: f32,
     y: f32,
+    text: String,
+    style: TextStyle,
+    align: TextAlign,
+    max_width: Option<f32>,
+    // Whether the position and glyphs are in pixels rather than normalized device coordinates
+    in_pixels: bool,
 }
 
-impl<'a> GameObject for Text<'a> {
+impl GameObject for Text<'static> {
     fn parent(&self) -> &Object {
         &self.parent
     }
 
     fn children(&self) -> Option<&[Box<GameObject>]> {
-        Some(&*self.chars)
-    }
-
-    fn construct_uniforms(&self, _: &Camera) -> UniformsVec {
-        unimplemented!()
+        Some(&*self.layers)
     }
 }
 
 impl<'a> Text<'a> {
-    pub fn new(ctxt: &mut EngineContext, x_start: f32, y_start: f32, text: &str) -> Self {
-        let mut path = ctxt.resource_dir.clone();
-        path.push("FiraSans-Regular.ttf");
+    /// Left aligned, unwrapped text in `font` whose first line starts at `(x_start, y_start)` in
+    /// normalized device coordinates. Fonts come from `EngineContext::font` or `default_font`.
+    pub fn new(ctxt: &mut EngineContext, font: &Font, x_start: f32, y_start: f32,
+               text: &str) -> Result<Self, Error> {
+        Self::with_style(ctxt, font, TextStyle::default(), x_start, y_start, text)
+    }
+
+    /// Like `new`, drawn with `style`
+    pub fn with_style(ctxt: &mut EngineContext, font: &Font, style: TextStyle, x_start: f32,
+                      y_start: f32, text: &str) -> Result<Self, Error> {
+        Self::create(ctxt, font, style, x_start, y_start, text, false)
+    }
+
+    /// Like `with_style`, with the first line starting at `(x_start, y_start)` in pixels and the
+    /// glyphs laid out in pixels, for adding to a `layout::Anchored` group. The text keeps its
+    /// size when the window is resized.
+    pub fn in_pixels(ctxt: &mut EngineContext, font: &Font, style: TextStyle, x_start: f32,
+                     y_start: f32, text: &str) -> Result<Self, Error> {
+        Self::create(ctxt, font, style, x_start, y_start, text, true)
+    }
+
+    fn create(ctxt: &mut EngineContext, font: &Font, style: TextStyle, x_start: f32,
+              y_start: f32, text: &str, in_pixels: bool) -> Result<Self, Error> {
+        let mut text = Text {
+            parent: ObjectBuilder::new().vert_shader(VertexShaderType::Gui).build(),
+            layers: Vec::new(),
+            font: font.clone(),
+            x: x_start,
+            y: y_start,
+            text: text.to_owned(),
+            style: style,
+            align: TextAlign::Left,
+            max_width: None,
+            in_pixels: in_pixels,
+        };
+        try!(text.rebuild(ctxt));
+        Ok(text)
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn set_text(&mut self, ctxt: &mut EngineContext, text: &str) -> Result<(), Error> {
+        self.text = text.to_owned();
+        self.rebuild(ctxt)
+    }
+
+    pub fn font(&self) -> &Font {
+        &self.font
+    }
+
+    /// Switches to another font or size, laying the text out again
+    pub fn set_font(&mut self, ctxt: &mut EngineContext, font: &Font) -> Result<(), Error> {
+        self.font = font.clone();
+        self.rebuild(ctxt)
+    }
+
+    pub fn style(&self) -> TextStyle {
+        self.style
+    }
+
+    pub fn set_style(&mut self, ctxt: &mut EngineContext, style: TextStyle) -> Result<(), Error> {
+        self.style = style;
+        self.rebuild(ctxt)
+    }
 
-        let freetype = ft::Library::init().unwrap();
-        let face = freetype.new_face(path, 0).unwrap();
-        face.set_pixel_sizes(0, 16).unwrap();
+    pub fn align(&self) -> TextAlign {
+        self.align
+    }
+
+    pub fn set_align(&mut self, ctxt: &mut EngineContext, align: TextAlign) -> Result<(), Error> {
+        self.align = align;
+        self.rebuild(ctxt)
+    }
 
-        // FIXME: This doesn't update after rescaling
-        let (w, h) = ::get_display_dim(&ctxt.display);
-        let (sx, sy) = (2. / w as f32, 2. / h as f32);
+    pub fn max_width(&self) -> Option<f32> {
+        self.max_width
+    }
 
-        let mut x = x_start;
-        let mut y = y_start;
-        let mut chars = Vec::new();
-        for c in text.chars() {
-            let char = ctxt.texture_cache.get_glyph(&ctxt.display, &face, c);
-            let advance_x = char.advance_x * sx;
-            let advance_y = char.advance_y * sy;
+    /// Wraps lines wider than `max_width` pixels between words. Words wider than that on their
+    /// own get a line to themselves. `None` turns wrapping off.
+    pub fn set_max_width(&mut self, ctxt: &mut EngineContext,
+                         max_width: Option<f32>) -> Result<(), Error> {
+        self.max_width = max_width;
+        self.rebuild(ctxt)
+    }
 
-            chars.push(Box::new(Char::new(&ctxt.display, x, y, sx, sy, char)) as Box<GameObject>);
+    // Lays out every glyph as a quad textured from the atlas, once for the text itself and once
+    // for each copy making up its shadow and outline, and puts a quad behind them if the style
+    // has a background
+    fn rebuild(&mut self, ctxt: &mut EngineContext) -> Result<(), Error> {
+        let font = &self.font;
+        let style = ctxt.accessibility.text_style(self.style);
+        let atlas = ctxt.texture_cache.glyphs();
+        let printable: String = self.text.chars().filter(|&c| c != '\n').collect();
+        let texture = try!(atlas.prepare(&ctxt.display, font, &printable));
+        let size = atlas.size();
+
+        let (sx, sy) = if self.in_pixels {
+            (1., 1.)
+        } else {
+            // FIXME: This doesn't update after rescaling, use `in_pixels` in an `Anchored` group
+            let (w, h) = ::get_display_dim(&ctxt.display);
+            (2. / w as f32, 2. / h as f32)
+        };
 
-            x += advance_x;
-            y += advance_y;
+        let placed = place_glyphs(atlas, font, &self.text, self.align, self.max_width,
+                                  (self.x, self.y), (sx, sy));
+        let quads: Vec<GlyphQuad> = placed.iter().map(|&(left, bottom, ref glyph)| {
+            let (right, top) = (left + glyph.width * sx, bottom + glyph.height * sy);
+            ((left, bottom, right, top), glyph.tex_coords(size))
+        }).collect();
+
+        let mut layers = Vec::new();
+        if let Some(color) = style.background_color {
+            let bounds = quads.iter().fold(None, |b: Option<(f32, f32, f32, f32)>, &(q, _)| {
+                Some(b.map_or(q, |b| (b.0.min(q.0), b.1.min(q.1), b.2.max(q.2), b.3.max(q.3))))
+            });
+            if let Some((left, bottom, right, top)) = bounds {
+                let (px, py) = (style.padding * sx, style.padding * sy);
+                let (l, b, r, t) = (left - px, bottom - py, right + px, top + py);
+                let shape = vec![Vertex::new(l, b, -0.9), Vertex::new(r, b, -0.9),
+                                 Vertex::new(r, t, -0.9), Vertex::new(l, b, -0.9),
+                                 Vertex::new(r, t, -0.9), Vertex::new(l, t, -0.9)];
+                let material = Material::color(color).vert_shader(VertexShaderType::Gui);
+                layers.push(text_layer(&ctxt.display, shape, material));
+            }
         }
+        if let Some(color) = style.shadow_color {
+            let (dx, dy) = style.shadow_offset;
+            let shape = glyph_vertices(&quads, &[(dx * sx, -dy * sy)]);
+            layers.push(text_layer(&ctxt.display, shape, glyph_material(&texture, color)));
+        }
+        if let Some(color) = style.outline_color {
+            let (ox, oy) = (style.outline_width * sx, style.outline_width * sy);
+            let offsets = [(-ox, -oy), (0., -oy), (ox, -oy), (-ox, 0.),
+                           (ox, 0.), (-ox, oy), (0., oy), (ox, oy)];
+            let shape = glyph_vertices(&quads, &offsets);
+            layers.push(text_layer(&ctxt.display, shape, glyph_material(&texture, color)));
+        }
+        let shape = glyph_vertices(&quads, &[(0., 0.)]);
+        layers.push(text_layer(&ctxt.display, shape, glyph_material(&texture, style.color)));
 
-        Text { chars: chars, face: face, x: x_start, y: y_start,
-               parent: ObjectBuilder::new().build() }
+        self.layers = layers;
+        Ok(())
     }
 
-    pub fn set_text(&mut self, ctxt: &mut EngineContext, text: &str) {
-        let mut path = ctxt.resource_dir.clone();
-        path.push("FiraSans-Regular.ttf");
+    /// Renders the text with its font, style, alignment and wrapping into a texture that can be
+    /// drawn on surfaces in the world, e.g. by a `Label`. The glyphs come from the glyph atlas
+    /// and are composited on the CPU, so the result doesn't depend on the window size or the
+    /// text's position. The first row of the texture is the top of the text.
+    pub fn bake(&self, ctxt: &mut EngineContext) -> Result<Texture2d, Error> {
+        bake_text(ctxt, &self.font, self.style, self.align, self.max_width, &self.text)
+    }
+}
 
-        // FIXME: This doesn't update after rescaling
-        let (w, h) = ::get_display_dim(&ctxt.display);
-        let (sx, sy) = (2. / w as f32, 2. / h as f32);
+// The texture behind `Text::bake` and `Label`, with the background, shadow, outline and glyphs
+// blended over each other in the order `Text` draws them
+fn bake_text(ctxt: &mut EngineContext, font: &Font, style: TextStyle, align: TextAlign,
+             max_width: Option<f32>, text: &str) -> Result<Texture2d, Error> {
+    let style = ctxt.accessibility.text_style(style);
+    let atlas = ctxt.texture_cache.glyphs();
+    let printable: String = text.chars().filter(|&c| c != '\n').collect();
+    try!(atlas.add_glyphs(font, &printable));
+    let placed = place_glyphs(atlas, font, text, align, max_width, (0., 0.), (1., 1.));
+
+    let mut layers = Vec::new();
+    if let Some(color) = style.shadow_color {
+        layers.push((vec![(style.shadow_offset.0, -style.shadow_offset.1)], color));
+    }
+    if let Some(color) = style.outline_color {
+        let o = style.outline_width;
+        layers.push((vec![(-o, -o), (0., -o), (o, -o), (-o, 0.), (o, 0.), (-o, o), (0., o),
+                          (o, o)], color));
+    }
+    layers.push((vec![(0., 0.)], style.color));
+
+    let union = |b: Option<(f32, f32, f32, f32)>, q: (f32, f32, f32, f32)| {
+        Some(b.map_or(q, |b| (b.0.min(q.0), b.1.min(q.1), b.2.max(q.2), b.3.max(q.3))))
+    };
+    let glyphs = placed.iter().fold(None, |b, &(left, bottom, ref g)| {
+        union(b, (left, bottom, left + g.width, bottom + g.height))
+    });
+    let background = match (glyphs, style.background_color) {
+        (Some((l, b, r, t)), Some(color)) => {
+            let p = style.padding;
+            Some(((l - p, b - p, r + p, t + p), color))
+        }
+        _ => None,
+    };
+    let mut bounds = background.map(|(b, _)| b);
+    for &(ref offsets, _) in layers.iter() {
+        for &(dx, dy) in offsets.iter() {
+            if let Some((l, b, r, t)) = glyphs {
+                bounds = union(bounds, (l + dx, b + dy, r + dx, t + dy));
+            }
+        }
+    }
+    let (left, bottom, right, top) = bounds.unwrap_or((0., 0., 1., 1.));
+    let (left, top) = (left.floor(), top.ceil());
+    let w = cmp::max((right.ceil() - left) as i32, 1);
+    let h = cmp::max((top - bottom.floor()) as i32, 1);
+
+    // Straight RGBA, top row first
+    let mut pixels = vec![[0f32; 4]; (w * h) as usize];
+    if let Some(((l, b, r, t), color)) = background {
+        for y in (top - t) as i32..(top - b) as i32 {
+            for x in (l - left) as i32..(r - left) as i32 {
+                blend(&mut pixels[(y * w + x) as usize], color, 1.);
+            }
+        }
+    }
+    let size = atlas.size() as i32;
+    for &(ref offsets, color) in layers.iter() {
+        for &(dx, dy) in offsets.iter() {
+            for &(glyph_left, glyph_bottom, ref g) in placed.iter() {
+                let x0 = (glyph_left + dx - left).round() as i32;
+                let y0 = (top - (glyph_bottom + dy + g.height)).round() as i32;
+                for row in 0..g.height as i32 {
+                    for col in 0..g.width as i32 {
+                        let (x, y) = (x0 + col, y0 + row);
+                        if x < 0 || y < 0 || x >= w || y >= h {
+                            continue;
+                        }
+                        let src = (g.y as i32 + row) * size + g.x as i32 + col;
+                        let alpha = atlas.pixels[src as usize] as f32 / 255.;
+                        blend(&mut pixels[(y * w + x) as usize], color, alpha);
+                    }
+                }
+            }
+        }
+    }
+
+    let mut data = Vec::with_capacity(pixels.len() * 4);
+    for p in pixels.iter() {
+        data.extend(p.iter().map(|&c| (c * 255.).round() as u8));
+    }
+    Ok(Texture2d::new(&ctxt.display, RawImage2d {
+        data: Cow::Owned(data),
+        width: w as u32, height: h as u32,
+        format: ClientFormat::U8U8U8U8
+    }))
+}
+
+// Blends `color` at `alpha` over a straight alpha pixel
+fn blend(dst: &mut [f32; 4], color: [f32; 3], alpha: f32) {
+    let out = alpha + dst[3] * (1. - alpha);
+    if out <= 0. {
+        return;
+    }
+    for i in 0..3 {
+        dst[i] = (color[i] * alpha + dst[i] * dst[3] * (1. - alpha)) / out;
+    }
+    dst[3] = out;
+}
+
+// The bottom left corner of every glyph of `text` with the first line's baseline starting at
+// `origin`, laid out the way `Text` does it. `scale` converts pixels to the units of `origin`.
+// Every character must already be in the atlas in `font`.
+fn place_glyphs(atlas: &GlyphAtlas, font: &Font, text: &str, align: TextAlign,
+                max_width: Option<f32>, origin: (f32, f32),
+                scale: (f32, f32)) -> Vec<(f32, f32, Glyph)> {
+    let (sx, sy) = scale;
+    let line_height = line_height(font);
+    let mut y = origin.1;
+    let mut placed = Vec::with_capacity(text.len());
+    for line in layout_lines(atlas, font, text, max_width) {
+        let offset = match align {
+            TextAlign::Left => 0.,
+            TextAlign::Center => line_width(atlas, font, &line) / 2.,
+            TextAlign::Right => line_width(atlas, font, &line),
+        };
+        let mut x = origin.0 - offset * sx;
+        for c in line.chars() {
+            let glyph = *atlas.glyph(font, c).unwrap();
+            placed.push((x + glyph.left * sx, y - (glyph.height - glyph.top) * sy, glyph));
+            x += glyph.advance_x * sx;
+        }
+        y -= line_height * sy;
+    }
+    placed
+}
 
-        let mut x = self.x;
-        let mut y = self.y;
-        let mut chars = Vec::new();
-        for c in text.chars() {
-            let char = ctxt.texture_cache.get_glyph(&ctxt.display, &self.face, c);
-            let advance_x = char.advance_x * sx;
-            let advance_y = char.advance_y * sy;
+/// Splits `text` at line breaks, and between words to keep lines within `max_width` pixels, the
+/// way `Text` lays it out. Every character must already be in the atlas in `font`.
+pub fn layout_lines(atlas: &GlyphAtlas, font: &Font, text: &str,
+                    max_width: Option<f32>) -> Vec<String> {
+    let mut lines = Vec::new();
+    for paragraph in text.split('\n') {
+        let max_width = match max_width {
+            Some(max_width) => max_width,
+            None => {
+                lines.push(paragraph.to_owned());
+                continue;
+            }
+        };
+        let space = line_width(atlas, font, " ");
+        let mut line = String::new();
+        let mut width = 0.;
+        for word in paragraph.split(' ') {
+            let word_width = line_width(atlas, font, word);
+            if !line.is_empty() && width + space + word_width > max_width {
+                lines.push(mem::replace(&mut line, String::new()));
+                width = 0.;
+            }
+            if !line.is_empty() {
+                line.push(' ');
+                width += space;
+            }
+            line.push_str(word);
+            width += word_width;
+        }
+        lines.push(line);
+    }
+    lines
+}
 
-            chars.push(Box::new(Char::new(&ctxt.display, x, y, sx, sy, char)) as Box<GameObject>);
+// Width of `text` on a single line in pixels
+fn line_width(atlas: &GlyphAtlas, font: &Font, text: &str) -> f32 {
+    text.chars()
+        .filter_map(|c| atlas.glyph(font, c))
+        .map(|g| g.advance_x)
+        .fold(0., |a, b| a + b)
+}
 
-            x += advance_x;
-            y += advance_y;
+// Glyph quads in normalized device coordinates, with their texture coordinates in the atlas
+type GlyphQuad = ((f32, f32, f32, f32), (f32, f32, f32, f32));
+
+// Two triangles per glyph, repeated at each of `offsets`
+fn glyph_vertices(quads: &[GlyphQuad], offsets: &[(f32, f32)]) -> Vec<Vertex> {
+    let mut shape = Vec::with_capacity(quads.len() * offsets.len() * 6);
+    for &(dx, dy) in offsets {
+        for &((left, bottom, right, top), (u0, v0, u1, v1)) in quads {
+            let (left, bottom, right, top) = (left + dx, bottom + dy, right + dx, top + dy);
+            // TODO: What is the correct z value?
+            let a = Vertex::with_texture(left, bottom, -0.9, u0, v1);
+            let b = Vertex::with_texture(left, top, -0.9, u0, v0);
+            let c = Vertex::with_texture(right, bottom, -0.9, u1, v1);
+            let d = Vertex::with_texture(right, top, -0.9, u1, v0);
+            shape.extend_from_slice(&[a, b, c, b, c, d]);
         }
-        self.chars = chars;
     }
+    shape
+}
+
+// The material holds on to the atlas texture, which keeps it from being purged
+fn glyph_material(texture: &Rc<Texture2d>, color: [f32; 3]) -> Material {
+    Material::new(Albedo::AlphaTexture(texture.clone(), color))
+        .vert_shader(VertexShaderType::Gui)
+        .sampler(clamp_sampler())
+}
+
+// One of the children a `Text` is drawn with
+fn text_layer(display: &Display, shape: Vec<Vertex>, material: Material) -> Box<GameObject> {
+    let vb = VertexBuffer::new(display, shape).into_vertex_buffer_any();
+    let params = DrawParameters {
+        // FIXME: This messes with the alpha blending
+        // depth_test: DepthTest::IfLess,
+        // depth_write: true,
+        blending_function: Some(BlendingFunction::Addition {
+            source: LinearBlendingFactor::SourceAlpha,
+            destination: LinearBlendingFactor::OneMinusSourceAlpha
+        }),
+        .. Default::default()
+    };
+    let parent = ObjectBuilder::new()
+        .vertex_buffer(vb, NoIndices(PrimitiveType::TrianglesList))
+        .draw_params(params)
+        .material(material)
+        .build();
+    Box::new(TextLayer { parent: parent })
 }
 
-pub struct Char<'a> {
+struct TextLayer<'a> {
     parent: Object<'a>,
-    char: Rc<Character>,
 }
 
-impl<'a> GameObject for Char<'a> {
+impl GameObject for TextLayer<'static> {
     fn parent(&self) -> &Object {
         &self.parent
     }
+}
 
-    fn construct_uniforms(&self, camera: &Camera) -> UniformsVec {
-        let clamp = SamplerWrapFunction::Clamp;
-        let sampler = SamplerBehavior {
-            wrap_function: (clamp, clamp, clamp),
-            .. Default::default()
+// Distance between baselines in pixels, from the font's metrics at its size
+fn line_height(font: &Font) -> f32 {
+    match font.face().size_metrics() {
+        Some(metrics) => (metrics.height >> 6) as f32,
+        None => font.size() as f32 * 1.25,
+    }
+}
+
+/// Text baked into a texture and drawn on an upright quad in the world, e.g. for signs. The text
+/// is laid out like a `Text`, in any font and style, and can be aligned and wrapped.
+pub struct Label<'a> {
+    parent: Object<'a>,
+    font: Font,
+    style: TextStyle,
+    align: TextAlign,
+    max_width: Option<f32>,
+    text: String,
+    pos: Vec3<f32>,
+    height: f32,
+}
+
+impl GameObject for Label<'static> {
+    fn parent(&self) -> &Object {
+        &self.parent
+    }
+}
+
+impl<'a> Label<'a> {
+    /// Creates a left aligned, unwrapped label whose bottom left corner is at `pos`, with lines
+    /// `height` units tall and as wide as the text requires
+    pub fn new(ctxt: &mut EngineContext, font: &Font, style: TextStyle, text: &str,
+               pos: Vec3<f32>, height: f32) -> Result<Self, Error> {
+        let mut label = Label {
+            parent: ObjectBuilder::new().build(),
+            font: font.clone(),
+            style: style,
+            align: TextAlign::Left,
+            max_width: None,
+            text: text.to_owned(),
+            pos: pos,
+            height: height,
+        };
+        try!(label.rebuild(ctxt));
+        Ok(label)
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn set_text(&mut self, ctxt: &mut EngineContext, text: &str) -> Result<(), Error> {
+        self.text = text.to_owned();
+        self.rebuild(ctxt)
+    }
+
+    pub fn set_font(&mut self, ctxt: &mut EngineContext, font: &Font) -> Result<(), Error> {
+        self.font = font.clone();
+        self.rebuild(ctxt)
+    }
+
+    pub fn set_style(&mut self, ctxt: &mut EngineContext, style: TextStyle) -> Result<(), Error> {
+        self.style = style;
+        self.rebuild(ctxt)
+    }
+
+    pub fn set_align(&mut self, ctxt: &mut EngineContext, align: TextAlign) -> Result<(), Error> {
+        self.align = align;
+        self.rebuild(ctxt)
+    }
+
+    /// Wraps lines wider than `max_width` pixels of the font between words, see
+    /// `Text::set_max_width`
+    pub fn set_max_width(&mut self, ctxt: &mut EngineContext,
+                         max_width: Option<f32>) -> Result<(), Error> {
+        self.max_width = max_width;
+        self.rebuild(ctxt)
+    }
+
+    /// Multiplies the baked colors, e.g. to flash the label. White leaves them as they are.
+    pub fn set_color(&mut self, color: [f32; 3]) {
+        if let Albedo::TintedTexture(_, ref mut c) = self.parent.material.albedo {
+            *c = color;
+        }
+    }
+
+    pub fn set_alpha(&mut self, alpha: f32) {
+        self.parent.material.alpha = alpha;
+    }
+
+    pub fn set_pos(&mut self, pos: Vec3<f32>) {
+        self.pos = pos;
+        self.parent.transform.position = pos;
+    }
+
+    // Bakes the text again and sizes the quad to it, keeping the color and alpha
+    fn rebuild(&mut self, ctxt: &mut EngineContext) -> Result<(), Error> {
+        let texture = try!(bake_text(ctxt, &self.font, self.style, self.align, self.max_width,
+                                     &self.text));
+        // World units per pixel of the font, so every line is `height` tall
+        let scale = self.height / line_height(&self.font);
+        let size = (texture.get_width() as f32 * scale,
+                    texture.get_height().unwrap() as f32 * scale);
+
+        let color = match self.parent.material.albedo {
+            Albedo::TintedTexture(_, color) => color,
+            _ => [1., 1., 1.],
         };
-        UniformsVec(vec![
-            ("type", UniformValue::UnsignedInt(TEXTURE_ALPHA_TYPE)),
-            ("proj_matrix", UniformValue::Mat4(*camera.projection_matrix().as_array())),
-            ("view_matrix", UniformValue::Mat4(*camera.view_matrix().as_array())),
-            ("transform", UniformValue::Mat4(*self.parent.transform.as_array())),
-            ("color", UniformValue::Vec3([0., 1., 0.])),
-            ("tex", UniformValue::Texture2d(&self.char.texture, Some(sampler)))])
-    }
-}
-
-impl<'a> Char<'a> {
-    fn new(display: &Display, x: f32, y: f32, sx: f32, sy: f32, char: Rc<Character>) -> Self {
-        let x = x + char.left * sx;
-        let y = y - (char.height - char.top) * sy;
-        let width = char.width * sx;
-        let height = char.height * sy;
-
-        // FIXME: Properly handle pitch
-        // TODO: What is the correct z value?
-        let v1 = Vertex::with_texture(x, y, -0.9, 0., 1.);
-        let v2 = Vertex::with_texture(x, y + height, -0.9, 0., 0.);
-        let v3 = Vertex::with_texture(x + width, y, -0.9, 1., 1.);
-        let v4 = Vertex::with_texture(x + width, y + height, -0.9, 1., 0.);
-
-        let shape = vec![v1, v2, v3, v2, v3, v4];
+        let mut material = Material::new(Albedo::TintedTexture(Rc::new(texture), color))
+            .sampler(clamp_sampler());
+        material.alpha = self.parent.material.alpha;
+        self.parent = Self::build_quad(&ctxt.display, material, self.pos, size);
+        Ok(())
+    }
+
+    fn build_quad(display: &Display, material: Material, pos: Vec3<f32>,
+                  size: (f32, f32)) -> Object<'a> {
+        let (width, height) = size;
+
+        let v1 = Vertex::with_texture(0., 0., 0., 0., 1.);
+        let v2 = Vertex::with_texture(width, 0., 0., 1., 1.);
+        let v3 = Vertex::with_texture(width, 0., height, 1., 0.);
+        let v4 = Vertex::with_texture(0., 0., height, 0., 0.);
+
+        let shape = vec![v1, v2, v3, v1, v3, v4];
         let vb = VertexBuffer::new(display, shape).into_vertex_buffer_any();
 
+        // Test against the scene but don't write depth, so the transparent parts of the quad
+        // don't hide what's behind them
         let params = DrawParameters {
-            // FIXME: This messes with the alpha blending
-            // depth_test: DepthTest::IfLess,
-            // depth_write: true,
+            depth_test: DepthTest::IfLess,
             blending_function: Some(BlendingFunction::Addition {
                 source: LinearBlendingFactor::SourceAlpha,
                 destination: LinearBlendingFactor::OneMinusSourceAlpha
@@ -401,39 +1265,309 @@ impl<'a> Char<'a> {
             .. Default::default()
         };
 
-        let parent = ObjectBuilder::new()
+        ObjectBuilder::new()
             .vertex_buffer(vb, NoIndices(PrimitiveType::TrianglesList))
             .draw_params(params)
-            .vert_shader(VertexShaderType::Gui)
-            .build();
-        Char { parent: parent, char: char }
+            .transform(Transform::new(pos))
+            .material(material)
+            .build()
     }
 }
 
-fn load_obj<F: Facade, R: Read>(facade: &F, data: &mut BufReader<R>) -> VertexBufferAny {
-    let data = obj::Obj::load(data);
-    let mut vertex_data = Vec::new();
+/// Spawns short lived labels that rise and fade out, e.g. for damage numbers. Expired labels are
+/// hidden and kept for reuse, and a new label takes an expired one with the same text if there
+/// is one, so popups repeating the same few strings don't bake any new textures.
+pub struct FloatingText<'a> {
+    parent: Object<'a>,
+    font: Font,
+    // Active and expired labels, which are all `FloatingLabel`s
+    labels: Vec<Box<GameObject>>,
+    lifetime: f32,
+    rise_speed: f32,
+    height: f32,
+}
+
+impl GameObject for FloatingText<'static> {
+    fn parent(&self) -> &Object {
+        &self.parent
+    }
 
-    for shape in data.object_iter().next().unwrap().group_iter().flat_map(|g| g.indices().iter()) {
-        match shape {
-            &genmesh::Polygon::PolyTri(genmesh::Triangle { x: v1, y: v2, z: v3 }) => {
-                for v in [v1, v2, v3].iter() {
-                    let position = data.position()[v.0];
-                    let texture = v.1.map(|index| data.texture()[index]);
-                    //let normal = v.2.map(|index| data.normal()[index]);
+    fn update(&mut self, ctxt: &UpdateContext) {
+        for label in self.labels.iter_mut() {
+            if label.parent().visible {
+                label.update(ctxt);
+            }
+        }
+    }
+
+    fn children(&self) -> Option<&[Box<GameObject>]> {
+        Some(&*self.labels)
+    }
+}
 
-                    let texture = texture.unwrap_or([0.0, 0.0]);
-                    //let normal = normal.unwrap_or([0.0, 0.0, 0.0]);
+impl<'a> FloatingText<'a> {
+    /// Labels in `font` live for `lifetime` seconds, rise `rise_speed` units per second and are
+    /// `height` units tall
+    pub fn new(font: &Font, lifetime: f32, rise_speed: f32, height: f32) -> Self {
+        FloatingText {
+            parent: ObjectBuilder::new().build(),
+            font: font.clone(),
+            labels: Vec::new(),
+            lifetime: lifetime,
+            rise_speed: rise_speed,
+            height: height,
+        }
+    }
 
-                    vertex_data.push(Vertex {
-                        position: position,
-                        tex_coord: texture,
-                    })
+    pub fn spawn(&mut self, ctxt: &mut EngineContext, text: &str, pos: Vec3<f32>,
+                 color: [f32; 3]) -> Result<(), Error> {
+        let expired = |l: &FloatingLabel| !l.label.parent.visible;
+        let same_text = self.labels.iter_mut()
+            .map(|l| floating_label(l))
+            .position(|l| expired(l) && l.label.text() == text);
+        let reusable = same_text.or_else(|| {
+            self.labels.iter_mut().map(|l| floating_label(l)).position(|l| expired(l))
+        });
+        match reusable {
+            Some(i) => {
+                let floating = floating_label(&mut self.labels[i]);
+                if floating.label.text() != text {
+                    try!(floating.label.set_text(ctxt, text));
                 }
-            },
-            _ => {println!("{:?}", shape); unimplemented!()}
+                floating.start(pos, color);
+            }
+            None => {
+                // Baked in white and tinted with `color`
+                let style = TextStyle { color: [1., 1., 1.], .. Default::default() };
+                let label = try!(Label::new(ctxt, &self.font, style, text, pos, self.height));
+                let mut floating = FloatingLabel {
+                    label: label,
+                    start: pos,
+                    age: 0.,
+                    lifetime: self.lifetime,
+                    rise_speed: self.rise_speed,
+                };
+                floating.start(pos, color);
+                self.labels.push(Box::new(floating));
+            }
+        }
+        Ok(())
+    }
+
+    /// Number of labels currently shown
+    pub fn len(&self) -> usize {
+        self.labels.iter().filter(|l| l.parent().visible).count()
+    }
+
+    /// Number of labels, shown or kept for reuse
+    pub fn capacity(&self) -> usize {
+        self.labels.len()
+    }
+}
+
+// Every label of a `FloatingText` is a `FloatingLabel`
+fn floating_label(label: &mut Box<GameObject>) -> &mut FloatingLabel<'static> {
+    (**label).as_any_mut().downcast_mut().unwrap()
+}
+
+struct FloatingLabel<'a> {
+    label: Label<'a>,
+    start: Vec3<f32>,
+    // Seconds since the label was spawned
+    age: f32,
+    lifetime: f32,
+    rise_speed: f32,
+}
+
+impl<'a> FloatingLabel<'a> {
+    // Shows the label again from the beginning of its life
+    fn start(&mut self, pos: Vec3<f32>, color: [f32; 3]) {
+        self.start = pos;
+        self.age = 0.;
+        self.label.set_pos(pos);
+        self.label.set_color(color);
+        self.label.set_alpha(1.);
+        self.label.parent.visible = true;
+    }
+}
+
+impl GameObject for FloatingLabel<'static> {
+    fn parent(&self) -> &Object {
+        self.label.parent()
+    }
+
+    fn update(&mut self, ctxt: &UpdateContext) {
+        self.age += ctxt.dt;
+        if self.age >= self.lifetime {
+            // Hidden until it's reused
+            self.label.parent.visible = false;
+            return;
+        }
+        let t = self.age / self.lifetime;
+
+        self.label.set_pos(self.start + Vec3::new(0., 0., self.rise_speed * self.age));
+        // Stay mostly opaque and fade out quickly towards the end
+        self.label.set_alpha(1. - t * t);
+    }
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum SpriteSpace {
+    /// Positioned and sized in normalized device coordinates, like `Text`
+    Screen,
+    /// A quad in the world's xy plane, drawn with the scene's camera. Pair it with an
+    /// orthographic `Projection` for 2D games.
+    World,
+}
+
+/// A textured quad for 2D games and overlays. The texture can be a sprite sheet of equally sized
+/// frames, numbered left to right and top to bottom, of which one is shown at a time.
+pub struct Sprite<'a> {
+    pub parent: Object<'a>,
+    space: SpriteSpace,
+    pos: Vec3<f32>,
+    // Counterclockwise, in radians
+    rotation: f32,
+    size: (f32, f32),
+    columns: u32,
+    rows: u32,
+    frame: u32,
+}
+
+impl GameObject for Sprite<'static> {
+    fn parent(&self) -> &Object {
+        &self.parent
+    }
+
+    fn construct_uniforms(&self, _: &Camera) -> UniformsVec {
+        let (w, h) = (1. / self.columns as f32, 1. / self.rows as f32);
+        let (col, row) = (self.frame % self.columns, self.frame / self.columns);
+        // Textures are loaded bottom row first, so the first row of frames is at the top
+        let frame = [col as f32 * w, 1. - (row + 1) as f32 * h, w, h];
+        let screen_space = if self.space == SpriteSpace::Screen { 1 } else { 0 };
+        UniformsVec(vec![("frame", UniformValue::Vec4(frame)),
+                         ("screen_space", UniformValue::UnsignedInt(screen_space))])
+    }
+}
+
+impl<'a> Sprite<'a> {
+    /// Creates a sprite showing all of `texture`, centered on `pos` and `size` units wide and high
+    pub fn new(display: &Display, texture: Rc<Texture2d>, space: SpriteSpace, pos: Vec3<f32>,
+               size: (f32, f32)) -> Self {
+        let v1 = Vertex::with_texture(-0.5, -0.5, 0., 0., 0.);
+        let v2 = Vertex::with_texture(0.5, -0.5, 0., 1., 0.);
+        let v3 = Vertex::with_texture(0.5, 0.5, 0., 1., 1.);
+        let v4 = Vertex::with_texture(-0.5, 0.5, 0., 0., 1.);
+
+        let shape = vec![v1, v2, v3, v1, v3, v4];
+        let vb = VertexBuffer::new(display, shape).into_vertex_buffer_any();
+
+        // World sprites are hidden behind the scene but don't write depth, so their transparent
+        // parts don't hide what's behind them
+        let depth_test = match space {
+            SpriteSpace::Screen => DepthTest::Overwrite,
+            SpriteSpace::World => DepthTest::IfLess,
+        };
+        let params = DrawParameters {
+            depth_test: depth_test,
+            blending_function: Some(BlendingFunction::Addition {
+                source: LinearBlendingFactor::SourceAlpha,
+                destination: LinearBlendingFactor::OneMinusSourceAlpha
+            }),
+            .. Default::default()
+        };
+
+        let material = Material::new(Albedo::TintedTexture(texture, [1., 1., 1.]))
+            .vert_shader(VertexShaderType::Sprite)
+            .sampler(clamp_sampler());
+        let builder = ObjectBuilder::new()
+            .vertex_buffer(vb, NoIndices(PrimitiveType::TrianglesList))
+            .draw_params(params)
+            .material(material);
+        // Screen sprites aren't in the world, so they can't be picked
+        let parent = match space {
+            SpriteSpace::Screen => builder.build(),
+            SpriteSpace::World => {
+                builder.bounds(Aabb::new(Vec3::new(-0.5, -0.5, 0.), Vec3::new(0.5, 0.5, 0.)))
+                    .build()
+            }
+        };
+
+        let mut sprite = Sprite {
+            parent: parent,
+            space: space,
+            pos: pos,
+            rotation: 0.,
+            size: size,
+            columns: 1,
+            rows: 1,
+            frame: 0,
+        };
+        sprite.update_transform();
+        sprite
+    }
+
+    pub fn set_pos(&mut self, pos: Vec3<f32>) {
+        self.pos = pos;
+        self.update_transform();
+    }
+
+    /// Rotates the sprite counterclockwise around its center, in radians
+    pub fn set_rotation(&mut self, rotation: f32) {
+        self.rotation = rotation;
+        self.update_transform();
+    }
+
+    pub fn set_size(&mut self, size: (f32, f32)) {
+        self.size = size;
+        self.update_transform();
+    }
+
+    /// Multiplies the texture's color, white by default
+    pub fn set_tint(&mut self, tint: [f32; 3]) {
+        if let Albedo::TintedTexture(_, ref mut c) = self.parent.material.albedo {
+            *c = tint;
         }
     }
 
-    VertexBuffer::new(facade, vertex_data).into_vertex_buffer_any()
+    pub fn set_alpha(&mut self, alpha: f32) {
+        self.parent.material.alpha = alpha;
+    }
+
+    /// Treats the texture as a sprite sheet with `columns` by `rows` frames and shows the first
+    pub fn set_sheet(&mut self, columns: u32, rows: u32) {
+        assert!(columns > 0 && rows > 0, "A sprite sheet needs at least one frame");
+        self.columns = columns;
+        self.rows = rows;
+        self.frame = 0;
+    }
+
+    /// Shows frame `frame` of the sprite sheet, wrapping around after the last one so animations
+    /// can just keep counting
+    pub fn set_frame(&mut self, frame: u32) {
+        self.frame = frame % self.frame_count();
+    }
+
+    pub fn frame(&self) -> u32 {
+        self.frame
+    }
+
+    pub fn frame_count(&self) -> u32 {
+        self.columns * self.rows
+    }
+
+    fn update_transform(&mut self) {
+        let (w, h) = self.size;
+        self.parent.transform = Transform::new(self.pos)
+            .with_rotation(UnitQuat::new(Vec3::new(0., 0., self.rotation)))
+            .with_scale(Vec3::new(w, h, 1.));
+    }
+}
+
+fn clamp_sampler() -> SamplerBehavior {
+    let clamp = SamplerWrapFunction::Clamp;
+    SamplerBehavior {
+        wrap_function: (clamp, clamp, clamp),
+        .. Default::default()
+    }
 }
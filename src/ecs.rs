@@ -0,0 +1,601 @@
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::mem;
+
+use EngineContext;
+use animation::Animator;
+use collision::Collisions;
+use coroutine::Coroutine;
+use draw::{AsAny, FrameUniforms, GameObject, Object, UpdateContext};
+use scene;
+use transform::Transform;
+
+use glium::Surface;
+
+use nalgebra::{self, Mat4};
+
+/// An id for a thing in a `World`. Ids of despawned entities are reused, but the generation
+/// keeps old ids from referring to the new entity.
+#[derive(Copy, Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct Entity {
+    index: u32,
+    generation: u32,
+}
+
+/// Draws an entity, placed by its `Transform` if it has one. Any `GameObject` can be used, so
+/// existing objects and their children can be moved into a world unchanged. They are updated
+/// with the world, and the entity is despawned once its object reports that it's dead.
+pub struct MeshRenderer {
+    object: Box<GameObject>,
+}
+
+impl MeshRenderer {
+    pub fn new(object: Object<'static>) -> Self {
+        Self::from_game_object(Mesh { parent: object })
+    }
+
+    pub fn from_game_object<G: GameObject + 'static>(object: G) -> Self {
+        MeshRenderer { object: Box::new(object) }
+    }
+
+    pub fn object(&self) -> &GameObject {
+        &*self.object
+    }
+
+    pub fn object_mut(&mut self) -> &mut GameObject {
+        &mut *self.object
+    }
+}
+
+// The object behind `MeshRenderer::new`
+struct Mesh {
+    parent: Object<'static>,
+}
+
+impl GameObject for Mesh {
+    fn parent(&self) -> &Object {
+        &self.parent
+    }
+}
+
+/// Makes an entity's `Transform` relative to the world transform of another entity, so it moves
+/// with it. Parents without a `Transform`, despawned parents and cycles are treated as no parent.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Parent(pub Entity);
+
+/// Behaviour run for an entity every update, with access to the rest of the world
+pub struct Script(Box<FnMut(Entity, &mut World, &UpdateContext)>);
+
+impl Script {
+    pub fn new<F: FnMut(Entity, &mut World, &UpdateContext) + 'static>(f: F) -> Self {
+        Script(Box::new(f))
+    }
+
+    /// Resumes `coroutine` with the world every update until it finishes. The entity is usually
+    /// moved into its steps when building it, so they know what to act on.
+    pub fn coroutine(mut coroutine: Coroutine<World>) -> Self {
+        Script::new(move |_, world, ctxt| {
+            coroutine.resume(world, ctxt.dt);
+        })
+    }
+}
+
+// Components of one type, indexed by entity
+trait Storage: AsAny {
+    fn remove_entity(&mut self, index: usize);
+}
+
+impl<T: Any> Storage for Vec<Option<T>> {
+    fn remove_entity(&mut self, index: usize) {
+        if index < self.len() {
+            self[index] = None;
+        }
+    }
+}
+
+/// Entities and their components, as an alternative to trait objects for games with many
+/// similar objects. Any `'static` type can be a component, and components of one type are
+/// stored together so systems can go through all of them, e.g. every entity with a `Transform`
+/// and a `Velocity`. `Transform`, `MeshRenderer`, `Script`, `Animator` and `Collider` are
+/// understood by the world itself: scripts and animators run, colliders collide and renderers are
+/// drawn when the scene owning the world is.
+pub struct World {
+    generations: Vec<u32>,
+    alive: Vec<bool>,
+    free: Vec<u32>,
+    storages: HashMap<TypeId, Box<Storage>>,
+    // Transforms before the last update, for drawing between updates
+    previous: Vec<Option<Transform>>,
+    world_transforms: WorldTransforms,
+    collisions: Collisions,
+}
+
+// World matrices of entities with a `Transform`, recomputed only for entities whose transform or
+// parent changed and for their descendants
+struct WorldTransforms {
+    // Entity indices, parents before their children
+    order: Vec<usize>,
+    // Set when entities gain or lose a `Transform` or `Parent`
+    order_dirty: bool,
+    // Entities whose parent is ignored because it's also their descendant
+    cycles: Vec<usize>,
+    cache: Vec<Option<CachedTransform>>,
+    // Number of matrices recomputed by the last update
+    recomputed: usize,
+}
+
+#[derive(Copy, Clone)]
+struct CachedTransform {
+    // What the matrix was computed from
+    local: Transform,
+    parent: Option<usize>,
+    world: Mat4<f32>,
+}
+
+impl World {
+    pub fn new() -> Self {
+        World {
+            generations: Vec::new(),
+            alive: Vec::new(),
+            free: Vec::new(),
+            storages: HashMap::new(),
+            previous: Vec::new(),
+            world_transforms: WorldTransforms {
+                order: Vec::new(),
+                order_dirty: false,
+                cycles: Vec::new(),
+                cache: Vec::new(),
+                recomputed: 0,
+            },
+            collisions: Collisions::new(),
+        }
+    }
+
+    pub fn spawn(&mut self) -> Entity {
+        match self.free.pop() {
+            Some(index) => {
+                self.alive[index as usize] = true;
+                Entity { index: index, generation: self.generations[index as usize] }
+            }
+            None => {
+                self.generations.push(0);
+                self.alive.push(true);
+                Entity { index: self.alive.len() as u32 - 1, generation: 0 }
+            }
+        }
+    }
+
+    /// Removes `entity` and all of its components. Returns false if it was already gone.
+    pub fn despawn(&mut self, entity: Entity) -> bool {
+        if !self.is_alive(entity) {
+            return false;
+        }
+        let index = entity.index as usize;
+        for storage in self.storages.values_mut() {
+            storage.remove_entity(index);
+        }
+        self.alive[index] = false;
+        self.generations[index] += 1;
+        self.world_transforms.order_dirty = true;
+        self.free.push(entity.index);
+        true
+    }
+
+    /// Number of entities that haven't been despawned
+    pub fn len(&self) -> usize {
+        self.alive.len() - self.free.len()
+    }
+
+    pub fn is_alive(&self, entity: Entity) -> bool {
+        let index = entity.index as usize;
+        index < self.alive.len() && self.alive[index] &&
+            self.generations[index] == entity.generation
+    }
+
+    /// Gives `entity` a component, replacing any it had of the same type. Returns false and
+    /// drops the component if the entity is gone.
+    pub fn insert<T: Any>(&mut self, entity: Entity, component: T) -> bool {
+        if !self.is_alive(entity) {
+            return false;
+        }
+        self.hierarchy_changed::<T>();
+        let index = entity.index as usize;
+        let storage = self.storages.entry(TypeId::of::<T>())
+            .or_insert_with(|| Box::new(Vec::<Option<T>>::new()) as Box<Storage>);
+        let components = (**storage).as_any_mut().downcast_mut::<Vec<Option<T>>>().unwrap();
+        while components.len() <= index {
+            components.push(None);
+        }
+        components[index] = Some(component);
+        true
+    }
+
+    pub fn remove<T: Any>(&mut self, entity: Entity) -> Option<T> {
+        if !self.is_alive(entity) {
+            return None;
+        }
+        self.hierarchy_changed::<T>();
+        self.components_mut::<T>()
+            .and_then(|c| c.get_mut(entity.index as usize))
+            .and_then(|c| c.take())
+    }
+
+    pub fn get<T: Any>(&self, entity: Entity) -> Option<&T> {
+        if !self.is_alive(entity) {
+            return None;
+        }
+        self.components::<T>()
+            .and_then(|c| c.get(entity.index as usize))
+            .and_then(|c| c.as_ref())
+    }
+
+    pub fn get_mut<T: Any>(&mut self, entity: Entity) -> Option<&mut T> {
+        if !self.is_alive(entity) {
+            return None;
+        }
+        self.components_mut::<T>()
+            .and_then(|c| c.get_mut(entity.index as usize))
+            .and_then(|c| c.as_mut())
+    }
+
+    /// Every entity with a `T`, e.g. to look up other components of the same entities
+    pub fn entities_with<T: Any>(&self) -> Vec<Entity> {
+        let components = match self.components::<T>() {
+            Some(components) => components,
+            None => return Vec::new(),
+        };
+        components.iter().enumerate()
+            .filter(|&(_, c)| c.is_some())
+            .map(|(i, _)| Entity { index: i as u32, generation: self.generations[i] })
+            .collect()
+    }
+
+    /// Calls `f` with every `T` and the entity it belongs to
+    pub fn each<T: Any, F: FnMut(Entity, &T)>(&self, mut f: F) {
+        if let Some(components) = self.components::<T>() {
+            for (i, c) in components.iter().enumerate() {
+                if let Some(ref c) = *c {
+                    f(Entity { index: i as u32, generation: self.generations[i] }, c);
+                }
+            }
+        }
+    }
+
+    /// Calls `f` with every `T`, mutably, and the entity it belongs to
+    pub fn each_mut<T: Any, F: FnMut(Entity, &mut T)>(&mut self, mut f: F) {
+        let generations = &self.generations;
+        let components = self.storages.get_mut(&TypeId::of::<T>())
+            .and_then(|s| (**s).as_any_mut().downcast_mut::<Vec<Option<T>>>());
+        if let Some(components) = components {
+            for (i, c) in components.iter_mut().enumerate() {
+                if let Some(ref mut c) = *c {
+                    f(Entity { index: i as u32, generation: generations[i] }, c);
+                }
+            }
+        }
+    }
+
+    /// Runs every entity's `Script` and updates the objects of its `MeshRenderer`. Called by the
+    /// scene owning the world.
+    pub fn update(&mut self, ctxt: &UpdateContext) {
+        self.previous = self.components::<Transform>().cloned().unwrap_or_else(Vec::new);
+
+        let mut dead = Vec::new();
+        self.each_mut(|entity, renderer: &mut MeshRenderer| {
+            scene::save_transforms(&*renderer.object);
+            renderer.object.update(ctxt);
+            if renderer.object.is_dead() {
+                dead.push(entity);
+            }
+        });
+        for entity in dead {
+            self.despawn(entity);
+        }
+
+        self.animate(ctxt.dt);
+
+        // Scripts are taken out while they run so they can change the world, including their
+        // own entity
+        for entity in self.entities_with::<Script>() {
+            if let Some(mut script) = self.remove::<Script>(entity) {
+                (script.0)(entity, self, ctxt);
+                if self.is_alive(entity) && self.get::<Script>(entity).is_none() {
+                    self.insert(entity, script);
+                }
+            }
+        }
+
+        self.update_world_transforms();
+
+        // Taken out while it runs so it can move the colliding entities
+        let mut collisions = mem::replace(&mut self.collisions, Collisions::new());
+        collisions.update(self, ctxt.events);
+        self.collisions = collisions;
+    }
+
+    /// The colliders that overlapped during the last update
+    pub fn collisions(&self) -> &Collisions {
+        &self.collisions
+    }
+
+    /// The transform from `entity`'s local space to the world, including its parents', as of
+    /// the last `update_world_transforms`
+    pub fn world_transform(&self, entity: Entity) -> Option<Mat4<f32>> {
+        if !self.is_alive(entity) {
+            return None;
+        }
+        self.world_transforms.cache.get(entity.index as usize)
+            .and_then(|c| c.map(|c| c.world))
+    }
+
+    /// Brings the world transforms up to date, going through the hierarchy parents first. Only
+    /// the matrices of entities whose `Transform` or `Parent` changed, and of everything below
+    /// them, are recomputed. Called at the end of every update, and only needs to be called
+    /// directly to see changes made since then in `world_transform`.
+    pub fn update_world_transforms(&mut self) {
+        if self.world_transforms.order_dirty || !self.update_cache() {
+            self.rebuild_order();
+            // The order was just built from the current parents, so it's valid
+            self.update_cache();
+        }
+    }
+
+    /// Number of world transforms recomputed by the last `update_world_transforms`
+    pub fn transforms_recomputed(&self) -> usize {
+        self.world_transforms.recomputed
+    }
+
+    // Plays the animators of entities with a `Transform`. Transforms are changed in place rather
+    // than inserted, so only the animated entities and their descendants are recomputed instead
+    // of the hierarchy order being rebuilt.
+    fn animate(&mut self, dt: f32) {
+        for entity in self.entities_with::<Animator>() {
+            let mut transform = match self.get::<Transform>(entity) {
+                Some(transform) => *transform,
+                None => continue,
+            };
+            self.get_mut::<Animator>(entity).unwrap().update(dt, &mut transform);
+            *self.get_mut::<Transform>(entity).unwrap() = transform;
+        }
+    }
+
+    // Recomputes the changed world transforms in order. Returns false if a parent was changed
+    // through `get_mut` so that it comes after its child in the order, in which case the child
+    // was treated as having no parent.
+    fn update_cache(&mut self) -> bool {
+        let len = self.alive.len();
+        let mut cache = mem::replace(&mut self.world_transforms.cache, Vec::new());
+        cache.resize(len, None);
+        let mut visited = vec![false; len];
+        let mut changed = vec![false; len];
+        let mut recomputed = 0;
+        let mut valid = true;
+        for &index in self.world_transforms.order.iter() {
+            let local = match self.component_at::<Transform>(index) {
+                Some(local) => *local,
+                None => {
+                    cache[index] = None;
+                    continue;
+                }
+            };
+            let mut parent = self.ordered_parent(index);
+            if parent.map_or(false, |p| !visited[p]) {
+                valid = false;
+                parent = None;
+            }
+            visited[index] = true;
+
+            let up_to_date = match cache[index] {
+                Some(ref c) => {
+                    c.local == local && c.parent == parent && !parent.map_or(false, |p| changed[p])
+                }
+                None => false,
+            };
+            if !up_to_date {
+                let parent_world = parent.and_then(|p| cache[p])
+                    .map_or_else(|| nalgebra::new_identity(4), |c| c.world);
+                cache[index] = Some(CachedTransform {
+                    local: local,
+                    parent: parent,
+                    world: parent_world * local.matrix(),
+                });
+                changed[index] = true;
+                recomputed += 1;
+            }
+        }
+        self.world_transforms.cache = cache;
+        self.world_transforms.recomputed = recomputed;
+        valid
+    }
+
+    // Orders the entities with a `Transform` so parents come before their children
+    fn rebuild_order(&mut self) {
+        let len = self.alive.len();
+        // 0 is unvisited, 1 is being visited and 2 is done
+        let mut state = vec![0u8; len];
+        let mut order = Vec::with_capacity(len);
+        let mut cycles = Vec::new();
+        for index in 0..len {
+            if self.alive[index] && self.component_at::<Transform>(index).is_some() {
+                self.visit(index, &mut state, &mut order, &mut cycles);
+            }
+        }
+        self.world_transforms.order = order;
+        self.world_transforms.cycles = cycles;
+        self.world_transforms.order_dirty = false;
+        // Forget entities that no longer have a transform
+        let cache = &mut self.world_transforms.cache;
+        for (index, c) in cache.iter_mut().enumerate() {
+            if state.get(index).map_or(true, |&s| s != 2) {
+                *c = None;
+            }
+        }
+    }
+
+    fn visit(&self, index: usize, state: &mut Vec<u8>, order: &mut Vec<usize>,
+             cycles: &mut Vec<usize>) {
+        if state[index] != 0 {
+            return;
+        }
+        state[index] = 1;
+        if let Some(parent) = self.parent_index(index) {
+            if state[parent] == 1 {
+                warn!("Entity {} is its own ancestor, ignoring its parent", index);
+                cycles.push(index);
+            } else {
+                self.visit(parent, state, order, cycles);
+            }
+        }
+        state[index] = 2;
+        order.push(index);
+    }
+
+    // Index of the entity whose world transform `index`'s transform is relative to
+    fn parent_index(&self, index: usize) -> Option<usize> {
+        let parent = match self.component_at::<Parent>(index) {
+            Some(&Parent(parent)) => parent,
+            None => return None,
+        };
+        let has_transform = self.component_at::<Transform>(parent.index as usize).is_some();
+        if self.is_alive(parent) && parent.index as usize != index && has_transform {
+            Some(parent.index as usize)
+        } else {
+            None
+        }
+    }
+
+    // Like `parent_index`, but without the parents ignored to break cycles
+    fn ordered_parent(&self, index: usize) -> Option<usize> {
+        if self.world_transforms.cycles.contains(&index) {
+            None
+        } else {
+            self.parent_index(index)
+        }
+    }
+
+    /// Draws every entity with a `MeshRenderer`, `alpha` of the way between the last two
+    /// updates. Called by the scene owning the world.
+    pub fn draw<S: Surface>(&self, target: &mut S, ctxt: &mut EngineContext,
+                            frame: &FrameUniforms, alpha: f32) {
+        let transforms = self.interpolated_transforms(alpha);
+        self.each(|entity, renderer: &MeshRenderer| {
+            // Entities given a transform since the last update aren't in the hierarchy yet
+            let transform = match transforms.get(entity.index as usize).and_then(|t| *t) {
+                Some(transform) => transform,
+                None => self.get::<Transform>(entity)
+                    .map_or_else(|| nalgebra::new_identity(4), |t| t.matrix()),
+            };
+            draw_obj(target, ctxt, frame, &transform, alpha, &renderer.object);
+        });
+    }
+
+    // World transforms `alpha` of the way between the last two updates, by entity index. Cached
+    // transforms are used for entities that didn't move and whose parents didn't either.
+    fn interpolated_transforms(&self, alpha: f32) -> Vec<Option<Mat4<f32>>> {
+        let len = self.alive.len();
+        let mut transforms = vec![None; len];
+        let mut moved = vec![false; len];
+        for &index in self.world_transforms.order.iter() {
+            let local = match self.component_at::<Transform>(index) {
+                Some(local) => *local,
+                None => continue,
+            };
+            let parent = self.ordered_parent(index);
+            let previous = self.previous.get(index).and_then(|t| *t).unwrap_or(local);
+            let cached = self.world_transforms.cache.get(index).and_then(|c| *c);
+            let parent_moved = parent.map_or(false, |p| moved[p]);
+            transforms[index] = match cached {
+                Some(c) if previous == local && c.local == local && c.parent == parent &&
+                           !parent_moved => Some(c.world),
+                _ => {
+                    moved[index] = true;
+                    let parent_world = parent.and_then(|p| transforms[p])
+                        .unwrap_or_else(|| nalgebra::new_identity(4));
+                    Some(parent_world * previous.lerp(&local, alpha).matrix())
+                }
+            };
+        }
+        transforms
+    }
+
+    fn component_at<T: Any>(&self, index: usize) -> Option<&T> {
+        self.components::<T>().and_then(|c| c.get(index)).and_then(|c| c.as_ref())
+    }
+
+    // The order parents are updated in depends on these
+    fn hierarchy_changed<T: Any>(&mut self) {
+        let id = TypeId::of::<T>();
+        if id == TypeId::of::<Transform>() || id == TypeId::of::<Parent>() {
+            self.world_transforms.order_dirty = true;
+        }
+    }
+
+    fn components<T: Any>(&self) -> Option<&Vec<Option<T>>> {
+        self.storages.get(&TypeId::of::<T>())
+            .and_then(|s| (**s).as_any().downcast_ref::<Vec<Option<T>>>())
+    }
+
+    fn components_mut<T: Any>(&mut self) -> Option<&mut Vec<Option<T>>> {
+        self.storages.get_mut(&TypeId::of::<T>())
+            .and_then(|s| (**s).as_any_mut().downcast_mut::<Vec<Option<T>>>())
+    }
+}
+
+// Draws `obj` and its children
+fn draw_obj<S: Surface>(target: &mut S, ctxt: &mut EngineContext, frame: &FrameUniforms,
+                        parent_transform: &Mat4<f32>, alpha: f32, obj: &Box<GameObject>) {
+    let parent = obj.parent();
+    if !parent.visible {
+        return;
+    }
+    if parent.vertex_buffer.is_some() && frame.camera.sees_layers(parent.layers) {
+        if let Err(e) = ctxt.draw_in_frame(target, frame, parent_transform, alpha, obj) {
+            error!("Couldn't draw {}: {}", obj.name().unwrap_or("entity"), e);
+        }
+    }
+    if let Some(children) = obj.children() {
+        let transform = *parent_transform * parent.interpolated_transform(alpha);
+        for child in children {
+            draw_obj(target, ctxt, frame, &transform, alpha, child);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use super::{Parent, World};
+    use animation::{Animator, Clip, Easing, LoopMode, Track};
+    use transform::Transform;
+
+    use nalgebra::Vec3;
+
+    #[test]
+    fn animating_does_not_rebuild_hierarchy() {
+        let mut world = World::new();
+        let animated = world.spawn();
+        world.insert(animated, Transform::new(Vec3::new(0., 0., 0.)));
+        let child = world.spawn();
+        world.insert(child, Transform::new(Vec3::new(1., 0., 0.)));
+        world.insert(child, Parent(animated));
+        let still = world.spawn();
+        world.insert(still, Transform::new(Vec3::new(5., 0., 0.)));
+
+        let track = Track::new()
+            .key(0., Vec3::new(0., 0., 0.), Easing::Linear)
+            .key(1., Vec3::new(0., 2., 0.), Easing::Linear);
+        let clip = Rc::new(Clip::new(LoopMode::Loop).position(track));
+        world.insert(animated, Animator::new(clip));
+        world.update_world_transforms();
+        assert!(!world.world_transforms.order_dirty);
+
+        world.animate(0.5);
+        assert!(!world.world_transforms.order_dirty);
+        world.update_world_transforms();
+        // The animated entity and its child, but not the entity that stood still
+        assert_eq!(world.transforms_recomputed(), 2);
+        let moved = world.get::<Transform>(animated).unwrap().position;
+        assert_eq!(moved, Vec3::new(0., 1., 0.));
+    }
+}
@@ -0,0 +1,363 @@
+use std::cmp;
+use std::net::SocketAddr;
+
+use {get_display_dim, EngineContext, EngineContextBuilder, Error};
+use accessibility::Accessibility;
+use camera::Camera;
+use draw::UpdateContext;
+use events::EngineEvent;
+use input::Key;
+use scene::{Scene, SceneStack};
+use stats::StatsEmitter;
+use window::{WindowMode, WindowSettings};
+
+use glium::DisplayBuild;
+use glium::glutin::{ElementState, Event, HeadlessRendererBuilder};
+
+use nalgebra::Vec3;
+
+use time;
+
+/// How `Engine::run` paces the main loop
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct EngineConfig {
+    /// Updates per second. Updates always advance the game by the same amount of time, while
+    /// frames are drawn as often as the display allows.
+    pub fixed_update_hz: u32,
+    /// Most updates run before drawing a frame. When the game falls further behind, e.g. after
+    /// the window was dragged, the missed time is dropped instead of catching up, so the game
+    /// slows down rather than freezing.
+    pub max_frame_skip: u32,
+    /// Wait for the display's vertical refresh before showing a frame, which avoids tearing and
+    /// caps the frame rate at the refresh rate
+    pub vsync: bool,
+    /// Size of the window in windowed mode
+    pub window_size: (u32, u32),
+    pub window_mode: WindowMode,
+    /// Monitor used for fullscreen and borderless modes, see `window::monitor_names`. `None`
+    /// uses the primary monitor.
+    pub monitor: Option<usize>,
+    /// Switch between windowed and fullscreen mode with Alt+Enter
+    pub fullscreen_toggle: bool,
+    /// Where a `StatsEmitter` sends a report every second, `None` to not send any
+    pub stats_address: Option<SocketAddr>,
+    /// Milliseconds per frame spent destroying resources deferred to `EngineContext::garbage`
+    pub gc_budget_ms: f32,
+    /// The accessibility options the game starts with
+    pub accessibility: Accessibility,
+}
+
+impl Default for EngineConfig {
+    fn default() -> Self {
+        EngineConfig {
+            fixed_update_hz: 30,
+            max_frame_skip: 5,
+            vsync: true,
+            window_size: (800, 600),
+            window_mode: WindowMode::Windowed,
+            monitor: None,
+            fullscreen_toggle: true,
+            stats_address: None,
+            gc_budget_ms: 1.,
+            accessibility: Default::default(),
+        }
+    }
+}
+
+/// Game specific logic driven by `Engine::run`. All callbacks have default implementations, so a
+/// game only implements the ones it needs.
+pub trait Game {
+    fn title(&self) -> String {
+        "Game".to_owned()
+    }
+
+    /// Where the engine loads resources and shaders from. The default searches for the
+    /// "resources" and "shaders" folders in the current directory and its parents.
+    fn context_builder(&self) -> EngineContextBuilder {
+        EngineContextBuilder::new()
+    }
+
+    /// The update rate and window settings, read once when the engine starts
+    fn config(&self) -> EngineConfig {
+        Default::default()
+    }
+
+    /// Called once before the main loop starts, typically to populate the scene. Returning an
+    /// error stops the engine.
+    fn init(&mut self, _: &mut EngineContext, _: &mut Scene) -> Result<(), Error> {
+        Ok(())
+    }
+
+    /// Called at a fixed rate, before the objects in the scene are updated. Returning an error
+    /// stops the engine.
+    fn update(&mut self, _: &mut EngineContext, _: &mut Scene) -> Result<(), Error> {
+        Ok(())
+    }
+
+    /// Called once per frame, which can be more or less often than updates. `alpha` is how far the
+    /// frame is between the last two updates, and is passed on to `Scene::draw` by default so
+    /// objects move smoothly at any frame rate.
+    fn draw(&mut self, ctxt: &mut EngineContext, scene: &Scene, alpha: f32) {
+        scene.draw(ctxt, alpha);
+    }
+
+    /// Called for every window event. Resizing and closing the window are handled by the engine
+    /// before the game sees the event. Games that only need to know which keys and buttons are
+    /// held can use `EngineContext::input` instead.
+    fn event(&mut self, _: &mut EngineContext, _: &mut Scene, _: &Event) {}
+}
+
+/// Like `Game`, for games made of several scenes at once and driven by `Engine::run_stack`, e.g.
+/// a persistent UI scene on top of level scenes loaded with `SceneStack::load_scene_additive`.
+/// The stack starts out empty.
+pub trait StackGame {
+    fn title(&self) -> String {
+        "Game".to_owned()
+    }
+
+    fn context_builder(&self) -> EngineContextBuilder {
+        EngineContextBuilder::new()
+    }
+
+    fn config(&self) -> EngineConfig {
+        Default::default()
+    }
+
+    /// Called once before the main loop starts, typically to push or load the first scenes
+    fn init(&mut self, _: &mut EngineContext, _: &mut SceneStack) -> Result<(), Error> {
+        Ok(())
+    }
+
+    /// Called at a fixed rate, before the scenes that have updates enabled are updated
+    fn update(&mut self, _: &mut EngineContext, _: &mut SceneStack) -> Result<(), Error> {
+        Ok(())
+    }
+
+    /// Called once per frame, drawing the scenes that have drawing enabled by default
+    fn draw(&mut self, ctxt: &mut EngineContext, scenes: &SceneStack, alpha: f32) {
+        scenes.draw(ctxt, alpha);
+    }
+
+    fn event(&mut self, _: &mut EngineContext, _: &mut SceneStack, _: &Event) {}
+}
+
+// Name of the only scene of a `Game` run as a stack
+const MAIN_SCENE: &'static str = "main";
+
+// Runs a `Game` as a stack holding just its scene
+struct SingleScene<G>(G);
+
+impl<G: Game> StackGame for SingleScene<G> {
+    fn title(&self) -> String {
+        self.0.title()
+    }
+
+    fn context_builder(&self) -> EngineContextBuilder {
+        self.0.context_builder()
+    }
+
+    fn config(&self) -> EngineConfig {
+        self.0.config()
+    }
+
+    fn init(&mut self, ctxt: &mut EngineContext, scenes: &mut SceneStack) -> Result<(), Error> {
+        let (w, h) = get_display_dim(ctxt.display());
+        let mut scene = Scene::new(Camera::new(Vec3::new(0., 0., 1.), w as f32 / h as f32));
+        try!(self.0.init(ctxt, &mut scene));
+        scenes.push(MAIN_SCENE, scene);
+        Ok(())
+    }
+
+    fn update(&mut self, ctxt: &mut EngineContext, scenes: &mut SceneStack) -> Result<(), Error> {
+        match scenes.get_mut(MAIN_SCENE) {
+            Some(scene) => self.0.update(ctxt, scene),
+            None => Ok(()),
+        }
+    }
+
+    fn draw(&mut self, ctxt: &mut EngineContext, scenes: &SceneStack, alpha: f32) {
+        if let Some(scene) = scenes.get(MAIN_SCENE) {
+            self.0.draw(ctxt, scene, alpha);
+        }
+    }
+
+    fn event(&mut self, ctxt: &mut EngineContext, scenes: &mut SceneStack, ev: &Event) {
+        if let Some(scene) = scenes.get_mut(MAIN_SCENE) {
+            self.0.event(ctxt, scene, ev);
+        }
+    }
+}
+
+pub struct Engine;
+
+impl Engine {
+    /// Opens a window and runs the main loop until the window is closed or the game returns an
+    /// error
+    pub fn run<G: Game>(game: G) -> Result<(), Error> {
+        Self::run_stack(SingleScene(game))
+    }
+
+    /// Like `run`, for a game made of several scenes
+    pub fn run_stack<G: StackGame>(mut game: G) -> Result<(), Error> {
+        let config = game.config();
+        let window = WindowSettings {
+            title: game.title(),
+            size: config.window_size,
+            mode: config.window_mode,
+            monitor: config.monitor,
+            vsync: config.vsync,
+        };
+        let display = try!(window.builder().build_glium());
+        let mut ctxt = try!(game.context_builder().build(display));
+        ctxt.window = window;
+        let mut scenes = SceneStack::new();
+        try!(init(&mut game, &mut ctxt, &mut scenes, &config));
+        let mut stats = match config.stats_address {
+            Some(address) => Some(try!(StatsEmitter::new(address))),
+            None => None,
+        };
+
+        let time_step = 1e9 as u64 / cmp::max(config.fixed_update_hz, 1) as u64;
+        let mut accumulator = 0;
+        let mut nframes = 0;
+        let mut previous_time = time::precise_time_ns();
+        let mut target_time = previous_time + 1e9 as u64;
+        loop {
+            let mut events: Vec<_> = ctxt.display.poll_events().collect();
+            events.extend(ctxt.input.take_injected());
+            if handle_events(&mut game, &mut ctxt, &mut scenes, &config, events) {
+                ctxt.flush_garbage();
+                return Ok(());
+            }
+
+            ctxt.recenter_cursor();
+
+            let now = time::precise_time_ns();
+            let delta = now - previous_time;
+            accumulator += delta;
+            previous_time = now;
+
+            // Updates run at a fixed rate while frames are drawn as often as the display allows
+            let max_lag = time_step * cmp::max(config.max_frame_skip, 1) as u64;
+            if accumulator > max_lag {
+                let behind = (accumulator - max_lag) / 1_000_000;
+                debug!("Updates fell {}ms behind, skipping ahead", behind);
+                accumulator = max_lag;
+            }
+            if accumulator >= time_step {
+                while accumulator >= time_step {
+                    accumulator -= time_step;
+                    try!(update(&mut game, &mut ctxt, &mut scenes, time_step as f32 / 1e9));
+                    if let Some(ref mut stats) = stats {
+                        stats.record_update();
+                    }
+                }
+                // Only forget this frame's presses once an update has seen them
+                ctxt.input.end_frame();
+            }
+
+            let alpha = accumulator as f32 / time_step as f32;
+            game.draw(&mut ctxt, &scenes, alpha);
+            ctxt.collect_garbage();
+            nframes += 1;
+            let now = time::precise_time_ns();
+            if let Some(ref mut stats) = stats {
+                stats.record_frame(delta);
+            }
+            if now > target_time {
+                target_time = now + 1e9 as u64;
+                debug!("fps: {}", nframes);
+                ctxt.fps = nframes;
+                nframes = 0;
+                if let Some(ref mut stats) = stats {
+                    stats.emit_stack(&ctxt, &scenes);
+                }
+            }
+        }
+    }
+
+    /// Runs a game without a window, e.g. an `InputScript` test on a build server without a
+    /// display. Frames draw into a hidden framebuffer of `EngineConfig::window_size` and each
+    /// runs one update, without waiting for real time to pass. Runs until the game injects an
+    /// `Event::Closed` with `InputState::push_event` or returns an error, or for at most
+    /// `max_updates` updates.
+    pub fn run_headless<G: Game>(game: G, max_updates: u32) -> Result<(), Error> {
+        Self::run_stack_headless(SingleScene(game), max_updates)
+    }
+
+    /// Like `run_headless`, for a game made of several scenes
+    pub fn run_stack_headless<G: StackGame>(mut game: G, max_updates: u32) -> Result<(), Error> {
+        let config = game.config();
+        let (width, height) = config.window_size;
+        let display = try!(HeadlessRendererBuilder::new(width, height).build_glium());
+        let mut ctxt = try!(game.context_builder().build(display));
+        ctxt.window.title = game.title();
+        let mut scenes = SceneStack::new();
+        try!(init(&mut game, &mut ctxt, &mut scenes, &config));
+
+        let dt = 1. / cmp::max(config.fixed_update_hz, 1) as f32;
+        for _ in 0..max_updates {
+            let events = ctxt.input.take_injected();
+            if handle_events(&mut game, &mut ctxt, &mut scenes, &config, events) {
+                break;
+            }
+            try!(update(&mut game, &mut ctxt, &mut scenes, dt));
+            ctxt.input.end_frame();
+            // Drawn as of the update that just ran
+            game.draw(&mut ctxt, &scenes, 1.);
+            ctxt.collect_garbage();
+        }
+        ctxt.flush_garbage();
+        Ok(())
+    }
+}
+
+fn init<G: StackGame>(game: &mut G, ctxt: &mut EngineContext, scenes: &mut SceneStack,
+                      config: &EngineConfig) -> Result<(), Error> {
+    ctxt.garbage.budget_ms = config.gc_budget_ms;
+    ctxt.set_accessibility(config.accessibility);
+    game.init(ctxt, scenes)
+}
+
+// Passes window and injected events to the input state, the event bus and the game, returning
+// whether the window was closed
+fn handle_events<G: StackGame>(game: &mut G, ctxt: &mut EngineContext, scenes: &mut SceneStack,
+                               config: &EngineConfig, events: Vec<Event>) -> bool {
+    for ev in events {
+        match ev {
+            Event::Resized(x, y) => {
+                scenes.set_aspect_ratio(x as f32 / y as f32);
+            },
+            Event::Closed => return true,
+            Event::KeyboardInput(ElementState::Pressed, _, Some(Key::Return))
+                if config.fullscreen_toggle &&
+                   (ctxt.input.is_key_down(Key::LAlt) || ctxt.input.is_key_down(Key::RAlt)) => {
+                ctxt.toggle_fullscreen();
+                let (w, h) = get_display_dim(&ctxt.display);
+                scenes.set_aspect_ratio(w as f32 / h as f32);
+            }
+            // Don't keep hold of the cursor while the player is in another window
+            Event::Focused(false) if ctxt.cursor_grabbed() => {
+                ctxt.set_cursor_grabbed(false);
+            }
+            _ => ()
+        }
+        ctxt.input.handle_event(&ev);
+        if let Some(engine_event) = EngineEvent::from_window_event(&ev) {
+            ctxt.events.publish(engine_event);
+        }
+        game.event(ctxt, scenes, &ev);
+    }
+    false
+}
+
+// One fixed update step of `dt` seconds
+fn update<G: StackGame>(game: &mut G, ctxt: &mut EngineContext, scenes: &mut SceneStack,
+                        dt: f32) -> Result<(), Error> {
+    ctxt.delta_time = dt;
+    ctxt.events.dispatch();
+    try!(game.update(ctxt, scenes));
+    scenes.update(&UpdateContext { dt: ctxt.delta_time, input: &ctxt.input,
+                                   events: &ctxt.events });
+    Ok(())
+}
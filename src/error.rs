@@ -0,0 +1,109 @@
+use std::error;
+use std::fmt;
+use std::io;
+
+use freetype as ft;
+
+use glium::{DrawError, GliumCreationError, ProgramCreationError};
+use glium::glutin::CreationError;
+
+use image::ImageError;
+
+/// Everything that can go wrong while loading resources or drawing
+#[derive(Debug)]
+pub enum Error {
+    /// A file couldn't be read, or a resource folder couldn't be found
+    Io(io::Error),
+    Image(ImageError),
+    Shader(ProgramCreationError),
+    /// An OBJ or MTL file is malformed, e.g. a face refers to a vertex that doesn't exist
+    Obj(String),
+    Font(ft::Error),
+    Draw(DrawError),
+    /// The window or its OpenGL context couldn't be created
+    Window(GliumCreationError<CreationError>),
+    /// An `InputScript` is malformed or one of its assertions failed
+    Script(String),
+    /// A scene file is malformed
+    Scene(String),
+    /// There is no audio device, or a sound couldn't be decoded
+    Audio(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Io(ref e) => write!(f, "IO error: {}", e),
+            Error::Image(ref e) => write!(f, "Couldn't load image: {}", e),
+            Error::Shader(ref e) => write!(f, "Couldn't build shader program: {:?}", e),
+            Error::Obj(ref msg) => write!(f, "Couldn't load OBJ file: {}", msg),
+            Error::Font(ref e) => write!(f, "Font error: {:?}", e),
+            Error::Draw(ref e) => write!(f, "Couldn't draw: {:?}", e),
+            Error::Window(ref e) => write!(f, "Couldn't create the window: {:?}", e),
+            Error::Script(ref msg) => write!(f, "Input script failed: {}", msg),
+            Error::Scene(ref msg) => write!(f, "Couldn't load scene file: {}", msg),
+            Error::Audio(ref msg) => write!(f, "Audio error: {}", msg),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn description(&self) -> &str {
+        match *self {
+            Error::Io(ref e) => e.description(),
+            Error::Image(ref e) => e.description(),
+            Error::Shader(_) => "shader program creation failed",
+            Error::Obj(ref msg) => msg,
+            Error::Font(_) => "font error",
+            Error::Draw(_) => "draw call failed",
+            Error::Window(_) => "window creation failed",
+            Error::Script(ref msg) => msg,
+            Error::Scene(ref msg) => msg,
+            Error::Audio(ref msg) => msg,
+        }
+    }
+
+    fn cause(&self) -> Option<&error::Error> {
+        match *self {
+            Error::Io(ref e) => Some(e),
+            Error::Image(ref e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+impl From<ImageError> for Error {
+    fn from(e: ImageError) -> Self {
+        Error::Image(e)
+    }
+}
+
+impl From<ProgramCreationError> for Error {
+    fn from(e: ProgramCreationError) -> Self {
+        Error::Shader(e)
+    }
+}
+
+impl From<ft::Error> for Error {
+    fn from(e: ft::Error) -> Self {
+        Error::Font(e)
+    }
+}
+
+impl From<DrawError> for Error {
+    fn from(e: DrawError) -> Self {
+        Error::Draw(e)
+    }
+}
+
+impl From<GliumCreationError<CreationError>> for Error {
+    fn from(e: GliumCreationError<CreationError>) -> Self {
+        Error::Window(e)
+    }
+}
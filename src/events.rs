@@ -0,0 +1,109 @@
+use std::any::{Any, TypeId};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::mem;
+
+use accessibility::Accessibility;
+use input::Key;
+
+use glium::glutin::{ElementState, Event, MouseButton};
+
+/// Window events the engine publishes on the `EventBus`, so objects can react to them without
+/// the game forwarding them by hand
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum EngineEvent {
+    /// The window's new width and height in pixels
+    Resized(u32, u32),
+    KeyPressed(Key),
+    KeyReleased(Key),
+    ButtonPressed(MouseButton),
+    ButtonReleased(MouseButton),
+    Focused(bool),
+    /// Published by `EngineContext::set_accessibility`, and once when the engine starts
+    AccessibilityChanged(Accessibility),
+}
+
+impl EngineEvent {
+    /// The engine event for a window event, if there is one
+    pub fn from_window_event(ev: &Event) -> Option<Self> {
+        match *ev {
+            Event::Resized(w, h) => Some(EngineEvent::Resized(w, h)),
+            Event::KeyboardInput(ElementState::Pressed, _, Some(key)) => {
+                Some(EngineEvent::KeyPressed(key))
+            }
+            Event::KeyboardInput(ElementState::Released, _, Some(key)) => {
+                Some(EngineEvent::KeyReleased(key))
+            }
+            Event::MouseInput(ElementState::Pressed, button) => {
+                Some(EngineEvent::ButtonPressed(button))
+            }
+            Event::MouseInput(ElementState::Released, button) => {
+                Some(EngineEvent::ButtonReleased(button))
+            }
+            Event::Focused(focused) => Some(EngineEvent::Focused(focused)),
+            _ => None,
+        }
+    }
+}
+
+/// Typed publish/subscribe messaging between objects, the game and the engine. Any `'static`
+/// type can be an event. Events published during one update step are delivered at the start of
+/// the next: subscribers are called then, and objects can `read` them for the rest of that step.
+/// The engine publishes `EngineEvent`s for window events.
+pub struct EventBus {
+    // `Vec<T>` for every event type, waiting for the next dispatch
+    pending: RefCell<HashMap<TypeId, Box<Any>>>,
+    // `Vec<T>` for every event type delivered by the last dispatch
+    current: HashMap<TypeId, Box<Any>>,
+    subscribers: HashMap<TypeId, Vec<Box<Fn(&Any)>>>,
+}
+
+impl EventBus {
+    pub fn new() -> Self {
+        EventBus {
+            pending: RefCell::new(HashMap::new()),
+            current: HashMap::new(),
+            subscribers: HashMap::new(),
+        }
+    }
+
+    /// Queues `event` for the next dispatch. Takes `&self` so objects can publish from `update`.
+    pub fn publish<T: Any>(&self, event: T) {
+        let mut pending = self.pending.borrow_mut();
+        let events = pending.entry(TypeId::of::<T>())
+            .or_insert_with(|| Box::new(Vec::<T>::new()) as Box<Any>);
+        events.downcast_mut::<Vec<T>>().unwrap().push(event);
+    }
+
+    /// Calls `f` with every event of type `T` as it's dispatched
+    pub fn subscribe<T: Any, F: Fn(&T) + 'static>(&mut self, f: F) {
+        let subscriber = move |events: &Any| {
+            for event in events.downcast_ref::<Vec<T>>().unwrap() {
+                f(event);
+            }
+        };
+        self.subscribers.entry(TypeId::of::<T>()).or_insert_with(Vec::new)
+            .push(Box::new(subscriber));
+    }
+
+    /// The events of type `T` delivered by the last dispatch
+    pub fn read<T: Any>(&self) -> &[T] {
+        match self.current.get(&TypeId::of::<T>()) {
+            Some(events) => &events.downcast_ref::<Vec<T>>().unwrap()[..],
+            None => &[],
+        }
+    }
+
+    /// Delivers the events published since the last dispatch, replacing the ones from before.
+    /// Called by the engine before every update step.
+    pub fn dispatch(&mut self) {
+        self.current = mem::replace(&mut *self.pending.borrow_mut(), HashMap::new());
+        for (type_id, events) in self.current.iter() {
+            if let Some(subscribers) = self.subscribers.get(type_id) {
+                for subscriber in subscribers {
+                    subscriber(&**events);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,69 @@
+use std::any::Any;
+use std::collections::VecDeque;
+
+use time;
+
+/// Resources waiting to be destroyed a few at a time, so unloading a large scene doesn't stall
+/// one frame deleting thousands of buffers and textures. Anything can be deferred, e.g. objects,
+/// vertex buffers or `Rc`s of cached textures, and it's dropped by `collect` within the budget.
+/// A deferred `Rc` only frees its resource once its last clone is dropped, so deferring a
+/// texture still in use elsewhere is harmless.
+///
+/// The engine context owns one and collects it once per frame, see
+/// `EngineContext::collect_garbage`. Anything left is dropped by `flush`, or when the queue is.
+pub struct GpuGarbage {
+    queue: VecDeque<Box<Any>>,
+    /// Milliseconds spent dropping resources per frame
+    pub budget_ms: f32,
+    freed: usize,
+}
+
+impl GpuGarbage {
+    pub fn new(budget_ms: f32) -> Self {
+        GpuGarbage { queue: VecDeque::new(), budget_ms: budget_ms, freed: 0 }
+    }
+
+    /// Queues `resource` to be dropped during a later `collect`
+    pub fn defer<T: Any>(&mut self, resource: T) {
+        self.queue.push_back(Box::new(resource));
+    }
+
+    /// Number of resources waiting to be dropped
+    pub fn len(&self) -> usize {
+        self.queue.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+
+    /// Number of resources dropped so far
+    pub fn freed(&self) -> usize {
+        self.freed
+    }
+
+    /// Drops resources in the order they were deferred until the budget is used up, returning
+    /// how many were dropped. At least one is dropped per call, so the queue always drains.
+    pub fn collect(&mut self) -> usize {
+        let start = time::precise_time_ns();
+        let budget = (self.budget_ms.max(0.) * 1e6) as u64;
+        let mut dropped = 0;
+        while let Some(resource) = self.queue.pop_front() {
+            drop(resource);
+            dropped += 1;
+            if time::precise_time_ns() - start >= budget {
+                break;
+            }
+        }
+        self.freed += dropped;
+        dropped
+    }
+
+    /// Drops everything regardless of the budget, e.g. on shutdown or before a loading screen
+    pub fn flush(&mut self) -> usize {
+        let dropped = self.queue.len();
+        self.queue.clear();
+        self.freed += dropped;
+        dropped
+    }
+}
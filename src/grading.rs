@@ -0,0 +1,264 @@
+use std::borrow::Cow;
+use std::path::Path;
+use std::rc::Rc;
+
+use {get_display_dim, EngineContext, Error};
+use camera::Camera;
+use draw::{GameObject, Object, ObjectBuilder, UniformsVec, Vertex};
+use material::Material;
+use render_texture::RenderTexture;
+use scene::Scene;
+use shader::{FragmentShaderType, VertexShaderType};
+use texture::TextureOptions;
+
+use glium::{Display, Surface, VertexBuffer};
+use glium::index::{NoIndices, PrimitiveType};
+use glium::texture::{ClientFormat, RawImage2d, Texture2d};
+use glium::uniforms::{MagnifySamplerFilter, MinifySamplerFilter, SamplerBehavior,
+                      SamplerWrapFunction, UniformValue};
+
+use image::ImageError;
+
+use nalgebra::{self, Mat4};
+
+/// A 3D color lookup table stored as a strip of `size` square slices side by side, the usual
+/// layout for LUTs exported from image editors. Red grows to the right within a slice, green
+/// grows downwards and blue picks the slice, so a 16 entry LUT is a 256x16 image.
+#[derive(Clone)]
+pub struct Lut {
+    texture: Rc<Texture2d>,
+    size: u32,
+}
+
+impl Lut {
+    /// Loads a strip image through the texture cache, relative to the resource folder
+    pub fn load<P: AsRef<Path>>(ctxt: &mut EngineContext, path: P) -> Result<Self, Error> {
+        let path = ctxt.resource_dir.join(path);
+        // Mipmaps would blend neighbouring slices together
+        let options = TextureOptions { mipmaps: false, .. Default::default() };
+        let texture = try!(ctxt.texture_cache.get_texture_with(&ctxt.display, &path, options));
+        let (width, height) = (texture.get_width(), texture.get_height().unwrap());
+        if height < 2 || width != height * height {
+            let msg = format!("{} is {}x{}, but a LUT strip is as wide as its height squared",
+                              path.display(), width, height);
+            return Err(Error::Image(ImageError::UnsupportedError(msg)));
+        }
+        Ok(Lut { texture: texture, size: height })
+    }
+
+    /// A LUT that leaves colors unchanged
+    pub fn identity(display: &Display, size: u32) -> Self {
+        let n = size as usize;
+        let step = 255. / (n - 1) as f32;
+        let mut pixels = Vec::with_capacity(n * n * n * 3);
+        // Bottom row first, like images loaded through the texture cache
+        for row in (0..n).rev() {
+            for x in 0..n * n {
+                let (b, r) = (x / n, x % n);
+                for &c in [r, row, b].iter() {
+                    pixels.push((c as f32 * step).round() as u8);
+                }
+            }
+        }
+        let texture = Texture2d::new(display, RawImage2d {
+            data: Cow::Owned(pixels),
+            width: size * size, height: size,
+            format: ClientFormat::U8U8U8
+        });
+        Lut { texture: Rc::new(texture), size: size }
+    }
+
+    /// Number of entries along each axis
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+}
+
+/// Grades the colors of drawn scenes with exposure, white balance, contrast and saturation
+/// controls, followed by a blend of two LUTs, e.g. to fade between the grading of two areas as
+/// the player walks from one to the other. Scenes are drawn into a texture the size of the
+/// window, which is drawn to the window through the grading shader. Call `draw` from
+/// `Game::draw` instead of `Scene::draw`.
+///
+/// The colorblind filter of `EngineContext::accessibility` is applied last, so games offering one
+/// draw through a `ColorGrading` even if they don't grade their colors.
+pub struct ColorGrading {
+    /// Stops of exposure, each doubling the brightness
+    pub exposure: f32,
+    /// Shifts the white point towards orange for positive values and blue for negative ones,
+    /// from -1 to 1
+    pub temperature: f32,
+    /// Shifts the white point towards green for positive values and magenta for negative ones,
+    /// from -1 to 1
+    pub tint: f32,
+    /// Scales colors away from mid grey, 1 leaves them unchanged
+    pub contrast: f32,
+    /// 0 is greyscale and 1 leaves colors unchanged
+    pub saturation: f32,
+    luts: (Lut, Lut),
+    // How much of the second LUT is used, from 0 to 1
+    lut_blend: f32,
+    target: Option<RenderTexture>,
+    quad: Option<Box<GameObject>>,
+}
+
+impl ColorGrading {
+    /// Grading that leaves colors unchanged until its controls or LUTs are changed
+    pub fn new(display: &Display) -> Self {
+        let identity = Lut::identity(display, 16);
+        ColorGrading {
+            exposure: 0.,
+            temperature: 0.,
+            tint: 0.,
+            contrast: 1.,
+            saturation: 1.,
+            luts: (identity.clone(), identity),
+            lut_blend: 0.,
+            target: None,
+            quad: None,
+        }
+    }
+
+    /// Grades with `lut` alone
+    pub fn set_lut(&mut self, lut: Lut) {
+        self.luts = (lut.clone(), lut);
+        self.lut_blend = 0.;
+    }
+
+    /// Grades with a mix of two LUTs, from all of `from` at a `blend` of 0 to all of `to` at 1
+    pub fn blend_luts(&mut self, from: Lut, to: Lut, blend: f32) {
+        self.luts = (from, to);
+        self.lut_blend = blend.max(0.).min(1.);
+    }
+
+    /// Multipliers for red, green and blue making up the white balance
+    pub fn white_balance(&self) -> [f32; 3] {
+        let (temperature, tint) = (self.temperature * 0.2, self.tint * 0.2);
+        [1. + temperature, 1. + tint, 1. - temperature]
+    }
+
+    /// Clears the window and draws `scene` into it through the grading
+    pub fn draw(&mut self, ctxt: &mut EngineContext, scene: &Scene, alpha: f32) {
+        let size = get_display_dim(ctxt.display());
+        if self.target.as_ref().map_or(true, |t| t.dimensions() != size) {
+            let target = ctxt.render_texture(size.0, size.1);
+            self.quad = Some(Box::new(GradeQuad::new(ctxt.display(), &target, &self.luts)));
+            self.target = Some(target);
+        }
+
+        let target = self.target.as_ref().unwrap();
+        {
+            let mut framebuffer = target.framebuffer(ctxt.display());
+            framebuffer.clear_color_and_depth((0., 0., 0., 1.), 1.);
+            scene.draw_into(&mut framebuffer, ctxt, alpha);
+        }
+
+        let white_balance = self.white_balance();
+        let quad = self.quad.as_mut().unwrap();
+        if let Some(quad) = (**quad).as_any_mut().downcast_mut::<GradeQuad>() {
+            quad.exposure = self.exposure;
+            quad.white_balance = white_balance;
+            quad.contrast = self.contrast.max(0.);
+            quad.saturation = self.saturation.max(0.);
+            quad.luts = self.luts.clone();
+            quad.lut_blend = self.lut_blend;
+            quad.colorblind = ctxt.accessibility.colorblind.matrix();
+        }
+        let mut frame = ctxt.display().draw();
+        frame.clear_color(0., 0., 0., 1.);
+        let identity: Mat4<f32> = nalgebra::new_identity(4);
+        let camera = scene.camera();
+        let result = ctxt.draw(&mut frame, camera, &[], [0., 0., 0.], &identity, alpha, quad);
+        if let Err(e) = result {
+            error!("Couldn't grade the scene: {}", e);
+        }
+        frame.finish().unwrap();
+    }
+}
+
+// A quad covering the window, showing the scene's texture through the grading shader
+struct GradeQuad<'a> {
+    parent: Object<'a>,
+    exposure: f32,
+    white_balance: [f32; 3],
+    contrast: f32,
+    saturation: f32,
+    luts: (Lut, Lut),
+    lut_blend: f32,
+    // Row by row
+    colorblind: [[f32; 3]; 3],
+    lut_sampler: SamplerBehavior,
+}
+
+impl GameObject for GradeQuad<'static> {
+    fn parent(&self) -> &Object {
+        &self.parent
+    }
+
+    fn construct_uniforms(&self, _: &Camera) -> UniformsVec {
+        let sampler = Some(self.lut_sampler);
+        // GLSL matrices are given column by column
+        let m = self.colorblind;
+        let colorblind = [[m[0][0], m[1][0], m[2][0]],
+                          [m[0][1], m[1][1], m[2][1]],
+                          [m[0][2], m[1][2], m[2][2]]];
+        UniformsVec(vec![("exposure", UniformValue::Float(self.exposure)),
+                         ("white_balance", UniformValue::Vec3(self.white_balance)),
+                         ("contrast", UniformValue::Float(self.contrast)),
+                         ("saturation", UniformValue::Float(self.saturation)),
+                         ("lut_from", UniformValue::Texture2d(&self.luts.0.texture, sampler)),
+                         ("lut_from_size", UniformValue::Float(self.luts.0.size as f32)),
+                         ("lut_to", UniformValue::Texture2d(&self.luts.1.texture, sampler)),
+                         ("lut_to_size", UniformValue::Float(self.luts.1.size as f32)),
+                         ("lut_blend", UniformValue::Float(self.lut_blend)),
+                         ("colorblind", UniformValue::Mat3(colorblind))])
+    }
+}
+
+impl<'a> GradeQuad<'a> {
+    fn new(display: &Display, target: &RenderTexture, luts: &(Lut, Lut)) -> Self {
+        let shape = vec![Vertex::with_texture(-1., -1., 0., 0., 0.),
+                         Vertex::with_texture(1., -1., 0., 1., 0.),
+                         Vertex::with_texture(1., 1., 0., 1., 1.),
+                         Vertex::with_texture(-1., -1., 0., 0., 0.),
+                         Vertex::with_texture(1., 1., 0., 1., 1.),
+                         Vertex::with_texture(-1., 1., 0., 0., 1.)];
+        let vb = VertexBuffer::new(display, shape).into_vertex_buffer_any();
+
+        // The scene is drawn at the window's size, so it's sampled one pixel per pixel. LUTs are
+        // filtered between their entries, and clamping keeps slices from wrapping into each
+        // other.
+        let clamp = SamplerWrapFunction::Clamp;
+        let nearest = SamplerBehavior {
+            wrap_function: (clamp, clamp, clamp),
+            minify_filter: MinifySamplerFilter::Nearest,
+            magnify_filter: MagnifySamplerFilter::Nearest,
+            .. Default::default()
+        };
+        let linear = SamplerBehavior {
+            minify_filter: MinifySamplerFilter::Linear,
+            magnify_filter: MagnifySamplerFilter::Linear,
+            .. nearest
+        };
+        let material = Material::texture(target.texture())
+            .vert_shader(VertexShaderType::Gui)
+            .frag_shader(FragmentShaderType::Grade)
+            .sampler(nearest);
+        let parent = ObjectBuilder::new()
+            .vertex_buffer(vb, NoIndices(PrimitiveType::TrianglesList))
+            .material(material)
+            .build();
+
+        GradeQuad {
+            parent: parent,
+            exposure: 0.,
+            white_balance: [1., 1., 1.],
+            contrast: 1.,
+            saturation: 1.,
+            luts: luts.clone(),
+            lut_blend: 0.,
+            colorblind: [[1., 0., 0.], [0., 1., 0.], [0., 0., 1.]],
+            lut_sampler: linear,
+        }
+    }
+}
@@ -0,0 +1,432 @@
+use {get_display_dim, EngineContext, Error, Font};
+use draw::{GameObject, Object, ObjectBuilder, Text, TextAlign, TextStyle, UpdateContext, Vertex};
+use events::{EngineEvent, EventBus};
+use input::InputState;
+use material::{Albedo, Material};
+use shader::VertexShaderType;
+use transform::Transform;
+
+use glium::{Display, VertexBuffer};
+use glium::glutin::MouseButton;
+use glium::index::{NoIndices, PrimitiveType};
+
+use nalgebra::Vec3;
+
+/// What widgets publish on the `EventBus` when they're used, with the widget's name
+#[derive(Clone, Debug, PartialEq)]
+pub enum GuiEvent {
+    /// A button was pressed and released while the mouse was over it
+    Clicked(String),
+    /// A checkbox was clicked, and is now checked or not
+    Toggled(String, bool),
+    /// A slider was dragged to a new value
+    Changed(String, f32),
+}
+
+/// A rectangle in GUI pixels, from the top left corner of the window like mouse coordinates
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Rect {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Rect {
+    pub fn new(x: f32, y: f32, width: f32, height: f32) -> Self {
+        Rect { x: x, y: y, width: width, height: height }
+    }
+
+    pub fn contains(&self, (x, y): (f32, f32)) -> bool {
+        x >= self.x && x < self.x + self.width && y >= self.y && y < self.y + self.height
+    }
+
+    // Shrunk by `by` pixels on every side
+    fn inset(&self, by: f32) -> Rect {
+        Rect::new(self.x + by, self.y + by, self.width - 2. * by, self.height - 2. * by)
+    }
+}
+
+/// Colors of the widgets
+#[derive(Copy, Clone, Debug)]
+pub struct GuiStyle {
+    pub panel: [f32; 3],
+    pub normal: [f32; 3],
+    pub hovered: [f32; 3],
+    pub pressed: [f32; 3],
+    /// Check marks and slider knobs
+    pub accent: [f32; 3],
+    pub text: [f32; 3],
+}
+
+impl Default for GuiStyle {
+    fn default() -> Self {
+        GuiStyle {
+            panel: [0.1, 0.1, 0.12],
+            normal: [0.25, 0.25, 0.3],
+            hovered: [0.35, 0.35, 0.42],
+            pressed: [0.18, 0.18, 0.22],
+            accent: [0.3, 0.6, 1.],
+            text: [1., 1., 1.],
+        }
+    }
+}
+
+/// Whether the mouse is over a widget, or holding it down
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum WidgetState {
+    Normal,
+    Hovered,
+    Pressed,
+}
+
+/// The root of a screen space GUI. Widgets added to it are positioned in pixels from the top left
+/// corner of the window, are hit tested against the mouse in their update, and publish
+/// `GuiEvent`s when they're used. Add the `Gui` to a scene that is updated and drawn after the
+/// world, e.g. the top of a `SceneStack`, and widgets are drawn in the order they were added.
+pub struct Gui<'a> {
+    pub parent: Object<'a>,
+    widgets: Vec<Box<GameObject>>,
+}
+
+impl GameObject for Gui<'static> {
+    fn parent(&self) -> &Object {
+        &self.parent
+    }
+
+    fn update(&mut self, ctxt: &UpdateContext) {
+        for event in ctxt.events.read::<EngineEvent>() {
+            if let EngineEvent::Resized(w, h) = *event {
+                // Minimized windows are 0 pixels wide
+                if w != 0 && h != 0 {
+                    self.parent.transform = pixel_transform((w, h));
+                }
+            }
+        }
+        for widget in self.widgets.iter_mut() {
+            widget.update(ctxt);
+        }
+    }
+
+    fn children(&self) -> Option<&[Box<GameObject>]> {
+        Some(&*self.widgets)
+    }
+}
+
+impl<'a> Gui<'a> {
+    pub fn new(display: &Display) -> Self {
+        let parent = ObjectBuilder::new()
+            .vert_shader(VertexShaderType::Gui)
+            .transform(pixel_transform(get_display_dim(display)))
+            .build();
+        Gui { parent: parent, widgets: Vec::new() }
+    }
+
+    pub fn add<G: GameObject + 'static>(&mut self, widget: G) {
+        self.widgets.push(Box::new(widget));
+    }
+
+    /// The widget called `name`, e.g. to read a slider's value
+    pub fn widget_mut<T: GameObject + 'static>(&mut self, name: &str) -> Option<&mut T> {
+        self.widgets.iter_mut()
+            .find(|w| w.name() == Some(name))
+            .and_then(|w| (**w).as_any_mut().downcast_mut())
+    }
+}
+
+// Maps pixels from the top left corner of the window to normalized device coordinates
+fn pixel_transform((w, h): (u32, u32)) -> Transform {
+    let (w, h) = (w as f32, h as f32);
+    Transform::new(Vec3::new(-1., 1., 0.)).with_scale(Vec3::new(2. / w, -2. / h, 1.))
+}
+
+/// A colored rectangle drawn behind other widgets
+pub struct Panel<'a> {
+    pub parent: Object<'a>,
+}
+
+impl GameObject for Panel<'static> {
+    fn parent(&self) -> &Object {
+        &self.parent
+    }
+}
+
+impl<'a> Panel<'a> {
+    pub fn new(display: &Display, style: &GuiStyle, rect: Rect) -> Self {
+        Panel { parent: quad(display, rect, style.panel) }
+    }
+}
+
+/// A button with a centered label, publishing `GuiEvent::Clicked` when it's pressed and released
+/// with the mouse still over it
+pub struct Button<'a> {
+    pub parent: Object<'a>,
+    label: Vec<Box<GameObject>>,
+    rect: Rect,
+    style: GuiStyle,
+    state: WidgetState,
+}
+
+impl GameObject for Button<'static> {
+    fn parent(&self) -> &Object {
+        &self.parent
+    }
+
+    fn update(&mut self, ctxt: &UpdateContext) {
+        let (state, clicked) = press_state(self.state, self.rect, ctxt.input);
+        if clicked {
+            publish(ctxt.events, &self.parent, GuiEvent::Clicked);
+        }
+        self.state = state;
+        let color = match state {
+            WidgetState::Normal => self.style.normal,
+            WidgetState::Hovered => self.style.hovered,
+            WidgetState::Pressed => self.style.pressed,
+        };
+        self.parent.material.albedo = Albedo::Color(color);
+    }
+
+    fn children(&self) -> Option<&[Box<GameObject>]> {
+        Some(&*self.label)
+    }
+}
+
+impl<'a> Button<'a> {
+    pub fn new(ctxt: &mut EngineContext, font: &Font, style: &GuiStyle, name: &str, rect: Rect,
+               label: &str) -> Result<Self, Error> {
+        let mut parent = quad(ctxt.display(), rect, style.normal);
+        parent.name = Some(name.to_owned());
+        let center = (rect.x + rect.width / 2., rect.y + rect.height / 2.);
+        let label = try!(label_text(ctxt, font, style, center, TextAlign::Center, label));
+        Ok(Button {
+            parent: parent,
+            label: vec![Box::new(label)],
+            rect: rect,
+            style: *style,
+            state: WidgetState::Normal,
+        })
+    }
+
+    pub fn state(&self) -> WidgetState {
+        self.state
+    }
+}
+
+/// A box that is checked and unchecked by clicking it, with a label to its right. Publishes
+/// `GuiEvent::Toggled`.
+pub struct Checkbox<'a> {
+    pub parent: Object<'a>,
+    // The check mark, then the label
+    children: Vec<Box<GameObject>>,
+    rect: Rect,
+    style: GuiStyle,
+    state: WidgetState,
+    checked: bool,
+}
+
+impl GameObject for Checkbox<'static> {
+    fn parent(&self) -> &Object {
+        &self.parent
+    }
+
+    fn update(&mut self, ctxt: &UpdateContext) {
+        let (state, clicked) = press_state(self.state, self.rect, ctxt.input);
+        if clicked {
+            self.set_checked(!self.checked);
+            let checked = self.checked;
+            publish(ctxt.events, &self.parent, |name| GuiEvent::Toggled(name, checked));
+        }
+        self.state = state;
+        let color = match state {
+            WidgetState::Normal => self.style.normal,
+            _ => self.style.hovered,
+        };
+        self.parent.material.albedo = Albedo::Color(color);
+    }
+
+    fn children(&self) -> Option<&[Box<GameObject>]> {
+        Some(&*self.children)
+    }
+}
+
+impl<'a> Checkbox<'a> {
+    /// A square box `size` pixels wide with its top left corner at `(x, y)`
+    pub fn new(ctxt: &mut EngineContext, font: &Font, style: &GuiStyle, name: &str,
+               (x, y): (f32, f32), size: f32, label: &str) -> Result<Self, Error> {
+        let rect = Rect::new(x, y, size, size);
+        let mut parent = quad(ctxt.display(), rect, style.normal);
+        parent.name = Some(name.to_owned());
+        let mut mark = quad(ctxt.display(), rect.inset(size / 4.), style.accent);
+        mark.visible = false;
+        let label_start = (x + size * 1.5, y + size / 2.);
+        let label = try!(label_text(ctxt, font, style, label_start, TextAlign::Left, label));
+        Ok(Checkbox {
+            parent: parent,
+            children: vec![Box::new(Quad { parent: mark }), Box::new(label)],
+            rect: rect,
+            style: *style,
+            state: WidgetState::Normal,
+            checked: false,
+        })
+    }
+
+    pub fn checked(&self) -> bool {
+        self.checked
+    }
+
+    /// Checks or unchecks the box without publishing an event
+    pub fn set_checked(&mut self, checked: bool) {
+        self.checked = checked;
+        quad_mut(&mut self.children[0]).visible = checked;
+    }
+}
+
+/// A horizontal track with a knob that is dragged between `min` and `max`. Publishes
+/// `GuiEvent::Changed` while the value changes.
+pub struct Slider<'a> {
+    pub parent: Object<'a>,
+    knob: Vec<Box<GameObject>>,
+    rect: Rect,
+    style: GuiStyle,
+    state: WidgetState,
+    min: f32,
+    max: f32,
+    value: f32,
+}
+
+impl GameObject for Slider<'static> {
+    fn parent(&self) -> &Object {
+        &self.parent
+    }
+
+    fn update(&mut self, ctxt: &UpdateContext) {
+        let (state, _) = press_state(self.state, self.rect, ctxt.input);
+        self.state = state;
+        if state == WidgetState::Pressed {
+            if let Some((x, _)) = mouse(ctxt.input) {
+                let t = ((x - self.rect.x) / self.rect.width).max(0.).min(1.);
+                let value = self.min + t * (self.max - self.min);
+                if value != self.value {
+                    self.set_value(value);
+                    publish(ctxt.events, &self.parent, |name| GuiEvent::Changed(name, value));
+                }
+            }
+        }
+        let color = if state == WidgetState::Normal { self.style.accent } else { self.style.text };
+        quad_mut(&mut self.knob[0]).material.albedo = Albedo::Color(color);
+    }
+
+    fn children(&self) -> Option<&[Box<GameObject>]> {
+        Some(&*self.knob)
+    }
+}
+
+impl<'a> Slider<'a> {
+    /// A slider covering `rect`, with its track along the middle, starting at `min`
+    pub fn new(display: &Display, style: &GuiStyle, name: &str, rect: Rect, min: f32,
+               max: f32) -> Self {
+        let track = Rect::new(rect.x, rect.y + rect.height * 0.4, rect.width, rect.height * 0.2);
+        let mut parent = quad(display, track, style.normal);
+        parent.name = Some(name.to_owned());
+        // The knob is centered on the left end of the track, and moved along it by its transform
+        let knob_width = rect.height / 2.;
+        let knob = Rect::new(rect.x - knob_width / 2., rect.y, knob_width, rect.height);
+        let mut slider = Slider {
+            parent: parent,
+            knob: vec![Box::new(Quad { parent: quad(display, knob, style.accent) })],
+            rect: rect,
+            style: *style,
+            state: WidgetState::Normal,
+            min: min,
+            max: max,
+            value: min,
+        };
+        slider.set_value(min);
+        slider
+    }
+
+    pub fn value(&self) -> f32 {
+        self.value
+    }
+
+    /// Moves the knob to `value`, clamped between `min` and `max`, without publishing an event
+    pub fn set_value(&mut self, value: f32) {
+        let (lo, hi) = (self.min.min(self.max), self.min.max(self.max));
+        self.value = value.max(lo).min(hi);
+        let range = self.max - self.min;
+        let t = if range == 0. { 0. } else { (self.value - self.min) / range };
+        quad_mut(&mut self.knob[0]).transform =
+            Transform::new(Vec3::new(t * self.rect.width, 0., 0.));
+    }
+}
+
+// A flat colored rectangle, the building block of every widget
+struct Quad<'a> {
+    parent: Object<'a>,
+}
+
+impl GameObject for Quad<'static> {
+    fn parent(&self) -> &Object {
+        &self.parent
+    }
+}
+
+fn quad<'a>(display: &Display, rect: Rect, color: [f32; 3]) -> Object<'a> {
+    let (x0, y0, x1, y1) = (rect.x, rect.y, rect.x + rect.width, rect.y + rect.height);
+    let shape = vec![Vertex::new(x0, y0, 0.), Vertex::new(x1, y0, 0.), Vertex::new(x1, y1, 0.),
+                     Vertex::new(x0, y0, 0.), Vertex::new(x1, y1, 0.), Vertex::new(x0, y1, 0.)];
+    let vb = VertexBuffer::new(display, shape).into_vertex_buffer_any();
+    ObjectBuilder::new()
+        .vertex_buffer(vb, NoIndices(PrimitiveType::TrianglesList))
+        .material(Material::color(color).vert_shader(VertexShaderType::Gui))
+        .build()
+}
+
+// Widgets only ever keep `Quad`s where this is used
+fn quad_mut(obj: &mut Box<GameObject>) -> &mut Object<'static> {
+    &mut (**obj).as_any_mut().downcast_mut::<Quad<'static>>().unwrap().parent
+}
+
+// Text vertically centered on `anchor`. Text is laid out with y up, so it's flipped back into the
+// GUI's y down pixels.
+fn label_text(ctxt: &mut EngineContext, font: &Font, style: &GuiStyle, anchor: (f32, f32),
+              align: TextAlign, label: &str) -> Result<Text<'static>, Error> {
+    let text_style = TextStyle { color: style.text, .. Default::default() };
+    let mut text = try!(Text::in_pixels(ctxt, font, text_style, 0., 0., label));
+    if align != TextAlign::Left {
+        try!(text.set_align(ctxt, align));
+    }
+    let baseline = anchor.1 + font.size() as f32 / 3.;
+    text.parent.transform = Transform::new(Vec3::new(anchor.0, baseline, 0.))
+        .with_scale(Vec3::new(1., -1., 1.));
+    Ok(text)
+}
+
+fn mouse(input: &InputState) -> Option<(f32, f32)> {
+    input.mouse_position().map(|(x, y)| (x as f32, y as f32))
+}
+
+// The widget's next state, and whether it was clicked: pressed while hovered, then released
+// while still hovered
+fn press_state(state: WidgetState, rect: Rect, input: &InputState) -> (WidgetState, bool) {
+    let hovered = mouse(input).map_or(false, |p| rect.contains(p));
+    if state == WidgetState::Pressed {
+        if input.is_button_down(MouseButton::Left) {
+            return (WidgetState::Pressed, false);
+        }
+        let next = if hovered { WidgetState::Hovered } else { WidgetState::Normal };
+        return (next, hovered);
+    }
+    if !hovered {
+        (WidgetState::Normal, false)
+    } else if input.was_button_pressed(MouseButton::Left) {
+        (WidgetState::Pressed, false)
+    } else {
+        (WidgetState::Hovered, false)
+    }
+}
+
+fn publish<F: FnOnce(String) -> GuiEvent>(events: &EventBus, parent: &Object, event: F) {
+    let name = parent.name.clone().unwrap_or_else(String::new);
+    events.publish(event(name));
+}
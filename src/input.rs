@@ -0,0 +1,146 @@
+use std::collections::HashSet;
+use std::mem;
+
+use glium::glutin::{ElementState, Event, MouseButton, MouseScrollDelta};
+
+pub use glium::glutin::VirtualKeyCode as Key;
+
+/// Keyboard and mouse state built from window events, so game code can ask "is W held down"
+/// instead of matching on events. The engine feeds it every event and clears the per frame state
+/// (presses, releases, mouse movement) after each batch of update steps.
+pub struct InputState {
+    keys_down: HashSet<Key>,
+    keys_pressed: HashSet<Key>,
+    keys_released: HashSet<Key>,
+    buttons_down: HashSet<MouseButton>,
+    buttons_pressed: HashSet<MouseButton>,
+    buttons_released: HashSet<MouseButton>,
+    mouse_position: Option<(i32, i32)>,
+    mouse_delta: (i32, i32),
+    wheel_delta: f32,
+    // Synthetic events waiting to be delivered by the engine
+    injected: Vec<Event>,
+}
+
+impl InputState {
+    pub fn new() -> Self {
+        InputState {
+            keys_down: HashSet::new(),
+            keys_pressed: HashSet::new(),
+            keys_released: HashSet::new(),
+            buttons_down: HashSet::new(),
+            buttons_pressed: HashSet::new(),
+            buttons_released: HashSet::new(),
+            mouse_position: None,
+            mouse_delta: (0, 0),
+            wheel_delta: 0.,
+            injected: Vec::new(),
+        }
+    }
+
+    pub fn handle_event(&mut self, ev: &Event) {
+        match *ev {
+            Event::KeyboardInput(ElementState::Pressed, _, Some(key)) => {
+                // Key repeat sends more presses while the key is held
+                if self.keys_down.insert(key) {
+                    self.keys_pressed.insert(key);
+                }
+            }
+            Event::KeyboardInput(ElementState::Released, _, Some(key)) => {
+                self.keys_down.remove(&key);
+                self.keys_released.insert(key);
+            }
+            Event::MouseInput(ElementState::Pressed, button) => {
+                self.buttons_down.insert(button);
+                self.buttons_pressed.insert(button);
+            }
+            Event::MouseInput(ElementState::Released, button) => {
+                self.buttons_down.remove(&button);
+                self.buttons_released.insert(button);
+            }
+            Event::MouseMoved((x, y)) => {
+                if let Some((old_x, old_y)) = self.mouse_position {
+                    self.mouse_delta.0 += x - old_x;
+                    self.mouse_delta.1 += y - old_y;
+                }
+                self.mouse_position = Some((x, y));
+            }
+            Event::MouseWheel(MouseScrollDelta::LineDelta(_, v)) => self.wheel_delta += v,
+            // We don't get release events for anything let go of while the window isn't focused
+            Event::Focused(false) => {
+                self.keys_down.clear();
+                self.buttons_down.clear();
+            }
+            _ => ()
+        }
+    }
+
+    /// Queues a synthetic event, e.g. from an `InputScript`. The engine delivers it on the next
+    /// frame as if it came from the window, to both the input state and `Game::event`.
+    pub fn push_event(&mut self, ev: Event) {
+        self.injected.push(ev);
+    }
+
+    /// Takes the events queued with `push_event`. Called by the engine before handling them.
+    pub fn take_injected(&mut self) -> Vec<Event> {
+        mem::replace(&mut self.injected, Vec::new())
+    }
+
+    /// Sets the cursor position without counting it as mouse movement, for when the cursor is
+    /// moved by the program rather than the user
+    pub fn warp_mouse(&mut self, position: (i32, i32)) {
+        self.mouse_position = Some(position);
+    }
+
+    /// Forgets presses, releases and mouse movement. Called by the engine once the updates that
+    /// could have seen them have run.
+    pub fn end_frame(&mut self) {
+        self.keys_pressed.clear();
+        self.keys_released.clear();
+        self.buttons_pressed.clear();
+        self.buttons_released.clear();
+        self.mouse_delta = (0, 0);
+        self.wheel_delta = 0.;
+    }
+
+    pub fn is_key_down(&self, key: Key) -> bool {
+        self.keys_down.contains(&key)
+    }
+
+    /// True if `key` went down since the last frame
+    pub fn was_key_pressed(&self, key: Key) -> bool {
+        self.keys_pressed.contains(&key)
+    }
+
+    /// True if `key` went up since the last frame
+    pub fn was_key_released(&self, key: Key) -> bool {
+        self.keys_released.contains(&key)
+    }
+
+    pub fn is_button_down(&self, button: MouseButton) -> bool {
+        self.buttons_down.contains(&button)
+    }
+
+    pub fn was_button_pressed(&self, button: MouseButton) -> bool {
+        self.buttons_pressed.contains(&button)
+    }
+
+    pub fn was_button_released(&self, button: MouseButton) -> bool {
+        self.buttons_released.contains(&button)
+    }
+
+    /// Cursor position in window pixels, `None` until the mouse has moved over the window
+    pub fn mouse_position(&self) -> Option<(i32, i32)> {
+        self.mouse_position
+    }
+
+    /// How far the cursor moved since the last frame, in pixels
+    pub fn mouse_delta(&self) -> (i32, i32) {
+        self.mouse_delta
+    }
+
+    /// Scroll wheel lines since the last frame
+    pub fn wheel_delta(&self) -> f32 {
+        self.wheel_delta
+    }
+}
@@ -0,0 +1,268 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use {EngineContext, Error};
+use input::Key;
+use scene::Scene;
+
+use glium::glutin::{ElementState, Event, MouseButton, MouseScrollDelta};
+
+/// Drives a game with synthetic input for automated tests, e.g. navigating a menu and checking
+/// that the level loaded. Scripts have one command per line:
+///
+/// ```text
+/// # Open the menu and start the game
+/// tap Escape
+/// wait 0.5
+/// move 400 300
+/// click Left
+/// wait 2
+/// assert player
+/// assert_missing menu
+/// check level_loaded
+/// ```
+///
+/// `press`, `release` and `tap` take a key name, `click`, `button_down` and `button_up` take
+/// `Left`, `Right` or `Middle`, `move` takes window pixels and `scroll` takes wheel lines. `wait`
+/// is in seconds of game time. `assert` and `assert_missing` look for a named object in the
+/// scene, and `check` runs a closure registered with `InputScript::check`.
+///
+/// Call `update` from `Game::update`. The injected events reach the game through the usual
+/// `InputState` and `Game::event` on the next frame. Scripts run in a real window with
+/// `Engine::run`, or without one on machines without a display with `Engine::run_headless`,
+/// injecting an `Event::Closed` once `update` returns that the script is finished.
+pub struct InputScript {
+    steps: Vec<(usize, Step)>,
+    pos: usize,
+    // Seconds left in the current `wait`
+    waiting: f32,
+    checks: HashMap<String, Box<Fn(&Scene) -> bool>>,
+}
+
+#[derive(Clone, Debug)]
+enum Step {
+    Event(Event),
+    Wait(f32),
+    // Lets one update see the events so far, e.g. between a tap's press and release
+    NextUpdate,
+    AssertExists(String),
+    AssertMissing(String),
+    Check(String),
+}
+
+impl InputScript {
+    pub fn parse(script: &str) -> Result<Self, String> {
+        let mut steps = Vec::new();
+        for (i, line) in script.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let n = i + 1;
+            let words: Vec<_> = line.split_whitespace().collect();
+            let arg = |j: usize| {
+                words.get(j).cloned().ok_or_else(|| format!("Line {}: missing argument", n))
+            };
+            let number = |j: usize| {
+                arg(j).and_then(|w| w.parse::<f32>().map_err(|_| {
+                    format!("Line {}: \"{}\" isn't a number", n, w)
+                }))
+            };
+            let key = |j: usize| {
+                arg(j).and_then(|w| parse_key(w).ok_or_else(|| {
+                    format!("Line {}: unknown key \"{}\"", n, w)
+                }))
+            };
+            let button = |j: usize| {
+                arg(j).and_then(|w| parse_button(w).ok_or_else(|| {
+                    format!("Line {}: unknown mouse button \"{}\"", n, w)
+                }))
+            };
+            let key_event = |state, key| Step::Event(Event::KeyboardInput(state, 0, Some(key)));
+
+            match words[0] {
+                "press" => steps.push((n, key_event(ElementState::Pressed, try!(key(1))))),
+                "release" => steps.push((n, key_event(ElementState::Released, try!(key(1))))),
+                "tap" => {
+                    let key = try!(key(1));
+                    steps.push((n, key_event(ElementState::Pressed, key)));
+                    steps.push((n, Step::NextUpdate));
+                    steps.push((n, key_event(ElementState::Released, key)));
+                }
+                "button_down" => {
+                    let button = try!(button(1));
+                    steps.push((n, Step::Event(Event::MouseInput(ElementState::Pressed, button))));
+                }
+                "button_up" => {
+                    let button = try!(button(1));
+                    steps.push((n, Step::Event(Event::MouseInput(ElementState::Released,
+                                                                 button))));
+                }
+                "click" => {
+                    let button = try!(button(1));
+                    steps.push((n, Step::Event(Event::MouseInput(ElementState::Pressed, button))));
+                    steps.push((n, Step::NextUpdate));
+                    steps.push((n, Step::Event(Event::MouseInput(ElementState::Released,
+                                                                 button))));
+                }
+                "move" => {
+                    let (x, y) = (try!(number(1)), try!(number(2)));
+                    steps.push((n, Step::Event(Event::MouseMoved((x as i32, y as i32)))));
+                }
+                "scroll" => {
+                    let lines = try!(number(1));
+                    let delta = MouseScrollDelta::LineDelta(0., lines);
+                    steps.push((n, Step::Event(Event::MouseWheel(delta))));
+                }
+                "wait" => steps.push((n, Step::Wait(try!(number(1))))),
+                "assert" => steps.push((n, Step::AssertExists(try!(arg(1)).to_owned()))),
+                "assert_missing" => {
+                    steps.push((n, Step::AssertMissing(try!(arg(1)).to_owned())));
+                }
+                "check" => steps.push((n, Step::Check(try!(arg(1)).to_owned()))),
+                command => return Err(format!("Line {}: unknown command \"{}\"", n, command)),
+            }
+        }
+        Ok(InputScript { steps: steps, pos: 0, waiting: 0., checks: HashMap::new() })
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let mut script = String::new();
+        try!(try!(File::open(path)).read_to_string(&mut script));
+        Self::parse(&script).map_err(Error::Script)
+    }
+
+    /// Registers a condition that `check <name>` lines require to hold
+    pub fn check<F: Fn(&Scene) -> bool + 'static>(&mut self, name: &str, check: F) {
+        self.checks.insert(name.to_owned(), Box::new(check));
+    }
+
+    /// Runs the script until it has to wait for time to pass or for an update to see its input.
+    /// Returns whether the script is finished, or an error for the first failed assertion, which
+    /// stops the engine when returned from `Game::update`.
+    pub fn update(&mut self, ctxt: &mut EngineContext, scene: &Scene) -> Result<bool, Error> {
+        if self.waiting > 0. {
+            self.waiting -= ctxt.delta_time();
+            if self.waiting > 0. {
+                return Ok(false);
+            }
+        }
+
+        while self.pos < self.steps.len() {
+            let (line, ref step) = self.steps[self.pos];
+            self.pos += 1;
+            let fail = |msg: String| Err(Error::Script(format!("Line {}: {}", line, msg)));
+            match *step {
+                Step::Event(ref ev) => ctxt.input_mut().push_event(ev.clone()),
+                Step::Wait(seconds) => {
+                    self.waiting = seconds;
+                    return Ok(false);
+                }
+                Step::NextUpdate => return Ok(false),
+                Step::AssertExists(ref name) => if !scene.contains(name) {
+                    return fail(format!("expected an object called \"{}\"", name));
+                },
+                Step::AssertMissing(ref name) => if scene.contains(name) {
+                    return fail(format!("expected no object called \"{}\"", name));
+                },
+                Step::Check(ref name) => match self.checks.get(name) {
+                    Some(check) => if !check(scene) {
+                        return fail(format!("check \"{}\" failed", name));
+                    },
+                    None => return fail(format!("no check called \"{}\"", name)),
+                },
+            }
+        }
+        Ok(true)
+    }
+}
+
+fn parse_button(name: &str) -> Option<MouseButton> {
+    match name {
+        "Left" => Some(MouseButton::Left),
+        "Right" => Some(MouseButton::Right),
+        "Middle" => Some(MouseButton::Middle),
+        _ => None,
+    }
+}
+
+// Only the keys games commonly bind, named like `Key`'s variants
+fn parse_key(name: &str) -> Option<Key> {
+    let key = match name {
+        "A" => Key::A, "B" => Key::B, "C" => Key::C, "D" => Key::D, "E" => Key::E,
+        "F" => Key::F, "G" => Key::G, "H" => Key::H, "I" => Key::I, "J" => Key::J,
+        "K" => Key::K, "L" => Key::L, "M" => Key::M, "N" => Key::N, "O" => Key::O,
+        "P" => Key::P, "Q" => Key::Q, "R" => Key::R, "S" => Key::S, "T" => Key::T,
+        "U" => Key::U, "V" => Key::V, "W" => Key::W, "X" => Key::X, "Y" => Key::Y,
+        "Z" => Key::Z,
+        "Key0" => Key::Key0, "Key1" => Key::Key1, "Key2" => Key::Key2, "Key3" => Key::Key3,
+        "Key4" => Key::Key4, "Key5" => Key::Key5, "Key6" => Key::Key6, "Key7" => Key::Key7,
+        "Key8" => Key::Key8, "Key9" => Key::Key9,
+        "F1" => Key::F1, "F2" => Key::F2, "F3" => Key::F3, "F4" => Key::F4, "F5" => Key::F5,
+        "F6" => Key::F6, "F7" => Key::F7, "F8" => Key::F8, "F9" => Key::F9, "F10" => Key::F10,
+        "F11" => Key::F11, "F12" => Key::F12,
+        "Up" => Key::Up, "Down" => Key::Down, "Left" => Key::Left, "Right" => Key::Right,
+        "Escape" => Key::Escape, "Return" => Key::Return, "Space" => Key::Space,
+        "Tab" => Key::Tab, "Back" => Key::Back,
+        "LShift" => Key::LShift, "RShift" => Key::RShift,
+        "LControl" => Key::LControl, "RControl" => Key::RControl,
+        "LAlt" => Key::LMenu, "RAlt" => Key::RMenu,
+        _ => return None,
+    };
+    Some(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{InputScript, Step};
+    use input::Key;
+
+    use glium::glutin::{ElementState, Event};
+
+    const SCRIPT: &'static str = "\
+# Open the menu and start the game
+tap Escape
+wait 0.5
+
+move 400 300
+click Left
+assert player
+check level_loaded
+";
+
+    #[test]
+    fn parses_steps_with_their_lines() {
+        let script = InputScript::parse(SCRIPT).unwrap();
+        let lines: Vec<_> = script.steps.iter().map(|&(line, _)| line).collect();
+        assert_eq!(lines, vec![2, 2, 2, 3, 5, 6, 6, 6, 7, 8]);
+        match script.steps[0].1 {
+            Step::Event(Event::KeyboardInput(ElementState::Pressed, _, Some(Key::Escape))) => (),
+            ref other => panic!("expected Escape to be pressed, got {:?}", other),
+        }
+        match script.steps[3].1 {
+            Step::Wait(seconds) if seconds == 0.5 => (),
+            ref other => panic!("expected a wait of 0.5 seconds, got {:?}", other),
+        }
+        match script.steps[4].1 {
+            Step::Event(Event::MouseMoved((400, 300))) => (),
+            ref other => panic!("expected a move to 400, 300, got {:?}", other),
+        }
+        match script.steps[9].1 {
+            Step::Check(ref name) if name == "level_loaded" => (),
+            ref other => panic!("expected the level_loaded check, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn errors_name_the_line() {
+        let cases = [("tap Escape\nwait soon\n", "Line 2: \"soon\" isn't a number"),
+                     ("\n# Comment\nclick Thumb\n", "Line 3: unknown mouse button \"Thumb\""),
+                     ("press\n", "Line 1: missing argument"),
+                     ("tap Escape\njump\n", "Line 2: unknown command \"jump\"")];
+        for &(script, error) in cases.iter() {
+            assert_eq!(InputScript::parse(script).err(), Some(error.to_owned()));
+        }
+    }
+}
@@ -0,0 +1,193 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::{self, JoinHandle};
+
+type Work = Box<FnMut() + Send>;
+
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+pub struct JobHandle(usize);
+
+struct Job {
+    work: Option<Work>,
+    // Number of dependencies that haven't finished yet
+    pending: usize,
+    dependents: Vec<usize>,
+}
+
+struct State {
+    // Jobs that haven't finished. A job is done once it is no longer in this map.
+    jobs: HashMap<usize, Job>,
+    ready: VecDeque<usize>,
+    next_id: usize,
+    shutdown: bool,
+}
+
+struct Shared {
+    state: Mutex<State>,
+    work_available: Condvar,
+    job_finished: Condvar,
+}
+
+/// A pool of worker threads running jobs once all of the jobs they depend on have finished.
+///
+/// Game objects and GPU resources aren't `Send`, so jobs should work on plain data and hand the
+/// results back to the main thread, which waits on the handles before it needs them (e.g. at the
+/// end of `update`). A job that panics never finishes, so anything waiting on it blocks forever.
+pub struct JobSystem {
+    shared: Arc<Shared>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl JobSystem {
+    pub fn new(threads: usize) -> Self {
+        assert!(threads > 0, "JobSystem needs at least one worker thread");
+        let shared = Arc::new(Shared {
+            state: Mutex::new(State {
+                jobs: HashMap::new(),
+                ready: VecDeque::new(),
+                next_id: 0,
+                shutdown: false,
+            }),
+            work_available: Condvar::new(),
+            job_finished: Condvar::new(),
+        });
+
+        let workers = (0..threads).map(|_| {
+            let shared = shared.clone();
+            thread::spawn(move || Self::worker(&shared))
+        }).collect();
+
+        JobSystem { shared: shared, workers: workers }
+    }
+
+    pub fn spawn<F: FnOnce() + Send + 'static>(&self, f: F) -> JobHandle {
+        self.spawn_after(&[], f)
+    }
+
+    /// Runs `f` once every job in `deps` has finished
+    pub fn spawn_after<F: FnOnce() + Send + 'static>(&self, deps: &[JobHandle],
+                                                       f: F) -> JobHandle {
+        let mut f = Some(f);
+        let work: Work = Box::new(move || (f.take().unwrap())());
+
+        let mut state = self.shared.state.lock().unwrap();
+        let id = state.next_id;
+        state.next_id += 1;
+
+        let mut pending = 0;
+        for dep in deps {
+            if let Some(job) = state.jobs.get_mut(&dep.0) {
+                job.dependents.push(id);
+                pending += 1;
+            }
+        }
+
+        state.jobs.insert(id, Job { work: Some(work), pending: pending, dependents: Vec::new() });
+        if pending == 0 {
+            state.ready.push_back(id);
+            self.shared.work_available.notify_one();
+        }
+        JobHandle(id)
+    }
+
+    pub fn is_done(&self, job: JobHandle) -> bool {
+        !self.shared.state.lock().unwrap().jobs.contains_key(&job.0)
+    }
+
+    /// Blocks until `job` has finished
+    pub fn wait(&self, job: JobHandle) {
+        let mut state = self.shared.state.lock().unwrap();
+        while state.jobs.contains_key(&job.0) {
+            state = self.shared.job_finished.wait(state).unwrap();
+        }
+    }
+
+    /// Blocks until every job spawned so far has finished
+    pub fn wait_all(&self) {
+        let mut state = self.shared.state.lock().unwrap();
+        while !state.jobs.is_empty() {
+            state = self.shared.job_finished.wait(state).unwrap();
+        }
+    }
+
+    fn worker(shared: &Shared) {
+        loop {
+            let (id, mut work) = {
+                let mut state = shared.state.lock().unwrap();
+                while state.ready.is_empty() && !state.shutdown {
+                    state = shared.work_available.wait(state).unwrap();
+                }
+                if state.shutdown {
+                    return;
+                }
+                let id = state.ready.pop_front().unwrap();
+                let work = state.jobs.get_mut(&id).unwrap().work.take().unwrap();
+                (id, work)
+            };
+
+            work();
+
+            let mut state = shared.state.lock().unwrap();
+            let job = state.jobs.remove(&id).unwrap();
+            for dependent in job.dependents {
+                let ready = {
+                    let job = state.jobs.get_mut(&dependent).unwrap();
+                    job.pending -= 1;
+                    job.pending == 0
+                };
+                if ready {
+                    state.ready.push_back(dependent);
+                    shared.work_available.notify_one();
+                }
+            }
+            shared.job_finished.notify_all();
+        }
+    }
+}
+
+impl Drop for JobSystem {
+    fn drop(&mut self) {
+        self.wait_all();
+        self.shared.state.lock().unwrap().shutdown = true;
+        self.shared.work_available.notify_all();
+        for worker in self.workers.drain(..) {
+            worker.join().unwrap();
+        }
+    }
+}
+
+/// Jobs that belong together, e.g. all the work for one frame, waited on as a unit
+pub struct JobGroup {
+    jobs: Vec<JobHandle>,
+}
+
+impl JobGroup {
+    pub fn new() -> Self {
+        JobGroup { jobs: Vec::new() }
+    }
+
+    pub fn spawn<F: FnOnce() + Send + 'static>(&mut self, system: &JobSystem,
+                                                 f: F) -> JobHandle {
+        let job = system.spawn(f);
+        self.jobs.push(job);
+        job
+    }
+
+    pub fn spawn_after<F: FnOnce() + Send + 'static>(&mut self, system: &JobSystem,
+                                                       deps: &[JobHandle], f: F) -> JobHandle {
+        let job = system.spawn_after(deps, f);
+        self.jobs.push(job);
+        job
+    }
+
+    /// Handles of every job in the group, e.g. to make a job depend on the whole group
+    pub fn handles(&self) -> &[JobHandle] {
+        &self.jobs
+    }
+
+    pub fn wait(self, system: &JobSystem) {
+        for job in self.jobs {
+            system.wait(job);
+        }
+    }
+}
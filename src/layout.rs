@@ -0,0 +1,147 @@
+use get_display_dim;
+use draw::{GameObject, Object, ObjectBuilder, UpdateContext};
+use events::EngineEvent;
+use shader::VertexShaderType;
+use transform::Transform;
+
+use glium::Display;
+
+use nalgebra::Vec3;
+
+/// A point of the window that screen space objects can be positioned from
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Anchor {
+    TopLeft,
+    Top,
+    TopRight,
+    Left,
+    Center,
+    Right,
+    BottomLeft,
+    Bottom,
+    BottomRight,
+}
+
+impl Anchor {
+    /// Where the anchor is in normalized device coordinates
+    pub fn position(&self) -> (f32, f32) {
+        match *self {
+            Anchor::TopLeft => (-1., 1.),
+            Anchor::Top => (0., 1.),
+            Anchor::TopRight => (1., 1.),
+            Anchor::Left => (-1., 0.),
+            Anchor::Center => (0., 0.),
+            Anchor::Right => (1., 0.),
+            Anchor::BottomLeft => (-1., -1.),
+            Anchor::Bottom => (0., -1.),
+            Anchor::BottomRight => (1., -1.),
+        }
+    }
+}
+
+/// Keeps screen space children a fixed number of pixels from an anchor point of the window, e.g.
+/// a score in the top right corner, and keeps their size in pixels when the window is resized.
+/// Children are laid out in pixels from the anchored point, with x to the right and y up, like
+/// `Text::in_pixels`. The window size is updated from `EngineEvent::Resized` and the UI scale from
+/// `EngineEvent::AccessibilityChanged`, so the group has to be in a scene that gets updated.
+/// Groups created after the engine started should be given `Accessibility::ui_scale` with
+/// `set_ui_scale`.
+pub struct Anchored<'a> {
+    pub parent: Object<'a>,
+    children: Vec<Box<GameObject>>,
+    anchor: Anchor,
+    offset: (f32, f32),
+    window_size: (u32, u32),
+    ui_scale: f32,
+}
+
+impl GameObject for Anchored<'static> {
+    fn parent(&self) -> &Object {
+        &self.parent
+    }
+
+    fn update(&mut self, ctxt: &UpdateContext) {
+        for event in ctxt.events.read::<EngineEvent>() {
+            match *event {
+                // Minimized windows are 0 pixels wide
+                EngineEvent::Resized(w, h) if w > 0 && h > 0 => {
+                    self.window_size = (w, h);
+                    self.layout();
+                }
+                EngineEvent::AccessibilityChanged(accessibility) => {
+                    self.set_ui_scale(accessibility.ui_scale);
+                }
+                _ => (),
+            }
+        }
+        for child in self.children.iter_mut() {
+            child.update(ctxt);
+        }
+        self.children.retain(|c| !c.is_dead());
+    }
+
+    fn children(&self) -> Option<&[Box<GameObject>]> {
+        Some(&*self.children)
+    }
+}
+
+impl<'a> Anchored<'a> {
+    /// Places the children's origin `offset` pixels right of and above `anchor`
+    pub fn new(display: &Display, anchor: Anchor, offset: (f32, f32)) -> Self {
+        let mut anchored = Anchored {
+            parent: ObjectBuilder::new().vert_shader(VertexShaderType::Gui).build(),
+            children: Vec::new(),
+            anchor: anchor,
+            offset: offset,
+            window_size: get_display_dim(display),
+            ui_scale: 1.,
+        };
+        anchored.layout();
+        anchored
+    }
+
+    pub fn add<G: GameObject + 'static>(&mut self, child: G) {
+        self.children.push(Box::new(child));
+    }
+
+    pub fn children_mut(&mut self) -> &mut [Box<GameObject>] {
+        &mut self.children
+    }
+
+    pub fn anchor(&self) -> Anchor {
+        self.anchor
+    }
+
+    pub fn set_anchor(&mut self, anchor: Anchor) {
+        self.anchor = anchor;
+        self.layout();
+    }
+
+    pub fn offset(&self) -> (f32, f32) {
+        self.offset
+    }
+
+    pub fn set_offset(&mut self, offset: (f32, f32)) {
+        self.offset = offset;
+        self.layout();
+    }
+
+    pub fn ui_scale(&self) -> f32 {
+        self.ui_scale
+    }
+
+    /// Scales the children and their offset from the anchor, e.g. 2 for twice as large
+    pub fn set_ui_scale(&mut self, ui_scale: f32) {
+        self.ui_scale = ui_scale;
+        self.layout();
+    }
+
+    // Maps scaled pixels from the anchored point to normalized device coordinates
+    fn layout(&mut self) {
+        let (w, h) = (self.window_size.0 as f32 / self.ui_scale,
+                      self.window_size.1 as f32 / self.ui_scale);
+        let (x, y) = self.anchor.position();
+        let position = Vec3::new(x + self.offset.0 * 2. / w, y + self.offset.1 * 2. / h, 0.);
+        self.parent.transform = Transform::new(position).with_scale(Vec3::new(2. / w, 2. / h, 1.));
+    }
+}
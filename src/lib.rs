@@ -0,0 +1,1170 @@
+#[macro_use]
+extern crate glium;
+#[macro_use]
+extern crate log;
+
+extern crate find_folder;
+extern crate freetype;
+extern crate image;
+extern crate nalgebra;
+#[cfg(feature = "audio")]
+extern crate rodio;
+extern crate time;
+
+pub mod ai;
+pub mod accessibility;
+pub mod animation;
+#[cfg(feature = "audio")]
+pub mod audio;
+pub mod bar;
+pub mod bounds;
+pub mod budget;
+pub mod camera;
+pub mod collision;
+pub mod coroutine;
+pub mod determinism;
+pub mod draw;
+pub mod ecs;
+pub mod engine;
+pub mod error;
+pub mod events;
+pub mod gc;
+pub mod grading;
+pub mod gui;
+pub mod input;
+pub mod input_script;
+pub mod jobs;
+pub mod layout;
+pub mod light;
+pub mod material;
+pub mod mesh;
+pub mod model;
+pub mod packed;
+pub mod pick_debug;
+pub mod render_texture;
+pub mod scene;
+pub mod scene_file;
+pub mod shader;
+pub mod soak;
+pub mod spatial;
+pub mod spline;
+pub mod stats;
+pub mod texture;
+pub mod transform;
+pub mod ui_surface;
+pub mod upscale;
+pub mod voxel;
+pub mod window;
+
+pub use camera::Camera;
+pub use draw::GameObject;
+pub use engine::{Engine, EngineConfig, Game, StackGame};
+pub use error::Error;
+pub use events::{EngineEvent, EventBus};
+pub use input::{InputState, Key};
+pub use light::Light;
+pub use material::Material;
+pub use render_texture::RenderTexture;
+pub use scene::{ObjectHandle, Scene, SceneRef, SceneStack};
+pub use transform::Transform;
+pub use window::WindowMode;
+
+use std::borrow::Cow;
+use std::cmp;
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::io::{self, Read};
+use std::mem;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use accessibility::Accessibility;
+use bounds::Aabb;
+use draw::{FrameUniforms, UniformsVec};
+use gc::GpuGarbage;
+use material::Albedo;
+use model::ObjData;
+use packed::MeshOptions;
+use shader::{ShaderType, FragmentShaderType, VertexShaderType};
+use stats::MemoryReport;
+use texture::{TextureFormat, TextureOptions};
+use window::WindowSettings;
+
+use find_folder::Search;
+
+use freetype as ft;
+
+use glium::{Display, DisplayBuild, DrawError, DrawParameters, Program, Rect, Surface,
+            VertexBuffer};
+use glium::glutin::CursorState;
+use glium::texture::{ClientFormat, MipmapsOption, RawImage2d, SrgbTexture2d, Texture2d,
+                     Texture2dArray};
+use glium::uniforms::AsUniformValue;
+use glium::vertex::VertexBufferAny;
+
+use image::GenericImage;
+
+use nalgebra::Mat4;
+
+/// Size of the window's client area, or of the framebuffer for a display without a window, e.g.
+/// a headless one or a window that was just closed
+pub fn get_display_dim(display: &Display) -> (u32, u32) {
+    display.get_window()
+        .and_then(|window| window.get_inner_size())
+        .unwrap_or_else(|| display.get_framebuffer_dimensions())
+}
+
+pub struct TextureCache {
+    cache: HashMap<String, Rc<Texture2d>>,
+    srgb: HashMap<String, Rc<SrgbTexture2d>>,
+    // By the paths of their layers
+    arrays: HashMap<Vec<String>, Rc<Texture2dArray>>,
+    glyphs: GlyphAtlas,
+}
+
+impl TextureCache {
+    pub fn new() -> Self {
+        TextureCache {
+            cache: HashMap::new(),
+            srgb: HashMap::new(),
+            arrays: HashMap::new(),
+            glyphs: GlyphAtlas::new(),
+        }
+    }
+
+    /// Loads a PNG, JPEG, GIF, BMP, TGA or DDS file with mipmaps, or returns the cached copy
+    pub fn get_texture<P: AsRef<Path>>(&mut self, display: &Display,
+                                       path: P) -> Result<Rc<Texture2d>, Error> {
+        self.get_texture_with(display, path, TextureOptions::default())
+    }
+
+    /// Like `get_texture`, with control over how the texture is uploaded. Textures are cached by
+    /// path, so `options` only matter the first time a file is loaded. The texture is linear
+    /// whatever `options.srgb` says, see `get_albedo` for color textures.
+    pub fn get_texture_with<P: AsRef<Path>>(&mut self, display: &Display, path: P,
+                                            options: TextureOptions)
+                                            -> Result<Rc<Texture2d>, Error> {
+        let path = path.as_ref();
+        let s = path.to_string_lossy().into_owned();
+        if let Some(texture) = self.cache.get(&s) {
+            return Ok(texture.clone());
+        }
+
+        let levels = try!(load_levels(path));
+        let texture = if uses_stored_mipmaps(&levels, options) {
+            let (width, height) = levels[0].dimensions();
+            let texture = Texture2d::empty_with_mipmaps(display, MipmapsOption::EmptyMipmaps,
+                                                        width, height);
+            for (level, image) in levels.into_iter().enumerate() {
+                texture.mipmap(level as u32).unwrap().write(level_rect(&image), image);
+            }
+            texture
+        } else {
+            let image = levels.into_iter().next().unwrap();
+            Texture2d::with_mipmaps(display, image, mipmaps(options))
+        };
+        let texture = Rc::new(texture);
+        self.cache.insert(s, texture.clone());
+        Ok(texture)
+    }
+
+    /// Like `get_texture_with`, but uploads the file as sRGB, so shaders sample its colors in
+    /// linear space. Cached separately from linear textures.
+    pub fn get_srgb_texture_with<P: AsRef<Path>>(&mut self, display: &Display, path: P,
+                                                 options: TextureOptions)
+                                                 -> Result<Rc<SrgbTexture2d>, Error> {
+        let path = path.as_ref();
+        let s = path.to_string_lossy().into_owned();
+        if let Some(texture) = self.srgb.get(&s) {
+            return Ok(texture.clone());
+        }
+
+        let levels = try!(load_levels(path));
+        let texture = if uses_stored_mipmaps(&levels, options) {
+            let (width, height) = levels[0].dimensions();
+            let texture = SrgbTexture2d::empty_with_mipmaps(display, MipmapsOption::EmptyMipmaps,
+                                                            width, height);
+            for (level, image) in levels.into_iter().enumerate() {
+                texture.mipmap(level as u32).unwrap().write(level_rect(&image), image);
+            }
+            texture
+        } else {
+            let image = levels.into_iter().next().unwrap();
+            SrgbTexture2d::with_mipmaps(display, image, mipmaps(options))
+        };
+        let texture = Rc::new(texture);
+        self.srgb.insert(s, texture.clone());
+        Ok(texture)
+    }
+
+    /// The base color of a material textured with a color image, uploaded as sRGB if
+    /// `options.srgb` is set
+    pub fn get_albedo<P: AsRef<Path>>(&mut self, display: &Display, path: P,
+                                      options: TextureOptions) -> Result<Albedo, Error> {
+        if options.srgb {
+            Ok(Albedo::SrgbTexture(try!(self.get_srgb_texture_with(display, path, options))))
+        } else {
+            Ok(Albedo::Texture(try!(self.get_texture_with(display, path, options))))
+        }
+    }
+
+    /// Loads images of the same size as the layers of a texture array, in order, or returns the
+    /// array loaded earlier from the same paths. Layers are filtered and mipmapped separately,
+    /// so unlike an atlas nothing bleeds in from neighbouring images.
+    pub fn get_texture_array<P: AsRef<Path>>(&mut self, display: &Display, paths: &[P],
+                                             options: TextureOptions)
+                                             -> Result<Rc<Texture2dArray>, Error> {
+        let key: Vec<_> = paths.iter()
+            .map(|p| p.as_ref().to_string_lossy().into_owned())
+            .collect();
+        if let Some(texture) = self.arrays.get(&key) {
+            return Ok(texture.clone());
+        }
+
+        let mut layers = Vec::with_capacity(paths.len());
+        for (path, s) in paths.iter().zip(key.iter()) {
+            let image = try!(load_image(path.as_ref()));
+            if let Some(first) = layers.first() {
+                if image.dimensions() != first.dimensions() {
+                    let msg = format!("{} isn't the same size as the other layers", s);
+                    return Err(Error::Image(image::ImageError::UnsupportedError(msg)));
+                }
+            }
+            layers.push(image);
+        }
+        let texture = Rc::new(Texture2dArray::with_mipmaps(display, layers, mipmaps(options)));
+        self.arrays.insert(key, texture.clone());
+        Ok(texture)
+    }
+
+    pub fn glyphs(&mut self) -> &mut GlyphAtlas {
+        &mut self.glyphs
+    }
+
+    /// Number of textures and texture arrays in the cache, not counting the glyph atlas
+    pub fn len(&self) -> usize {
+        self.cache.len() + self.srgb.len() + self.arrays.len()
+    }
+
+    /// Removes textures that are only referenced by the cache, and the glyph atlas if no text is
+    /// using it, returning how many textures and glyphs were freed
+    pub fn purge_unused(&mut self) -> (usize, usize) {
+        let textures = take_unused(&mut self.cache, |t| Rc::strong_count(t) == 1).len();
+        let srgb = take_unused(&mut self.srgb, |t| Rc::strong_count(t) == 1).len();
+        let arrays = take_unused(&mut self.arrays, |t| Rc::strong_count(t) == 1).len();
+        let glyphs = self.take_unused_glyphs().map_or(0, |atlas| atlas.len());
+        (textures + srgb + arrays, glyphs)
+    }
+
+    /// Like `purge_unused`, but hands the textures and glyph atlas to `garbage` to be destroyed
+    /// over the next frames instead of all at once
+    pub fn defer_unused(&mut self, garbage: &mut GpuGarbage) -> (usize, usize) {
+        let textures = take_unused(&mut self.cache, |t| Rc::strong_count(t) == 1);
+        let srgb = take_unused(&mut self.srgb, |t| Rc::strong_count(t) == 1);
+        let arrays = take_unused(&mut self.arrays, |t| Rc::strong_count(t) == 1);
+        let freed = textures.len() + srgb.len() + arrays.len();
+        let glyphs = self.take_unused_glyphs();
+        for texture in textures {
+            garbage.defer(texture);
+        }
+        for texture in srgb {
+            garbage.defer(texture);
+        }
+        for texture in arrays {
+            garbage.defer(texture);
+        }
+        let glyphs = glyphs.map_or(0, |atlas| {
+            let len = atlas.len();
+            garbage.defer(atlas);
+            len
+        });
+        (freed, glyphs)
+    }
+
+    // The glyph atlas, replaced by an empty one, if no text is using it
+    fn take_unused_glyphs(&mut self) -> Option<GlyphAtlas> {
+        if self.glyphs.in_use() {
+            None
+        } else {
+            Some(mem::replace(&mut self.glyphs, GlyphAtlas::new()))
+        }
+    }
+}
+
+// Removes the entries of `map` that are `unused`, returning them
+fn take_unused<K, V, F>(map: &mut HashMap<K, V>, unused: F) -> Vec<V>
+    where K: Clone + Eq + Hash, F: Fn(&V) -> bool
+{
+    let keys: Vec<_> = map.iter().filter(|&(_, v)| unused(v)).map(|(k, _)| k.clone()).collect();
+    keys.iter().filter_map(|k| map.remove(k)).collect()
+}
+
+fn load_image(path: &Path) -> Result<image::DynamicImage, Error> {
+    let mut levels = try!(load_levels(path));
+    Ok(levels.swap_remove(0))
+}
+
+// The image with the mipmap levels stored in its file, largest first
+fn load_levels(path: &Path) -> Result<Vec<image::DynamicImage>, Error> {
+    let mut bytes = Vec::new();
+    try!(try!(File::open(path)).read_to_end(&mut bytes));
+    match TextureFormat::detect(path, &bytes) {
+        Some(format) => texture::decode_levels(&bytes, format),
+        None => {
+            let msg = format!("unknown image format: {}", path.to_string_lossy());
+            Err(Error::Image(image::ImageError::UnsupportedError(msg)))
+        }
+    }
+}
+
+// Whether the file's own mipmaps are uploaded instead of generating them, which needs every
+// level down to 1x1
+fn uses_stored_mipmaps(levels: &[image::DynamicImage], options: TextureOptions) -> bool {
+    let (width, height) = levels[0].dimensions();
+    options.mipmaps && levels.len() > 1 && levels.len() == texture::full_mipmap_count(width, height)
+}
+
+fn level_rect(image: &image::DynamicImage) -> Rect {
+    let (width, height) = image.dimensions();
+    Rect { left: 0, bottom: 0, width: width, height: height }
+}
+
+fn mipmaps(options: TextureOptions) -> MipmapsOption {
+    if options.mipmaps {
+        MipmapsOption::AutoGeneratedMipmaps
+    } else {
+        MipmapsOption::NoMipmap
+    }
+}
+
+/// A vertex buffer loaded from an OBJ file, along with the bounds of its vertices
+#[derive(Clone)]
+pub struct CachedMesh {
+    pub vertex_buffer: Rc<VertexBufferAny>,
+    pub bounds: Option<Aabb>,
+    /// Whether the buffer holds `PackedVertex`es, which need the `Packed` vertex shader
+    pub packed: bool,
+}
+
+/// Vertex buffers of OBJ files by path, so objects loading the same file share one buffer the
+/// same way they share textures from the `TextureCache`. Draw them with `ObjectBuilder::mesh`.
+pub struct MeshCache {
+    // By path and whether the vertices are packed
+    cache: HashMap<(String, bool), CachedMesh>,
+}
+
+impl MeshCache {
+    pub fn new() -> Self {
+        MeshCache { cache: HashMap::new() }
+    }
+
+    /// Loads every object in an OBJ file into one vertex buffer, ignoring materials, or returns
+    /// the buffer loaded earlier from the same path
+    pub fn get_obj<P: AsRef<Path>>(&mut self, display: &Display,
+                                   path: P) -> Result<CachedMesh, Error> {
+        self.get_obj_with(display, path, MeshOptions::default())
+    }
+
+    /// Like `get_obj`, with control over how the vertices are stored. A file loaded both packed
+    /// and unpacked is cached once each way.
+    pub fn get_obj_with<P: AsRef<Path>>(&mut self, display: &Display, path: P,
+                                        options: MeshOptions) -> Result<CachedMesh, Error> {
+        let key = (path.as_ref().to_string_lossy().into_owned(), options.packed);
+        if let Some(mesh) = self.cache.get(&key) {
+            return Ok(mesh.clone());
+        }
+
+        let vertices = try!(ObjData::load(path)).vertices();
+        let bounds = Aabb::from_vertices(&vertices);
+        let vertex_buffer = if options.packed {
+            let vertices = packed::pack_vertices(&vertices);
+            VertexBuffer::new(display, vertices).into_vertex_buffer_any()
+        } else {
+            VertexBuffer::new(display, vertices).into_vertex_buffer_any()
+        };
+        let mesh = CachedMesh {
+            bounds: bounds,
+            vertex_buffer: Rc::new(vertex_buffer),
+            packed: options.packed,
+        };
+        self.cache.insert(key, mesh.clone());
+        Ok(mesh)
+    }
+
+    pub fn len(&self) -> usize {
+        self.cache.len()
+    }
+
+    /// Removes meshes that are only referenced by the cache, returning how many were freed
+    pub fn purge_unused(&mut self) -> usize {
+        take_unused(&mut self.cache, |m| Rc::strong_count(&m.vertex_buffer) == 1).len()
+    }
+
+    /// Like `purge_unused`, but hands the buffers to `garbage` to be destroyed over the next
+    /// frames instead of all at once
+    pub fn defer_unused(&mut self, garbage: &mut GpuGarbage) -> usize {
+        let meshes = take_unused(&mut self.cache, |m| Rc::strong_count(&m.vertex_buffer) == 1);
+        let freed = meshes.len();
+        for mesh in meshes {
+            garbage.defer(mesh.vertex_buffer);
+        }
+        freed
+    }
+}
+
+/// Font used by text that doesn't choose one, relative to the resource folder
+pub const DEFAULT_FONT: &'static str = "FiraSans-Regular.ttf";
+/// Pixel size of `DEFAULT_FONT`
+pub const DEFAULT_FONT_SIZE: u32 = 16;
+
+/// A font face set to one pixel size, from the `FontCache`. Cloning it is cheap, and the clones
+/// share their glyphs in the atlas.
+#[derive(Clone)]
+pub struct Font {
+    // Identifies the font and size in the glyph atlas
+    id: usize,
+    face: Rc<ft::Face<'static>>,
+    size: u32,
+}
+
+impl Font {
+    pub fn face(&self) -> &ft::Face<'static> {
+        &self.face
+    }
+
+    /// Height of the font in pixels
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+}
+
+/// Font faces by path and pixel size, so text using the same font and size shares one face and
+/// one set of glyphs in the atlas. A file used at several sizes is loaded once per size.
+pub struct FontCache {
+    library: ft::Library,
+    cache: HashMap<(String, u32), Font>,
+    next_id: usize,
+}
+
+impl FontCache {
+    pub fn new() -> Result<Self, Error> {
+        Ok(FontCache { library: try!(ft::Library::init()), cache: HashMap::new(), next_id: 0 })
+    }
+
+    /// Loads a TrueType or OpenType font at `size` pixels, or returns the font loaded earlier
+    /// from the same path at that size
+    pub fn get_font<P: AsRef<Path>>(&mut self, path: P, size: u32) -> Result<Font, Error> {
+        let path = path.as_ref();
+        let key = (path.to_string_lossy().into_owned(), size);
+        if let Some(font) = self.cache.get(&key) {
+            return Ok(font.clone());
+        }
+
+        let face = try!(self.library.new_face(path, 0));
+        try!(face.set_pixel_sizes(0, size));
+        let font = Font { id: self.next_id, face: Rc::new(face), size: size };
+        self.next_id += 1;
+        self.cache.insert(key, font.clone());
+        Ok(font)
+    }
+
+    pub fn len(&self) -> usize {
+        self.cache.len()
+    }
+
+    /// Removes fonts that are only referenced by the cache, returning how many were freed. Their
+    /// glyphs stay in the atlas until it is purged.
+    pub fn purge_unused(&mut self) -> usize {
+        take_unused(&mut self.cache, |f| Rc::strong_count(&f.face) == 1).len()
+    }
+}
+
+/// Metrics of a glyph in pixels, and where it is in the atlas
+#[derive(Copy, Clone, Debug)]
+pub struct Glyph {
+    pub left: f32,
+    pub top: f32,
+    pub width: f32,
+    pub height: f32,
+    pub advance_x: f32,
+    pub advance_y: f32,
+    x: u32,
+    y: u32,
+}
+
+impl Glyph {
+    /// Texture coordinates of the glyph's left, top, right and bottom edges in an atlas `size`
+    /// pixels wide
+    pub fn tex_coords(&self, size: u32) -> (f32, f32, f32, f32) {
+        let size = size as f32;
+        (self.x as f32 / size, self.y as f32 / size,
+         (self.x as f32 + self.width) / size, (self.y as f32 + self.height) / size)
+    }
+}
+
+/// Every glyph rendered so far packed into one texture, so a whole string can be drawn with a
+/// single vertex buffer. Glyphs are placed left to right in rows, and the atlas doubles in size
+/// when it runs out of room. Each font and size gets its own glyphs.
+pub struct GlyphAtlas {
+    // By font id and character
+    glyphs: HashMap<(usize, char), Glyph>,
+    // Copy of the texture, so it can be uploaded again after adding glyphs or growing
+    pixels: Vec<u8>,
+    size: u32,
+    next_x: u32,
+    next_y: u32,
+    row_height: u32,
+    // `None` when glyphs were added since the last upload
+    texture: Option<Rc<Texture2d>>,
+}
+
+impl GlyphAtlas {
+    pub fn new() -> Self {
+        const INITIAL_SIZE: u32 = 256;
+        GlyphAtlas {
+            glyphs: HashMap::new(),
+            pixels: vec![0; (INITIAL_SIZE * INITIAL_SIZE) as usize],
+            size: INITIAL_SIZE,
+            next_x: 0,
+            next_y: 0,
+            row_height: 0,
+            texture: None,
+        }
+    }
+
+    /// Adds any characters of `text` that aren't in the atlas yet in `font`, returning the
+    /// texture to draw the text with. Texture coordinates must be computed with the current
+    /// `size`.
+    pub fn prepare(&mut self, display: &Display, font: &Font,
+                   text: &str) -> Result<Rc<Texture2d>, Error> {
+        try!(self.add_glyphs(font, text));
+        if let Some(ref texture) = self.texture {
+            return Ok(texture.clone());
+        }
+        let texture = Rc::new(Texture2d::new(display, RawImage2d {
+            data: Cow::Borrowed(&*self.pixels),
+            width: self.size, height: self.size,
+            format: ClientFormat::U8
+        }));
+        self.texture = Some(texture.clone());
+        Ok(texture)
+    }
+
+    /// Rasterizes the characters of `text` that aren't in the atlas yet in `font`, without
+    /// uploading the texture
+    pub fn add_glyphs(&mut self, font: &Font, text: &str) -> Result<(), Error> {
+        for c in text.chars() {
+            if !self.glyphs.contains_key(&(font.id, c)) {
+                try!(self.add(font, c));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn glyph(&self, font: &Font, c: char) -> Option<&Glyph> {
+        self.glyphs.get(&(font.id, c))
+    }
+
+    /// Width and height of the atlas in pixels
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+
+    pub fn len(&self) -> usize {
+        self.glyphs.len()
+    }
+
+    // Whether any text is drawn with the latest texture
+    fn in_use(&self) -> bool {
+        self.texture.as_ref().map_or(false, |t| Rc::strong_count(t) > 1)
+    }
+
+    fn add(&mut self, font: &Font, c: char) -> Result<(), Error> {
+        try!(font.face.load_char(c as usize, ft::face::RENDER));
+        let g = font.face.glyph();
+        let bitmap = g.bitmap();
+        let (w, h) = (bitmap.width() as u32, bitmap.rows() as u32);
+
+        // Leave a pixel between glyphs so filtering doesn't pick up their neighbours
+        if self.next_x + w + 1 > self.size {
+            self.next_x = 0;
+            self.next_y += self.row_height + 1;
+            self.row_height = 0;
+        }
+        while self.next_x + w + 1 > self.size || self.next_y + h + 1 > self.size {
+            self.grow();
+        }
+
+        let buffer = bitmap.buffer();
+        let pitch = bitmap.pitch().abs() as u32;
+        for row in 0..h {
+            for col in 0..w {
+                let dst = (self.next_y + row) * self.size + self.next_x + col;
+                self.pixels[dst as usize] = buffer[(row * pitch + col) as usize];
+            }
+        }
+
+        self.glyphs.insert((font.id, c), Glyph {
+            left: g.bitmap_left() as f32,
+            top: g.bitmap_top() as f32,
+            width: w as f32,
+            height: h as f32,
+            advance_x: (g.advance().x >> 6) as f32,
+            advance_y: (g.advance().y >> 6) as f32,
+            x: self.next_x,
+            y: self.next_y,
+        });
+        self.next_x += w + 1;
+        self.row_height = cmp::max(self.row_height, h);
+        self.texture = None;
+        Ok(())
+    }
+
+    // Glyphs keep their pixel positions, so only texture coordinates computed later change
+    fn grow(&mut self) {
+        let size = self.size * 2;
+        let mut pixels = vec![0; (size * size) as usize];
+        for row in 0..self.size as usize {
+            let old = self.size as usize;
+            for col in 0..old {
+                pixels[row * size as usize + col] = self.pixels[row * old + col];
+            }
+        }
+        self.pixels = pixels;
+        self.size = size;
+    }
+}
+
+pub struct EngineContext {
+    resource_dir: PathBuf,
+    /// `None` when only the embedded shaders are used
+    shader_dir: Option<PathBuf>,
+    embedded_shaders: bool,
+    display: Display,
+    vert_shader_map: HashMap<VertexShaderType, String>,
+    frag_shader_map: HashMap<FragmentShaderType, String>,
+    /// Linked programs, or `None` for shader pairs that failed to compile
+    programs: HashMap<(VertexShaderType, FragmentShaderType), Option<Program>>,
+    texture_cache: TextureCache,
+    mesh_cache: MeshCache,
+    font_cache: FontCache,
+    garbage: GpuGarbage,
+    // Whether to purge the caches once the garbage has been collected, since resources used by
+    // deferred objects aren't unused until the objects are dropped
+    purge_after_garbage: bool,
+    fps: u32,
+    delta_time: f32,
+    input: InputState,
+    events: EventBus,
+    cursor_grabbed: bool,
+    window: WindowSettings,
+    accessibility: Accessibility,
+    // When the context was built, in nanoseconds
+    start_time: u64,
+}
+
+impl EngineContext {
+    /// Uses the "resources" and "shaders" folders in the current directory or one of its parents,
+    /// see `EngineContextBuilder` for other locations
+    pub fn new(display: Display) -> Result<Self, Error> {
+        EngineContextBuilder::new().build(display)
+    }
+
+    pub fn display(&self) -> &Display {
+        &self.display
+    }
+
+    pub fn resource_dir(&self) -> &Path {
+        &self.resource_dir
+    }
+
+    /// Creates a texture `width` by `height` pixels that scenes can be drawn into
+    pub fn render_texture(&self, width: u32, height: u32) -> RenderTexture {
+        RenderTexture::new(&self.display, width, height)
+    }
+
+    pub fn texture_cache(&mut self) -> &mut TextureCache {
+        &mut self.texture_cache
+    }
+
+    pub fn mesh_cache(&mut self) -> &mut MeshCache {
+        &mut self.mesh_cache
+    }
+
+    pub fn font_cache(&mut self) -> &mut FontCache {
+        &mut self.font_cache
+    }
+
+    pub fn garbage(&mut self) -> &mut GpuGarbage {
+        &mut self.garbage
+    }
+
+    /// Destroys deferred resources within the garbage budget, returning how many were dropped.
+    /// Called by `Engine::run` after every frame. Once the objects of a scene unloaded with
+    /// `Scene::unload_deferred` are all gone, the textures, meshes, glyphs and fonts only they
+    /// used are deferred in turn.
+    pub fn collect_garbage(&mut self) -> usize {
+        let dropped = self.garbage.collect();
+        if self.purge_after_garbage && self.garbage.is_empty() {
+            self.purge_after_garbage = false;
+            self.defer_unused();
+        }
+        dropped
+    }
+
+    /// Destroys every deferred resource and anything the caches no longer need right away, e.g.
+    /// on shutdown
+    pub fn flush_garbage(&mut self) -> usize {
+        let mut dropped = self.garbage.flush();
+        if self.purge_after_garbage {
+            self.purge_after_garbage = false;
+            self.defer_unused();
+            dropped += self.garbage.flush();
+        }
+        dropped
+    }
+
+    // Hands cached resources nothing else holds on to to the garbage
+    fn defer_unused(&mut self) {
+        let (textures, glyphs) = self.texture_cache.defer_unused(&mut self.garbage);
+        let meshes = self.mesh_cache.defer_unused(&mut self.garbage);
+        let fonts = self.font_cache.purge_unused();
+        debug!("Deferred {} textures, {} meshes and {} glyphs, freed {} fonts", textures, meshes,
+               glyphs, fonts);
+    }
+
+    /// Loads a font from the resource folder at `size` pixels through the `FontCache`
+    pub fn font(&mut self, path: &str, size: u32) -> Result<Font, Error> {
+        let path = self.resource_dir.join(path);
+        self.font_cache.get_font(path, size)
+    }
+
+    /// `DEFAULT_FONT` at `DEFAULT_FONT_SIZE`
+    pub fn default_font(&mut self) -> Result<Font, Error> {
+        self.font(DEFAULT_FONT, DEFAULT_FONT_SIZE)
+    }
+
+    /// Number of frames drawn during the last second
+    pub fn fps(&self) -> u32 {
+        self.fps
+    }
+
+    /// How many resources the engine's caches are holding on to
+    pub fn memory_report(&self) -> MemoryReport {
+        MemoryReport {
+            textures: self.texture_cache.len(),
+            glyphs: self.texture_cache.glyphs.len(),
+            meshes: self.mesh_cache.len(),
+            fonts: self.font_cache.len(),
+            programs: self.programs.len(),
+        }
+    }
+
+    pub fn input(&self) -> &InputState {
+        &self.input
+    }
+
+    pub fn events(&self) -> &EventBus {
+        &self.events
+    }
+
+    /// Mutable access to the event bus, e.g. to subscribe to events
+    pub fn events_mut(&mut self) -> &mut EventBus {
+        &mut self.events
+    }
+
+    pub fn accessibility(&self) -> Accessibility {
+        self.accessibility
+    }
+
+    /// Changes the accessibility options, e.g. from a settings menu, and publishes
+    /// `EngineEvent::AccessibilityChanged` so objects depending on them can update
+    pub fn set_accessibility(&mut self, accessibility: Accessibility) {
+        self.accessibility = accessibility;
+        self.events.publish(EngineEvent::AccessibilityChanged(accessibility));
+    }
+
+    /// Mutable access to the input state, e.g. to inject events with `InputState::push_event`
+    pub fn input_mut(&mut self) -> &mut InputState {
+        &mut self.input
+    }
+
+    /// Hides the cursor and keeps it in the middle of the window, so `InputState::mouse_delta`
+    /// keeps reporting movement however far the mouse goes, e.g. for first person mouse look.
+    /// The engine releases the cursor when the window loses focus. Does nothing without a window.
+    pub fn set_cursor_grabbed(&mut self, grabbed: bool) {
+        let state = if grabbed { CursorState::Grab } else { CursorState::Normal };
+        let window = match self.display.get_window() {
+            Some(window) => window,
+            None => return,
+        };
+        match window.set_cursor_state(state) {
+            Ok(()) => self.cursor_grabbed = grabbed,
+            Err(e) => warn!("Couldn't change the cursor state: {}", e),
+        }
+    }
+
+    pub fn cursor_grabbed(&self) -> bool {
+        self.cursor_grabbed
+    }
+
+    /// Moves a grabbed cursor back to the middle of the window without it counting as mouse
+    /// movement. Called by the engine every frame.
+    pub fn recenter_cursor(&mut self) {
+        if !self.cursor_grabbed {
+            return;
+        }
+        let (w, h) = get_display_dim(&self.display);
+        let center = (w as i32 / 2, h as i32 / 2);
+        let moved = match self.display.get_window() {
+            Some(window) => window.set_cursor_position(center.0, center.1).is_ok(),
+            None => false,
+        };
+        if moved {
+            self.input.warp_mouse(center);
+        }
+    }
+
+    pub fn window_mode(&self) -> WindowMode {
+        self.window.mode
+    }
+
+    /// Rebuilds the window in `mode`, keeping every loaded resource. The size of the window before
+    /// it went fullscreen is restored when going back to windowed mode.
+    pub fn set_window_mode(&mut self, mode: WindowMode) {
+        if mode == self.window.mode {
+            return;
+        }
+        if self.window.mode == WindowMode::Windowed {
+            self.window.size = get_display_dim(&self.display);
+        }
+        let previous = self.window.mode;
+        self.window.mode = mode;
+        if !self.rebuild_window() {
+            self.window.mode = previous;
+        }
+    }
+
+    /// Switches between windowed and fullscreen mode. The engine calls this on Alt+Enter unless
+    /// `EngineConfig::fullscreen_toggle` is off.
+    pub fn toggle_fullscreen(&mut self) {
+        let mode = match self.window.mode {
+            WindowMode::Windowed => WindowMode::Fullscreen,
+            WindowMode::Fullscreen | WindowMode::Borderless => WindowMode::Windowed,
+        };
+        self.set_window_mode(mode);
+    }
+
+    /// Chooses the monitor for fullscreen and borderless modes, moving the window there if it's
+    /// already in one of them. See `window::monitor_names`.
+    pub fn set_monitor(&mut self, monitor: Option<usize>) {
+        self.window.monitor = monitor;
+        if self.window.mode != WindowMode::Windowed {
+            self.rebuild_window();
+        }
+    }
+
+    // Builds the window again from the current settings, returning whether it worked
+    fn rebuild_window(&mut self) -> bool {
+        if let Err(e) = self.window.builder().rebuild_glium(&self.display) {
+            warn!("Couldn't rebuild the window in {:?} mode: {:?}", self.window.mode, e);
+            return false;
+        }
+        // The new window doesn't have the old one's cursor state
+        if self.cursor_grabbed {
+            self.cursor_grabbed = false;
+            self.set_cursor_grabbed(true);
+        }
+        true
+    }
+
+    pub fn title(&self) -> &str {
+        &self.window.title
+    }
+
+    /// Renames the window, or only remembers the title for when one is built if there's none
+    pub fn set_title(&mut self, title: &str) {
+        if let Some(window) = self.display.get_window() {
+            window.set_title(title);
+        }
+        self.window.title = title.to_owned();
+    }
+
+    /// Seconds since the engine started
+    pub fn time(&self) -> f32 {
+        ((time::precise_time_ns() - self.start_time) as f64 / 1e9) as f32
+    }
+
+    /// Seconds of game time covered by the current update step
+    pub fn delta_time(&self) -> f32 {
+        self.delta_time
+    }
+
+    /// Forgets every loaded shader so they are read from disk and compiled again the next time
+    /// they are used, e.g. after editing them while the game is running
+    pub fn reload_shaders(&mut self) {
+        self.vert_shader_map.clear();
+        self.frag_shader_map.clear();
+        self.programs.clear();
+        match self.shader_dir {
+            Some(ref dir) => info!("Reloading shaders from {}", dir.display()),
+            None => info!("Only embedded shaders are used, nothing to reload"),
+        }
+    }
+
+    /// The uniforms shared by everything drawn from `camera` onto a surface `viewport` pixels in
+    /// size
+    pub fn frame_uniforms<'a>(&self, camera: &'a Camera, lights: &'a [Light], ambient: [f32; 3],
+                              viewport: (u32, u32)) -> FrameUniforms<'a> {
+        FrameUniforms::new(camera, lights, ambient, self.time(), viewport)
+    }
+
+    /// Draws `obj`, whose parent is at `parent_transform` in the world, `alpha` of the way between
+    /// its last two updates. Drawing many objects with `draw_in_frame` avoids building the shared
+    /// uniforms for each of them.
+    pub fn draw<S: Surface>(&mut self, surface: &mut S, camera: &Camera, lights: &[Light],
+                            ambient: [f32; 3], parent_transform: &Mat4<f32>, alpha: f32,
+                            obj: &Box<GameObject>) -> Result<(), Error> {
+        let frame = self.frame_uniforms(camera, lights, ambient, surface.get_dimensions());
+        self.draw_in_frame(surface, &frame, parent_transform, alpha, obj)
+    }
+
+    /// Draws `obj` like `draw`, with the camera, lights and other uniforms shared by the frame.
+    /// The frame's uniforms come first, then the uniforms of the object's material, followed by
+    /// any it adds itself. The lights are only passed to objects using a lit fragment shader. A
+    /// shader program that fails to build is only reported once, after which objects using it are
+    /// skipped until the shaders are reloaded.
+    pub fn draw_in_frame<S: Surface>(&mut self, surface: &mut S, frame: &FrameUniforms,
+                                     parent_transform: &Mat4<f32>, alpha: f32,
+                                     obj: &Box<GameObject>) -> Result<(), Error> {
+        let parent = obj.parent();
+        let material = &parent.material;
+
+        if let (&Some(ref vb), &Some(ref indices)) = (&parent.vertex_buffer, &parent.indices) {
+            // Before the program, which borrows the context
+            let transform = obj.draw_transform(frame.camera, parent_transform, alpha);
+            let transform_buffer = parent.transform_buffer(&self.display, &transform);
+            let program = match try!(self.get_program(material.vert_shader,
+                                                      material.frag_shader)) {
+                Some(program) => program,
+                None => return Ok(()),
+            };
+
+            let lit = match material.frag_shader {
+                FragmentShaderType::Lambert | FragmentShaderType::Phong |
+                FragmentShaderType::NormalMapped | FragmentShaderType::SpecularMapped |
+                FragmentShaderType::Cutout | FragmentShaderType::Layered => true,
+                _ => false,
+            };
+            let mut uniforms = UniformsVec(Vec::new());
+            frame.push_uniforms(&mut uniforms, lit);
+            uniforms.0.push(("ObjectBlock", (&*transform_buffer).as_uniform_value()));
+            material.push_uniforms(&mut uniforms);
+            uniforms.0.extend(obj.construct_uniforms(frame.camera).0);
+
+            let params = DrawParameters {
+                backface_culling: material.backface_culling(),
+                .. parent.draw_params.clone()
+            };
+            try!(match parent.instances {
+                Some(ref instances) => {
+                    let per_instance = try!(require_instancing(
+                        instances.per_instance_if_supported()));
+                    surface.draw((&**vb, per_instance), indices.clone(), program, &uniforms,
+                                 &params)
+                }
+                None => surface.draw(&**vb, indices.clone(), program, &uniforms, &params),
+            });
+        }
+        Ok(())
+    }
+
+    // Programs that failed to build are remembered as `None`, so the error is only returned once
+    fn get_program(&mut self, vert_shader: VertexShaderType,
+                   frag_shader: FragmentShaderType) -> Result<Option<&Program>, Error> {
+        let key = (vert_shader, frag_shader);
+        if !self.programs.contains_key(&key) {
+            match self.build_program(vert_shader, frag_shader) {
+                Ok(program) => {
+                    self.programs.insert(key, Some(program));
+                }
+                Err(e) => {
+                    self.programs.insert(key, None);
+                    return Err(e);
+                }
+            }
+        }
+        Ok(self.programs[&key].as_ref())
+    }
+
+    fn build_program(&mut self, vert_shader: VertexShaderType,
+                     frag_shader: FragmentShaderType) -> Result<Program, Error> {
+        let &mut EngineContext {
+            ref shader_dir,
+            embedded_shaders,
+            ref display,
+            ref mut vert_shader_map,
+            ref mut frag_shader_map,
+            ..
+        } = self;
+        let vertex_shader = try!(Self::get_shader(shader_dir, embedded_shaders, vert_shader_map,
+                                                  vert_shader));
+        let fragment_shader = try!(Self::get_shader(shader_dir, embedded_shaders, frag_shader_map,
+                                                    frag_shader));
+        Ok(try!(Program::from_source(display, vertex_shader, fragment_shader, None)))
+    }
+
+    // Reads the shader from the shader folder, falling back to the embedded copy if allowed
+    fn get_shader<'a, S: ShaderType>(shader_dir: &Option<PathBuf>, embedded: bool,
+                                     shader_map: &'a mut HashMap<S, String>,
+                                     shader_type: S) -> Result<&'a str, Error> {
+        if !shader_map.contains_key(&shader_type) {
+            let path = shader_dir.as_ref().map(|dir| dir.join(shader_type.to_filename()));
+            let shader = match path {
+                Some(ref path) if !embedded || path.is_file() => {
+                    let mut shader = String::new();
+                    try!(try!(File::open(path)).read_to_string(&mut shader));
+                    shader
+                }
+                _ => shader_type.embedded_source().to_owned(),
+            };
+            shader_map.insert(shader_type, shader);
+        }
+        Ok(&shader_map[&shader_type])
+    }
+}
+
+/// Configures where `EngineContext` loads resources and shaders from. By default the "resources"
+/// and "shaders" folders are searched for in the current directory and its parents, which only
+/// works when running from inside the project, so games using the engine as a dependency should
+/// set the folders explicitly or add search paths.
+pub struct EngineContextBuilder {
+    resource_dir: Option<PathBuf>,
+    shader_dir: Option<PathBuf>,
+    search_paths: Vec<PathBuf>,
+    embedded_shaders: bool,
+}
+
+impl EngineContextBuilder {
+    pub fn new() -> Self {
+        EngineContextBuilder {
+            resource_dir: None,
+            shader_dir: None,
+            search_paths: Vec::new(),
+            embedded_shaders: false,
+        }
+    }
+
+    /// Loads resources from `dir` instead of searching for a "resources" folder
+    pub fn resource_dir<P: Into<PathBuf>>(mut self, dir: P) -> Self {
+        self.resource_dir = Some(dir.into());
+        self
+    }
+
+    /// Loads shaders from `dir` instead of searching for a "shaders" folder
+    pub fn shader_dir<P: Into<PathBuf>>(mut self, dir: P) -> Self {
+        self.shader_dir = Some(dir.into());
+        self
+    }
+
+    /// Adds a directory to look for the "resources" and "shaders" folders in. Search paths are
+    /// tried in the order they were added, before the current directory and its parents.
+    pub fn search_path<P: Into<PathBuf>>(mut self, dir: P) -> Self {
+        self.search_paths.push(dir.into());
+        self
+    }
+
+    /// Uses the engine's shaders compiled into the binary for any shader that isn't in the
+    /// shader folder, or for all of them if there is no shader folder
+    pub fn embedded_shaders(mut self, embedded: bool) -> Self {
+        self.embedded_shaders = embedded;
+        self
+    }
+
+    /// Fails if a folder that wasn't set explicitly can't be found, unless it's the shader folder
+    /// and embedded shaders are enabled
+    pub fn build(self, display: Display) -> Result<EngineContext, Error> {
+        let resource_dir = match self.resource_dir {
+            Some(ref dir) => dir.clone(),
+            None => try!(self.find_folder("resources")),
+        };
+        let shader_dir = match self.shader_dir {
+            Some(ref dir) => Some(dir.clone()),
+            None => match self.find_folder("shaders") {
+                Ok(dir) => Some(dir),
+                Err(_) if self.embedded_shaders => None,
+                Err(e) => return Err(e),
+            },
+        };
+        let size = get_display_dim(&display);
+        Ok(EngineContext {
+            resource_dir: resource_dir,
+            shader_dir: shader_dir,
+            embedded_shaders: self.embedded_shaders,
+            display: display,
+            vert_shader_map: HashMap::new(),
+            frag_shader_map: HashMap::new(),
+            programs: HashMap::new(),
+            texture_cache: TextureCache::new(),
+            mesh_cache: MeshCache::new(),
+            font_cache: try!(FontCache::new()),
+            garbage: GpuGarbage::new(1.),
+            purge_after_garbage: false,
+            fps: 0,
+            delta_time: 0.,
+            input: InputState::new(),
+            events: EventBus::new(),
+            cursor_grabbed: false,
+            window: WindowSettings {
+                title: String::new(),
+                size: size,
+                mode: WindowMode::Windowed,
+                monitor: None,
+                vsync: true,
+            },
+            accessibility: Default::default(),
+            start_time: time::precise_time_ns(),
+        })
+    }
+
+    fn find_folder(&self, name: &str) -> Result<PathBuf, Error> {
+        for path in self.search_paths.iter() {
+            let dir = path.join(name);
+            if dir.is_dir() {
+                return Ok(dir);
+            }
+        }
+        find_folder(name)
+    }
+}
+
+fn find_folder(name: &str) -> Result<PathBuf, Error> {
+    Search::Parents(4).for_folder(name).map_err(|_| {
+        Error::Io(io::Error::new(io::ErrorKind::NotFound,
+                                 format!("Couldn't find the \"{}\" folder", name)))
+    })
+}
+
+// The per instance attributes of an `InstancedObject`, or an error if the driver can't draw
+// instances and returned none
+fn require_instancing<T>(per_instance: Option<T>) -> Result<T, Error> {
+    per_instance.ok_or(Error::Draw(DrawError::InstancingNotSupported))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::require_instancing;
+    use Error;
+
+    use glium::DrawError;
+
+    #[test]
+    fn instancing_unsupported_is_an_error() {
+        match require_instancing::<()>(None) {
+            Err(Error::Draw(DrawError::InstancingNotSupported)) => (),
+            other => panic!("expected InstancingNotSupported, got {:?}", other),
+        }
+        assert!(require_instancing(Some(3)).is_ok());
+    }
+}
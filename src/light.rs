@@ -0,0 +1,113 @@
+use std::cmp;
+
+use draw::UniformsVec;
+
+use glium::uniforms::UniformValue;
+
+use nalgebra::{Norm, Vec3};
+
+/// Maximum number of lights passed to the lit shaders. Must match `MAX_LIGHTS` in the shaders.
+pub const MAX_LIGHTS: usize = 8;
+
+const DIRECTIONAL_TYPE: u32 = 0;
+const POINT_TYPE: u32 = 1;
+const SPOT_TYPE: u32 = 2;
+
+// Uniform names have to be 'static, so the array element names are spelled out
+const LIGHT_TYPE: [&'static str; MAX_LIGHTS] = [
+    "light_type[0]", "light_type[1]", "light_type[2]", "light_type[3]",
+    "light_type[4]", "light_type[5]", "light_type[6]", "light_type[7]"];
+const LIGHT_POSITION: [&'static str; MAX_LIGHTS] = [
+    "light_position[0]", "light_position[1]", "light_position[2]", "light_position[3]",
+    "light_position[4]", "light_position[5]", "light_position[6]", "light_position[7]"];
+const LIGHT_DIRECTION: [&'static str; MAX_LIGHTS] = [
+    "light_direction[0]", "light_direction[1]", "light_direction[2]", "light_direction[3]",
+    "light_direction[4]", "light_direction[5]", "light_direction[6]", "light_direction[7]"];
+const LIGHT_COLOR: [&'static str; MAX_LIGHTS] = [
+    "light_color[0]", "light_color[1]", "light_color[2]", "light_color[3]",
+    "light_color[4]", "light_color[5]", "light_color[6]", "light_color[7]"];
+const LIGHT_RANGE: [&'static str; MAX_LIGHTS] = [
+    "light_range[0]", "light_range[1]", "light_range[2]", "light_range[3]",
+    "light_range[4]", "light_range[5]", "light_range[6]", "light_range[7]"];
+const LIGHT_CUTOFF: [&'static str; MAX_LIGHTS] = [
+    "light_cutoff[0]", "light_cutoff[1]", "light_cutoff[2]", "light_cutoff[3]",
+    "light_cutoff[4]", "light_cutoff[5]", "light_cutoff[6]", "light_cutoff[7]"];
+
+#[derive(Copy, Clone, Debug)]
+pub enum LightKind {
+    /// Infinitely far away light shining in `direction`, like the sun
+    Directional { direction: Vec3<f32> },
+    /// Light shining in every direction from `position`, fading out at `range`
+    Point { position: Vec3<f32>, range: f32 },
+    /// Cone of light from `position` towards `direction`, `angle` radians from the center to the
+    /// edge of the cone
+    Spot { position: Vec3<f32>, direction: Vec3<f32>, range: f32, angle: f32 },
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct Light {
+    pub kind: LightKind,
+    pub color: [f32; 3],
+    pub intensity: f32,
+}
+
+impl Light {
+    pub fn directional(direction: Vec3<f32>, color: [f32; 3]) -> Self {
+        Light { kind: LightKind::Directional { direction: direction }, color: color, intensity: 1. }
+    }
+
+    pub fn point(position: Vec3<f32>, range: f32, color: [f32; 3]) -> Self {
+        Light { kind: LightKind::Point { position: position, range: range }, color: color,
+                intensity: 1. }
+    }
+
+    pub fn spot(position: Vec3<f32>, direction: Vec3<f32>, range: f32, angle: f32,
+                color: [f32; 3]) -> Self {
+        Light {
+            kind: LightKind::Spot { position: position, direction: direction, range: range,
+                                    angle: angle },
+            color: color,
+            intensity: 1.,
+        }
+    }
+
+    pub fn intensity(mut self, intensity: f32) -> Self {
+        self.intensity = intensity;
+        self
+    }
+}
+
+/// Appends the uniforms the lit shaders expect for `lights` to `uniforms`. Only the first
+/// `MAX_LIGHTS` lights are used.
+pub fn push_uniforms(uniforms: &mut UniformsVec, lights: &[Light], ambient: [f32; 3]) {
+    if lights.len() > MAX_LIGHTS {
+        warn!("{} lights in the scene, only the first {} are used", lights.len(), MAX_LIGHTS);
+    }
+
+    let count = cmp::min(lights.len(), MAX_LIGHTS);
+    uniforms.0.push(("light_count", UniformValue::UnsignedInt(count as u32)));
+    uniforms.0.push(("ambient", UniformValue::Vec3(ambient)));
+
+    for (i, light) in lights.iter().take(MAX_LIGHTS).enumerate() {
+        let (ty, position, direction, range, cutoff) = match light.kind {
+            LightKind::Directional { direction } =>
+                (DIRECTIONAL_TYPE, Vec3::new(0., 0., 0.), direction, 0., 0.),
+            LightKind::Point { position, range } =>
+                (POINT_TYPE, position, Vec3::new(0., 0., 0.), range, 0.),
+            LightKind::Spot { position, direction, range, angle } =>
+                (SPOT_TYPE, position, direction, range, angle.cos()),
+        };
+        let direction = if direction.sqnorm() > 0. { direction.normalize() } else { direction };
+        let c = light.color;
+        let color = [c[0] * light.intensity, c[1] * light.intensity, c[2] * light.intensity];
+
+        uniforms.0.push((LIGHT_TYPE[i], UniformValue::UnsignedInt(ty)));
+        uniforms.0.push((LIGHT_POSITION[i],
+                         UniformValue::Vec3([position.x, position.y, position.z])));
+        uniforms.0.push((LIGHT_DIRECTION[i],
+                         UniformValue::Vec3([direction.x, direction.y, direction.z])));
+        uniforms.0.push((LIGHT_COLOR[i], UniformValue::Vec3(color)));
+        uniforms.0.push((LIGHT_RANGE[i], UniformValue::Float(range)));
+        uniforms.0.push((LIGHT_CUTOFF[i], UniformValue::Float(cutoff)));
+    }
+}
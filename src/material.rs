@@ -0,0 +1,316 @@
+use std::rc::Rc;
+
+use draw::{COLOR_TYPE, TEXTURE_ALPHA_FADE_TYPE, TEXTURE_ARRAY_TYPE, TEXTURE_RGB_TYPE,
+           TEXTURE_TINT_TYPE, VERTEX_COLOR_TYPE, UniformsVec};
+use shader::{FragmentShaderType, VertexShaderType};
+
+use glium::{BackfaceCullingMode, DepthTest, DrawParameters};
+use glium::texture::{SrgbTexture2d, Texture2d, Texture2dArray};
+use glium::uniforms::{SamplerBehavior, UniformValue};
+
+/// Where the base color of a surface comes from
+#[derive(Clone)]
+pub enum Albedo {
+    Color([f32; 3]),
+    Texture(Rc<Texture2d>),
+    /// A color texture uploaded as sRGB, sampled as linear colors. See
+    /// `TextureCache::get_albedo`.
+    SrgbTexture(Rc<SrgbTexture2d>),
+    /// A single channel texture used as the alpha of a solid color, e.g. text
+    AlphaTexture(Rc<Texture2d>, [f32; 3]),
+    /// A texture multiplied by a color, e.g. to tint sprites
+    TintedTexture(Rc<Texture2d>, [f32; 3]),
+    /// The per instance color of an `InstancedObject`
+    VertexColor,
+    /// One layer of a texture array, chosen by the material's `layer`, e.g. terrain or foliage
+    /// variants sharing one texture. Drawn with the `Layered` shader.
+    TextureArray(Rc<Texture2dArray>),
+}
+
+/// Which side of triangles isn't drawn
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum CullFace {
+    /// Draw both sides, e.g. for planes seen from both sides
+    None,
+    /// Skip triangles facing away from the camera, which are normally hidden by the front of
+    /// a closed mesh anyway
+    Back,
+    Front,
+}
+
+/// The order the vertices of a triangle's front side appear in on screen
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Winding {
+    CounterClockwise,
+    /// For meshes exported with the opposite winding, which would otherwise be culled inside
+    /// out
+    Clockwise,
+}
+
+/// Moves, scales and rotates the texture coordinates of a material, e.g. to tile a texture or
+/// line it up with a mesh. `scroll` and `spin` animate it from the shaders' `time` uniform, for
+/// conveyor belts, flowing water or panning clouds that don't need updating every frame.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct UvTransform {
+    pub offset: [f32; 2],
+    /// Repeats the texture this many times, with a repeating sampler
+    pub scale: [f32; 2],
+    /// Radians counterclockwise around the center of the texture
+    pub rotation: f32,
+    /// Added to `offset` every second
+    pub scroll: [f32; 2],
+    /// Added to `rotation` every second
+    pub spin: f32,
+}
+
+impl UvTransform {
+    pub fn identity() -> Self {
+        UvTransform {
+            offset: [0., 0.],
+            scale: [1., 1.],
+            rotation: 0.,
+            scroll: [0., 0.],
+            spin: 0.,
+        }
+    }
+
+    /// Scrolls the texture `speed` units per second, e.g. `[0., 0.5]` for water flowing down
+    pub fn scrolling(speed: [f32; 2]) -> Self {
+        UvTransform { scroll: speed, .. UvTransform::identity() }
+    }
+
+    /// Appends `uv_offset`, `uv_scale`, `uv_rotation`, `uv_scroll` and `uv_spin` to `uniforms`
+    pub fn push_uniforms(&self, uniforms: &mut UniformsVec) {
+        uniforms.0.push(("uv_offset", UniformValue::Vec2(self.offset)));
+        uniforms.0.push(("uv_scale", UniformValue::Vec2(self.scale)));
+        uniforms.0.push(("uv_rotation", UniformValue::Float(self.rotation)));
+        uniforms.0.push(("uv_scroll", UniformValue::Vec2(self.scroll)));
+        uniforms.0.push(("uv_spin", UniformValue::Float(self.spin)));
+    }
+}
+
+/// How an object looks: which shaders draw it and what they are given. `EngineContext` turns the
+/// material into uniforms, so objects can swap materials without writing any uniform code.
+#[derive(Clone)]
+pub struct Material {
+    pub vert_shader: VertexShaderType,
+    pub frag_shader: FragmentShaderType,
+    pub albedo: Albedo,
+    /// Multiplies the alpha of `AlphaTexture` and `TintedTexture` materials, e.g. to fade text out
+    pub alpha: f32,
+    pub sampler: SamplerBehavior,
+    /// Specular exponent used by the Phong shader
+    pub shininess: f32,
+    /// Strength of the specular highlight used by the Phong shader
+    pub specular: f32,
+    /// Tangent space normals used by the `NormalMapped` shader
+    pub normal_map: Option<Rc<Texture2d>>,
+    /// Scales the specular highlight of the mapped shaders by its red channel
+    pub specular_map: Option<Rc<Texture2d>>,
+    /// Alpha below which the `Cutout` shader discards pixels
+    pub alpha_cutoff: f32,
+    pub cull_face: CullFace,
+    /// Which winding is the front of a triangle, for `cull_face` and two sided shaders
+    pub front_face: Winding,
+    /// Applied to the texture coordinates by the world vertex shaders
+    pub uv_transform: UvTransform,
+    /// Layer of a `TextureArray` albedo to draw with
+    pub layer: u32,
+}
+
+impl Material {
+    pub fn new(albedo: Albedo) -> Self {
+        Material {
+            vert_shader: VertexShaderType::Perspective,
+            frag_shader: FragmentShaderType::Unlit,
+            albedo: albedo,
+            alpha: 1.,
+            sampler: Default::default(),
+            shininess: 32.,
+            specular: 0.5,
+            normal_map: None,
+            specular_map: None,
+            alpha_cutoff: 0.5,
+            cull_face: CullFace::None,
+            front_face: Winding::CounterClockwise,
+            uv_transform: UvTransform::identity(),
+            layer: 0,
+        }
+    }
+
+    pub fn color(color: [f32; 3]) -> Self {
+        Material::new(Albedo::Color(color))
+    }
+
+    pub fn texture(texture: Rc<Texture2d>) -> Self {
+        Material::new(Albedo::Texture(texture))
+    }
+
+    pub fn srgb_texture(texture: Rc<SrgbTexture2d>) -> Self {
+        Material::new(Albedo::SrgbTexture(texture))
+    }
+
+    /// A texture whose transparent pixels are cut out instead of blended, e.g. foliage or a chain
+    /// link fence. Draw it with `cutout_draw_params` so it's depth tested, and keep `cull_face` at
+    /// `None` so both sides are drawn.
+    pub fn cutout(texture: Rc<Texture2d>, alpha_cutoff: f32) -> Self {
+        Material::texture(texture)
+            .frag_shader(FragmentShaderType::Cutout)
+            .alpha_cutoff(alpha_cutoff)
+    }
+
+    /// Layer `layer` of a texture array, drawn with the `Layered` shader. Objects sharing the
+    /// array can be drawn without binding another texture, and `InstancedObject` instances can
+    /// each pick their own layer.
+    pub fn texture_array(texture: Rc<Texture2dArray>, layer: u32) -> Self {
+        Material::new(Albedo::TextureArray(texture))
+            .frag_shader(FragmentShaderType::Layered)
+            .layer(layer)
+    }
+
+    pub fn vert_shader(mut self, vert_shader: VertexShaderType) -> Self {
+        self.vert_shader = vert_shader;
+        self
+    }
+
+    pub fn frag_shader(mut self, frag_shader: FragmentShaderType) -> Self {
+        self.frag_shader = frag_shader;
+        self
+    }
+
+    pub fn sampler(mut self, sampler: SamplerBehavior) -> Self {
+        self.sampler = sampler;
+        self
+    }
+
+    pub fn alpha(mut self, alpha: f32) -> Self {
+        self.alpha = alpha;
+        self
+    }
+
+    pub fn alpha_cutoff(mut self, alpha_cutoff: f32) -> Self {
+        self.alpha_cutoff = alpha_cutoff;
+        self
+    }
+
+    pub fn cull_face(mut self, cull_face: CullFace) -> Self {
+        self.cull_face = cull_face;
+        self
+    }
+
+    pub fn front_face(mut self, front_face: Winding) -> Self {
+        self.front_face = front_face;
+        self
+    }
+
+    pub fn layer(mut self, layer: u32) -> Self {
+        self.layer = layer;
+        self
+    }
+
+    pub fn uv_transform(mut self, uv_transform: UvTransform) -> Self {
+        self.uv_transform = uv_transform;
+        self
+    }
+
+    /// The culling `EngineContext::draw` uses, overriding the object's draw parameters
+    pub fn backface_culling(&self) -> BackfaceCullingMode {
+        match (self.cull_face, self.front_face) {
+            (CullFace::None, _) => BackfaceCullingMode::CullingDisabled,
+            (CullFace::Back, Winding::CounterClockwise) |
+            (CullFace::Front, Winding::Clockwise) => BackfaceCullingMode::CullClockwise,
+            (CullFace::Back, Winding::Clockwise) |
+            (CullFace::Front, Winding::CounterClockwise) => {
+                BackfaceCullingMode::CullCounterClockwise
+            }
+        }
+    }
+
+    /// Uses `normal_map` for the surface normals, switching to the `NormalMapped` shader
+    pub fn normal_map(mut self, normal_map: Rc<Texture2d>) -> Self {
+        self.normal_map = Some(normal_map);
+        self.frag_shader = FragmentShaderType::NormalMapped;
+        self
+    }
+
+    /// Uses `specular_map` for the strength of the specular highlight. Switches to the
+    /// `SpecularMapped` shader unless the material is already normal mapped.
+    pub fn specular_map(mut self, specular_map: Rc<Texture2d>) -> Self {
+        self.specular_map = Some(specular_map);
+        if self.frag_shader != FragmentShaderType::NormalMapped {
+            self.frag_shader = FragmentShaderType::SpecularMapped;
+        }
+        self
+    }
+
+    /// The texture sampled by the material, if any
+    pub fn texture_ref(&self) -> Option<&Rc<Texture2d>> {
+        match self.albedo {
+            Albedo::Texture(ref t) | Albedo::AlphaTexture(ref t, _) |
+            Albedo::TintedTexture(ref t, _) => Some(t),
+            Albedo::Color(_) | Albedo::SrgbTexture(_) | Albedo::VertexColor |
+            Albedo::TextureArray(_) => None,
+        }
+    }
+
+    /// Appends the uniforms for this material to `uniforms`
+    pub fn push_uniforms<'a>(&'a self, uniforms: &mut UniformsVec<'a>) {
+        let sampler = Some(self.sampler);
+        match self.albedo {
+            Albedo::Color(color) => {
+                uniforms.0.push(("type", UniformValue::UnsignedInt(COLOR_TYPE)));
+                uniforms.0.push(("color", UniformValue::Vec3(color)));
+            }
+            Albedo::Texture(ref texture) => {
+                uniforms.0.push(("type", UniformValue::UnsignedInt(TEXTURE_RGB_TYPE)));
+                uniforms.0.push(("tex", UniformValue::Texture2d(texture, sampler)));
+            }
+            Albedo::SrgbTexture(ref texture) => {
+                uniforms.0.push(("type", UniformValue::UnsignedInt(TEXTURE_RGB_TYPE)));
+                uniforms.0.push(("tex", UniformValue::SrgbTexture2d(texture, sampler)));
+            }
+            Albedo::AlphaTexture(ref texture, color) => {
+                uniforms.0.push(("type", UniformValue::UnsignedInt(TEXTURE_ALPHA_FADE_TYPE)));
+                uniforms.0.push(("color", UniformValue::Vec3(color)));
+                uniforms.0.push(("tex", UniformValue::Texture2d(texture, sampler)));
+            }
+            Albedo::TintedTexture(ref texture, color) => {
+                uniforms.0.push(("type", UniformValue::UnsignedInt(TEXTURE_TINT_TYPE)));
+                uniforms.0.push(("color", UniformValue::Vec3(color)));
+                uniforms.0.push(("tex", UniformValue::Texture2d(texture, sampler)));
+            }
+            Albedo::VertexColor => {
+                uniforms.0.push(("type", UniformValue::UnsignedInt(VERTEX_COLOR_TYPE)));
+            }
+            Albedo::TextureArray(ref texture) => {
+                uniforms.0.push(("type", UniformValue::UnsignedInt(TEXTURE_ARRAY_TYPE)));
+                uniforms.0.push(("tex_array", UniformValue::Texture2dArray(texture, sampler)));
+                uniforms.0.push(("layer", UniformValue::UnsignedInt(self.layer)));
+            }
+        }
+        uniforms.0.push(("alpha", UniformValue::Float(self.alpha)));
+        uniforms.0.push(("shininess", UniformValue::Float(self.shininess)));
+        uniforms.0.push(("specular_strength", UniformValue::Float(self.specular)));
+        if let Some(ref normal_map) = self.normal_map {
+            uniforms.0.push(("normal_map", UniformValue::Texture2d(normal_map, sampler)));
+        }
+        if let Some(ref specular_map) = self.specular_map {
+            uniforms.0.push(("specular_map", UniformValue::Texture2d(specular_map, sampler)));
+        }
+        uniforms.0.push(("has_specular_map", UniformValue::Bool(self.specular_map.is_some())));
+        uniforms.0.push(("alpha_cutoff", UniformValue::Float(self.alpha_cutoff)));
+        let clockwise = self.front_face == Winding::Clockwise;
+        uniforms.0.push(("clockwise_front", UniformValue::Bool(clockwise)));
+        self.uv_transform.push_uniforms(uniforms);
+    }
+}
+
+/// Draw parameters for `Cutout` materials: depth tested and written like opaque objects, since
+/// cut out pixels are discarded rather than blended
+pub fn cutout_draw_params<'a>() -> DrawParameters<'a> {
+    DrawParameters {
+        depth_test: DepthTest::IfLess,
+        depth_write: true,
+        .. Default::default()
+    }
+}
@@ -0,0 +1,193 @@
+use std::cmp;
+
+use bounds::Aabb;
+use draw::{ObjectBuilder, Vertex};
+use spline::Spline;
+
+use glium::VertexBuffer;
+use glium::backend::Facade;
+use glium::index::{NoIndices, PrimitiveType};
+use glium::vertex::VertexBufferAny;
+
+use nalgebra::{cross, Mat4, Norm, Vec3, Vec4};
+
+/// Builds triangle meshes in code. Normals are computed from the winding order, so faces should
+/// be counter-clockwise when seen from the front.
+pub struct MeshBuilder {
+    vertices: Vec<Vertex>,
+}
+
+impl MeshBuilder {
+    pub fn new() -> Self {
+        MeshBuilder { vertices: Vec::new() }
+    }
+
+    pub fn triangle(mut self, a: Vec3<f32>, b: Vec3<f32>, c: Vec3<f32>,
+                    uvs: [[f32; 2]; 3]) -> Self {
+        self.push_triangle(a, b, c, uvs);
+        self
+    }
+
+    /// Adds the quad `a b c d` as two triangles
+    pub fn quad(mut self, a: Vec3<f32>, b: Vec3<f32>, c: Vec3<f32>, d: Vec3<f32>,
+                uvs: [[f32; 2]; 4]) -> Self {
+        self.push_quad(a, b, c, d, uvs);
+        self
+    }
+
+    /// Sweeps a 2D cross section along `path`. Profile points are `(x, y)` offsets in the plane
+    /// perpendicular to the path, where y points "up" (towards +z) as much as possible. The v
+    /// texture coordinate is the distance travelled along the path.
+    pub fn extrude(mut self, profile: &[(f32, f32)], path: &[Vec3<f32>]) -> Self {
+        if profile.len() < 2 || path.len() < 2 {
+            return self;
+        }
+
+        let mut rings = Vec::with_capacity(path.len());
+        let mut distances = Vec::with_capacity(path.len());
+        let mut distance = 0.;
+        for i in 0..path.len() {
+            let prev = path[if i == 0 { 0 } else { i - 1 }];
+            let next = path[if i + 1 == path.len() { i } else { i + 1 }];
+            let (right, up) = frame(next - prev);
+
+            if i > 0 {
+                distance += (path[i] - path[i - 1]).norm();
+            }
+            distances.push(distance);
+            rings.push(profile.iter()
+                       .map(|&(x, y)| path[i] + right * x + up * y)
+                       .collect::<Vec<_>>());
+        }
+
+        let last = (profile.len() - 1) as f32;
+        for i in 0..path.len() - 1 {
+            let (v0, v1) = (distances[i], distances[i + 1]);
+            for j in 0..profile.len() - 1 {
+                let (u0, u1) = (j as f32 / last, (j + 1) as f32 / last);
+                self.push_quad(rings[i][j], rings[i][j + 1], rings[i + 1][j + 1], rings[i + 1][j],
+                               [[u0, v0], [u1, v0], [u1, v1], [u0, v1]]);
+            }
+        }
+        self
+    }
+
+    /// Sweeps `profile` along `spline`, sampled roughly every `spacing` units. If `ground` is
+    /// given, the path is moved onto the height it returns for each (x, y) so the mesh follows
+    /// the terrain. `spacing` must be positive.
+    pub fn extrude_spline(self, profile: &[(f32, f32)], spline: &Spline, spacing: f32,
+                          ground: Option<&Fn(f32, f32) -> f32>) -> Self {
+        assert!(spacing > 0., "Spline extrusion spacing must be positive");
+        let n = cmp::max(1, (spline.length() / spacing).ceil() as usize);
+        let mut path = spline.sample(n);
+        if let Some(ground) = ground {
+            for p in path.iter_mut() {
+                p.z = ground(p.x, p.y);
+            }
+        }
+        self.extrude(profile, &path)
+    }
+
+    /// Revolves a profile of `(radius, height)` points around the z axis
+    pub fn lathe(mut self, profile: &[(f32, f32)], segments: u32) -> Self {
+        if profile.len() < 2 || segments < 3 {
+            return self;
+        }
+
+        let point = |(r, h): (f32, f32), seg: u32| {
+            let angle = seg as f32 / segments as f32 * 2. * ::std::f32::consts::PI;
+            Vec3::new(r * angle.cos(), r * angle.sin(), h)
+        };
+
+        let last = (profile.len() - 1) as f32;
+        for seg in 0..segments {
+            let (u0, u1) = (seg as f32 / segments as f32, (seg + 1) as f32 / segments as f32);
+            for j in 0..profile.len() - 1 {
+                let (v0, v1) = (j as f32 / last, (j + 1) as f32 / last);
+                self.push_quad(point(profile[j], seg), point(profile[j], seg + 1),
+                               point(profile[j + 1], seg + 1), point(profile[j + 1], seg),
+                               [[u0, v0], [u1, v0], [u1, v1], [u0, v1]]);
+            }
+        }
+        self
+    }
+
+    /// Appends the triangles of `other`, transformed by `transform`
+    pub fn append(mut self, other: &MeshBuilder, transform: Mat4<f32>) -> Self {
+        for v in other.vertices.iter() {
+            let p = transform * Vec4::new(v.position[0], v.position[1], v.position[2], 1.);
+            let n = transform * Vec4::new(v.normal[0], v.normal[1], v.normal[2], 0.);
+            let n = Vec3::new(n.x, n.y, n.z);
+            let n = if n.sqnorm() > 0. { n.normalize() } else { n };
+            let t = transform * Vec4::new(v.tangent[0], v.tangent[1], v.tangent[2], 0.);
+            let t = Vec3::new(t.x, t.y, t.z);
+            let t = if t.sqnorm() > 0. { t.normalize() } else { t };
+            let mut vertex = Vertex::with_normal([p.x, p.y, p.z], v.tex_coord, [n.x, n.y, n.z]);
+            vertex.tangent = [t.x, t.y, t.z];
+            self.vertices.push(vertex);
+        }
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.vertices.len()
+    }
+
+    pub fn build<F: Facade>(self, facade: &F) -> VertexBufferAny {
+        VertexBuffer::new(facade, self.vertices).into_vertex_buffer_any()
+    }
+
+    /// Uploads the mesh and returns an `ObjectBuilder` drawing it as a triangle list
+    pub fn object<'a, F: Facade>(self, facade: &F) -> ObjectBuilder<'a> {
+        let bounds = Aabb::from_vertices(&self.vertices);
+        let vb = self.build(facade);
+        let builder = ObjectBuilder::new()
+            .vertex_buffer(vb, NoIndices(PrimitiveType::TrianglesList));
+        match bounds {
+            Some(bounds) => builder.bounds(bounds),
+            None => builder,
+        }
+    }
+
+    fn push_triangle(&mut self, a: Vec3<f32>, b: Vec3<f32>, c: Vec3<f32>, uvs: [[f32; 2]; 3]) {
+        let n = cross(&(b - a), &(c - a));
+        let n = if n.sqnorm() > 0. { n.normalize() } else { n };
+        for (p, uv) in [a, b, c].iter().zip(uvs.iter()) {
+            self.vertices.push(Vertex::with_normal([p.x, p.y, p.z], *uv, [n.x, n.y, n.z]));
+        }
+        let start = self.vertices.len() - 3;
+        Vertex::compute_tangents(&mut self.vertices[start..]);
+    }
+
+    fn push_quad(&mut self, a: Vec3<f32>, b: Vec3<f32>, c: Vec3<f32>, d: Vec3<f32>,
+                 uvs: [[f32; 2]; 4]) {
+        self.push_triangle(a, b, c, [uvs[0], uvs[1], uvs[2]]);
+        self.push_triangle(a, c, d, [uvs[0], uvs[2], uvs[3]]);
+    }
+}
+
+/// A flat strip `width` units wide, for roads and rivers
+pub fn ribbon_profile(width: f32) -> Vec<(f32, f32)> {
+    vec![(-width / 2., 0.), (width / 2., 0.)]
+}
+
+/// A closed circle of `sides` segments, for pipes and tubes. The points go clockwise so that the
+/// extruded faces point outwards.
+pub fn circle_profile(radius: f32, sides: u32) -> Vec<(f32, f32)> {
+    (0..sides + 1).map(|i| {
+        let angle = i as f32 / sides as f32 * 2. * ::std::f32::consts::PI;
+        (radius * angle.cos(), -radius * angle.sin())
+    }).collect()
+}
+
+/// Returns the (right, up) axes of the plane perpendicular to `tangent`
+pub fn frame(tangent: Vec3<f32>) -> (Vec3<f32>, Vec3<f32>) {
+    let tangent = if tangent.sqnorm() > 0. { tangent.normalize() } else { Vec3::new(1., 0., 0.) };
+    let mut right = cross(&tangent, &Vec3::new(0., 0., 1.));
+    if right.sqnorm() < 1e-6 {
+        // Travelling straight up or down, any horizontal axis will do
+        right = cross(&tangent, &Vec3::new(0., 1., 0.));
+    }
+    let right = right.normalize();
+    (right, cross(&right, &tangent))
+}
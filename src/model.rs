@@ -0,0 +1,343 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::mem;
+use std::path::Path;
+use std::str::SplitWhitespace;
+
+use {EngineContext, Error};
+use bounds::Aabb;
+use draw::{GameObject, Object, ObjectBuilder, Vertex};
+use material::Material;
+use shader::FragmentShaderType;
+
+use glium::{DepthTest, DrawParameters, VertexBuffer};
+use glium::index::{NoIndices, PrimitiveType};
+
+use nalgebra::{cross, Norm, Vec3};
+
+/// The triangles of an OBJ file that belong to the same object or group and use the same material
+pub struct ObjPart {
+    pub name: String,
+    pub material: Option<String>,
+    pub vertices: Vec<Vertex>,
+}
+
+/// The contents of an OBJ file. Polygons with more than three vertices are triangulated, and faces
+/// without normals get flat ones.
+pub struct ObjData {
+    pub parts: Vec<ObjPart>,
+    /// Material libraries referenced with `mtllib`, relative to the OBJ file
+    pub material_libs: Vec<String>,
+}
+
+impl ObjData {
+    pub fn parse<R: BufRead>(input: R) -> Result<Self, Error> {
+        let mut positions = Vec::new();
+        let mut tex_coords = Vec::new();
+        let mut normals = Vec::new();
+        let mut data = ObjData { parts: Vec::new(), material_libs: Vec::new() };
+        let mut part = ObjPart { name: String::new(), material: None, vertices: Vec::new() };
+
+        for (n, line) in input.lines().enumerate() {
+            let line = try!(line);
+            let mut words = line.split_whitespace();
+            match words.next() {
+                Some("v") => {
+                    let v = try!(parse_floats(&mut words, 3, n));
+                    positions.push([v[0], v[1], v[2]]);
+                }
+                Some("vt") => {
+                    let v = try!(parse_floats(&mut words, 2, n));
+                    tex_coords.push([v[0], v[1]]);
+                }
+                Some("vn") => {
+                    let v = try!(parse_floats(&mut words, 3, n));
+                    normals.push([v[0], v[1], v[2]]);
+                }
+                Some("f") => {
+                    let mut face = Vec::new();
+                    for word in words {
+                        face.push(try!(parse_face_vertex(word, &positions, &tex_coords,
+                                                         &normals, n)));
+                    }
+                    if face.len() < 3 {
+                        return Err(obj_error(n, "face with fewer than three vertices"));
+                    }
+                    // Fan triangulation, which is enough for the convex polygons exporters write
+                    for i in 1..face.len() - 1 {
+                        push_triangle(&mut part.vertices, [face[0], face[i], face[i + 1]]);
+                    }
+                }
+                Some("o") | Some("g") => {
+                    let name = line.trim()[1..].trim().to_owned();
+                    let material = part.material.clone();
+                    data.finish_part(&mut part, name, material);
+                }
+                Some("usemtl") => {
+                    let name = part.name.clone();
+                    data.finish_part(&mut part, name, words.next().map(|s| s.to_owned()));
+                }
+                Some("mtllib") => data.material_libs.extend(words.map(|s| s.to_owned())),
+                _ => (),
+            }
+        }
+
+        let (name, material) = (part.name.clone(), part.material.clone());
+        data.finish_part(&mut part, name, material);
+        if data.parts.is_empty() {
+            return Err(Error::Obj("the file has no faces".to_owned()));
+        }
+        Ok(data)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        ObjData::parse(BufReader::new(try!(File::open(path))))
+    }
+
+    /// Every part merged into one triangle list
+    pub fn vertices(&self) -> Vec<Vertex> {
+        self.parts.iter().flat_map(|p| p.vertices.iter().cloned()).collect()
+    }
+
+    // Keeps `part` if it has any triangles and starts a new one
+    fn finish_part(&mut self, part: &mut ObjPart, name: String, material: Option<String>) {
+        let next = ObjPart { name: name, material: material, vertices: Vec::new() };
+        let finished = mem::replace(part, next);
+        if !finished.vertices.is_empty() {
+            self.parts.push(finished);
+        }
+    }
+}
+
+// A face vertex as `position`, `position/tex_coord`, `position//normal` or
+// `position/tex_coord/normal`. Negative indices count back from the last element.
+fn parse_face_vertex(word: &str, positions: &[[f32; 3]], tex_coords: &[[f32; 2]],
+                     normals: &[[f32; 3]], line: usize) -> Result<FaceVertex, Error> {
+    let mut indices = word.split('/');
+    let position = match indices.next() {
+        Some(i) => try!(lookup(i, positions, line)),
+        None => return Err(obj_error(line, "empty face vertex")),
+    };
+    let tex_coord = match indices.next() {
+        Some(i) if !i.is_empty() => Some(try!(lookup(i, tex_coords, line))),
+        _ => None,
+    };
+    let normal = match indices.next() {
+        Some(i) if !i.is_empty() => Some(try!(lookup(i, normals, line))),
+        _ => None,
+    };
+    Ok(FaceVertex { position: position, tex_coord: tex_coord, normal: normal })
+}
+
+#[derive(Copy, Clone)]
+struct FaceVertex {
+    position: [f32; 3],
+    tex_coord: Option<[f32; 2]>,
+    normal: Option<[f32; 3]>,
+}
+
+fn push_triangle(vertices: &mut Vec<Vertex>, face: [FaceVertex; 3]) {
+    let p = |i: usize| Vec3::new(face[i].position[0], face[i].position[1], face[i].position[2]);
+    let flat = cross(&(p(1) - p(0)), &(p(2) - p(0)));
+    let flat = if flat.sqnorm() > 0. { flat.normalize() } else { flat };
+    for v in face.iter() {
+        vertices.push(Vertex::with_normal(v.position, v.tex_coord.unwrap_or([0., 0.]),
+                                          v.normal.unwrap_or([flat.x, flat.y, flat.z])));
+    }
+    let start = vertices.len() - 3;
+    Vertex::compute_tangents(&mut vertices[start..]);
+}
+
+fn lookup<T: Copy>(index: &str, elements: &[T], line: usize) -> Result<T, Error> {
+    let i: i64 = try!(index.parse().map_err(|_| obj_error(line, "invalid index")));
+    let i = if i < 0 { elements.len() as i64 + i } else { i - 1 };
+    if i < 0 || i >= elements.len() as i64 {
+        return Err(obj_error(line, "index out of range"));
+    }
+    Ok(elements[i as usize])
+}
+
+// Reads `count` floats, ignoring any extra ones like the w coordinate
+fn parse_floats(words: &mut SplitWhitespace, count: usize,
+                line: usize) -> Result<Vec<f32>, Error> {
+    let mut floats = Vec::with_capacity(count);
+    for _ in 0..count {
+        match words.next().map(|w| w.parse()) {
+            Some(Ok(f)) => floats.push(f),
+            _ => return Err(obj_error(line, "expected a number")),
+        }
+    }
+    Ok(floats)
+}
+
+fn obj_error(line: usize, msg: &str) -> Error {
+    Error::Obj(format!("line {}: {}", line + 1, msg))
+}
+
+/// The parts of an MTL file the engine's shaders can use
+#[derive(Clone, Debug)]
+pub struct MtlMaterial {
+    pub diffuse: [f32; 3],
+    pub specular: [f32; 3],
+    pub shininess: f32,
+    /// Diffuse texture, relative to the MTL file
+    pub diffuse_map: Option<String>,
+    /// Tangent space normal map, relative to the MTL file
+    pub normal_map: Option<String>,
+    /// Specular strength texture, relative to the MTL file
+    pub specular_map: Option<String>,
+}
+
+/// Parses an MTL file into its materials by name
+pub fn parse_mtl<R: BufRead>(input: R) -> Result<HashMap<String, MtlMaterial>, Error> {
+    let mut materials = HashMap::new();
+    let mut current: Option<(String, MtlMaterial)> = None;
+    for (n, line) in input.lines().enumerate() {
+        let line = try!(line);
+        let mut words = line.split_whitespace();
+        let keyword = words.next();
+        if keyword == Some("newmtl") {
+            if let Some((name, material)) = current.take() {
+                materials.insert(name, material);
+            }
+            let name = line.trim()["newmtl".len()..].trim().to_owned();
+            current = Some((name, MtlMaterial {
+                diffuse: [1., 1., 1.],
+                specular: [0., 0., 0.],
+                shininess: 32.,
+                diffuse_map: None,
+                normal_map: None,
+                specular_map: None,
+            }));
+            continue;
+        }
+
+        let material = match current {
+            Some((_, ref mut material)) => material,
+            None => continue,
+        };
+        match keyword {
+            Some("Kd") => {
+                let c = try!(parse_floats(&mut words, 3, n));
+                material.diffuse = [c[0], c[1], c[2]];
+            }
+            Some("Ks") => {
+                let c = try!(parse_floats(&mut words, 3, n));
+                material.specular = [c[0], c[1], c[2]];
+            }
+            Some("Ns") => material.shininess = try!(parse_floats(&mut words, 1, n))[0],
+            Some("map_Kd") => material.diffuse_map = words.last().map(|s| s.to_owned()),
+            Some("map_Bump") | Some("map_bump") | Some("bump") | Some("norm") => {
+                material.normal_map = words.last().map(|s| s.to_owned())
+            }
+            Some("map_Ks") => material.specular_map = words.last().map(|s| s.to_owned()),
+            _ => (),
+        }
+    }
+    if let Some((name, material)) = current {
+        materials.insert(name, material);
+    }
+    Ok(materials)
+}
+
+/// An OBJ file drawn with the materials from its MTL files, with one child per part so parts can
+/// be found by name. Parts without a material, or whose material can't be found, are white.
+pub struct Model<'a> {
+    pub parent: Object<'a>,
+    parts: Vec<Box<GameObject>>,
+}
+
+impl GameObject for Model<'static> {
+    fn parent(&self) -> &Object {
+        &self.parent
+    }
+
+    fn children(&self) -> Option<&[Box<GameObject>]> {
+        Some(&*self.parts)
+    }
+}
+
+impl<'a> Model<'a> {
+    /// Loads the OBJ file at `path` along with the MTL files and textures it references.
+    /// Textures go through the texture cache.
+    pub fn new<P: AsRef<Path>>(ctxt: &mut EngineContext, path: P) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let dir = path.parent().unwrap_or(Path::new(""));
+        let data = try!(ObjData::load(path));
+
+        let mut materials = HashMap::new();
+        for lib in data.material_libs.iter() {
+            let lib_path = dir.join(lib);
+            let lib_dir = lib_path.parent().unwrap_or(Path::new("")).to_path_buf();
+            let lib = try!(parse_mtl(BufReader::new(try!(File::open(&lib_path)))));
+            for (name, mtl) in lib {
+                let material = try!(Self::convert_material(ctxt, &lib_dir, &mtl));
+                materials.insert(name, material);
+            }
+        }
+
+        let mut parts = Vec::new();
+        for part in data.parts {
+            let material = part.material.as_ref()
+                .and_then(|name| materials.get(name))
+                .cloned()
+                .unwrap_or_else(|| Material::color([1., 1., 1.]));
+            let params = DrawParameters {
+                depth_test: DepthTest::IfLess,
+                depth_write: true,
+                .. Default::default()
+            };
+            // Parts are never empty
+            let bounds = Aabb::from_vertices(&part.vertices).unwrap();
+            let vb = VertexBuffer::new(ctxt.display(), part.vertices).into_vertex_buffer_any();
+            let mut parent = ObjectBuilder::new()
+                .vertex_buffer(vb, NoIndices(PrimitiveType::TrianglesList))
+                .draw_params(params)
+                .bounds(bounds)
+                .material(material)
+                .build();
+            parent.name = Some(part.name);
+            parts.push(Box::new(ModelPart { parent: parent }) as Box<GameObject>);
+        }
+
+        Ok(Model { parent: ObjectBuilder::new().build(), parts: parts })
+    }
+
+    pub fn parts_mut(&mut self) -> &mut [Box<GameObject>] {
+        &mut self.parts
+    }
+
+    fn convert_material(ctxt: &mut EngineContext, dir: &Path,
+                        mtl: &MtlMaterial) -> Result<Material, Error> {
+        let material = match mtl.diffuse_map {
+            Some(ref map) => {
+                let texture = try!(ctxt.texture_cache.get_texture(&ctxt.display, dir.join(map)));
+                Material::texture(texture)
+            }
+            None => Material::color(mtl.diffuse),
+        };
+        let mut material = material.frag_shader(FragmentShaderType::Phong);
+        material.shininess = mtl.shininess;
+        material.specular = (mtl.specular[0] + mtl.specular[1] + mtl.specular[2]) / 3.;
+        if let Some(ref map) = mtl.specular_map {
+            let texture = try!(ctxt.texture_cache.get_texture(&ctxt.display, dir.join(map)));
+            material = material.specular_map(texture);
+        }
+        if let Some(ref map) = mtl.normal_map {
+            let texture = try!(ctxt.texture_cache.get_texture(&ctxt.display, dir.join(map)));
+            material = material.normal_map(texture);
+        }
+        Ok(material)
+    }
+}
+
+struct ModelPart<'a> {
+    parent: Object<'a>,
+}
+
+impl GameObject for ModelPart<'static> {
+    fn parent(&self) -> &Object {
+        &self.parent
+    }
+}
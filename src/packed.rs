@@ -0,0 +1,80 @@
+use std::mem;
+
+use draw::Vertex;
+
+/// A `Vertex` in 20 bytes instead of 44: half float positions and texture coordinates, and
+/// normals and tangents packed into 10 bits per component. Drawn with the `Packed` vertex shader,
+/// which unpacks them again. Positions keep about three significant digits, which is plenty for
+/// meshes modelled around the origin.
+#[derive(Copy, Clone, Debug)]
+pub struct PackedVertex {
+    /// x, y and z, with the fourth half unused so attributes stay 4 byte aligned
+    pub position: [u16; 4],
+    pub tex_coord: [u16; 2],
+    pub normal: u32,
+    pub tangent: u32,
+}
+
+implement_vertex!(PackedVertex, position, tex_coord, normal, tangent);
+
+/// How `MeshCache` stores the meshes it loads
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct MeshOptions {
+    /// Store vertices as `PackedVertex`, roughly halving the memory and bandwidth they take
+    pub packed: bool,
+}
+
+impl Default for MeshOptions {
+    fn default() -> Self {
+        MeshOptions { packed: false }
+    }
+}
+
+impl PackedVertex {
+    pub fn new(v: &Vertex) -> Self {
+        let p = v.position;
+        PackedVertex {
+            position: [to_half(p[0]), to_half(p[1]), to_half(p[2]), 0],
+            tex_coord: [to_half(v.tex_coord[0]), to_half(v.tex_coord[1])],
+            normal: pack_unit(v.normal),
+            tangent: pack_unit(v.tangent),
+        }
+    }
+}
+
+pub fn pack_vertices(vertices: &[Vertex]) -> Vec<PackedVertex> {
+    vertices.iter().map(PackedVertex::new).collect()
+}
+
+/// The IEEE half float closest to `x`. Values too large for a half become infinity.
+pub fn to_half(x: f32) -> u16 {
+    let bits: u32 = unsafe { mem::transmute(x) };
+    let sign = (bits >> 16) as u16 & 0x8000;
+    let exponent = ((bits >> 23) & 0xff) as i32 - 127 + 15;
+    let mantissa = bits & 0x7f_ffff;
+
+    if exponent <= 0 {
+        // Too small for a normal half, so store it without the implicit leading one
+        if exponent < -10 {
+            return sign;
+        }
+        let m = (mantissa | 0x80_0000) >> (14 - exponent) as u32;
+        return sign | m as u16;
+    }
+    if exponent >= 31 {
+        return sign | 0x7c00;
+    }
+    // Rounding up can carry into the exponent, which is still the right result
+    let half = ((exponent as u32) << 10 | mantissa >> 13) + ((mantissa >> 12) & 1);
+    sign | if half >= 0x7c00 { 0x7c00 } else { half as u16 }
+}
+
+/// Packs the components of a unit vector, each between -1 and 1, into the low 30 bits as signed
+/// 10 bit integers, x first
+pub fn pack_unit(v: [f32; 3]) -> u32 {
+    let pack = |x: f32| {
+        let i = (x.max(-1.).min(1.) * 511.).round() as i32;
+        (i as u32) & 0x3ff
+    };
+    pack(v[0]) | pack(v[1]) << 10 | pack(v[2]) << 20
+}
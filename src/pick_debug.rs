@@ -0,0 +1,157 @@
+use {get_display_dim, EngineContext, Scene};
+use bounds::{Aabb, Ray};
+use draw::{GameObject, Object, ObjectBuilder, Vertex};
+use input::Key;
+use material::Material;
+use scene::PickHit;
+use shader::VertexShaderType;
+
+use glium::{DepthTest, Display, DrawParameters, VertexBuffer};
+use glium::glutin::MouseButton;
+use glium::index::{NoIndices, PrimitiveType};
+
+use nalgebra::Vec3;
+
+/// Name of the object `PickDebug` adds to the scene
+pub const PICK_DEBUG_NAME: &'static str = "pick debug";
+
+/// Shows what clicking picks, for debugging picking and anything built on it. While it's on, every
+/// left click logs the result of `Scene::pick_hit` for the ray through the cursor and draws the
+/// ray, the hit point and normal, and the bounding box that was hit. Move the camera afterwards to
+/// see the ray, which starts at the camera. Call `update` once per update step.
+pub struct PickDebug {
+    /// Whether clicks are being picked and shown
+    pub enabled: bool,
+    /// Key toggling the visualization, `None` to only change `enabled` from code
+    pub toggle_key: Option<Key>,
+    /// How far the ray is drawn when it doesn't hit anything
+    pub ray_length: f32,
+    /// Length of the normal drawn at the hit point
+    pub normal_length: f32,
+    pub color: [f32; 3],
+}
+
+impl PickDebug {
+    pub fn new() -> Self {
+        PickDebug {
+            enabled: false,
+            toggle_key: Some(Key::F3),
+            ray_length: 100.,
+            normal_length: 0.5,
+            color: [1., 1., 0.],
+        }
+    }
+
+    /// Toggles the visualization when `toggle_key` is pressed, and picks and shows the ray through
+    /// the cursor on every left click while it's on
+    pub fn update(&mut self, ctxt: &EngineContext, scene: &mut Scene) {
+        if self.toggle_key.map_or(false, |k| ctxt.input().was_key_pressed(k)) {
+            self.enabled = !self.enabled;
+            info!("Pick debugging {}", if self.enabled { "on" } else { "off" });
+            if !self.enabled {
+                scene.remove(PICK_DEBUG_NAME);
+            }
+        }
+        if !self.enabled || !ctxt.input().was_button_pressed(MouseButton::Left) {
+            return;
+        }
+        let (x, y) = match ctxt.input().mouse_position() {
+            Some(position) => position,
+            None => return,
+        };
+
+        let dim = get_display_dim(ctxt.display());
+        let ray = scene.camera().screen_to_ray(x as f32, y as f32, dim);
+        let lines = {
+            let hit = scene.pick_hit(&ray);
+            match hit {
+                Some(ref hit) => {
+                    info!("Pick at ({}, {}) hit {} at {:?}, {} units away, normal {:?}",
+                          x, y, hit.name, hit.point, hit.distance, hit.normal);
+                }
+                None => info!("Pick at ({}, {}) hit nothing", x, y),
+            }
+            self.lines(&ray, hit.as_ref())
+        };
+
+        scene.remove(PICK_DEBUG_NAME);
+        scene.add(PickLines::new(ctxt.display(), lines, self.color));
+    }
+
+    // End points of the lines showing the ray and what it hit
+    fn lines(&self, ray: &Ray, hit: Option<&PickHit>) -> Vec<Vec3<f32>> {
+        let hit = match hit {
+            Some(hit) => hit,
+            None => return vec![ray.origin, ray.at(self.ray_length)],
+        };
+        let mut lines = vec![ray.origin, hit.point,
+                             hit.point, hit.point + hit.normal * self.normal_length];
+
+        // A small cross marking the hit point
+        let size = self.normal_length / 5.;
+        for axis in [Vec3::new(size, 0., 0.), Vec3::new(0., size, 0.),
+                     Vec3::new(0., 0., size)].iter() {
+            lines.push(hit.point - *axis);
+            lines.push(hit.point + *axis);
+        }
+
+        lines.extend(box_edges(&hit.bounds));
+        lines
+    }
+}
+
+// Pairs of corners of the 12 edges of `bounds`
+fn box_edges(bounds: &Aabb) -> Vec<Vec3<f32>> {
+    let (a, b) = (bounds.min, bounds.max);
+    let corner = |i: usize| {
+        Vec3::new(if i & 1 == 0 { a.x } else { b.x },
+                  if i & 2 == 0 { a.y } else { b.y },
+                  if i & 4 == 0 { a.z } else { b.z })
+    };
+    let mut edges = Vec::new();
+    for i in 0..8 {
+        // Connect each corner to the corners one axis further along
+        for bit in [1, 2, 4].iter() {
+            if i & bit == 0 {
+                edges.push(corner(i));
+                edges.push(corner(i | bit));
+            }
+        }
+    }
+    edges
+}
+
+// World space lines, drawn without depth testing so the ray isn't hidden inside what it hit
+struct PickLines<'a> {
+    parent: Object<'a>,
+}
+
+impl GameObject for PickLines<'static> {
+    fn parent(&self) -> &Object {
+        &self.parent
+    }
+}
+
+impl<'a> PickLines<'a> {
+    fn new(display: &Display, lines: Vec<Vec3<f32>>, color: [f32; 3]) -> Self {
+        // Undo the vertex shader's scaling so the lines are where the world bounds are
+        let scale = VertexShaderType::Perspective.world_scale().unwrap();
+        let shape: Vec<_> = lines.iter()
+            .map(|p| Vertex::new(p.x / scale, p.y / scale, p.z / scale))
+            .collect();
+
+        let params = DrawParameters {
+            depth_test: DepthTest::Overwrite,
+            .. Default::default()
+        };
+        let vb = VertexBuffer::new(display, shape).into_vertex_buffer_any();
+        let mut parent = ObjectBuilder::new()
+            .vertex_buffer(vb, NoIndices(PrimitiveType::LinesList))
+            .draw_params(params)
+            .material(Material::color(color))
+            .build();
+        parent.name = Some(PICK_DEBUG_NAME.to_owned());
+
+        PickLines { parent: parent }
+    }
+}
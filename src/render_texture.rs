@@ -0,0 +1,37 @@
+use std::rc::Rc;
+
+use glium::Display;
+use glium::framebuffer::{DepthRenderBuffer, SimpleFrameBuffer};
+use glium::texture::{DepthFormat, Texture2d};
+
+/// A texture that scenes can be drawn into, e.g. for a minimap or a security camera screen. Draw
+/// into `framebuffer` with `Scene::draw_with`, then show `texture` on a quad with
+/// `Material::texture`.
+pub struct RenderTexture {
+    texture: Rc<Texture2d>,
+    depth: DepthRenderBuffer,
+}
+
+impl RenderTexture {
+    pub fn new(display: &Display, width: u32, height: u32) -> Self {
+        RenderTexture {
+            texture: Rc::new(Texture2d::empty(display, width, height)),
+            depth: DepthRenderBuffer::new(display, DepthFormat::I24, width, height),
+        }
+    }
+
+    /// The texture holding whatever was drawn last
+    pub fn texture(&self) -> Rc<Texture2d> {
+        self.texture.clone()
+    }
+
+    /// Width and height in pixels, e.g. for the aspect ratio of the camera drawing into it
+    pub fn dimensions(&self) -> (u32, u32) {
+        (self.texture.get_width(), self.texture.get_height().unwrap())
+    }
+
+    /// A surface drawing into the texture, with its own depth buffer
+    pub fn framebuffer<'a>(&'a self, display: &Display) -> SimpleFrameBuffer<'a> {
+        SimpleFrameBuffer::with_depth_buffer(display, &*self.texture, &self.depth)
+    }
+}
@@ -0,0 +1,627 @@
+use std::cell::Cell;
+use std::marker::PhantomData;
+use std::mem;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+
+use {EngineContext, Error};
+use bounds::{Aabb, Frustum, Ray};
+use camera::Camera;
+use draw::{FrameUniforms, GameObject, UpdateContext};
+use ecs::{MeshRenderer, World};
+use light::Light;
+use scene_file::{CameraDesc, SceneFile, SceneObject};
+
+use glium::Surface;
+
+use nalgebra::{self, Mat4, Vec3};
+
+pub struct Scene<'a> {
+    // TODO: Do we want this to be GameObject + 'a?
+    named_objects: HashMap<String, Box<GameObject + 'a>>,
+    // Kept in the order they were added, along with the id of their handle
+    unamed_objects: Vec<(usize, Box<GameObject + 'a>)>,
+    next_id: usize,
+    camera: Camera,
+    // Extra cameras for views drawn with `draw_with`, e.g. a minimap
+    cameras: HashMap<String, Camera>,
+    lights: Vec<Light>,
+    ambient: [f32; 3],
+    // Objects skipped during the last draw because they were outside the camera's view
+    culled: Cell<usize>,
+    // Entities updated and drawn after the objects
+    world: World,
+}
+
+impl<'a> Scene<'a> {
+    pub fn new(camera: Camera) -> Self {
+        Scene {
+            camera: camera,
+            cameras: HashMap::new(),
+            named_objects: HashMap::new(),
+            unamed_objects: Vec::new(),
+            next_id: 0,
+            lights: Vec::new(),
+            ambient: [0.1, 0.1, 0.1],
+            culled: Cell::new(0),
+            world: World::new(),
+        }
+    }
+
+    /// Loads a scene file, see `SceneFile` for the format. Meshes and textures are relative to
+    /// the resource folder.
+    pub fn load<P: AsRef<Path>>(ctxt: &mut EngineContext, path: P) -> Result<Self, Error> {
+        try!(SceneFile::load(path)).instantiate(ctxt)
+    }
+
+    /// Writes the main camera, the lights and the objects loaded from a scene file to `path`.
+    /// Other objects can't be described by a scene file and are left out.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        let mut named: Vec<_> = self.named_objects.iter().collect();
+        named.sort_by(|a, b| a.0.cmp(b.0));
+        let objects = named.into_iter().map(|(_, o)| o)
+            .chain(self.unamed_objects.iter().map(|o| &o.1))
+            .filter_map(|o| (**o).as_any().downcast_ref::<SceneObject>())
+            .map(|o| o.desc())
+            .collect();
+        let file = SceneFile {
+            ambient: self.ambient,
+            camera: CameraDesc::from_camera(&self.camera),
+            lights: self.lights.clone(),
+            objects: objects,
+        };
+        try!(try!(File::create(path)).write_all(file.write().as_bytes()));
+        Ok(())
+    }
+
+    /// Adds a light used by objects drawn with the `Lambert`, `Phong` or mapped fragment
+    /// shaders
+    pub fn add_light(&mut self, light: Light) {
+        self.lights.push(light);
+    }
+
+    pub fn lights(&self) -> &[Light] {
+        &self.lights
+    }
+
+    pub fn lights_mut(&mut self) -> &mut Vec<Light> {
+        &mut self.lights
+    }
+
+    /// Sets the light color applied to lit objects regardless of the scene's lights
+    pub fn set_ambient(&mut self, ambient: [f32; 3]) {
+        self.ambient = ambient;
+    }
+
+    pub fn camera(&self) -> &Camera {
+        &self.camera
+    }
+
+    pub fn camera_mut(&mut self) -> &mut Camera {
+        &mut self.camera
+    }
+
+    /// Adds a camera that the scene can be drawn from with `draw_with`, replacing any camera with
+    /// the same name. The main camera is still the one used by `draw`.
+    pub fn add_camera(&mut self, name: &str, camera: Camera) {
+        self.cameras.insert(name.to_owned(), camera);
+    }
+
+    pub fn named_camera(&self, name: &str) -> Option<&Camera> {
+        self.cameras.get(name)
+    }
+
+    pub fn named_camera_mut(&mut self, name: &str) -> Option<&mut Camera> {
+        self.cameras.get_mut(name)
+    }
+
+    pub fn remove_camera(&mut self, name: &str) -> Option<Camera> {
+        self.cameras.remove(name)
+    }
+
+    pub fn update(&mut self, ctxt: &UpdateContext) {
+        for obj in self.named_objects.values().chain(self.unamed_objects.iter().map(|o| &o.1)) {
+            save_transforms(&**obj);
+        }
+        for obj in self.named_objects.iter_mut().map(|(_, v)| v)
+            .chain(self.unamed_objects.iter_mut().map(|o| &mut o.1)) {
+            obj.update(ctxt);
+        }
+
+        // Sweep objects that died during the update
+        let dead: Vec<_> = self.named_objects.iter()
+            .filter(|&(_, o)| o.is_dead())
+            .map(|(k, _)| k.clone())
+            .collect();
+        for name in dead.iter() {
+            self.named_objects.remove(name);
+        }
+        self.unamed_objects.retain(|o| !o.1.is_dead());
+
+        self.world.update(ctxt);
+    }
+
+    /// The entities of the scene, drawn with the scene's camera and lights
+    pub fn world(&self) -> &World {
+        &self.world
+    }
+
+    pub fn world_mut(&mut self) -> &mut World {
+        &mut self.world
+    }
+
+    /// Draws the scene `alpha` of the way between the last two updates, where 0 is the state
+    /// before the last update and 1 the current state
+    pub fn draw(&self, ctxt: &mut EngineContext, alpha: f32) {
+        let mut target = ctxt.display.draw();
+        target.clear_color_and_depth((0., 0., 0., 1.), 1.);
+        self.draw_into(&mut target, ctxt, alpha);
+        target.finish().unwrap();
+    }
+
+    pub fn draw_into<S: Surface>(&self, target: &mut S, ctxt: &mut EngineContext, alpha: f32) {
+        self.draw_from(&self.camera, target, ctxt, alpha);
+    }
+
+    /// Clears `target` and draws the scene as seen by the camera called `camera_name`, e.g. into
+    /// a `RenderTexture` for a minimap. Returns false without drawing if there is no such camera.
+    /// Objects showing the texture being drawn into must not be visible to that camera.
+    pub fn draw_with<S: Surface>(&self, ctxt: &mut EngineContext, camera_name: &str,
+                                 target: &mut S, alpha: f32) -> bool {
+        match self.cameras.get(camera_name) {
+            Some(camera) => {
+                target.clear_color_and_depth((0., 0., 0., 1.), 1.);
+                self.draw_from(camera, target, ctxt, alpha);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn draw_from<S: Surface>(&self, camera: &Camera, target: &mut S, ctxt: &mut EngineContext,
+                             alpha: f32) {
+        self.culled.set(0);
+        let frustum = camera.frustum();
+        let frame = ctxt.frame_uniforms(camera, &self.lights, self.ambient,
+                                        target.get_dimensions());
+        self.draw_objs(target, ctxt, &frame, &frustum, &nalgebra::new_identity(4), alpha,
+                       self.named_objects.values()
+                           .chain(self.unamed_objects.iter().map(|o| &o.1)));
+        self.world.draw(target, ctxt, &frame, alpha);
+    }
+
+    /// Number of objects added to the scene, not counting their children or the world's entities
+    pub fn len(&self) -> usize {
+        self.named_objects.len() + self.unamed_objects.len()
+    }
+
+    /// Number of objects that weren't drawn during the last draw, from any camera, because their
+    /// bounding box was outside of the camera's view
+    pub fn culled_count(&self) -> usize {
+        self.culled.get()
+    }
+
+    // Draws `objs` and their children, composing each object's interpolated transform with the
+    // world transform of its parent. Objects outside of `frustum` or on layers the camera doesn't
+    // draw are skipped, but their children are still considered since they can be anywhere and on
+    // any layer. Hidden objects are skipped along with their children.
+    fn draw_objs<I: Iterator<Item=&'a Box<GameObject>>, S: Surface>(&self, target: &mut S,
+                                                                    ctxt: &mut EngineContext,
+                                                                    frame: &FrameUniforms,
+                                                                    frustum: &Frustum,
+                                                                    parent_transform: &Mat4<f32>,
+                                                                    alpha: f32, objs: I) {
+        let camera = frame.camera;
+        for obj in objs {
+            let parent = obj.parent();
+            if !parent.visible {
+                continue;
+            }
+            if parent.vertex_buffer.is_some() && camera.sees_layers(parent.layers) {
+                let transform = obj.draw_transform(camera, parent_transform, alpha);
+                let visible = parent.transformed_bounds(&transform)
+                    .map_or(true, |b| frustum.intersects(&b));
+                if !visible {
+                    self.culled.set(self.culled.get() + 1);
+                } else if let Err(e) = ctxt.draw_in_frame(target, frame, parent_transform, alpha,
+                                                          obj) {
+                    // Keep drawing the rest of the scene, e.g. while a shader is being fixed
+                    error!("Couldn't draw {}: {}", obj.name().unwrap_or("object"), e);
+                }
+            }
+            if let Some(children) = obj.children() {
+                let transform = *parent_transform * parent.interpolated_transform(alpha);
+                self.draw_objs(target, ctxt, frame, frustum, &transform, alpha, children.iter());
+            }
+        }
+    }
+
+    /// Adds an object, returning a handle to get it back with `get`. Named objects can also be
+    /// looked up with `get_object`.
+    pub fn add<G: GameObject + 'a>(&mut self, object: G) -> ObjectHandle<G> {
+        let key = match object.name() {
+            Some(name) => HandleKey::Named(name.to_owned()),
+            None => {
+                self.next_id += 1;
+                HandleKey::Unnamed(self.next_id)
+            }
+        };
+        match key {
+            HandleKey::Named(ref name) => {
+                assert!(self.named_objects.insert(name.clone(), Box::new(object)).is_none(),
+                        "Duplicate object name");
+            }
+            HandleKey::Unnamed(id) => self.unamed_objects.push((id, Box::new(object))),
+        }
+        ObjectHandle { key: key, object_type: PhantomData }
+    }
+
+    /// Removes the object called `name`, returning whether there was one
+    pub fn remove(&mut self, name: &str) -> bool {
+        self.named_objects.remove(name).is_some()
+    }
+
+    /// Removes the unnamed object with the id from `ObjectHandle::id`, returning whether there was
+    /// one
+    pub fn remove_by_id(&mut self, id: usize) -> bool {
+        let len = self.unamed_objects.len();
+        self.unamed_objects.retain(|o| o.0 != id);
+        self.unamed_objects.len() != len
+    }
+
+    /// Removes the object `handle` was returned for, returning whether it was still in the scene
+    pub fn remove_handle<G>(&mut self, handle: &ObjectHandle<G>) -> bool {
+        match handle.key {
+            HandleKey::Named(ref name) => self.remove(name),
+            HandleKey::Unnamed(id) => self.remove_by_id(id),
+        }
+    }
+
+    /// The object `handle` was returned for, or `None` if it's no longer in the scene
+    pub fn get<G: GameObject + 'static>(&mut self, handle: &ObjectHandle<G>) -> Option<&mut G> {
+        let obj = match handle.key {
+            HandleKey::Named(ref name) => self.named_objects.get_mut(name),
+            HandleKey::Unnamed(id) => {
+                self.unamed_objects.iter_mut().find(|o| o.0 == id).map(|o| &mut o.1)
+            }
+        };
+        obj.and_then(|o| (**o).as_any_mut().downcast_mut())
+    }
+
+    /// Drops every object in the scene along with its GPU buffers, then frees any cached textures,
+    /// meshes, glyphs and fonts that nothing else holds on to. Resources still shared with objects
+    /// outside of this scene are kept.
+    pub fn unload(&mut self, ctxt: &mut EngineContext) {
+        self.named_objects.clear();
+        self.unamed_objects.clear();
+        self.world = World::new();
+        self.lights.clear();
+
+        let (textures, glyphs) = ctxt.texture_cache.purge_unused();
+        let meshes = ctxt.mesh_cache.purge_unused();
+        let fonts = ctxt.font_cache.purge_unused();
+        debug!("Scene unloaded, freed {} textures, {} meshes, {} glyphs and {} fonts ({} textures, \
+                {} meshes, {} glyphs and {} fonts left)",
+               textures, meshes, glyphs, fonts, ctxt.texture_cache.cache.len(),
+               ctxt.mesh_cache.len(), ctxt.texture_cache.glyphs().len(), ctxt.font_cache.len());
+    }
+
+    /// Like `unload`, but hands the objects and entity renderers to the context's garbage to be
+    /// destroyed a few per frame instead of all at once, which avoids a hitch when unloading a
+    /// large scene. Cached resources they used are freed once they're all gone.
+    pub fn unload_deferred(&mut self, ctxt: &mut EngineContext) where 'a: 'static {
+        for (_, obj) in self.named_objects.drain() {
+            ctxt.garbage.defer(obj);
+        }
+        for (_, obj) in self.unamed_objects.drain(..) {
+            ctxt.garbage.defer(obj);
+        }
+        let mut world = mem::replace(&mut self.world, World::new());
+        for entity in world.entities_with::<MeshRenderer>() {
+            if let Some(renderer) = world.remove::<MeshRenderer>(entity) {
+                ctxt.garbage.defer(renderer);
+            }
+        }
+        ctxt.garbage.defer(world);
+        self.lights.clear();
+
+        ctxt.purge_after_garbage = true;
+        debug!("Scene unloaded, {} resources waiting to be destroyed", ctxt.garbage.len());
+    }
+
+    /// The closest named object hit by `ray` and the distance to it. Rays are tested against the
+    /// world bounding boxes of objects and their children, and a hit on a child counts as a hit
+    /// on the named object it belongs to. Unnamed objects and objects without bounds are ignored.
+    pub fn pick(&self, ray: &Ray) -> Option<(&str, f32)> {
+        self.pick_hit(ray).map(|hit| (hit.name, hit.distance))
+    }
+
+    /// Like `pick`, but also returns where the ray hit and the bounding box it hit
+    pub fn pick_hit(&self, ray: &Ray) -> Option<PickHit> {
+        let identity = nalgebra::new_identity(4);
+        let mut closest: Option<(&str, f32, Aabb)> = None;
+        for (name, obj) in self.named_objects.iter() {
+            if let Some((distance, bounds)) = pick_obj(&**obj, &identity, ray) {
+                if closest.map_or(true, |(_, d, _)| distance < d) {
+                    closest = Some((&**name, distance, bounds));
+                }
+            }
+        }
+        closest.map(|(name, distance, bounds)| {
+            let point = ray.at(distance);
+            PickHit {
+                name: name,
+                distance: distance,
+                point: point,
+                normal: bounds.normal_at(point),
+                bounds: bounds,
+            }
+        })
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.named_objects.contains_key(name)
+    }
+
+    /// The object called `name`, whatever its type
+    pub fn find_object(&self, name: &str) -> Option<&Box<GameObject + 'a>> {
+        self.named_objects.get(name)
+    }
+
+    /// The object called `name`, or `None` if there is no such object or it isn't a `T`
+    pub fn get_object<T: GameObject + 'static>(&mut self, name: &str) -> Option<&mut T> {
+        self.named_objects.get_mut(name).and_then(|o| (**o).as_any_mut().downcast_mut())
+    }
+}
+
+/// Refers to an object added to a scene, remembering its type so it can be retrieved without
+/// naming the type again
+pub struct ObjectHandle<T> {
+    key: HandleKey,
+    object_type: PhantomData<T>,
+}
+
+impl<T> ObjectHandle<T> {
+    /// The id of an unnamed object, which stays the same until it's removed and is never reused
+    /// within a scene. Named objects are identified by their name instead and have no id.
+    pub fn id(&self) -> Option<usize> {
+        match self.key {
+            HandleKey::Named(_) => None,
+            HandleKey::Unnamed(id) => Some(id),
+        }
+    }
+
+    pub fn name(&self) -> Option<&str> {
+        match self.key {
+            HandleKey::Named(ref name) => Some(&**name),
+            HandleKey::Unnamed(_) => None,
+        }
+    }
+}
+
+// Not derived, since that would require `T: Clone`
+impl<T> Clone for ObjectHandle<T> {
+    fn clone(&self) -> Self {
+        ObjectHandle { key: self.key.clone(), object_type: PhantomData }
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum HandleKey {
+    Named(String),
+    Unnamed(usize),
+}
+
+/// Refers to an object in one of the scenes of a `SceneStack`, e.g. a door in the UI scene that
+/// a level scene opens. Unnamed objects are identified by the scene's name and their id, which
+/// isn't reused within the scene, so the reference stays valid for as long as the object exists.
+pub struct SceneRef<T> {
+    pub scene: String,
+    pub handle: ObjectHandle<T>,
+}
+
+impl<T> SceneRef<T> {
+    pub fn new(scene: &str, handle: ObjectHandle<T>) -> Self {
+        SceneRef { scene: scene.to_owned(), handle: handle }
+    }
+}
+
+// Not derived, since that would require `T: Clone`
+impl<T> Clone for SceneRef<T> {
+    fn clone(&self) -> Self {
+        SceneRef { scene: self.scene.clone(), handle: self.handle.clone() }
+    }
+}
+
+/// What `Scene::pick_hit` found along a ray
+#[derive(Copy, Clone, Debug)]
+pub struct PickHit<'s> {
+    /// The named object that was hit
+    pub name: &'s str,
+    /// Distance along the ray to the hit
+    pub distance: f32,
+    /// Where the ray entered the bounding box, in world space
+    pub point: Vec3<f32>,
+    /// Outward normal of the face of the bounding box that was hit
+    pub normal: Vec3<f32>,
+    /// World bounding box of the object, or of the child of it that was hit
+    pub bounds: Aabb,
+}
+
+/// Several scenes active at once, e.g. a persistent UI scene on top of a level scene that gets
+/// swapped out. Scenes are updated and drawn in the order they were added, each one on top of the
+/// ones before it. Run a stack with `Engine::run_stack`.
+///
+/// Objects in other scenes are found by name with `find_object` and `get_object`, or through a
+/// `SceneRef`, which also refers to unnamed objects.
+pub struct SceneStack<'a> {
+    scenes: Vec<SceneEntry<'a>>,
+}
+
+struct SceneEntry<'a> {
+    name: String,
+    scene: Scene<'a>,
+    update: bool,
+    draw: bool,
+}
+
+impl<'a> SceneStack<'a> {
+    pub fn new() -> Self {
+        SceneStack { scenes: Vec::new() }
+    }
+
+    /// Adds a scene on top of the existing ones
+    pub fn push(&mut self, name: &str, scene: Scene<'a>) {
+        assert!(self.position(name).is_none(), "Duplicate scene name");
+        self.scenes.push(SceneEntry { name: name.to_owned(), scene: scene, update: true,
+                                      draw: true });
+    }
+
+    /// Loads a scene file on top of the existing scenes without unloading any of them, e.g. the
+    /// next area of a level streamed in next to the current one. Fails if there already is a
+    /// scene called `name`.
+    pub fn load_scene_additive<P: AsRef<Path>>(&mut self, ctxt: &mut EngineContext, name: &str,
+                                               path: P) -> Result<(), Error> {
+        if self.position(name).is_some() {
+            return Err(Error::Scene(format!("there already is a scene called {}", name)));
+        }
+        let scene = try!(Scene::load(ctxt, path));
+        self.push(name, scene);
+        Ok(())
+    }
+
+    /// Swaps the scene called `name` for `scene`, unloading the old one. The new scene keeps the
+    /// old one's place in the stack.
+    pub fn replace(&mut self, ctxt: &mut EngineContext, name: &str, scene: Scene<'a>) {
+        match self.position(name) {
+            Some(i) => {
+                let old = mem::replace(&mut self.scenes[i].scene, scene);
+                Self::unload_scene(ctxt, old);
+            }
+            None => self.push(name, scene),
+        }
+    }
+
+    pub fn remove(&mut self, ctxt: &mut EngineContext, name: &str) -> bool {
+        match self.position(name) {
+            Some(i) => {
+                let entry = self.scenes.remove(i);
+                Self::unload_scene(ctxt, entry.scene);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Scene<'a>> {
+        self.scenes.iter().find(|e| e.name == name).map(|e| &e.scene)
+    }
+
+    /// Names of the scenes, bottom first
+    pub fn names(&self) -> Vec<&str> {
+        self.scenes.iter().map(|e| &*e.name).collect()
+    }
+
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut Scene<'a>> {
+        self.scenes.iter_mut().find(|e| e.name == name).map(|e| &mut e.scene)
+    }
+
+    pub fn set_update_enabled(&mut self, name: &str, enabled: bool) {
+        if let Some(e) = self.scenes.iter_mut().find(|e| e.name == name) {
+            e.update = enabled;
+        }
+    }
+
+    pub fn set_draw_enabled(&mut self, name: &str, enabled: bool) {
+        if let Some(e) = self.scenes.iter_mut().find(|e| e.name == name) {
+            e.draw = enabled;
+        }
+    }
+
+    /// Looks up a named object in any of the scenes, returning the name of the scene it is in
+    pub fn find_object(&self, name: &str) -> Option<(&str, &Box<GameObject + 'a>)> {
+        self.scenes.iter()
+            .filter_map(|e| e.scene.named_objects.get(name).map(|o| (&*e.name, o)))
+            .next()
+    }
+
+    /// The object called `name` in the first scene that has one, or `None` if there is no such
+    /// object or it isn't a `T`
+    pub fn get_object<T: GameObject + 'static>(&mut self, name: &str) -> Option<&mut T> {
+        self.scenes.iter_mut()
+            .filter_map(|e| e.scene.named_objects.get_mut(name))
+            .next()
+            .and_then(|o| (**o).as_any_mut().downcast_mut())
+    }
+
+    /// The object `r` refers to, or `None` if its scene or the object is gone
+    pub fn resolve<T: GameObject + 'static>(&mut self, r: &SceneRef<T>) -> Option<&mut T> {
+        self.get_mut(&r.scene).and_then(|scene| scene.get(&r.handle))
+    }
+
+    /// Sets the aspect ratio of every scene's main camera, e.g. after the window was resized
+    pub fn set_aspect_ratio(&mut self, aspect_ratio: f32) {
+        for e in self.scenes.iter_mut() {
+            e.scene.camera_mut().set_aspect_ratio(aspect_ratio);
+        }
+    }
+
+    pub fn update(&mut self, ctxt: &UpdateContext) {
+        for e in self.scenes.iter_mut().filter(|e| e.update) {
+            e.scene.update(ctxt);
+        }
+    }
+
+    /// Draws the scenes `alpha` of the way between the last two updates, see `Scene::draw`
+    pub fn draw(&self, ctxt: &mut EngineContext, alpha: f32) {
+        let mut target = ctxt.display.draw();
+        target.clear_color_and_depth((0., 0., 0., 1.), 1.);
+        for e in self.scenes.iter().filter(|e| e.draw) {
+            // Every scene is drawn over the previous ones regardless of depth
+            target.clear_depth(1.);
+            e.scene.draw_into(&mut target, ctxt, alpha);
+        }
+        target.finish().unwrap();
+    }
+
+    fn position(&self, name: &str) -> Option<usize> {
+        self.scenes.iter().position(|e| e.name == name)
+    }
+
+    fn unload_scene(ctxt: &mut EngineContext, mut scene: Scene<'a>) {
+        scene.unload(ctxt);
+    }
+}
+
+/// Lets every object and its children be drawn between its old and new transform after the update
+pub fn save_transforms(obj: &GameObject) {
+    obj.parent().save_transform();
+    if let Some(children) = obj.children() {
+        for child in children {
+            save_transforms(&**child);
+        }
+    }
+}
+
+// Distance to the closest of `obj` and its children along `ray`, and the world bounds of the one
+// that was hit
+fn pick_obj(obj: &GameObject, parent_transform: &Mat4<f32>, ray: &Ray) -> Option<(f32, Aabb)> {
+    if !obj.parent().visible {
+        return None;
+    }
+    let mut closest = obj.world_bounds(parent_transform)
+        .and_then(|b| b.intersect_ray(ray).map(|d| (d, b)));
+    if let Some(children) = obj.children() {
+        let transform = obj.world_transform(parent_transform);
+        for child in children {
+            if let Some((distance, bounds)) = pick_obj(&**child, &transform, ray) {
+                if closest.map_or(true, |(d, _)| distance < d) {
+                    closest = Some((distance, bounds));
+                }
+            }
+        }
+    }
+    closest
+}
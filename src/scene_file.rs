@@ -0,0 +1,547 @@
+use std::collections::HashSet;
+use std::fmt::Write;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use {get_display_dim, EngineContext, Error};
+use camera::{Camera, Projection};
+use draw::{GameObject, Object, ObjectBuilder};
+use light::{Light, LightKind};
+use material::{Material, UvTransform};
+use packed::MeshOptions;
+use scene::Scene;
+use shader::{FragmentShaderType, VertexShaderType};
+use transform::Transform;
+
+use glium::{DepthTest, DrawParameters};
+use glium::index::{NoIndices, PrimitiveType};
+
+use nalgebra::{BaseFloat, Quat, UnitQuat, Vec3};
+
+/// A level as text, so it can be edited without recompiling the game. Every `camera`, `light`
+/// or `object` line starts a section, and the lines after it set its properties:
+///
+/// ```text
+/// ambient 0.1 0.1 0.1
+///
+/// camera
+/// position 0 0 1
+/// rotation 1 0 0 0
+/// fov 1.5707964
+///
+/// light point
+/// position 0.5 -0.5 0.5
+/// range 2
+/// color 1 0.6 0.2
+///
+/// object crate
+/// mesh cube.obj
+/// texture cube.png
+/// frag_shader Phong
+/// uv_scroll 0 0.5
+/// position 0 0 0
+/// scale 1 1 1
+/// ```
+///
+/// Rotations are quaternions written `w i j k`. Lights are `directional`, `point` or `spot`,
+/// with `position`, `direction`, `range`, `angle`, `color` and `intensity` properties as they
+/// apply. Objects have an optional name, an OBJ `mesh` and a `texture` or `color`, both relative
+/// to the resource folder, `vert_shader` and `frag_shader` names, `uv_offset`, `uv_scale`,
+/// `uv_rotation`, `uv_scroll` and `uv_spin` for the texture, and a transform. Object names are
+/// the rest of the `object` line and must be unique. The camera can also be `orthographic` with
+/// the height of its view.
+#[derive(Clone, Debug)]
+pub struct SceneFile {
+    pub ambient: [f32; 3],
+    pub camera: CameraDesc,
+    pub lights: Vec<Light>,
+    pub objects: Vec<ObjectDesc>,
+}
+
+/// The main camera of a scene file
+#[derive(Copy, Clone, Debug)]
+pub struct CameraDesc {
+    pub position: Vec3<f32>,
+    pub rotation: UnitQuat<f32>,
+    pub fov: f32,
+    pub projection: Projection,
+}
+
+impl CameraDesc {
+    pub fn from_camera(camera: &Camera) -> Self {
+        CameraDesc {
+            position: camera.position(),
+            rotation: camera.orientation(),
+            fov: camera.fov(),
+            projection: camera.projection(),
+        }
+    }
+
+    pub fn to_camera(&self, aspect_ratio: f32) -> Camera {
+        let mut camera = Camera::new(self.position, aspect_ratio);
+        camera.set_orientation(self.rotation);
+        camera.set_fov(self.fov);
+        camera.set_projection(self.projection);
+        camera
+    }
+}
+
+/// A mesh object in a scene file
+#[derive(Clone, Debug)]
+pub struct ObjectDesc {
+    pub name: Option<String>,
+    /// OBJ file, relative to the resource folder
+    pub mesh: String,
+    /// Texture relative to the resource folder, used instead of `color` if set
+    pub texture: Option<String>,
+    pub color: [f32; 3],
+    pub vert_shader: VertexShaderType,
+    pub frag_shader: FragmentShaderType,
+    pub uv_transform: UvTransform,
+    pub transform: Transform,
+}
+
+enum Section {
+    None,
+    Camera,
+    Light,
+    Object,
+}
+
+impl SceneFile {
+    pub fn parse(input: &str) -> Result<Self, Error> {
+        let mut file = SceneFile {
+            ambient: [0.1, 0.1, 0.1],
+            camera: CameraDesc {
+                position: Vec3::new(0., 0., 1.),
+                rotation: UnitQuat::new(Vec3::new(0., 0., 0.)),
+                fov: BaseFloat::frac_pi_2(),
+                projection: Projection::Perspective,
+            },
+            lights: Vec::new(),
+            objects: Vec::new(),
+        };
+        let mut section = Section::None;
+        let mut names = HashSet::new();
+        for (n, line) in input.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut words = line.split_whitespace();
+            let keyword = words.next().unwrap();
+            let args: Vec<_> = words.collect();
+
+            match keyword {
+                "ambient" => file.ambient = try!(parse_color(&args, n)),
+                "camera" => section = Section::Camera,
+                "light" => {
+                    let kind = match args.first().map(|s| *s) {
+                        Some("directional") => {
+                            LightKind::Directional { direction: Vec3::new(0., 0., -1.) }
+                        }
+                        Some("point") => {
+                            LightKind::Point { position: Vec3::new(0., 0., 0.), range: 1. }
+                        }
+                        Some("spot") => LightKind::Spot {
+                            position: Vec3::new(0., 0., 0.),
+                            direction: Vec3::new(0., 0., -1.),
+                            range: 1.,
+                            angle: 0.5,
+                        },
+                        _ => return Err(scene_error(n, "expected directional, point or spot")),
+                    };
+                    file.lights.push(Light { kind: kind, color: [1., 1., 1.], intensity: 1. });
+                    section = Section::Light;
+                }
+                "object" => {
+                    // The rest of the line, so names can contain spaces like paths can
+                    let name = if args.is_empty() { None } else { Some(args.join(" ")) };
+                    if let Some(ref name) = name {
+                        if !names.insert(name.clone()) {
+                            return Err(scene_error(n, &format!("duplicate object name {}",
+                                                               name)));
+                        }
+                    }
+                    file.objects.push(ObjectDesc {
+                        name: name,
+                        mesh: String::new(),
+                        texture: None,
+                        color: [1., 1., 1.],
+                        vert_shader: VertexShaderType::Perspective,
+                        frag_shader: FragmentShaderType::Unlit,
+                        uv_transform: UvTransform::identity(),
+                        transform: Transform::identity(),
+                    });
+                    section = Section::Object;
+                }
+                _ => match section {
+                    Section::Camera => try!(file.parse_camera(keyword, &args, n)),
+                    Section::Light => try!(file.parse_light(keyword, &args, n)),
+                    Section::Object => try!(file.parse_object(keyword, &args, n)),
+                    Section::None => return Err(scene_error(n, "unknown command")),
+                },
+            }
+        }
+
+        for object in file.objects.iter() {
+            if object.mesh.is_empty() {
+                let name = object.name.as_ref().map_or("unnamed", |s| &**s);
+                return Err(Error::Scene(format!("object {} has no mesh", name)));
+            }
+        }
+        Ok(file)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let mut input = String::new();
+        try!(try!(File::open(path)).read_to_string(&mut input));
+        Self::parse(&input)
+    }
+
+    fn parse_camera(&mut self, keyword: &str, args: &[&str], n: usize) -> Result<(), Error> {
+        let camera = &mut self.camera;
+        match keyword {
+            "position" => camera.position = try!(parse_vec3(args, n)),
+            "rotation" => camera.rotation = try!(parse_rotation(args, n)),
+            "fov" => camera.fov = try!(parse_floats(args, 1, n))[0],
+            "orthographic" => {
+                camera.projection = Projection::Orthographic {
+                    height: try!(parse_floats(args, 1, n))[0],
+                }
+            }
+            _ => return Err(scene_error(n, "unknown camera property")),
+        }
+        Ok(())
+    }
+
+    fn parse_light(&mut self, keyword: &str, args: &[&str], n: usize) -> Result<(), Error> {
+        let light = self.lights.last_mut().unwrap();
+        match (keyword, &mut light.kind) {
+            ("color", _) => light.color = try!(parse_color(args, n)),
+            ("intensity", _) => light.intensity = try!(parse_floats(args, 1, n))[0],
+            ("position", &mut LightKind::Point { ref mut position, .. }) |
+            ("position", &mut LightKind::Spot { ref mut position, .. }) => {
+                *position = try!(parse_vec3(args, n))
+            }
+            ("direction", &mut LightKind::Directional { ref mut direction }) |
+            ("direction", &mut LightKind::Spot { ref mut direction, .. }) => {
+                *direction = try!(parse_vec3(args, n))
+            }
+            ("range", &mut LightKind::Point { ref mut range, .. }) |
+            ("range", &mut LightKind::Spot { ref mut range, .. }) => {
+                *range = try!(parse_floats(args, 1, n))[0]
+            }
+            ("angle", &mut LightKind::Spot { ref mut angle, .. }) => {
+                *angle = try!(parse_floats(args, 1, n))[0]
+            }
+            _ => return Err(scene_error(n, "unknown property for this kind of light")),
+        }
+        Ok(())
+    }
+
+    fn parse_object(&mut self, keyword: &str, args: &[&str], n: usize) -> Result<(), Error> {
+        let object = self.objects.last_mut().unwrap();
+        match keyword {
+            "mesh" => object.mesh = try!(parse_path(args, n)),
+            "texture" => object.texture = Some(try!(parse_path(args, n))),
+            "color" => object.color = try!(parse_color(args, n)),
+            "vert_shader" => {
+                object.vert_shader = match args.first().map(|s| *s) {
+                    Some("Perspective") => VertexShaderType::Perspective,
+                    Some("Instanced") => VertexShaderType::Instanced,
+                    Some("Packed") => VertexShaderType::Packed,
+                    Some("Gui") => VertexShaderType::Gui,
+                    Some("Sprite") => VertexShaderType::Sprite,
+                    Some("Voxel") => VertexShaderType::Voxel,
+                    _ => return Err(scene_error(n, "unknown vertex shader")),
+                }
+            }
+            "frag_shader" => {
+                object.frag_shader = match args.first().map(|s| *s) {
+                    Some("Unlit") => FragmentShaderType::Unlit,
+                    Some("Lambert") => FragmentShaderType::Lambert,
+                    Some("Phong") => FragmentShaderType::Phong,
+                    Some("NormalMapped") => FragmentShaderType::NormalMapped,
+                    Some("SpecularMapped") => FragmentShaderType::SpecularMapped,
+                    Some("Cutout") => FragmentShaderType::Cutout,
+                    Some("Layered") => FragmentShaderType::Layered,
+                    Some("Sharpen") => FragmentShaderType::Sharpen,
+                    Some("Grade") => FragmentShaderType::Grade,
+                    Some("Voxel") => FragmentShaderType::Voxel,
+                    _ => return Err(scene_error(n, "unknown fragment shader")),
+                }
+            }
+            "uv_offset" => object.uv_transform.offset = try!(parse_vec2(args, n)),
+            "uv_scale" => object.uv_transform.scale = try!(parse_vec2(args, n)),
+            "uv_rotation" => object.uv_transform.rotation = try!(parse_floats(args, 1, n))[0],
+            "uv_scroll" => object.uv_transform.scroll = try!(parse_vec2(args, n)),
+            "uv_spin" => object.uv_transform.spin = try!(parse_floats(args, 1, n))[0],
+            "position" => object.transform.position = try!(parse_vec3(args, n)),
+            "rotation" => object.transform.rotation = try!(parse_rotation(args, n)),
+            "scale" => object.transform.scale = try!(parse_vec3(args, n)),
+            _ => return Err(scene_error(n, "unknown object property")),
+        }
+        Ok(())
+    }
+
+    /// The scene file's text, which `parse` reads back into the same scene
+    pub fn write(&self) -> String {
+        let mut out = String::new();
+        let vec3 = |v: Vec3<f32>| format!("{} {} {}", v.x, v.y, v.z);
+        let quat = |q: &UnitQuat<f32>| {
+            let q = q.quat();
+            format!("{} {} {} {}", q.w, q.i, q.j, q.k)
+        };
+        let color = |c: [f32; 3]| format!("{} {} {}", c[0], c[1], c[2]);
+
+        // Writing to a String can't fail
+        writeln!(out, "ambient {}", color(self.ambient)).unwrap();
+        writeln!(out, "\ncamera").unwrap();
+        writeln!(out, "position {}", vec3(self.camera.position)).unwrap();
+        writeln!(out, "rotation {}", quat(&self.camera.rotation)).unwrap();
+        writeln!(out, "fov {}", self.camera.fov).unwrap();
+        if let Projection::Orthographic { height } = self.camera.projection {
+            writeln!(out, "orthographic {}", height).unwrap();
+        }
+
+        for light in self.lights.iter() {
+            match light.kind {
+                LightKind::Directional { direction } => {
+                    writeln!(out, "\nlight directional").unwrap();
+                    writeln!(out, "direction {}", vec3(direction)).unwrap();
+                }
+                LightKind::Point { position, range } => {
+                    writeln!(out, "\nlight point").unwrap();
+                    writeln!(out, "position {}", vec3(position)).unwrap();
+                    writeln!(out, "range {}", range).unwrap();
+                }
+                LightKind::Spot { position, direction, range, angle } => {
+                    writeln!(out, "\nlight spot").unwrap();
+                    writeln!(out, "position {}", vec3(position)).unwrap();
+                    writeln!(out, "direction {}", vec3(direction)).unwrap();
+                    writeln!(out, "range {}", range).unwrap();
+                    writeln!(out, "angle {}", angle).unwrap();
+                }
+            }
+            writeln!(out, "color {}", color(light.color)).unwrap();
+            writeln!(out, "intensity {}", light.intensity).unwrap();
+        }
+
+        for object in self.objects.iter() {
+            match object.name {
+                Some(ref name) => writeln!(out, "\nobject {}", name).unwrap(),
+                None => writeln!(out, "\nobject").unwrap(),
+            }
+            writeln!(out, "mesh {}", object.mesh).unwrap();
+            match object.texture {
+                Some(ref texture) => writeln!(out, "texture {}", texture).unwrap(),
+                None => writeln!(out, "color {}", color(object.color)).unwrap(),
+            }
+            writeln!(out, "vert_shader {:?}", object.vert_shader).unwrap();
+            writeln!(out, "frag_shader {:?}", object.frag_shader).unwrap();
+            let uv = object.uv_transform;
+            if uv != UvTransform::identity() {
+                writeln!(out, "uv_offset {} {}", uv.offset[0], uv.offset[1]).unwrap();
+                writeln!(out, "uv_scale {} {}", uv.scale[0], uv.scale[1]).unwrap();
+                writeln!(out, "uv_rotation {}", uv.rotation).unwrap();
+                writeln!(out, "uv_scroll {} {}", uv.scroll[0], uv.scroll[1]).unwrap();
+                writeln!(out, "uv_spin {}", uv.spin).unwrap();
+            }
+            writeln!(out, "position {}", vec3(object.transform.position)).unwrap();
+            writeln!(out, "rotation {}", quat(&object.transform.rotation)).unwrap();
+            writeln!(out, "scale {}", vec3(object.transform.scale)).unwrap();
+        }
+        out
+    }
+
+    /// Builds the scene, loading meshes and textures through the engine's caches
+    pub fn instantiate<'a>(&self, ctxt: &mut EngineContext) -> Result<Scene<'a>, Error> {
+        let (w, h) = get_display_dim(ctxt.display());
+        let mut scene = Scene::new(self.camera.to_camera(w as f32 / h as f32));
+        scene.set_ambient(self.ambient);
+        // Objects can be added to a parsed file, so names are checked again
+        let mut names = HashSet::new();
+        for name in self.objects.iter().filter_map(|o| o.name.as_ref()) {
+            if !names.insert(name) {
+                return Err(Error::Scene(format!("duplicate object name {}", name)));
+            }
+        }
+        for light in self.lights.iter() {
+            scene.add_light(*light);
+        }
+        for desc in self.objects.iter() {
+            let object = try!(SceneObject::new(ctxt, desc.clone()));
+            scene.add(object);
+        }
+        Ok(scene)
+    }
+}
+
+/// A mesh object created from a scene file. It remembers its description, so `Scene::save` can
+/// write it back out with its current transform.
+pub struct SceneObject<'a> {
+    pub parent: Object<'a>,
+    desc: ObjectDesc,
+}
+
+impl GameObject for SceneObject<'static> {
+    fn parent(&self) -> &Object {
+        &self.parent
+    }
+}
+
+impl<'a> SceneObject<'a> {
+    pub fn new(ctxt: &mut EngineContext, desc: ObjectDesc) -> Result<Self, Error> {
+        let path = ctxt.resource_dir.join(&desc.mesh);
+        let options = MeshOptions { packed: desc.vert_shader == VertexShaderType::Packed };
+        let mesh = try!(ctxt.mesh_cache.get_obj_with(&ctxt.display, path, options));
+        let material = match desc.texture {
+            Some(ref texture) => {
+                let path = ctxt.resource_dir.join(texture);
+                Material::texture(try!(ctxt.texture_cache.get_texture(&ctxt.display, path)))
+            }
+            None => Material::color(desc.color),
+        };
+        let material = material.vert_shader(desc.vert_shader)
+            .frag_shader(desc.frag_shader)
+            .uv_transform(desc.uv_transform);
+        let params = DrawParameters {
+            depth_test: DepthTest::IfLess,
+            depth_write: true,
+            .. Default::default()
+        };
+        let mut parent = ObjectBuilder::new()
+            .mesh(&mesh, NoIndices(PrimitiveType::TrianglesList))
+            .draw_params(params)
+            .transform(desc.transform)
+            .material(material)
+            .build();
+        parent.name = desc.name.clone();
+        Ok(SceneObject { parent: parent, desc: desc })
+    }
+
+    /// The object's description, with its current name, transform and UV transform
+    pub fn desc(&self) -> ObjectDesc {
+        ObjectDesc {
+            name: self.parent.name.clone(),
+            uv_transform: self.parent.material.uv_transform,
+            transform: self.parent.transform,
+            .. self.desc.clone()
+        }
+    }
+}
+
+fn parse_floats(args: &[&str], count: usize, line: usize) -> Result<Vec<f32>, Error> {
+    if args.len() != count {
+        return Err(scene_error(line, &format!("expected {} numbers", count)));
+    }
+    args.iter()
+        .map(|a| a.parse().map_err(|_| scene_error(line, "expected a number")))
+        .collect()
+}
+
+fn parse_vec2(args: &[&str], line: usize) -> Result<[f32; 2], Error> {
+    let v = try!(parse_floats(args, 2, line));
+    Ok([v[0], v[1]])
+}
+
+fn parse_vec3(args: &[&str], line: usize) -> Result<Vec3<f32>, Error> {
+    let v = try!(parse_floats(args, 3, line));
+    Ok(Vec3::new(v[0], v[1], v[2]))
+}
+
+fn parse_color(args: &[&str], line: usize) -> Result<[f32; 3], Error> {
+    let c = try!(parse_floats(args, 3, line));
+    Ok([c[0], c[1], c[2]])
+}
+
+// Normalized, so hand written rotations don't need to be exact
+fn parse_rotation(args: &[&str], line: usize) -> Result<UnitQuat<f32>, Error> {
+    let q = try!(parse_floats(args, 4, line));
+    Ok(UnitQuat::new_with_quat(Quat::new(q[0], q[1], q[2], q[3])))
+}
+
+// The rest of the line, so paths can contain spaces
+fn parse_path(args: &[&str], line: usize) -> Result<String, Error> {
+    if args.is_empty() {
+        return Err(scene_error(line, "expected a path"));
+    }
+    Ok(args.join(" "))
+}
+
+fn scene_error(line: usize, msg: &str) -> Error {
+    Error::Scene(format!("line {}: {}", line + 1, msg))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs::{self, File};
+    use std::io::Write;
+    use std::path::Path;
+
+    use super::SceneFile;
+    use Error;
+
+    use nalgebra::Vec3;
+
+    const SCENE: &'static str = "\
+ambient 0.2 0.3 0.4
+
+camera
+position 1 2 3
+fov 1.2
+
+light point
+position 0.5 -0.5 0.5
+range 2
+
+object wooden crate
+mesh cube.obj
+texture crate texture.png
+frag_shader Phong
+uv_scroll 0 0.5
+position 4 5 6
+scale 2 2 2
+
+object
+mesh floor.obj
+color 0.5 0.5 0.5
+";
+
+    #[test]
+    fn saves_and_reloads() {
+        let file = SceneFile::parse(SCENE).unwrap();
+        let path = env::temp_dir().join("engine_scene_file_round_trip.txt");
+        write_file(&path, &file.write());
+        let reloaded = SceneFile::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(reloaded.write(), file.write());
+        assert_eq!(reloaded.ambient, [0.2, 0.3, 0.4]);
+        assert_eq!(reloaded.camera.position, Vec3::new(1., 2., 3.));
+        assert_eq!(reloaded.lights.len(), 1);
+        assert_eq!(reloaded.objects.len(), 2);
+        let crate_desc = &reloaded.objects[0];
+        assert_eq!(crate_desc.name, Some("wooden crate".to_owned()));
+        assert_eq!(crate_desc.texture, Some("crate texture.png".to_owned()));
+        assert_eq!(crate_desc.uv_transform, file.objects[0].uv_transform);
+        assert_eq!(crate_desc.transform, file.objects[0].transform);
+        assert_eq!(reloaded.objects[1].name, None);
+    }
+
+    #[test]
+    fn duplicate_names_are_errors() {
+        let input = "object a\nmesh a.obj\n\nobject a\nmesh b.obj\n";
+        match SceneFile::parse(input) {
+            Err(Error::Scene(ref msg)) if msg.starts_with("line 4:") => (),
+            other => panic!("expected a duplicate name error on line 4, got {:?}", other),
+        }
+    }
+
+    fn write_file(path: &Path, contents: &str) {
+        File::create(path).unwrap().write_all(contents.as_bytes()).unwrap();
+    }
+}
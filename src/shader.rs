@@ -4,23 +4,99 @@ use std::hash::Hash;
 // TODO: Make this As<Path>?
 pub trait ShaderType : Copy + Clone + Debug + Eq + Hash + PartialEq {
     fn to_filename(&self) -> &'static str;
+
+    /// The source of the shader as it was when the engine was compiled
+    fn embedded_source(&self) -> &'static str;
 }
 
 #[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
 pub enum FragmentShaderType {
     Unlit,
+    /// Diffuse lighting from the scene's lights
+    Lambert,
+    /// Diffuse and specular lighting from the scene's lights
+    Phong,
+    /// Phong with normals from the material's normal map, and its specular map if it has one
+    NormalMapped,
+    /// Phong with the specular highlight scaled by the material's specular map
+    SpecularMapped,
+    /// Lambert lighting for alpha tested, two sided surfaces like leaves and fences. Pixels with
+    /// an alpha below the material's `alpha_cutoff` are discarded.
+    Cutout,
+    /// Lambert lighting for `TextureArray` materials, sampling the material's layer, plus the
+    /// instance's layer for `InstancedObject`. Pixels below `alpha_cutoff` are discarded.
+    Layered,
+    /// Contrast adaptive sharpening of a texture being scaled up, for `Upscaler`
+    Sharpen,
+    /// Exposure, white balance, contrast, saturation and LUTs applied to a drawn scene, for
+    /// `ColorGrading`
+    Grade,
+    Voxel,
 }
 
 #[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
 pub enum VertexShaderType {
     Perspective,
+    /// Perspective with per instance transforms and colors, for `InstancedObject`
+    Instanced,
+    /// Perspective for meshes made of `PackedVertex`
+    Packed,
+    /// `Instanced` for meshes made of `PackedVertex`
+    PackedInstanced,
     Gui,
+    /// Screen or world space quads showing one frame of a sprite sheet, for `Sprite`
+    Sprite,
+    Voxel,
 }
 
 impl ShaderType for FragmentShaderType {
     fn to_filename(&self) -> &'static str {
         match self {
             &FragmentShaderType::Unlit => "unlit.fragment.glsl",
+            &FragmentShaderType::Lambert => "lambert.fragment.glsl",
+            &FragmentShaderType::Phong => "phong.fragment.glsl",
+            &FragmentShaderType::NormalMapped => "normal_mapped.fragment.glsl",
+            &FragmentShaderType::SpecularMapped => "specular_mapped.fragment.glsl",
+            &FragmentShaderType::Cutout => "cutout.fragment.glsl",
+            &FragmentShaderType::Layered => "layered.fragment.glsl",
+            &FragmentShaderType::Sharpen => "sharpen.fragment.glsl",
+            &FragmentShaderType::Grade => "grade.fragment.glsl",
+            &FragmentShaderType::Voxel => "voxel.fragment.glsl",
+        }
+    }
+
+    fn embedded_source(&self) -> &'static str {
+        match self {
+            &FragmentShaderType::Unlit => include_str!("../shaders/unlit.fragment.glsl"),
+            &FragmentShaderType::Lambert => include_str!("../shaders/lambert.fragment.glsl"),
+            &FragmentShaderType::Phong => include_str!("../shaders/phong.fragment.glsl"),
+            &FragmentShaderType::NormalMapped => {
+                include_str!("../shaders/normal_mapped.fragment.glsl")
+            }
+            &FragmentShaderType::SpecularMapped => {
+                include_str!("../shaders/specular_mapped.fragment.glsl")
+            }
+            &FragmentShaderType::Cutout => include_str!("../shaders/cutout.fragment.glsl"),
+            &FragmentShaderType::Layered => include_str!("../shaders/layered.fragment.glsl"),
+            &FragmentShaderType::Sharpen => include_str!("../shaders/sharpen.fragment.glsl"),
+            &FragmentShaderType::Grade => include_str!("../shaders/grade.fragment.glsl"),
+            &FragmentShaderType::Voxel => include_str!("../shaders/voxel.fragment.glsl"),
+        }
+    }
+}
+
+impl VertexShaderType {
+    /// How much the shader scales vertex positions before applying the object's transform, or
+    /// `None` for shaders that draw in screen space
+    pub fn world_scale(&self) -> Option<f32> {
+        match self {
+            &VertexShaderType::Perspective |
+            &VertexShaderType::Instanced |
+            &VertexShaderType::Packed |
+            &VertexShaderType::PackedInstanced |
+            &VertexShaderType::Voxel => Some(0.1),
+            &VertexShaderType::Sprite => Some(1.),
+            &VertexShaderType::Gui => None,
         }
     }
 }
@@ -29,7 +105,26 @@ impl ShaderType for VertexShaderType {
     fn to_filename(&self) -> &'static str {
         match self {
             &VertexShaderType::Perspective => "perspective.vertex.glsl",
-            &VertexShaderType::Gui => "gui.vertex.glsl"
+            &VertexShaderType::Instanced => "instanced.vertex.glsl",
+            &VertexShaderType::Packed => "packed.vertex.glsl",
+            &VertexShaderType::PackedInstanced => "packed_instanced.vertex.glsl",
+            &VertexShaderType::Gui => "gui.vertex.glsl",
+            &VertexShaderType::Sprite => "sprite.vertex.glsl",
+            &VertexShaderType::Voxel => "voxel.vertex.glsl",
+        }
+    }
+
+    fn embedded_source(&self) -> &'static str {
+        match self {
+            &VertexShaderType::Perspective => include_str!("../shaders/perspective.vertex.glsl"),
+            &VertexShaderType::Instanced => include_str!("../shaders/instanced.vertex.glsl"),
+            &VertexShaderType::Packed => include_str!("../shaders/packed.vertex.glsl"),
+            &VertexShaderType::PackedInstanced => {
+                include_str!("../shaders/packed_instanced.vertex.glsl")
+            }
+            &VertexShaderType::Gui => include_str!("../shaders/gui.vertex.glsl"),
+            &VertexShaderType::Sprite => include_str!("../shaders/sprite.vertex.glsl"),
+            &VertexShaderType::Voxel => include_str!("../shaders/voxel.vertex.glsl"),
         }
     }
 }
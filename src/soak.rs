@@ -0,0 +1,277 @@
+use std::collections::VecDeque;
+use std::error;
+use std::fmt;
+use std::fs::File;
+use std::io::Read;
+use std::path::PathBuf;
+
+use {EngineContext, Error};
+use scene::Scene;
+use stats::MemoryReport;
+
+use nalgebra::Vec3;
+
+/// Adds an object called `name` at a position to the scene
+pub type Prefab = Box<FnMut(&mut EngineContext, &mut Scene, &str,
+                           Vec3<f32>) -> Result<(), Error>>;
+
+/// Churns a scene for hours to catch leaks and slowdowns that only show up in long sessions, like
+/// caches that never shrink or buffers that are recreated every frame. Every `churn_interval` it
+/// spawns one of its prefabs at a random position or removes an object it spawned, every
+/// `asset_interval` it loads one of `meshes` or `textures` and purges the caches again, and every
+/// `camera_interval` it moves the camera somewhere else in `area`.
+///
+/// Once `warmup` seconds have passed it remembers how many resources the caches hold and how much
+/// memory the process uses, and from then on every `check_interval` fails if either grew by more
+/// than the limits, or if the frame rate dropped below `min_fps`. Call `update` from
+/// `Game::update`, and on a `SoakError::Limit` log it and exit with a failure status.
+pub struct SoakTest {
+    prefabs: Vec<Prefab>,
+    /// Loaded through the engine's caches and released again, relative to the resource folder
+    pub meshes: Vec<PathBuf>,
+    pub textures: Vec<PathBuf>,
+    /// Most spawned objects alive at once
+    pub max_objects: usize,
+    /// Corners of the box objects are spawned and the camera is moved in
+    pub area: (Vec3<f32>, Vec3<f32>),
+    /// Seconds between spawning or removing an object
+    pub churn_interval: f32,
+    pub asset_interval: f32,
+    pub camera_interval: f32,
+    /// Seconds before the baseline is taken, so caches can fill up first
+    pub warmup: f32,
+    pub check_interval: f32,
+    /// How many more resources any one cache may hold than at the baseline
+    pub max_cache_growth: usize,
+    /// How many more kilobytes of memory the process may use than at the baseline
+    pub max_memory_growth_kb: usize,
+    pub min_fps: u32,
+    seed: u32,
+    rng: u32,
+    spawned: VecDeque<String>,
+    next_id: usize,
+    elapsed: f32,
+    // Seconds until the next churn, asset and camera step and check
+    timers: [f32; 4],
+    baseline: Option<(MemoryReport, Option<usize>)>,
+}
+
+impl SoakTest {
+    /// `seed` picks the sequence of random steps, so a failing run can be repeated
+    pub fn new(seed: u32) -> Self {
+        SoakTest {
+            prefabs: Vec::new(),
+            meshes: Vec::new(),
+            textures: Vec::new(),
+            max_objects: 200,
+            area: (Vec3::new(-10., -10., -10.), Vec3::new(10., 10., 10.)),
+            churn_interval: 0.05,
+            asset_interval: 1.,
+            camera_interval: 5.,
+            warmup: 60.,
+            check_interval: 60.,
+            max_cache_growth: 16,
+            max_memory_growth_kb: 64 * 1024,
+            min_fps: 20,
+            seed: seed,
+            rng: if seed == 0 { 1 } else { seed },
+            spawned: VecDeque::new(),
+            next_id: 0,
+            elapsed: 0.,
+            timers: [0.; 4],
+            baseline: None,
+        }
+    }
+
+    /// Adds a kind of object to spawn
+    pub fn prefab<F>(&mut self, prefab: F)
+        where F: FnMut(&mut EngineContext, &mut Scene, &str, Vec3<f32>) -> Result<(), Error>,
+              F: 'static
+    {
+        self.prefabs.push(Box::new(prefab));
+    }
+
+    /// Seconds of game time the test has been running for
+    pub fn elapsed(&self) -> f32 {
+        self.elapsed
+    }
+
+    /// Runs the steps that are due, returning an error when a limit was exceeded or a prefab or
+    /// asset failed to load
+    pub fn update(&mut self, ctxt: &mut EngineContext,
+                  scene: &mut Scene) -> Result<(), SoakError> {
+        let dt = ctxt.delta_time();
+        self.elapsed += dt;
+        for timer in self.timers.iter_mut() {
+            *timer -= dt;
+        }
+
+        if self.timers[0] <= 0. {
+            self.timers[0] += self.churn_interval;
+            try!(self.churn(ctxt, scene));
+        }
+        if self.timers[1] <= 0. {
+            self.timers[1] += self.asset_interval;
+            try!(self.churn_assets(ctxt));
+        }
+        if self.timers[2] <= 0. {
+            self.timers[2] += self.camera_interval;
+            let (position, target) = (self.random_point(), self.random_point());
+            let camera = scene.camera_mut();
+            camera.set_pos(&position);
+            camera.look_at(&target);
+        }
+        if self.elapsed >= self.warmup && self.timers[3] <= 0. {
+            self.timers[3] += self.check_interval;
+            try!(self.check(ctxt, scene));
+        }
+        Ok(())
+    }
+
+    // Spawns a random prefab, or removes the oldest spawned object when there are enough of them
+    // and sometimes before that
+    fn churn(&mut self, ctxt: &mut EngineContext, scene: &mut Scene) -> Result<(), Error> {
+        let full = self.spawned.len() >= self.max_objects;
+        if self.prefabs.is_empty() || full || (!self.spawned.is_empty() && self.random() < 0.3) {
+            if let Some(name) = self.spawned.pop_front() {
+                scene.remove(&name);
+            }
+            return Ok(());
+        }
+
+        let i = (self.random() * self.prefabs.len() as f32) as usize % self.prefabs.len();
+        let name = format!("soak {}", self.next_id);
+        self.next_id += 1;
+        let position = self.random_point();
+        try!((self.prefabs[i])(ctxt, scene, &name, position));
+        self.spawned.push_back(name);
+        Ok(())
+    }
+
+    // Loads an asset and immediately lets go of it, so the purge should free it again
+    fn churn_assets(&mut self, ctxt: &mut EngineContext) -> Result<(), Error> {
+        let count = self.meshes.len() + self.textures.len();
+        if count == 0 {
+            return Ok(());
+        }
+        let i = (self.random() * count as f32) as usize % count;
+        if i < self.meshes.len() {
+            let path = ctxt.resource_dir.join(&self.meshes[i]);
+            try!(ctxt.mesh_cache.get_obj(&ctxt.display, path));
+        } else {
+            let path = ctxt.resource_dir.join(&self.textures[i - self.meshes.len()]);
+            try!(ctxt.texture_cache.get_texture(&ctxt.display, path));
+        }
+        ctxt.texture_cache.purge_unused();
+        ctxt.mesh_cache.purge_unused();
+        ctxt.font_cache.purge_unused();
+        Ok(())
+    }
+
+    fn check(&mut self, ctxt: &EngineContext, scene: &Scene) -> Result<(), SoakError> {
+        let report = ctxt.memory_report();
+        let memory = resident_memory_kb();
+        info!("Soak test at {:.0}s: {:?}, {} objects, {} KB resident, {} fps", self.elapsed,
+              report, scene.len(), memory.unwrap_or(0), ctxt.fps());
+
+        let (base, base_memory) = match self.baseline {
+            Some(baseline) => baseline,
+            None => {
+                self.baseline = Some((report, memory));
+                return Ok(());
+            }
+        };
+        let caches = [("textures", base.textures, report.textures),
+                      ("glyphs", base.glyphs, report.glyphs),
+                      ("meshes", base.meshes, report.meshes),
+                      ("fonts", base.fonts, report.fonts),
+                      ("programs", base.programs, report.programs)];
+        for &(cache, before, now) in caches.iter() {
+            if now > before + self.max_cache_growth {
+                return Err(self.failure(format!("{} grew from {} to {}", cache, before, now)));
+            }
+        }
+        if let (Some(before), Some(now)) = (base_memory, memory) {
+            if now > before + self.max_memory_growth_kb {
+                return Err(self.failure(format!("resident memory grew from {} KB to {} KB",
+                                                before, now)));
+            }
+        }
+        if ctxt.fps() < self.min_fps {
+            return Err(self.failure(format!("frame rate dropped to {} fps", ctxt.fps())));
+        }
+        Ok(())
+    }
+
+    fn failure(&self, msg: String) -> SoakError {
+        SoakError::Limit(format!("after {:.0}s with seed {}: {}", self.elapsed, self.seed, msg))
+    }
+
+    // xorshift, from 0 to 1
+    fn random(&mut self) -> f32 {
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 17;
+        self.rng ^= self.rng << 5;
+        self.rng as f32 / ::std::u32::MAX as f32
+    }
+
+    fn random_point(&mut self) -> Vec3<f32> {
+        let (min, max) = self.area;
+        let t = Vec3::new(self.random(), self.random(), self.random());
+        Vec3::new(min.x + t.x * (max.x - min.x),
+                  min.y + t.y * (max.y - min.y),
+                  min.z + t.z * (max.z - min.z))
+    }
+}
+
+/// Why a `SoakTest` stopped
+#[derive(Debug)]
+pub enum SoakError {
+    /// A prefab or asset failed to load
+    Engine(Error),
+    /// A cache, the process memory or the frame rate drifted past its limit
+    Limit(String),
+}
+
+impl fmt::Display for SoakError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SoakError::Engine(ref e) => write!(f, "{}", e),
+            SoakError::Limit(ref msg) => write!(f, "Soak test failed: {}", msg),
+        }
+    }
+}
+
+impl error::Error for SoakError {
+    fn description(&self) -> &str {
+        match *self {
+            SoakError::Engine(ref e) => error::Error::description(e),
+            SoakError::Limit(ref msg) => msg,
+        }
+    }
+
+    fn cause(&self) -> Option<&error::Error> {
+        match *self {
+            SoakError::Engine(ref e) => Some(e),
+            SoakError::Limit(_) => None,
+        }
+    }
+}
+
+impl From<Error> for SoakError {
+    fn from(e: Error) -> Self {
+        SoakError::Engine(e)
+    }
+}
+
+// Memory the process is using, in kilobytes. Only known on Linux.
+fn resident_memory_kb() -> Option<usize> {
+    let mut status = String::new();
+    if File::open("/proc/self/status").and_then(|mut f| f.read_to_string(&mut status)).is_err() {
+        return None;
+    }
+    status.lines()
+        .find(|line| line.starts_with("VmRSS:"))
+        .and_then(|line| line.split_whitespace().nth(1))
+        .and_then(|kb| kb.parse().ok())
+}
@@ -0,0 +1,200 @@
+use std::collections::HashMap;
+
+use nalgebra::{Norm, Vec3};
+
+/// Gameplay side spatial lookups over the ground plane, e.g. "nearest enemy within 10 units".
+/// Entities are identified by whatever ids the game uses. The usual pattern is to `clear` and
+/// re-`insert` every entity once per tick.
+pub struct SpatialHash {
+    cell_size: f32,
+    cells: HashMap<(i32, i32), Vec<(usize, Vec3<f32>)>>,
+    len: usize,
+}
+
+impl SpatialHash {
+    /// `cell_size` works best around the radius of the most common queries
+    pub fn new(cell_size: f32) -> Self {
+        assert!(cell_size > 0., "SpatialHash cell size must be positive");
+        SpatialHash { cell_size: cell_size, cells: HashMap::new(), len: 0 }
+    }
+
+    /// Removes every entity, keeping the allocated cells around for the next tick
+    pub fn clear(&mut self) {
+        for cell in self.cells.values_mut() {
+            cell.clear();
+        }
+        self.len = 0;
+    }
+
+    pub fn insert(&mut self, id: usize, pos: Vec3<f32>) {
+        let cell = self.cell(pos);
+        self.cells.entry(cell).or_insert_with(Vec::new).push((id, pos));
+        self.len += 1;
+    }
+
+    /// Replaces the contents with `entities`
+    pub fn rebuild<I: IntoIterator<Item=(usize, Vec3<f32>)>>(&mut self, entities: I) {
+        self.clear();
+        for (id, pos) in entities {
+            self.insert(id, pos);
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Ids of the entities within `radius` of `center`
+    pub fn query_radius(&self, center: Vec3<f32>, radius: f32) -> Vec<usize> {
+        let mut found = Vec::new();
+        self.for_each_within(center, radius, |id, _, _| found.push(id));
+        found
+    }
+
+    /// Number of entities within `radius` of `center`
+    pub fn density(&self, center: Vec3<f32>, radius: f32) -> usize {
+        let mut count = 0;
+        self.for_each_within(center, radius, |_, _, _| count += 1);
+        count
+    }
+
+    /// The closest entity within `radius` of `center` for which `filter` returns true, e.g. to
+    /// skip the entity asking or entities on the same team
+    pub fn nearest<F: Fn(usize) -> bool>(&self, center: Vec3<f32>, radius: f32,
+                                         filter: F) -> Option<usize> {
+        let mut nearest = None;
+        let mut nearest_dist = ::std::f32::MAX;
+        self.for_each_within(center, radius, |id, _, dist_sq| {
+            if dist_sq < nearest_dist && filter(id) {
+                nearest = Some(id);
+                nearest_dist = dist_sq;
+            }
+        });
+        nearest
+    }
+
+    fn cell(&self, pos: Vec3<f32>) -> (i32, i32) {
+        ((pos.x / self.cell_size).floor() as i32, (pos.y / self.cell_size).floor() as i32)
+    }
+
+    // Calls `f` with the id, position and squared distance of every entity within `radius`
+    fn for_each_within<F: FnMut(usize, Vec3<f32>, f32)>(&self, center: Vec3<f32>, radius: f32,
+                                                        mut f: F) {
+        let (x0, y0) = self.cell(center - Vec3::new(radius, radius, 0.));
+        let (x1, y1) = self.cell(center + Vec3::new(radius, radius, 0.));
+        for x in x0..x1 + 1 {
+            for y in y0..y1 + 1 {
+                if let Some(cell) = self.cells.get(&(x, y)) {
+                    for &(id, pos) in cell {
+                        let offset = pos - center;
+                        let dist_sq = Vec3::new(offset.x, offset.y, 0.).sqnorm();
+                        if dist_sq <= radius * radius {
+                            f(id, pos, dist_sq);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// A grid of values spread out from sources on the ground plane, e.g. how dangerous or how
+/// contested each area is. Influence falls off linearly with distance and can decay over time so
+/// the map forgets old information.
+pub struct InfluenceMap {
+    origin: Vec3<f32>,
+    cell_size: f32,
+    width: usize,
+    height: usize,
+    values: Vec<f32>,
+}
+
+impl InfluenceMap {
+    /// A `width` by `height` grid of `cell_size` cells with its lower left corner at `origin`
+    pub fn new(origin: Vec3<f32>, cell_size: f32, width: usize, height: usize) -> Self {
+        InfluenceMap {
+            origin: origin,
+            cell_size: cell_size,
+            width: width,
+            height: height,
+            values: vec![0.; width * height],
+        }
+    }
+
+    pub fn clear(&mut self) {
+        for v in self.values.iter_mut() {
+            *v = 0.;
+        }
+    }
+
+    /// Multiplies every value by `factor`, e.g. 0.9 every tick
+    pub fn decay(&mut self, factor: f32) {
+        for v in self.values.iter_mut() {
+            *v *= factor;
+        }
+    }
+
+    /// Adds `strength` at `pos`, falling off to nothing at `radius`. Negative strengths work too,
+    /// e.g. for the other team.
+    pub fn add(&mut self, pos: Vec3<f32>, strength: f32, radius: f32) {
+        let cells = (radius / self.cell_size).ceil() as i32;
+        let (cx, cy) = self.cell_coords(pos);
+        for x in cx - cells..cx + cells + 1 {
+            for y in cy - cells..cy + cells + 1 {
+                if x < 0 || y < 0 || x >= self.width as i32 || y >= self.height as i32 {
+                    continue;
+                }
+                let center = self.cell_center(x as usize, y as usize);
+                let dist = Vec3::new(center.x - pos.x, center.y - pos.y, 0.).norm();
+                if dist < radius {
+                    self.values[y as usize * self.width + x as usize] +=
+                        strength * (1. - dist / radius);
+                }
+            }
+        }
+    }
+
+    /// The value of the cell containing `pos`, 0 outside of the map
+    pub fn value_at(&self, pos: Vec3<f32>) -> f32 {
+        let (x, y) = self.cell_coords(pos);
+        if x < 0 || y < 0 || x >= self.width as i32 || y >= self.height as i32 {
+            return 0.;
+        }
+        self.values[y as usize * self.width + x as usize]
+    }
+
+    /// Center of the cell with the highest value within `radius` of `pos`, e.g. where to retreat
+    /// to on a safety map
+    pub fn best_within(&self, pos: Vec3<f32>, radius: f32) -> Option<Vec3<f32>> {
+        let cells = (radius / self.cell_size).ceil() as i32;
+        let (cx, cy) = self.cell_coords(pos);
+        let mut best: Option<(f32, Vec3<f32>)> = None;
+        for x in cx - cells..cx + cells + 1 {
+            for y in cy - cells..cy + cells + 1 {
+                if x < 0 || y < 0 || x >= self.width as i32 || y >= self.height as i32 {
+                    continue;
+                }
+                let center = self.cell_center(x as usize, y as usize);
+                if Vec3::new(center.x - pos.x, center.y - pos.y, 0.).norm() > radius {
+                    continue;
+                }
+                let value = self.values[y as usize * self.width + x as usize];
+                if best.map_or(true, |(v, _)| value > v) {
+                    best = Some((value, center));
+                }
+            }
+        }
+        best.map(|(_, p)| p)
+    }
+
+    fn cell_coords(&self, pos: Vec3<f32>) -> (i32, i32) {
+        (((pos.x - self.origin.x) / self.cell_size).floor() as i32,
+         ((pos.y - self.origin.y) / self.cell_size).floor() as i32)
+    }
+
+    fn cell_center(&self, x: usize, y: usize) -> Vec3<f32> {
+        Vec3::new(self.origin.x + (x as f32 + 0.5) * self.cell_size,
+                  self.origin.y + (y as f32 + 0.5) * self.cell_size,
+                  self.origin.z)
+    }
+}
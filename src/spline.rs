@@ -0,0 +1,293 @@
+use std::rc::Rc;
+
+use draw::{GameObject, Object, ObjectBuilder, Vertex};
+use material::Material;
+use mesh::{self, MeshBuilder};
+use transform::{self, Transform};
+
+use glium::{DepthTest, Display, DrawParameters, VertexBuffer};
+use glium::index::{NoIndices, PrimitiveType};
+use glium::texture::Texture2d;
+use glium::uniforms::{SamplerBehavior, SamplerWrapFunction};
+
+use nalgebra::{Norm, Vec3};
+
+/// Number of samples per segment used to build the arc length table
+const SAMPLES_PER_SEGMENT: usize = 32;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum SplineKind {
+    /// Piecewise cubic Bézier curve. Every segment uses four control points, and consecutive
+    /// segments share their end points, so there must be `3n + 1` points.
+    Bezier,
+    /// Catmull-Rom spline passing through every point
+    CatmullRom,
+}
+
+/// A cubic spline parameterized both by `t` in [0, 1] and by distance along the curve
+pub struct Spline {
+    kind: SplineKind,
+    points: Vec<Vec3<f32>>,
+    // Cumulative length at each sample, SAMPLES_PER_SEGMENT samples per segment
+    lengths: Vec<f32>,
+}
+
+impl Spline {
+    pub fn bezier(points: Vec<Vec3<f32>>) -> Self {
+        assert!(points.len() >= 4 && (points.len() - 1) % 3 == 0,
+                "Bezier splines need 3n + 1 control points");
+        Self::new(SplineKind::Bezier, points)
+    }
+
+    pub fn catmull_rom(points: Vec<Vec3<f32>>) -> Self {
+        assert!(points.len() >= 2, "Catmull-Rom splines need at least two points");
+        Self::new(SplineKind::CatmullRom, points)
+    }
+
+    fn new(kind: SplineKind, points: Vec<Vec3<f32>>) -> Self {
+        let mut spline = Spline { kind: kind, points: points, lengths: Vec::new() };
+
+        let samples = spline.segments() * SAMPLES_PER_SEGMENT;
+        let mut length = 0.;
+        let mut prev = spline.point(0.);
+        spline.lengths.push(0.);
+        for i in 1..samples + 1 {
+            let p = spline.point(i as f32 / samples as f32);
+            length += (p - prev).norm();
+            spline.lengths.push(length);
+            prev = p;
+        }
+        spline
+    }
+
+    pub fn kind(&self) -> SplineKind {
+        self.kind
+    }
+
+    pub fn segments(&self) -> usize {
+        match self.kind {
+            SplineKind::Bezier => (self.points.len() - 1) / 3,
+            SplineKind::CatmullRom => self.points.len() - 1,
+        }
+    }
+
+    pub fn length(&self) -> f32 {
+        *self.lengths.last().unwrap()
+    }
+
+    pub fn point(&self, t: f32) -> Vec3<f32> {
+        let (i, t) = self.segment(t);
+        let (p0, p1, p2, p3) = self.control_points(i);
+        match self.kind {
+            SplineKind::Bezier => {
+                let s = 1. - t;
+                p0 * (s * s * s) + p1 * (3. * s * s * t) + p2 * (3. * s * t * t) + p3 * (t * t * t)
+            }
+            SplineKind::CatmullRom => {
+                let (t2, t3) = (t * t, t * t * t);
+                (p1 * 2. +
+                 (p2 - p0) * t +
+                 (p0 * 2. - p1 * 5. + p2 * 4. - p3) * t2 +
+                 (p1 * 3. - p0 - p2 * 3. + p3) * t3) * 0.5
+            }
+        }
+    }
+
+    /// Unnormalized derivative of the curve at `t`
+    pub fn tangent(&self, t: f32) -> Vec3<f32> {
+        let (i, t) = self.segment(t);
+        let (p0, p1, p2, p3) = self.control_points(i);
+        match self.kind {
+            SplineKind::Bezier => {
+                let s = 1. - t;
+                (p1 - p0) * (3. * s * s) + (p2 - p1) * (6. * s * t) + (p3 - p2) * (3. * t * t)
+            }
+            SplineKind::CatmullRom => {
+                ((p2 - p0) +
+                 (p0 * 2. - p1 * 5. + p2 * 4. - p3) * (2. * t) +
+                 (p1 * 3. - p0 - p2 * 3. + p3) * (3. * t * t)) * 0.5
+            }
+        }
+    }
+
+    /// Converts a distance along the curve into the curve parameter `t`
+    pub fn t_at_distance(&self, distance: f32) -> f32 {
+        let distance = distance.max(0.).min(self.length());
+
+        // Find the first sample at or past `distance` and interpolate from the one before it
+        let (mut lo, mut hi) = (0, self.lengths.len() - 1);
+        while lo < hi {
+            let mid = (lo + hi) / 2;
+            if self.lengths[mid] < distance { lo = mid + 1 } else { hi = mid }
+        }
+        if lo == 0 {
+            return 0.;
+        }
+
+        let (l0, l1) = (self.lengths[lo - 1], self.lengths[lo]);
+        let frac = if l1 > l0 { (distance - l0) / (l1 - l0) } else { 0. };
+        let samples = (self.lengths.len() - 1) as f32;
+        ((lo - 1) as f32 + frac) / samples
+    }
+
+    pub fn point_at_distance(&self, distance: f32) -> Vec3<f32> {
+        self.point(self.t_at_distance(distance))
+    }
+
+    /// Samples the curve at `n + 1` points evenly spaced by distance
+    pub fn sample(&self, n: usize) -> Vec<Vec3<f32>> {
+        let length = self.length();
+        (0..n + 1).map(|i| self.point_at_distance(length * i as f32 / n as f32)).collect()
+    }
+
+    fn segment(&self, t: f32) -> (usize, f32) {
+        let n = self.segments();
+        let t = t.max(0.).min(1.) * n as f32;
+        let i = (t.floor() as usize).min(n - 1);
+        (i, t - i as f32)
+    }
+
+    fn control_points(&self, i: usize) -> (Vec3<f32>, Vec3<f32>, Vec3<f32>, Vec3<f32>) {
+        let p = &self.points;
+        match self.kind {
+            SplineKind::Bezier => (p[3 * i], p[3 * i + 1], p[3 * i + 2], p[3 * i + 3]),
+            SplineKind::CatmullRom => {
+                // The end points are repeated so the curve reaches them
+                let last = p.len() - 1;
+                (p[if i == 0 { 0 } else { i - 1 }], p[i], p[i + 1], p[(i + 2).min(last)])
+            }
+        }
+    }
+}
+
+/// Moves along a spline at a constant speed, for camera rails, moving platforms and the like.
+/// Call `advance` from the owning object's `update` and use `transform` as its transform.
+pub struct PathFollow {
+    spline: Rc<Spline>,
+    speed: f32,
+    distance: f32,
+    looping: bool,
+}
+
+impl PathFollow {
+    pub fn new(spline: Rc<Spline>, speed: f32) -> Self {
+        PathFollow { spline: spline, speed: speed, distance: 0., looping: false }
+    }
+
+    pub fn looping(mut self, looping: bool) -> Self {
+        self.looping = looping;
+        self
+    }
+
+    pub fn set_speed(&mut self, speed: f32) {
+        self.speed = speed;
+    }
+
+    pub fn advance(&mut self, dt: f32) {
+        let length = self.spline.length();
+        self.distance += self.speed * dt;
+        if self.looping && length > 0. {
+            self.distance %= length;
+            if self.distance < 0. {
+                self.distance += length;
+            }
+        } else {
+            self.distance = self.distance.max(0.).min(length);
+        }
+    }
+
+    pub fn finished(&self) -> bool {
+        !self.looping && self.distance >= self.spline.length()
+    }
+
+    pub fn position(&self) -> Vec3<f32> {
+        self.spline.point_at_distance(self.distance)
+    }
+
+    /// Transform placing an object on the path with its x axis along the direction of travel and
+    /// its z axis as close to world up as possible
+    pub fn transform(&self) -> Transform {
+        let t = self.spline.t_at_distance(self.distance);
+        let p = self.spline.point(t);
+        let mut forward = self.spline.tangent(t);
+        if self.speed < 0. {
+            forward = -forward;
+        }
+        let (right, up) = mesh::frame(forward);
+        let forward = if forward.sqnorm() > 0. { forward.normalize() } else { Vec3::new(1., 0., 0.) };
+        Transform::new(p).with_rotation(transform::from_axes(forward, -right, up))
+    }
+}
+
+/// Draws a spline as a line strip, for debugging paths
+pub struct SplineDebug<'a> {
+    parent: Object<'a>,
+}
+
+impl GameObject for SplineDebug<'static> {
+    fn parent(&self) -> &Object {
+        &self.parent
+    }
+}
+
+impl<'a> SplineDebug<'a> {
+    pub fn new(display: &Display, spline: &Spline, color: [f32; 3]) -> Self {
+        let shape: Vec<_> = spline.sample(spline.segments() * SAMPLES_PER_SEGMENT).iter()
+            .map(|p| Vertex::new(p.x, p.y, p.z))
+            .collect();
+
+        let params = DrawParameters {
+            depth_test: DepthTest::IfLess,
+            depth_write: true,
+            .. Default::default()
+        };
+
+        let vb = VertexBuffer::new(display, shape).into_vertex_buffer_any();
+        let parent = ObjectBuilder::new()
+            .vertex_buffer(vb, NoIndices(PrimitiveType::LineStrip))
+            .draw_params(params)
+            .material(Material::color(color))
+            .build();
+
+        SplineDebug { parent: parent }
+    }
+}
+
+/// A textured mesh swept along a spline, e.g. a road, river or pipe. The texture repeats once per
+/// unit of length along the spline.
+pub struct SplineMesh<'a> {
+    parent: Object<'a>,
+}
+
+impl GameObject for SplineMesh<'static> {
+    fn parent(&self) -> &Object {
+        &self.parent
+    }
+}
+
+impl<'a> SplineMesh<'a> {
+    /// See `MeshBuilder::extrude_spline` for `spacing` and `ground`, and `mesh::ribbon_profile`
+    /// and `mesh::circle_profile` for common profiles
+    pub fn new(display: &Display, spline: &Spline, profile: &[(f32, f32)], spacing: f32,
+               ground: Option<&Fn(f32, f32) -> f32>, texture: Rc<Texture2d>) -> Self {
+        let repeat = SamplerWrapFunction::Repeat;
+        let sampler = SamplerBehavior {
+            wrap_function: (repeat, repeat, repeat),
+            .. Default::default()
+        };
+        let params = DrawParameters {
+            depth_test: DepthTest::IfLess,
+            depth_write: true,
+            .. Default::default()
+        };
+
+        let parent = MeshBuilder::new()
+            .extrude_spline(profile, spline, spacing, ground)
+            .object(display)
+            .draw_params(params)
+            .material(Material::texture(texture).sampler(sampler))
+            .build();
+
+        SplineMesh { parent: parent }
+    }
+}
@@ -0,0 +1,125 @@
+use std::net::{SocketAddr, UdpSocket};
+
+use {EngineContext, Error, Scene, SceneStack};
+
+/// How many resources the engine's caches hold, see `EngineContext::memory_report`. Counts that
+/// keep growing over a long session point at resources that are never purged.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct MemoryReport {
+    /// Textures and texture arrays
+    pub textures: usize,
+    /// Glyphs in the text atlas
+    pub glyphs: usize,
+    pub meshes: usize,
+    pub fonts: usize,
+    /// Linked shader programs
+    pub programs: usize,
+}
+
+/// Frame timings collected over one report
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct FrameStats {
+    pub frames: u32,
+    pub updates: u32,
+    /// Mean and longest time between frames, in milliseconds
+    pub mean_frame_ms: f32,
+    pub max_frame_ms: f32,
+}
+
+/// Sends the frame stats, memory report and object counts as a line of JSON in a UDP datagram
+/// once per second while `Engine::run` is running, so an external dashboard or soak test can
+/// watch a long running session. Turned on with `EngineConfig::stats_address`; nothing is sent
+/// when it's `None`. UDP never blocks the game, and reports are simply lost while nothing is
+/// listening, e.g. with `nc -ul 9999`:
+///
+/// ```text
+/// {"time":12.5,"fps":60,"frames":60,"updates":30,"mean_frame_ms":16.67,"max_frame_ms":18.02,
+///  "textures":4,"glyphs":95,"meshes":2,"fonts":1,"programs":3,"objects":12,"entities":40,
+///  "culled":3}
+/// ```
+pub struct StatsEmitter {
+    socket: UdpSocket,
+    target: SocketAddr,
+    frames: u32,
+    updates: u32,
+    total_frame_ns: u64,
+    max_frame_ns: u64,
+}
+
+impl StatsEmitter {
+    pub fn new(target: SocketAddr) -> Result<Self, Error> {
+        let local = if target.is_ipv4() { "0.0.0.0:0" } else { "[::]:0" };
+        let socket = try!(UdpSocket::bind(local));
+        try!(socket.set_nonblocking(true));
+        Ok(StatsEmitter {
+            socket: socket,
+            target: target,
+            frames: 0,
+            updates: 0,
+            total_frame_ns: 0,
+            max_frame_ns: 0,
+        })
+    }
+
+    /// Counts a frame that took `frame_ns` nanoseconds since the previous one
+    pub fn record_frame(&mut self, frame_ns: u64) {
+        self.frames += 1;
+        self.total_frame_ns += frame_ns;
+        if frame_ns > self.max_frame_ns {
+            self.max_frame_ns = frame_ns;
+        }
+    }
+
+    /// Counts an update step
+    pub fn record_update(&mut self) {
+        self.updates += 1;
+    }
+
+    /// The timings recorded since the last report
+    pub fn frame_stats(&self) -> FrameStats {
+        let mean = if self.frames == 0 { 0 } else { self.total_frame_ns / self.frames as u64 };
+        FrameStats {
+            frames: self.frames,
+            updates: self.updates,
+            mean_frame_ms: mean as f32 / 1e6,
+            max_frame_ms: self.max_frame_ns as f32 / 1e6,
+        }
+    }
+
+    /// Sends a report and starts collecting timings for the next one. Failing to send is logged
+    /// rather than returned, since the game shouldn't stop because a dashboard went away.
+    pub fn emit(&mut self, ctxt: &EngineContext, scene: &Scene) {
+        self.send(ctxt, scene.len(), scene.world().len(), scene.culled_count());
+    }
+
+    /// Like `emit`, counting the objects and entities of every scene in the stack
+    pub fn emit_stack(&mut self, ctxt: &EngineContext, scenes: &SceneStack) {
+        let (mut objects, mut entities, mut culled) = (0, 0, 0);
+        for scene in scenes.names().into_iter().filter_map(|name| scenes.get(name)) {
+            objects += scene.len();
+            entities += scene.world().len();
+            culled += scene.culled_count();
+        }
+        self.send(ctxt, objects, entities, culled);
+    }
+
+    fn send(&mut self, ctxt: &EngineContext, objects: usize, entities: usize, culled: usize) {
+        let frame = self.frame_stats();
+        let memory = ctxt.memory_report();
+        let report = format!(
+            "{{\"time\":{:.2},\"fps\":{},\"frames\":{},\"updates\":{},\"mean_frame_ms\":{:.2},\
+             \"max_frame_ms\":{:.2},\"textures\":{},\"glyphs\":{},\"meshes\":{},\"fonts\":{},\
+             \"programs\":{},\"objects\":{},\"entities\":{},\"culled\":{}}}\n",
+            ctxt.time(), ctxt.fps(), frame.frames, frame.updates, frame.mean_frame_ms,
+            frame.max_frame_ms, memory.textures, memory.glyphs, memory.meshes, memory.fonts,
+            memory.programs, objects, entities, culled);
+        if let Err(e) = self.socket.send_to(report.as_bytes(), self.target) {
+            debug!("Couldn't send stats to {}: {}", self.target, e);
+        }
+
+        self.frames = 0;
+        self.updates = 0;
+        self.total_frame_ns = 0;
+        self.max_frame_ns = 0;
+    }
+}
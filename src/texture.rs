@@ -0,0 +1,375 @@
+use std::cmp;
+use std::path::Path;
+
+use Error;
+
+use image::{self, DynamicImage, ImageBuffer, ImageError, ImageFormat};
+
+/// Image file formats `TextureCache` can load
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum TextureFormat {
+    Png,
+    Jpeg,
+    Gif,
+    Bmp,
+    Tga,
+    /// Uncompressed or DXT1/3/5 compressed. Mipmaps stored in the file are uploaded as they are
+    /// if the file has the full chain down to 1x1, otherwise they're generated from the top
+    /// level.
+    Dds,
+}
+
+impl TextureFormat {
+    pub fn from_extension(path: &Path) -> Option<Self> {
+        let extension = match path.extension().and_then(|e| e.to_str()) {
+            Some(e) => e.to_lowercase(),
+            None => return None,
+        };
+        match &*extension {
+            "png" => Some(TextureFormat::Png),
+            "jpg" | "jpeg" => Some(TextureFormat::Jpeg),
+            "gif" => Some(TextureFormat::Gif),
+            "bmp" => Some(TextureFormat::Bmp),
+            "tga" => Some(TextureFormat::Tga),
+            "dds" => Some(TextureFormat::Dds),
+            _ => None,
+        }
+    }
+
+    /// The format whose signature `bytes` starts with. TGA files don't have one.
+    pub fn from_magic(bytes: &[u8]) -> Option<Self> {
+        if bytes.starts_with(b"\x89PNG") {
+            Some(TextureFormat::Png)
+        } else if bytes.starts_with(b"\xff\xd8\xff") {
+            Some(TextureFormat::Jpeg)
+        } else if bytes.starts_with(b"GIF8") {
+            Some(TextureFormat::Gif)
+        } else if bytes.starts_with(b"BM") {
+            Some(TextureFormat::Bmp)
+        } else if bytes.starts_with(b"DDS ") {
+            Some(TextureFormat::Dds)
+        } else {
+            None
+        }
+    }
+
+    /// Trusts the file's contents over its extension, which is only used for formats without a
+    /// signature
+    pub fn detect(path: &Path, bytes: &[u8]) -> Option<Self> {
+        Self::from_magic(bytes).or_else(|| Self::from_extension(path))
+    }
+}
+
+/// How `TextureCache` uploads a texture
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct TextureOptions {
+    /// Generate mipmaps, or use the ones stored in a DDS file, which keeps minified textures
+    /// from shimmering. Pixel art drawn at its own size doesn't need them.
+    pub mipmaps: bool,
+    /// The file holds sRGB colors, as painted color textures do, and is sampled as linear
+    /// colors so blending and lighting happen in linear space. Only `TextureCache::get_albedo`
+    /// uploads sRGB textures; data like normal maps and LUTs is always linear.
+    pub srgb: bool,
+}
+
+impl Default for TextureOptions {
+    fn default() -> Self {
+        TextureOptions { mipmaps: true, srgb: false }
+    }
+}
+
+/// Decodes an image file in `format`, keeping only the top level of DDS files
+pub fn decode(bytes: &[u8], format: TextureFormat) -> Result<DynamicImage, Error> {
+    let mut levels = try!(decode_levels(bytes, format));
+    Ok(levels.swap_remove(0))
+}
+
+/// Decodes an image file in `format` with the mipmap levels it stores, largest first. Only DDS
+/// files store more than one.
+pub fn decode_levels(bytes: &[u8], format: TextureFormat) -> Result<Vec<DynamicImage>, Error> {
+    let format = match format {
+        TextureFormat::Png => ImageFormat::PNG,
+        TextureFormat::Jpeg => ImageFormat::JPEG,
+        TextureFormat::Gif => ImageFormat::GIF,
+        TextureFormat::Bmp => ImageFormat::BMP,
+        TextureFormat::Tga => ImageFormat::TGA,
+        TextureFormat::Dds => return decode_dds(bytes),
+    };
+    Ok(vec![try!(image::load_from_memory_with_format(bytes, format))])
+}
+
+/// Number of mipmap levels from a `width` by `height` image down to 1x1
+pub fn full_mipmap_count(width: u32, height: u32) -> usize {
+    32 - cmp::max(width, height).max(1).leading_zeros() as usize
+}
+
+// Offsets into a DDS file, which starts with "DDS " and a 124 byte header
+const DDS_FLAGS: usize = 8;
+const DDS_HEIGHT: usize = 12;
+const DDS_WIDTH: usize = 16;
+const DDS_MIPMAP_COUNT: usize = 28;
+const DDS_PF_FLAGS: usize = 80;
+const DDS_FOUR_CC: usize = 84;
+const DDS_BIT_COUNT: usize = 88;
+const DDS_MASKS: usize = 92;
+const DDS_DATA: usize = 128;
+
+// Header flags
+const DDSD_MIPMAPCOUNT: u32 = 0x20000;
+
+// Pixel format flags
+const DDPF_ALPHAPIXELS: u32 = 0x1;
+const DDPF_FOURCC: u32 = 0x4;
+
+fn decode_dds(bytes: &[u8]) -> Result<Vec<DynamicImage>, Error> {
+    if bytes.len() < DDS_DATA || !bytes.starts_with(b"DDS ") {
+        return Err(dds_error("truncated header"));
+    }
+    let (width, height) = (read_u32(bytes, DDS_WIDTH), read_u32(bytes, DDS_HEIGHT));
+    let count = if read_u32(bytes, DDS_FLAGS) & DDSD_MIPMAPCOUNT != 0 {
+        cmp::min(read_u32(bytes, DDS_MIPMAP_COUNT) as usize, full_mipmap_count(width, height))
+    } else {
+        1
+    };
+
+    // The levels are stored one after the other, each half the size of the previous one
+    let mut levels = Vec::with_capacity(cmp::max(count, 1));
+    let mut data = &bytes[DDS_DATA..];
+    for level in 0..cmp::max(count, 1) {
+        let (w, h) = (cmp::max(width >> level, 1), cmp::max(height >> level, 1));
+        let (pixels, len) = try!(decode_dds_level(bytes, data, w, h));
+        data = &data[len..];
+        match ImageBuffer::from_raw(w, h, pixels) {
+            Some(buffer) => levels.push(DynamicImage::ImageRgba8(buffer)),
+            None => return Err(dds_error("truncated image data")),
+        }
+    }
+    Ok(levels)
+}
+
+// The RGBA pixels of one level starting at `data`, and how many bytes it takes up
+fn decode_dds_level(header: &[u8], data: &[u8], width: u32,
+                    height: u32) -> Result<(Vec<u8>, usize), Error> {
+    if read_u32(header, DDS_PF_FLAGS) & DDPF_FOURCC == 0 {
+        return decode_dds_rgb(header, data, width, height);
+    }
+    let four_cc = &header[DDS_FOUR_CC..DDS_FOUR_CC + 4];
+    let block_size = if four_cc == b"DXT1" {
+        8
+    } else if four_cc == b"DXT3" || four_cc == b"DXT5" {
+        16
+    } else {
+        let name = String::from_utf8_lossy(four_cc).into_owned();
+        return Err(dds_error(&format!("unsupported compression {}", name)));
+    };
+    let len = ((width as usize + 3) / 4) * ((height as usize + 3) / 4) * block_size;
+    if data.len() < len {
+        return Err(dds_error("truncated image data"));
+    }
+    Ok((decode_dxt(data, width, height, four_cc), len))
+}
+
+// Uncompressed pixels, with each channel wherever the header's bit masks put it
+fn decode_dds_rgb(header: &[u8], data: &[u8], width: u32,
+                  height: u32) -> Result<(Vec<u8>, usize), Error> {
+    let bytes_per_pixel = match read_u32(header, DDS_BIT_COUNT) {
+        24 => 3,
+        32 => 4,
+        bits => return Err(dds_error(&format!("unsupported {} bit pixels", bits))),
+    };
+    let count = width as usize * height as usize;
+    if data.len() < count * bytes_per_pixel {
+        return Err(dds_error("truncated image data"));
+    }
+
+    let mut masks = [0; 4];
+    for (i, mask) in masks.iter_mut().enumerate() {
+        *mask = read_u32(header, DDS_MASKS + i * 4);
+    }
+    if read_u32(header, DDS_PF_FLAGS) & DDPF_ALPHAPIXELS == 0 {
+        masks[3] = 0;
+    }
+
+    let mut pixels = Vec::with_capacity(count * 4);
+    for pixel in data.chunks(bytes_per_pixel).take(count) {
+        let value = pixel.iter().rev().fold(0u32, |v, &b| v << 8 | b as u32);
+        for (i, &mask) in masks.iter().enumerate() {
+            pixels.push(if mask == 0 {
+                if i == 3 { 255 } else { 0 }
+            } else {
+                ((value & mask) >> mask.trailing_zeros()) as u8
+            });
+        }
+    }
+    Ok((pixels, count * bytes_per_pixel))
+}
+
+// Block compressed pixels. Every 4x4 block has its own colors, and DXT3 and DXT5 blocks start
+// with 8 bytes of alpha.
+fn decode_dxt(data: &[u8], width: u32, height: u32, four_cc: &[u8]) -> Vec<u8> {
+    let (width, height) = (width as usize, height as usize);
+    let block_size = if four_cc == b"DXT1" { 8 } else { 16 };
+    let mut pixels = vec![0; width * height * 4];
+    let blocks_wide = (width + 3) / 4;
+    for (n, block) in data.chunks(block_size).take(blocks_wide * ((height + 3) / 4)).enumerate() {
+        let (colors, alphas) = if four_cc == b"DXT1" {
+            (dxt_colors(block, true), None)
+        } else if four_cc == b"DXT3" {
+            (dxt_colors(&block[8..], false), Some(dxt3_alphas(block)))
+        } else {
+            (dxt_colors(&block[8..], false), Some(dxt5_alphas(block)))
+        };
+        let (bx, by) = (n % blocks_wide * 4, n / blocks_wide * 4);
+        for i in 0..16 {
+            let (x, y) = (bx + i % 4, by + i / 4);
+            if x >= width || y >= height {
+                continue;
+            }
+            let mut color = colors[i];
+            if let Some(alphas) = alphas {
+                color[3] = alphas[i];
+            }
+            let offset = (y * width + x) * 4;
+            for c in 0..4 {
+                pixels[offset + c] = color[c];
+            }
+        }
+    }
+    pixels
+}
+
+// The colors of the 16 pixels in an 8 byte color block
+fn dxt_colors(block: &[u8], allow_transparent: bool) -> [[u8; 4]; 16] {
+    let c0 = block[0] as u16 | (block[1] as u16) << 8;
+    let c1 = block[2] as u16 | (block[3] as u16) << 8;
+    let (a, b) = (rgb565(c0), rgb565(c1));
+    let mix = |wa: u32, wb: u32| {
+        let mut c = [0; 4];
+        for i in 0..3 {
+            c[i] = ((a[i] as u32 * wa + b[i] as u32 * wb) / (wa + wb)) as u8;
+        }
+        c[3] = 255;
+        c
+    };
+    // DXT1 uses the order of the two colors to mark blocks with transparent pixels
+    let palette = if c0 > c1 || !allow_transparent {
+        [a, b, mix(2, 1), mix(1, 2)]
+    } else {
+        [a, b, mix(1, 1), [0, 0, 0, 0]]
+    };
+
+    let indices = read_u32(block, 4);
+    let mut colors = [[0; 4]; 16];
+    for (i, color) in colors.iter_mut().enumerate() {
+        *color = palette[(indices >> (i * 2) & 0x3) as usize];
+    }
+    colors
+}
+
+// Explicit 4 bit alpha for every pixel
+fn dxt3_alphas(block: &[u8]) -> [u8; 16] {
+    let mut alphas = [0; 16];
+    for (i, alpha) in alphas.iter_mut().enumerate() {
+        let nibble = block[i / 2] >> (i % 2 * 4) & 0xf;
+        *alpha = nibble * 17;
+    }
+    alphas
+}
+
+// Two alpha values, with 3 bit indices into the values interpolated between them
+fn dxt5_alphas(block: &[u8]) -> [u8; 16] {
+    let (a0, a1) = (block[0] as u32, block[1] as u32);
+    let mut palette = [0; 8];
+    palette[0] = a0 as u8;
+    palette[1] = a1 as u8;
+    if a0 > a1 {
+        for i in 1..7 {
+            palette[i + 1] = (((7 - i) * a0 + i * a1) / 7) as u8;
+        }
+    } else {
+        for i in 1..5 {
+            palette[i + 1] = (((5 - i) * a0 + i * a1) / 5) as u8;
+        }
+        palette[6] = 0;
+        palette[7] = 255;
+    }
+
+    let indices = block[2..8].iter().rev().fold(0u64, |v, &b| v << 8 | b as u64);
+    let mut alphas = [0; 16];
+    for (i, alpha) in alphas.iter_mut().enumerate() {
+        *alpha = palette[(indices >> (i * 3) & 0x7) as usize];
+    }
+    alphas
+}
+
+fn rgb565(c: u16) -> [u8; 4] {
+    let (r, g, b) = ((c >> 11) & 0x1f, (c >> 5) & 0x3f, c & 0x1f);
+    [(r * 255 / 31) as u8, (g * 255 / 63) as u8, (b * 255 / 31) as u8, 255]
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    bytes[offset..offset + 4].iter().rev().fold(0, |v, &b| v << 8 | b as u32)
+}
+
+fn dds_error(msg: &str) -> Error {
+    Error::Image(ImageError::FormatError(format!("DDS: {}", msg)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode_levels, full_mipmap_count, TextureFormat};
+
+    use image::GenericImage;
+
+    // A 32 bit uncompressed DDS header with `levels` mipmap levels
+    fn dds_header(width: u32, height: u32, levels: u32) -> Vec<u8> {
+        let mut header = vec![0; 128];
+        {
+            let mut put = |offset: usize, value: u32| {
+                for i in 0..4 {
+                    header[offset + i] = (value >> (i * 8)) as u8;
+                }
+            };
+            put(0, 0x20534444);
+            put(8, 0x20000);
+            put(12, height);
+            put(16, width);
+            put(28, levels);
+            put(80, 0x41);
+            put(88, 32);
+            put(92, 0xff0000);
+            put(96, 0xff00);
+            put(100, 0xff);
+            put(104, 0xff000000);
+        }
+        header
+    }
+
+    #[test]
+    fn full_chain() {
+        assert_eq!(full_mipmap_count(1, 1), 1);
+        assert_eq!(full_mipmap_count(4, 4), 3);
+        assert_eq!(full_mipmap_count(256, 16), 9);
+        assert_eq!(full_mipmap_count(5, 3), 3);
+    }
+
+    #[test]
+    fn dds_keeps_its_mipmaps() {
+        let mut bytes = dds_header(4, 2, 3);
+        // Blue, green and red levels of 4x2, 2x1 and 1x1 BGRA pixels
+        bytes.extend(vec![255, 0, 0, 255].iter().cycle().take(4 * 2 * 4));
+        bytes.extend(vec![0, 255, 0, 255].iter().cycle().take(2 * 4));
+        bytes.extend(&[0, 0, 255, 255]);
+
+        let levels = decode_levels(&bytes, TextureFormat::Dds).unwrap();
+        let sizes: Vec<_> = levels.iter().map(|l| l.dimensions()).collect();
+        assert_eq!(sizes, vec![(4, 2), (2, 1), (1, 1)]);
+        assert_eq!(levels[0].get_pixel(3, 1).data, [0, 0, 255, 255]);
+        assert_eq!(levels[1].get_pixel(1, 0).data, [0, 255, 0, 255]);
+        assert_eq!(levels[2].get_pixel(0, 0).data, [255, 0, 0, 255]);
+
+        // Missing levels are an error rather than garbage
+        bytes.pop();
+        assert!(decode_levels(&bytes, TextureFormat::Dds).is_err());
+    }
+}
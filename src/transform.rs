@@ -0,0 +1,140 @@
+use nalgebra::{self, cross, Mat4, Norm, Quat, Rotate, UnitQuat, Vec3};
+
+/// Position, orientation and scale, turned into a matrix when it's needed. Storing the rotation
+/// as a quaternion keeps it from drifting as rotations are accumulated, and lets it be
+/// interpolated smoothly.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Transform {
+    pub position: Vec3<f32>,
+    pub rotation: UnitQuat<f32>,
+    pub scale: Vec3<f32>,
+}
+
+impl Transform {
+    pub fn new(position: Vec3<f32>) -> Self {
+        Transform {
+            position: position,
+            rotation: UnitQuat::new(nalgebra::zero()),
+            scale: Vec3::new(1., 1., 1.),
+        }
+    }
+
+    pub fn identity() -> Self {
+        Transform::new(nalgebra::zero())
+    }
+
+    pub fn with_rotation(mut self, rotation: UnitQuat<f32>) -> Self {
+        self.rotation = rotation;
+        self
+    }
+
+    pub fn with_scale(mut self, scale: Vec3<f32>) -> Self {
+        self.scale = scale;
+        self
+    }
+
+    /// Scales all three axes by `scale`
+    pub fn with_uniform_scale(self, scale: f32) -> Self {
+        self.with_scale(Vec3::new(scale, scale, scale))
+    }
+
+    /// Scales, then rotates, then translates
+    pub fn matrix(&self) -> Mat4<f32> {
+        let x = self.axis(Vec3::new(1., 0., 0.));
+        let y = self.axis(Vec3::new(0., 1., 0.));
+        let z = self.axis(Vec3::new(0., 0., 1.));
+        let (s, p) = (self.scale, self.position);
+        Mat4::new(x.x * s.x, y.x * s.y, z.x * s.z, p.x,
+                  x.y * s.x, y.y * s.y, z.y * s.z, p.y,
+                  x.z * s.x, y.z * s.y, z.z * s.z, p.z,
+                  0.,        0.,        0.,        1.)
+    }
+
+    /// `v` rotated from the local space of the transform into its parent's space
+    pub fn axis(&self, v: Vec3<f32>) -> Vec3<f32> {
+        self.rotation.rotate(&v)
+    }
+
+    /// Applies `rotation` after the current rotation, renormalizing so rounding errors don't
+    /// build up
+    pub fn rotate(&mut self, rotation: &UnitQuat<f32>) {
+        self.rotation = UnitQuat::new_with_quat(*(*rotation * self.rotation).quat());
+    }
+
+    /// Turns the transform so its -z axis points at `target`, keeping its y axis as close to
+    /// `up` as possible
+    pub fn look_at(&mut self, target: Vec3<f32>, up: Vec3<f32>) {
+        if let Some(rotation) = look_rotation(target - self.position, up) {
+            self.rotation = rotation;
+        }
+    }
+
+    /// The transform `t` of the way from this one to `other`. Positions and scales are blended
+    /// linearly and rotations are slerped.
+    pub fn lerp(&self, other: &Transform, t: f32) -> Self {
+        Transform {
+            position: self.position * (1. - t) + other.position * t,
+            rotation: slerp(&self.rotation, &other.rotation, t),
+            scale: self.scale * (1. - t) + other.scale * t,
+        }
+    }
+}
+
+/// The rotation `t` of the way from `a` to `b`, turning at a constant speed the short way round
+pub fn slerp(a: &UnitQuat<f32>, b: &UnitQuat<f32>, t: f32) -> UnitQuat<f32> {
+    let (a, mut b) = (*a.quat(), *b.quat());
+    let mut cos = a.w * b.w + a.i * b.i + a.j * b.j + a.k * b.k;
+    // `b` and `-b` are the same rotation, but only one of them is less than half a turn away
+    if cos < 0. {
+        b = Quat::new(-b.w, -b.i, -b.j, -b.k);
+        cos = -cos;
+    }
+
+    // Nearly identical rotations would divide by almost zero, and blending them linearly is
+    // indistinguishable
+    let (wa, wb) = if cos > 0.9995 {
+        (1. - t, t)
+    } else {
+        let angle = cos.acos();
+        let sin = angle.sin();
+        (((1. - t) * angle).sin() / sin, (t * angle).sin() / sin)
+    };
+    UnitQuat::new_with_quat(Quat::new(a.w * wa + b.w * wb, a.i * wa + b.i * wb,
+                                      a.j * wa + b.j * wb, a.k * wa + b.k * wb))
+}
+
+/// The rotation turning -z towards `forward` with y as close to `up` as possible, or `None` if
+/// `forward` is zero or parallel to `up`
+pub fn look_rotation(forward: Vec3<f32>, up: Vec3<f32>) -> Option<UnitQuat<f32>> {
+    if forward.sqnorm() == 0. {
+        return None;
+    }
+    let z = -forward.normalize();
+    let x = cross(&up, &z);
+    if x.sqnorm() < 1e-8 {
+        return None;
+    }
+    let x = x.normalize();
+    let y = cross(&z, &x);
+    Some(from_axes(x, y, z))
+}
+
+/// The rotation turning the x, y and z axes into `x`, `y` and `z`, which must be orthonormal
+pub fn from_axes(x: Vec3<f32>, y: Vec3<f32>, z: Vec3<f32>) -> UnitQuat<f32> {
+    // Divide by the largest of the four components to stay accurate
+    let trace = x.x + y.y + z.z;
+    let q = if trace > 0. {
+        let s = (trace + 1.).sqrt() * 2.;
+        Quat::new(s / 4., (y.z - z.y) / s, (z.x - x.z) / s, (x.y - y.x) / s)
+    } else if x.x > y.y && x.x > z.z {
+        let s = (1. + x.x - y.y - z.z).sqrt() * 2.;
+        Quat::new((y.z - z.y) / s, s / 4., (y.x + x.y) / s, (z.x + x.z) / s)
+    } else if y.y > z.z {
+        let s = (1. + y.y - x.x - z.z).sqrt() * 2.;
+        Quat::new((z.x - x.z) / s, (y.x + x.y) / s, s / 4., (z.y + y.z) / s)
+    } else {
+        let s = (1. + z.z - x.x - y.y).sqrt() * 2.;
+        Quat::new((x.y - y.x) / s, (z.x + x.z) / s, (z.y + y.z) / s, s / 4.)
+    };
+    UnitQuat::new_with_quat(q)
+}
@@ -0,0 +1,115 @@
+use {EngineContext, RenderTexture, Scene};
+use bounds::{Aabb, Ray};
+use draw::{GameObject, Object, ObjectBuilder, Vertex};
+use material::Material;
+use shader::VertexShaderType;
+use transform::Transform;
+
+use glium::{DepthTest, DrawParameters, Surface, VertexBuffer};
+use glium::index::{NoIndices, PrimitiveType};
+
+use nalgebra::{cross, dot, Col, Mat4, Norm, Vec3, Vec4};
+
+/// A GUI drawn into a texture and shown on an upright quad in the world facing -y, e.g. a
+/// computer screen or a hologram. The GUI is any scene, usually of screen space objects like
+/// `Text`, drawn with `draw_gui` before the world. `gui_point` maps the mouse ray onto the
+/// surface so the GUI can be interacted with.
+pub struct UiSurface<'a> {
+    pub parent: Object<'a>,
+    target: RenderTexture,
+}
+
+impl GameObject for UiSurface<'static> {
+    fn parent(&self) -> &Object {
+        &self.parent
+    }
+}
+
+impl<'a> UiSurface<'a> {
+    /// A surface `width` units wide whose GUI is `resolution` pixels, centered on the position
+    /// of `transform`. The height follows from the resolution's aspect ratio.
+    pub fn new(ctxt: &EngineContext, resolution: (u32, u32), width: f32,
+               transform: Transform) -> Self {
+        let target = ctxt.render_texture(resolution.0, resolution.1);
+        let height = width * resolution.1 as f32 / resolution.0 as f32;
+
+        // Undo the vertex shader's scaling so the quad is `width` units wide
+        let scale = VertexShaderType::Perspective.world_scale().unwrap();
+        let (x, z) = (width / 2. / scale, height / 2. / scale);
+        let shape = vec![Vertex::with_texture(-x, 0., -z, 0., 0.),
+                         Vertex::with_texture(x, 0., -z, 1., 0.),
+                         Vertex::with_texture(x, 0., z, 1., 1.),
+                         Vertex::with_texture(-x, 0., -z, 0., 0.),
+                         Vertex::with_texture(x, 0., z, 1., 1.),
+                         Vertex::with_texture(-x, 0., z, 0., 1.)];
+        let bounds = Aabb::from_vertices(&shape).unwrap();
+        let vb = VertexBuffer::new(ctxt.display(), shape).into_vertex_buffer_any();
+
+        let params = DrawParameters {
+            depth_test: DepthTest::IfLess,
+            depth_write: true,
+            .. Default::default()
+        };
+        let parent = ObjectBuilder::new()
+            .vertex_buffer(vb, NoIndices(PrimitiveType::TrianglesList))
+            .draw_params(params)
+            .transform(transform)
+            .bounds(bounds)
+            .material(Material::texture(target.texture()))
+            .build();
+
+        UiSurface { parent: parent, target: target }
+    }
+
+    /// Size of the GUI in pixels
+    pub fn resolution(&self) -> (u32, u32) {
+        self.target.dimensions()
+    }
+
+    /// Clears the surface and draws `gui` onto it with the GUI scene's camera. Call this before
+    /// drawing the scene the surface is in, so the surface shows this frame's GUI.
+    pub fn draw_gui(&self, ctxt: &mut EngineContext, gui: &Scene, alpha: f32) {
+        let mut target = self.target.framebuffer(ctxt.display());
+        target.clear_color_and_depth((0., 0., 0., 1.), 1.);
+        gui.draw_into(&mut target, ctxt, alpha);
+    }
+
+    /// Where `ray` hits the front or back of the surface, in GUI pixels from its top left corner
+    /// like mouse coordinates, or `None` if it misses. `parent_transform` is the world transform
+    /// of the surface's parent, or the identity if it was added to the scene directly.
+    pub fn gui_point(&self, ray: &Ray, parent_transform: &Mat4<f32>) -> Option<(f32, f32)> {
+        let m = *parent_transform * self.parent.transform.matrix();
+        let scale = self.parent.material.vert_shader.world_scale().unwrap_or(1.);
+        let vector = |v: Vec4<f32>| Vec3::new(v.x, v.y, v.z);
+        let origin = vector(m.col(3));
+        let (right, up) = (vector(m.col(0)) * scale, vector(m.col(2)) * scale);
+
+        let normal = cross(&right, &up);
+        let facing = dot(&ray.direction, &normal);
+        if facing == 0. {
+            return None;
+        }
+        let distance = dot(&(origin - ray.origin), &normal) / facing;
+        if distance < 0. {
+            return None;
+        }
+
+        // Position on the quad, from -0.5 to 0.5 in either direction before it was scaled
+        let offset = ray.at(distance) - origin;
+        let (width, height) = self.bounds_size();
+        let u = dot(&offset, &right) / right.sqnorm() / width;
+        let v = dot(&offset, &up) / up.sqnorm() / height;
+        if u.abs() > 0.5 || v.abs() > 0.5 {
+            return None;
+        }
+
+        let (w, h) = self.resolution();
+        Some(((u + 0.5) * w as f32, (0.5 - v) * h as f32))
+    }
+
+    // Width and height of the quad's vertices, before the shader and transform scale them
+    fn bounds_size(&self) -> (f32, f32) {
+        let bounds = self.parent.bounds.unwrap();
+        (bounds.max.x - bounds.min.x, bounds.max.z - bounds.min.z)
+    }
+}
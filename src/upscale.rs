@@ -0,0 +1,129 @@
+use std::cmp;
+
+use {get_display_dim, EngineContext};
+use camera::Camera;
+use draw::{GameObject, Object, ObjectBuilder, UniformsVec, Vertex};
+use material::Material;
+use render_texture::RenderTexture;
+use scene::Scene;
+use shader::{FragmentShaderType, VertexShaderType};
+
+use glium::{Display, Surface, VertexBuffer};
+use glium::index::{NoIndices, PrimitiveType};
+use glium::uniforms::{MagnifySamplerFilter, MinifySamplerFilter, SamplerBehavior,
+                      SamplerWrapFunction, UniformValue};
+
+use nalgebra::{self, Mat4};
+
+/// Draws scenes at a fraction of the window's resolution and scales them up to fill it, sharpening
+/// the result so it doesn't look blurry, e.g. to keep the frame rate up on high-DPI displays. Call
+/// `draw` from `Game::draw` instead of `Scene::draw`. Screen space objects in the scene are scaled
+/// too.
+pub struct Upscaler {
+    /// Fraction of the window's width and height the scene is drawn at. 1 draws straight to the
+    /// window.
+    pub render_scale: f32,
+    /// How strongly the upscaled image is sharpened, from 0 for plain bilinear filtering to 1
+    pub sharpness: f32,
+    target: Option<RenderTexture>,
+    quad: Option<Box<GameObject>>,
+}
+
+impl Upscaler {
+    pub fn new(render_scale: f32, sharpness: f32) -> Self {
+        Upscaler { render_scale: render_scale, sharpness: sharpness, target: None, quad: None }
+    }
+
+    /// Clears the window and draws `scene` into it, through a smaller texture when `render_scale`
+    /// is below 1
+    pub fn draw(&mut self, ctxt: &mut EngineContext, scene: &Scene, alpha: f32) {
+        if self.render_scale >= 1. {
+            self.target = None;
+            self.quad = None;
+            scene.draw(ctxt, alpha);
+            return;
+        }
+
+        let (w, h) = get_display_dim(ctxt.display());
+        let render_scale = self.render_scale;
+        let scale = |x: u32| cmp::max((x as f32 * render_scale) as u32, 1);
+        let size = (scale(w), scale(h));
+        if self.target.as_ref().map_or(true, |t| t.dimensions() != size) {
+            debug!("Drawing scenes at {}x{} for a {}x{} window", size.0, size.1, w, h);
+            let target = ctxt.render_texture(size.0, size.1);
+            self.quad = Some(Box::new(UpscaleQuad::new(ctxt.display(), &target)));
+            self.target = Some(target);
+        }
+
+        let target = self.target.as_ref().unwrap();
+        {
+            let mut framebuffer = target.framebuffer(ctxt.display());
+            framebuffer.clear_color_and_depth((0., 0., 0., 1.), 1.);
+            scene.draw_into(&mut framebuffer, ctxt, alpha);
+        }
+
+        let quad = self.quad.as_mut().unwrap();
+        if let Some(quad) = (**quad).as_any_mut().downcast_mut::<UpscaleQuad>() {
+            quad.sharpness = self.sharpness.max(0.).min(1.);
+        }
+        let mut frame = ctxt.display().draw();
+        frame.clear_color(0., 0., 0., 1.);
+        let identity: Mat4<f32> = nalgebra::new_identity(4);
+        let camera = scene.camera();
+        let result = ctxt.draw(&mut frame, camera, &[], [0., 0., 0.], &identity, alpha, quad);
+        if let Err(e) = result {
+            error!("Couldn't upscale the scene: {}", e);
+        }
+        frame.finish().unwrap();
+    }
+}
+
+// A quad covering the window, showing the scene's texture through the sharpening shader
+struct UpscaleQuad<'a> {
+    parent: Object<'a>,
+    sharpness: f32,
+    texel_size: [f32; 2],
+}
+
+impl GameObject for UpscaleQuad<'static> {
+    fn parent(&self) -> &Object {
+        &self.parent
+    }
+
+    fn construct_uniforms(&self, _: &Camera) -> UniformsVec {
+        UniformsVec(vec![("sharpness", UniformValue::Float(self.sharpness)),
+                         ("texel_size", UniformValue::Vec2(self.texel_size))])
+    }
+}
+
+impl<'a> UpscaleQuad<'a> {
+    fn new(display: &Display, target: &RenderTexture) -> Self {
+        let shape = vec![Vertex::with_texture(-1., -1., 0., 0., 0.),
+                         Vertex::with_texture(1., -1., 0., 1., 0.),
+                         Vertex::with_texture(1., 1., 0., 1., 1.),
+                         Vertex::with_texture(-1., -1., 0., 0., 0.),
+                         Vertex::with_texture(1., 1., 0., 1., 1.),
+                         Vertex::with_texture(-1., 1., 0., 0., 1.)];
+        let vb = VertexBuffer::new(display, shape).into_vertex_buffer_any();
+
+        // Bilinear filtering does the upscaling, and clamping keeps the edges from wrapping
+        let clamp = SamplerWrapFunction::Clamp;
+        let sampler = SamplerBehavior {
+            wrap_function: (clamp, clamp, clamp),
+            minify_filter: MinifySamplerFilter::Linear,
+            magnify_filter: MagnifySamplerFilter::Linear,
+            .. Default::default()
+        };
+        let material = Material::texture(target.texture())
+            .vert_shader(VertexShaderType::Gui)
+            .frag_shader(FragmentShaderType::Sharpen)
+            .sampler(sampler);
+        let parent = ObjectBuilder::new()
+            .vertex_buffer(vb, NoIndices(PrimitiveType::TrianglesList))
+            .material(material)
+            .build();
+
+        let (w, h) = target.dimensions();
+        UpscaleQuad { parent: parent, sharpness: 0., texel_size: [1. / w as f32, 1. / h as f32] }
+    }
+}
@@ -0,0 +1,316 @@
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+
+use draw::{GameObject, Object, ObjectBuilder, UniformsVec};
+use material::Material;
+use shader::{FragmentShaderType, VertexShaderType};
+use camera::Camera;
+
+use glium::{DepthTest, Display, DrawParameters, VertexBuffer};
+use glium::index::{NoIndices, PrimitiveType};
+use glium::texture::Texture2d;
+use glium::uniforms::{MinifySamplerFilter, MagnifySamplerFilter, SamplerBehavior, UniformValue};
+
+pub const CHUNK_SIZE: usize = 16;
+
+/// Block type stored in a chunk. `AIR` is the only non-solid block.
+pub type BlockId = u8;
+pub const AIR: BlockId = 0;
+
+pub type ChunkPos = (i32, i32, i32);
+
+#[derive(Copy, Clone, Debug)]
+struct VoxelVertex {
+    position: [f32; 3],
+    tex_coord: [f32; 2],
+    tile: [f32; 2],
+}
+
+implement_vertex!(VoxelVertex, position, tex_coord, tile);
+
+/// Maps block faces to tiles in a square texture atlas. Faces are ordered +x, -x, +y, -y, +z, -z.
+pub struct BlockAtlas {
+    texture: Rc<Texture2d>,
+    tiles_per_row: u32,
+    faces: HashMap<BlockId, [u32; 6]>,
+}
+
+impl BlockAtlas {
+    pub fn new(texture: Rc<Texture2d>, tiles_per_row: u32) -> Self {
+        BlockAtlas { texture: texture, tiles_per_row: tiles_per_row, faces: HashMap::new() }
+    }
+
+    /// Uses the same tile for every face of `block`
+    pub fn set_tile(&mut self, block: BlockId, tile: u32) {
+        self.faces.insert(block, [tile; 6]);
+    }
+
+    pub fn set_face_tiles(&mut self, block: BlockId, tiles: [u32; 6]) {
+        self.faces.insert(block, tiles);
+    }
+
+    fn tile(&self, block: BlockId, face: usize) -> [f32; 2] {
+        let tile = self.faces.get(&block).map(|t| t[face]).unwrap_or(0);
+        [(tile % self.tiles_per_row) as f32, (tile / self.tiles_per_row) as f32]
+    }
+}
+
+struct Chunk {
+    blocks: Vec<BlockId>,
+    mesh: Option<usize>,
+}
+
+impl Chunk {
+    fn new() -> Self {
+        Chunk { blocks: vec![AIR; CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE], mesh: None }
+    }
+
+    fn index(x: usize, y: usize, z: usize) -> usize {
+        x + CHUNK_SIZE * (y + CHUNK_SIZE * z)
+    }
+}
+
+/// A world made of fixed size chunks. Block edits mark the affected chunks dirty, and only dirty
+/// chunks are remeshed by `remesh`.
+pub struct VoxelWorld<'a> {
+    parent: Object<'a>,
+    atlas: BlockAtlas,
+    chunks: HashMap<ChunkPos, Chunk>,
+    meshes: Vec<Box<GameObject>>,
+    dirty: HashSet<ChunkPos>,
+}
+
+impl GameObject for VoxelWorld<'static> {
+    fn parent(&self) -> &Object {
+        &self.parent
+    }
+
+    fn children(&self) -> Option<&[Box<GameObject>]> {
+        Some(&*self.meshes)
+    }
+}
+
+impl<'a> VoxelWorld<'a> {
+    pub fn new(atlas: BlockAtlas) -> Self {
+        VoxelWorld {
+            parent: ObjectBuilder::new().build(),
+            atlas: atlas,
+            chunks: HashMap::new(),
+            meshes: Vec::new(),
+            dirty: HashSet::new(),
+        }
+    }
+
+    pub fn block(&self, x: i32, y: i32, z: i32) -> BlockId {
+        let (cx, lx) = split(x);
+        let (cy, ly) = split(y);
+        let (cz, lz) = split(z);
+        self.chunks.get(&(cx, cy, cz))
+            .map(|c| c.blocks[Chunk::index(lx, ly, lz)])
+            .unwrap_or(AIR)
+    }
+
+    pub fn set_block(&mut self, x: i32, y: i32, z: i32, block: BlockId) {
+        let (cx, lx) = split(x);
+        let (cy, ly) = split(y);
+        let (cz, lz) = split(z);
+        let pos = (cx, cy, cz);
+        self.chunks.entry(pos).or_insert_with(Chunk::new).blocks[Chunk::index(lx, ly, lz)] = block;
+        self.dirty.insert(pos);
+
+        // Faces on a chunk border are culled against the neighbouring chunk, so it needs to be
+        // remeshed as well
+        let last = CHUNK_SIZE - 1;
+        for &(local, offset) in [(lx, (1, 0, 0)), (ly, (0, 1, 0)), (lz, (0, 0, 1))].iter() {
+            let neighbors = [(local == 0, -1), (local == last, 1)];
+            for &(on_border, sign) in neighbors.iter() {
+                let neighbor = (cx + offset.0 * sign, cy + offset.1 * sign, cz + offset.2 * sign);
+                if on_border && self.chunks.contains_key(&neighbor) {
+                    self.dirty.insert(neighbor);
+                }
+            }
+        }
+    }
+
+    /// Rebuilds the meshes of all chunks that changed since the last call
+    pub fn remesh(&mut self, display: &Display) {
+        let dirty: Vec<_> = self.dirty.drain().collect();
+        for pos in dirty {
+            let vertices = self.mesh_chunk(pos);
+            let mesh = Box::new(ChunkMesh::new(display, &self.atlas, vertices)) as Box<GameObject>;
+
+            let slot = self.chunks.get(&pos).and_then(|c| c.mesh);
+            match slot {
+                Some(i) => self.meshes[i] = mesh,
+                None => {
+                    self.meshes.push(mesh);
+                    let i = self.meshes.len() - 1;
+                    self.chunks.get_mut(&pos).unwrap().mesh = Some(i);
+                }
+            }
+        }
+    }
+
+    /// Greedy meshing: for every slice along each axis, build a mask of the visible faces in that
+    /// slice and merge runs of identical faces into the largest rectangles possible.
+    fn mesh_chunk(&self, pos: ChunkPos) -> Vec<VoxelVertex> {
+        let n = CHUNK_SIZE as i32;
+        let origin = [pos.0 * n, pos.1 * n, pos.2 * n];
+        let mut vertices = Vec::new();
+
+        for d in 0..3 {
+            let u = (d + 1) % 3;
+            let v = (d + 2) % 3;
+            let mut x = [0i32; 3];
+            let mut q = [0i32; 3];
+            q[d] = 1;
+
+            // Each mask entry is the block owning the face and whether it faces +d
+            let mut mask: Vec<Option<(BlockId, bool)>> = vec![None; CHUNK_SIZE * CHUNK_SIZE];
+
+            x[d] = -1;
+            while x[d] < n {
+                let mut i = 0;
+                for xv in 0..n {
+                    x[v] = xv;
+                    for xu in 0..n {
+                        x[u] = xu;
+                        let a = self.block(origin[0] + x[0], origin[1] + x[1], origin[2] + x[2]);
+                        let b = self.block(origin[0] + x[0] + q[0], origin[1] + x[1] + q[1],
+                                           origin[2] + x[2] + q[2]);
+                        // Only emit faces for blocks inside this chunk
+                        mask[i] = if a != AIR && b == AIR && x[d] >= 0 {
+                            Some((a, true))
+                        } else if a == AIR && b != AIR && x[d] + 1 < n {
+                            Some((b, false))
+                        } else {
+                            None
+                        };
+                        i += 1;
+                    }
+                }
+                x[d] += 1;
+
+                let size = CHUNK_SIZE;
+                for j in 0..size {
+                    let mut i = 0;
+                    while i < size {
+                        let face = match mask[i + j * size] {
+                            Some(face) => face,
+                            None => { i += 1; continue }
+                        };
+
+                        let mut w = 1;
+                        while i + w < size && mask[i + w + j * size] == Some(face) {
+                            w += 1;
+                        }
+
+                        let mut h = 1;
+                        'grow: while j + h < size {
+                            for k in 0..w {
+                                if mask[i + k + (j + h) * size] != Some(face) {
+                                    break 'grow;
+                                }
+                            }
+                            h += 1;
+                        }
+
+                        let mut base = [0.; 3];
+                        base[d] = (origin[d] + x[d]) as f32;
+                        base[u] = (origin[u] + i as i32) as f32;
+                        base[v] = (origin[v] + j as i32) as f32;
+                        let mut du = [0.; 3];
+                        du[u] = w as f32;
+                        let mut dv = [0.; 3];
+                        dv[v] = h as f32;
+
+                        let (block, front) = face;
+                        let face_index = d * 2 + if front { 0 } else { 1 };
+                        let tile = self.atlas.tile(block, face_index);
+                        push_quad(&mut vertices, base, du, dv, w as f32, h as f32, tile, front);
+
+                        for l in 0..h {
+                            for k in 0..w {
+                                mask[i + k + (j + l) * size] = None;
+                            }
+                        }
+                        i += w;
+                    }
+                }
+            }
+        }
+
+        vertices
+    }
+}
+
+fn push_quad(vertices: &mut Vec<VoxelVertex>, base: [f32; 3], du: [f32; 3], dv: [f32; 3],
+             w: f32, h: f32, tile: [f32; 2], front: bool) {
+    let add = |a: [f32; 3], b: [f32; 3]| [a[0] + b[0], a[1] + b[1], a[2] + b[2]];
+    let corners = [(base, [0., 0.]),
+                   (add(base, du), [w, 0.]),
+                   (add(add(base, du), dv), [w, h]),
+                   (add(base, dv), [0., h])];
+
+    // Counter-clockwise when seen from the side the face points towards
+    let order = if front { [0, 1, 2, 0, 2, 3] } else { [0, 2, 1, 0, 3, 2] };
+    for &k in order.iter() {
+        let (position, tex_coord) = corners[k];
+        vertices.push(VoxelVertex { position: position, tex_coord: tex_coord, tile: tile });
+    }
+}
+
+/// Splits a world coordinate into a chunk coordinate and an offset inside that chunk
+fn split(c: i32) -> (i32, usize) {
+    let n = CHUNK_SIZE as i32;
+    let chunk = if c < 0 { (c + 1) / n - 1 } else { c / n };
+    (chunk, (c - chunk * n) as usize)
+}
+
+struct ChunkMesh<'a> {
+    parent: Object<'a>,
+    tile_size: f32,
+}
+
+impl GameObject for ChunkMesh<'static> {
+    fn parent(&self) -> &Object {
+        &self.parent
+    }
+
+    fn construct_uniforms(&self, _: &Camera) -> UniformsVec {
+        UniformsVec(vec![("tile_size", UniformValue::Float(self.tile_size))])
+    }
+}
+
+impl<'a> ChunkMesh<'a> {
+    fn new(display: &Display, atlas: &BlockAtlas, vertices: Vec<VoxelVertex>) -> Self {
+        let params = DrawParameters {
+            depth_test: DepthTest::IfLess,
+            depth_write: true,
+            .. Default::default()
+        };
+
+        let sampler = SamplerBehavior {
+            minify_filter: MinifySamplerFilter::Nearest,
+            magnify_filter: MagnifySamplerFilter::Nearest,
+            .. Default::default()
+        };
+        let material = Material::texture(atlas.texture.clone())
+            .vert_shader(VertexShaderType::Voxel)
+            .frag_shader(FragmentShaderType::Voxel)
+            .sampler(sampler);
+
+        let mut builder = ObjectBuilder::new()
+            .draw_params(params)
+            .material(material);
+        if !vertices.is_empty() {
+            let vb = VertexBuffer::new(display, vertices).into_vertex_buffer_any();
+            builder = builder.vertex_buffer(vb, NoIndices(PrimitiveType::TrianglesList));
+        }
+
+        ChunkMesh {
+            parent: builder.build(),
+            tile_size: 1. / atlas.tiles_per_row as f32,
+        }
+    }
+}
@@ -0,0 +1,62 @@
+use glium::glutin::{self, MonitorID, WindowBuilder};
+
+/// How the window covers the screen
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum WindowMode {
+    Windowed,
+    /// Exclusive fullscreen on the chosen monitor
+    Fullscreen,
+    /// A window without decorations covering the chosen monitor, which switches to and from
+    /// other windows faster than exclusive fullscreen
+    Borderless,
+}
+
+/// What the window is built from, kept so it can be built again in another mode
+#[derive(Clone, Debug)]
+pub struct WindowSettings {
+    pub title: String,
+    /// Size in windowed mode
+    pub size: (u32, u32),
+    pub mode: WindowMode,
+    /// Index into `monitor_names` of the monitor to go fullscreen on, or `None` for the primary
+    /// monitor
+    pub monitor: Option<usize>,
+    pub vsync: bool,
+}
+
+impl WindowSettings {
+    pub fn builder(&self) -> WindowBuilder<'static> {
+        let mut builder = WindowBuilder::new().with_title(self.title.clone());
+        if self.vsync {
+            builder = builder.with_vsync();
+        }
+        match self.mode {
+            WindowMode::Windowed => builder.with_dimensions(self.size.0, self.size.1),
+            WindowMode::Fullscreen => builder.with_fullscreen(self.monitor_id()),
+            WindowMode::Borderless => {
+                let (w, h) = self.monitor_id().get_dimensions();
+                builder.with_dimensions(w, h).with_decorations(false)
+            }
+        }
+    }
+
+    fn monitor_id(&self) -> MonitorID {
+        let monitor = self.monitor.and_then(|i| glutin::get_available_monitors().nth(i));
+        match monitor {
+            Some(monitor) => monitor,
+            None => {
+                if let Some(i) = self.monitor {
+                    warn!("There is no monitor {}, using the primary monitor", i);
+                }
+                glutin::get_primary_monitor()
+            }
+        }
+    }
+}
+
+/// Names of the connected monitors, in the order `WindowSettings::monitor` indexes them
+pub fn monitor_names() -> Vec<String> {
+    glutin::get_available_monitors().enumerate()
+        .map(|(i, m)| m.get_name().unwrap_or_else(|| format!("Monitor {}", i)))
+        .collect()
+}
@@ -0,0 +1,303 @@
+//! Runs the collision, steering and crowd simulations for 600 ticks from fixed seeds and
+//! compares hashes of their state against references recorded earlier on the same platform, to
+//! catch nondeterminism before it breaks replays or lockstep networking. References are stored per
+//! OS and architecture, e.g. `determinism/linux-x86_64.txt`, since floating point results can
+//! differ between platforms. Every simulation is also run twice to catch state that differs
+//! between runs in one process, e.g. from iterating over a `HashMap`.
+//!
+//! The test fails on platforms without references. Run
+//! `RECORD_DETERMINISM=1 cargo test --release --test determinism` to record missing references or
+//! replace changed ones after a deliberate change to a simulation, and commit the file it writes.
+
+extern crate engine;
+extern crate env_logger;
+#[macro_use]
+extern crate log;
+extern crate nalgebra;
+
+use std::collections::BTreeMap;
+use std::env;
+use std::error;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
+use engine::{Camera, EventBus, InputState, Scene, Transform};
+use engine::ai::crowd::{Crowd, CrowdAgent};
+use engine::ai::steering::{self, Agent, Wander};
+use engine::collision::{Collider, Shape};
+use engine::determinism::StateHash;
+use engine::draw::UpdateContext;
+use engine::ecs::Script;
+
+use nalgebra::Vec3;
+
+const TICKS: u32 = 600;
+const CHECKPOINT_EVERY: u32 = 60;
+const DT: f32 = 1. / 30.;
+
+#[test]
+fn simulations_are_deterministic() {
+    let _ = env_logger::init();
+    let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("determinism");
+    let record = env::var_os("RECORD_DETERMINISM").is_some();
+    let result = DeterminismCheck::load(dir, record).and_then(|mut check| {
+        try!(check.check("collisions", collisions));
+        try!(check.check("steering", steering));
+        try!(check.check("crowd", crowd));
+        check.save()
+    });
+    if let Err(e) = result {
+        panic!("{}", e);
+    }
+}
+
+#[derive(Debug)]
+enum CheckError {
+    Io(io::Error),
+    /// A reference file is malformed or has no reference for a simulation
+    References(String),
+    /// A simulation didn't reproduce its own state or the reference
+    Nondeterministic(String),
+}
+
+impl fmt::Display for CheckError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            CheckError::Io(ref e) => write!(f, "IO error: {}", e),
+            CheckError::References(ref msg) => write!(f, "Bad references: {}", msg),
+            CheckError::Nondeterministic(ref msg) => {
+                write!(f, "Nondeterministic simulation: {}", msg)
+            }
+        }
+    }
+}
+
+impl error::Error for CheckError {
+    fn description(&self) -> &str {
+        match *self {
+            CheckError::Io(ref e) => e.description(),
+            CheckError::References(ref msg) => msg,
+            CheckError::Nondeterministic(ref msg) => msg,
+        }
+    }
+}
+
+impl From<io::Error> for CheckError {
+    fn from(e: io::Error) -> Self {
+        CheckError::Io(e)
+    }
+}
+
+struct DeterminismCheck {
+    path: PathBuf,
+    record: bool,
+    // Checkpoint hashes by simulation name, sorted so the file diffs cleanly
+    references: BTreeMap<String, Vec<u64>>,
+    changed: bool,
+}
+
+impl DeterminismCheck {
+    // Reads this platform's references from `dir`, if it has any
+    fn load<P: AsRef<Path>>(dir: P, record: bool) -> Result<Self, CheckError> {
+        let path = dir.as_ref().join(format!("{}-{}.txt", env::consts::OS, env::consts::ARCH));
+        let mut references = BTreeMap::new();
+        let mut contents = String::new();
+        match File::open(&path) {
+            Ok(mut file) => {
+                try!(file.read_to_string(&mut contents));
+            }
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => (),
+            Err(e) => return Err(CheckError::Io(e)),
+        }
+        for (n, line) in contents.lines().enumerate() {
+            let mut words = line.split_whitespace();
+            let name = match words.next() {
+                Some(name) => name,
+                None => continue,
+            };
+            let hashes: Result<Vec<_>, _> = words.map(|w| u64::from_str_radix(w, 16)).collect();
+            let hashes = try!(hashes.map_err(|_| {
+                CheckError::References(format!("{}:{}: expected hex hashes",
+                                                path.display(), n + 1))
+            }));
+            references.insert(name.to_owned(), hashes);
+        }
+        Ok(DeterminismCheck { path: path, record: record, references: references, changed: false })
+    }
+
+    // Runs `simulate` twice and compares the hashes it returns with each other and with the
+    // reference called `name`. `name` can't contain whitespace.
+    fn check<F: FnMut() -> Vec<u64>>(&mut self, name: &str,
+                                     mut simulate: F) -> Result<(), CheckError> {
+        let hashes = simulate();
+        if let Some(i) = first_difference(&hashes, &simulate()) {
+            return Err(CheckError::Nondeterministic(format!("{} differs between two runs at \
+                                                             checkpoint {}", name, i)));
+        }
+
+        let matches = match self.references.get(name) {
+            Some(reference) => match first_difference(reference, &hashes) {
+                Some(i) if !self.record => {
+                    return Err(CheckError::Nondeterministic(format!(
+                        "{} differs from the reference in {} at checkpoint {}", name,
+                        self.path.display(), i)));
+                }
+                Some(_) => false,
+                None => true,
+            },
+            None if !self.record => {
+                return Err(CheckError::References(format!(
+                    "no reference for {} in {}, record one with RECORD_DETERMINISM=1", name,
+                    self.path.display())));
+            }
+            None => false,
+        };
+        if !matches {
+            info!("Recording {} checkpoints for {}", hashes.len(), name);
+            self.references.insert(name.to_owned(), hashes);
+            self.changed = true;
+        }
+        Ok(())
+    }
+
+    // Writes the references back if any were recorded
+    fn save(&self) -> Result<(), CheckError> {
+        if !self.changed {
+            return Ok(());
+        }
+        if let Some(dir) = self.path.parent() {
+            try!(fs::create_dir_all(dir));
+        }
+        let mut file = try!(File::create(&self.path));
+        for (name, hashes) in self.references.iter() {
+            let hashes: Vec<_> = hashes.iter().map(|h| format!("{:016x}", h)).collect();
+            try!(writeln!(file, "{} {}", name, hashes.join(" ")));
+        }
+        Ok(())
+    }
+}
+
+// Index of the first checkpoint that differs, counting a missing checkpoint as different
+fn first_difference(a: &[u64], b: &[u64]) -> Option<usize> {
+    match a.iter().zip(b).position(|(a, b)| a != b) {
+        Some(i) => Some(i),
+        None if a.len() != b.len() => Some(a.len().min(b.len())),
+        None => None,
+    }
+}
+
+// Spheres and boxes scattered by a seeded generator falling towards the origin and pushing each
+// other apart around a fixed box
+fn collisions() -> Vec<u64> {
+    let mut scene = Scene::new(Camera::new(Vec3::new(0., 0., 1.), 1.));
+    let mut entities = Vec::new();
+    {
+        let world = scene.world_mut();
+        let floor = world.spawn();
+        world.insert(floor, Transform::identity());
+        let half_extents = Vec3::new(2., 2., 2.);
+        world.insert(floor, Collider::fixed(Shape::Obb { half_extents: half_extents }));
+
+        let mut rng = Xorshift(12345);
+        for i in 0..200 {
+            let entity = world.spawn();
+            let position = Vec3::new(rng.range(10.), rng.range(10.), rng.range(10.));
+            world.insert(entity, Transform::new(position));
+            let shape = if i % 2 == 0 {
+                Shape::Sphere { radius: 0.5 }
+            } else {
+                Shape::Aabb { half_extents: Vec3::new(0.4, 0.4, 0.4) }
+            };
+            world.insert(entity, Collider::new(shape));
+            world.insert(entity, Script::new(|entity, world, ctxt| {
+                if let Some(transform) = world.get_mut::<Transform>(entity) {
+                    transform.position = transform.position * (1. - ctxt.dt);
+                }
+            }));
+            entities.push(entity);
+        }
+    }
+
+    let (input, events) = (InputState::new(), EventBus::new());
+    simulate(|tick| {
+        scene.update(&UpdateContext { dt: DT, input: &input, events: &events });
+        if tick % CHECKPOINT_EVERY != 0 {
+            return None;
+        }
+        let mut hash = StateHash::new();
+        for &entity in entities.iter() {
+            hash.write_transform(scene.world().get::<Transform>(entity).unwrap());
+        }
+        Some(hash.finish())
+    })
+}
+
+// Wandering agents that also seek the origin
+fn steering() -> Vec<u64> {
+    let mut rng = Xorshift(6789);
+    let mut agents: Vec<_> = (0..100).map(|i| {
+        let position = Vec3::new(rng.range(20.), rng.range(20.), 0.);
+        (Agent::new(position, 3., 5., 0.5), Wander::new(2., 1., 0.5, i + 1))
+    }).collect();
+
+    simulate(|tick| {
+        for &mut (ref mut agent, ref mut wander) in agents.iter_mut() {
+            let acceleration = wander.steer(agent) + steering::seek(agent, Vec3::new(0., 0., 0.));
+            agent.apply(acceleration, DT);
+        }
+        if tick % CHECKPOINT_EVERY != 0 {
+            return None;
+        }
+        let mut hash = StateHash::new();
+        for &(ref agent, _) in agents.iter() {
+            hash.write_vec3(agent.position);
+            hash.write_vec3(agent.velocity);
+        }
+        Some(hash.finish())
+    })
+}
+
+// Two groups of agents crossing through each other
+fn crowd() -> Vec<u64> {
+    let mut crowd = Crowd::new();
+    for i in 0..50 {
+        let y = i as f32 - 25.;
+        let left = crowd.add(CrowdAgent::new(Vec3::new(-20., y, 0.), 0.4, 2.));
+        crowd.set_preferred_velocity(left, Vec3::new(2., 0., 0.));
+        let right = crowd.add(CrowdAgent::new(Vec3::new(20., y + 0.5, 0.), 0.4, 2.));
+        crowd.set_preferred_velocity(right, Vec3::new(-2., 0., 0.));
+    }
+
+    simulate(|tick| {
+        crowd.step(DT);
+        if tick % CHECKPOINT_EVERY != 0 {
+            return None;
+        }
+        let mut hash = StateHash::new();
+        for agent in crowd.agents() {
+            hash.write_vec3(agent.position);
+            hash.write_vec3(agent.velocity);
+        }
+        Some(hash.finish())
+    })
+}
+
+// Runs `tick` for every tick, collecting the hashes it returns at checkpoints
+fn simulate<F: FnMut(u32) -> Option<u64>>(mut tick: F) -> Vec<u64> {
+    (1..TICKS + 1).filter_map(|t| tick(t)).collect()
+}
+
+// The same sequence everywhere, unlike a generator seeded from the OS
+struct Xorshift(u32);
+
+impl Xorshift {
+    // From -extent to extent
+    fn range(&mut self, extent: f32) -> f32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 17;
+        self.0 ^= self.0 << 5;
+        (self.0 as f32 / ::std::u32::MAX as f32 * 2. - 1.) * extent
+    }
+}